@@ -1,6 +1,11 @@
 extern crate core;
 
-use std::{fs, path::PathBuf, str::FromStr};
+use std::{
+    fs,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    str::FromStr,
+};
 
 use clap::{Parser, Subcommand};
 use jsonrpsee::{
@@ -14,15 +19,17 @@ use protocol::{
 };
 use serde::{Deserialize, Serialize};
 use spaced::{
-    config::{default_spaces_rpc_port, ExtendedNetwork},
+    config::{default_spaces_rpc_port, EstimateMode, ExtendedNetwork},
     rpc::{
         BidParams, ExecuteParams, OpenParams, RegisterParams, RpcClient, RpcWalletRequest,
         RpcWalletTxBuilder, SendCoinsParams, TransferSpacesParams,
     },
     store::Sha256,
-    wallets::AddressKind,
+    wallets::{
+        AddressKind, ConfirmPolicy, ExportFormat, FeePolicy, OutbidLimits, SpaceStateFilter,
+    },
 };
-use wallet::export::WalletExport;
+use wallet::{builder::CoinSelectionStrategy, export::WalletExport};
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -36,6 +43,15 @@ pub struct Args {
     /// Specify wallet to use
     #[arg(long, short, global = true, default_value = "default")]
     wallet: String,
+    /// Wallet account to use for balance, address and listing commands
+    #[arg(long, global = true)]
+    account: Option<u32>,
+    /// Confirmation target for the node's fee estimate when no fee rate is given
+    #[arg(long, global = true)]
+    conf_target: Option<u16>,
+    /// Mode for the node's fee estimate: unset, economical or conservative
+    #[arg(long, global = true)]
+    estimate_mode: Option<EstimateMode>,
     /// Custom dust amount in sat for bid outputs
     #[arg(long, short, global = true)]
     dust: Option<u64>,
@@ -45,6 +61,30 @@ pub struct Args {
     /// Skip tx checker (not recommended)
     #[arg(long, global = true, default_value = "false")]
     skip_tx_check: bool,
+    /// Coin selection algorithm: branch_and_bound, largest_first or oldest_first
+    #[arg(long, global = true, default_value = "branch_and_bound")]
+    coin_selection: CoinSelectionStrategy,
+    /// Avoid spending outputs of a reused address together and warn when inputs link addresses
+    #[arg(long, global = true, default_value = "false")]
+    avoid_address_reuse: bool,
+    /// Add change below the dust limit to the fee instead of failing the transaction
+    #[arg(long, global = true, default_value = "true", action = clap::ArgAction::Set)]
+    donate_dust_change_to_fee: bool,
+    /// Refuse to outbid a competing bid requiring more than this multiple of the fee rate
+    #[arg(long, global = true)]
+    max_outbid_multiplier: Option<f64>,
+    /// Refuse to outbid a competing bid requiring a fee rate above this (sat/vB)
+    #[arg(long, global = true)]
+    max_outbid_fee_rate: Option<u64>,
+    /// Resolve space names using pending updates in the mempool (less safe)
+    #[arg(long, global = true, default_value = "false")]
+    resolve_pending: bool,
+    /// Confirmation token for a high value request that required confirmation
+    #[arg(long, global = true)]
+    confirm_token: Option<String>,
+    /// Build and sign transactions without broadcasting, showing the change they create
+    #[arg(long, global = true, default_value = "false")]
+    preview: bool,
     #[command(subcommand)]
     command: Commands,
 }
@@ -54,14 +94,41 @@ enum Commands {
     /// Generate a new wallet
     #[command(name = "createwallet")]
     CreateWallet,
+    /// Recover a wallet from a BIP-39 mnemonic
+    #[command(name = "recoverwallet")]
+    RecoverWallet {
+        /// The mnemonic words separated by spaces
+        #[arg(long)]
+        mnemonic: String,
+        /// Optional BIP-39 passphrase
+        #[arg(long)]
+        passphrase: Option<String>,
+        /// Block height to start scanning from (defaults to protocol activation)
+        #[arg(long)]
+        start_height: Option<u32>,
+    },
     /// Load a wallet
     #[command(name = "loadwallet")]
-    LoadWallet,
+    LoadWallet {
+        /// Passphrase of an encrypted wallet
+        #[arg(long)]
+        passphrase: Option<String>,
+    },
     /// Export a wallet
     #[command(name = "exportwallet")]
     ExportWallet {
         // Destination path to export json file
         path: PathBuf,
+        /// Passphrase of an encrypted wallet that isn't loaded
+        #[arg(long)]
+        passphrase: Option<String>,
+    },
+    /// Encrypt a loaded wallet's files at rest with a passphrase
+    #[command(name = "encryptwallet")]
+    EncryptWallet {
+        /// Passphrase required to load the wallet from now on
+        #[arg(long)]
+        passphrase: String,
     },
     /// Import a wallet
     #[command(name = "importwallet")]
@@ -72,9 +139,24 @@ enum Commands {
     /// Export a wallet
     #[command(name = "getwalletinfo")]
     GetWalletInfo,
+    /// Show the network, birth height and derivation paths needed to recreate the wallet
+    #[command(name = "getwalletmetadata")]
+    GetWalletMetadata,
     /// Export a wallet
     #[command(name = "getserverinfo")]
     GetServerInfo,
+    /// Get the sync progress of the bitcoin node
+    #[command(name = "getnodesyncstatus")]
+    GetNodeSyncStatus,
+    /// Get the spaces protocol activation height and whether it's active on this network
+    #[command(name = "getprotocolstatus")]
+    GetProtocolStatus,
+    /// Get the timestamp and median time past of the block at the given height
+    #[command(name = "getblocktime")]
+    GetBlockTime {
+        /// Block height
+        height: u32,
+    },
     /// Open an auction
     Open {
         /// Space name
@@ -114,6 +196,52 @@ enum Commands {
         /// The space name
         space: String,
     },
+    /// Get the auction status of a space including blocks left until it can be claimed
+    #[command(name = "getauctionstatus")]
+    GetAuctionStatus {
+        /// The space name
+        space: String,
+    },
+    /// Check whether a name is a valid space name and if it already exists
+    #[command(name = "validatename")]
+    ValidateName {
+        /// The space name
+        space: String,
+    },
+    /// Check whether a space's current output is still unspent
+    #[command(name = "getspaceoutstatus")]
+    GetSpaceOutStatus {
+        /// The space name
+        space: String,
+    },
+    /// Verify that a space address is the current on-chain address of a space
+    #[command(name = "verifyspaceaddress")]
+    VerifySpaceAddress {
+        /// The space name
+        space: String,
+        /// The space address to check
+        address: String,
+    },
+    /// Show a space's auction and registration expiry heights with estimated times
+    #[command(name = "getauctiontimeline")]
+    GetAuctionTimeline {
+        /// The space name
+        space: String,
+    },
+    /// Export the transactions establishing a space's current ownership, from its
+    /// open through its bids, registration and transfers (requires the block index)
+    #[command(name = "getownershipproof")]
+    GetOwnershipProof {
+        /// The space name
+        space: String,
+    },
+    /// Check whether a set of names are available to open
+    #[command(name = "checkavailability")]
+    CheckAvailability {
+        /// The space names
+        #[arg(required = true)]
+        spaces: Vec<String>,
+    },
     /// Transfer ownership of a set of spaces to the given name or address
     #[command(
         name = "transfer",
@@ -143,12 +271,15 @@ enum Commands {
         override_usage = "space-cli send <AMOUNT> --to <SPACE-OR-ADDRESS>"
     )]
     SendCoins {
-        /// Amount to send in satoshi
+        /// Amount to send in satoshi (may be omitted if the recipient is a URI with an amount)
         #[arg(display_order = 0)]
-        amount: u64,
-        /// Recipient space name or address
+        amount: Option<u64>,
+        /// Recipient space name, address or bitcoin: URI
         #[arg(long, display_order = 1)]
         to: String,
+        /// Only fund the send with these confirmed wallet coins
+        #[arg(long, num_args = 1..)]
+        from: Vec<OutPoint>,
         /// Fee rate to use in sat/vB
         #[arg(long, short)]
         fee_rate: Option<u64>,
@@ -156,6 +287,58 @@ enum Commands {
     /// Get wallet balance
     #[command(name = "balance")]
     Balance,
+    /// Show the coin value held in each owned space
+    #[command(name = "getspacesvaluelocked")]
+    GetSpacesValueLocked,
+    /// Set the wallet's default fee rate policy used when no fee rate is given.
+    /// Without arguments, the node's fee estimate is used
+    #[command(name = "setfeepolicy")]
+    SetFeePolicy {
+        /// Always use this fee rate in sat/vB
+        #[arg(long, conflicts_with = "target")]
+        fixed: Option<u64>,
+        /// Estimate a fee rate for confirmation within this many blocks
+        #[arg(long)]
+        target: Option<u16>,
+    },
+    /// Get the wallet's default fee rate policy
+    #[command(name = "getfeepolicy")]
+    GetFeePolicy,
+    /// Require a confirmation token for operations above a value.
+    /// Without arguments, confirmations are disabled
+    #[command(name = "setconfirmpolicy")]
+    SetConfirmPolicy {
+        /// Operations committing more than this amount in sat require confirmation
+        #[arg(long)]
+        threshold: Option<u64>,
+    },
+    /// Get the wallet's high value confirmation policy
+    #[command(name = "getconfirmpolicy")]
+    GetConfirmPolicy,
+    /// Broadcast signed raw transactions in the given order, stopping at the first failure
+    #[command(name = "broadcastbatch")]
+    BroadcastBatch {
+        /// Hex encoded signed transactions
+        #[arg(required = true)]
+        raw_txs: Vec<String>,
+    },
+    /// Attach a note to a wallet transaction (an empty note removes it)
+    #[command(name = "settxnote")]
+    SetTxNote {
+        /// The transaction id
+        txid: Txid,
+        /// The note
+        note: String,
+    },
+    /// Get the note attached to a wallet transaction
+    #[command(name = "gettxnote")]
+    GetTxNote {
+        /// The transaction id
+        txid: Txid,
+    },
+    /// Show how far behind the wallet is and an estimate of the time left to sync
+    #[command(name = "getsyncprogress")]
+    GetSyncProgress,
     /// Pre-create outputs that can be auctioned off during the bidding process
     #[command(name = "createbidouts")]
     CreateBidOuts {
@@ -166,6 +349,28 @@ enum Commands {
         #[arg(long, short)]
         fee_rate: Option<u64>,
     },
+    /// Bump the fee of all stuck unconfirmed wallet transactions, using CPFP
+    /// for ones that can't be replaced
+    #[command(name = "bumpallstuck")]
+    BumpAllStuck {
+        /// Fee rate to bump to in sat/vB
+        #[arg(long, short)]
+        fee_rate: u64,
+        /// Only bump transactions that spent at least this many blocks in the mempool
+        #[arg(long, default_value = "0")]
+        min_age: u32,
+    },
+    /// List stuck unconfirmed wallet transactions with the fee rate and method
+    /// to bump each one with
+    #[command(name = "listbumpcandidates")]
+    ListBumpCandidates {
+        /// Only list transactions that spent at least this many blocks in the mempool
+        #[arg(long, default_value = "0")]
+        min_age: u32,
+        /// Blocks the transactions should confirm within (defaults to the node's setting)
+        #[arg(long, short)]
+        conf_target: Option<u16>,
+    },
     /// Bump the fee for a transaction created by this wallet
     #[command(name = "bumpfee")]
     BumpFee {
@@ -173,6 +378,46 @@ enum Commands {
         /// Fee rate to use in sat/vB
         #[arg(long, short)]
         fee_rate: u64,
+        /// Look up the loaded wallet owning the transaction instead of using --wallet
+        #[arg(long)]
+        any_wallet: bool,
+        /// Build and sign the replacement and report its cost without broadcasting it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Bump the fee of a transaction to the rate estimated to confirm within a target
+    #[command(name = "bumpfeetotarget")]
+    BumpFeeToTarget {
+        txid: Txid,
+        /// Blocks the transaction should confirm within
+        #[arg(long, short, default_value = "1")]
+        conf_target: u16,
+        /// Build and sign the replacement and report its cost without broadcasting it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Cancel a pending bid by replacing it with a transaction paying back to the wallet
+    #[command(name = "cancelbid")]
+    CancelBid {
+        txid: Txid,
+        /// Fee rate to use in sat/vB, must be higher than the bid's
+        #[arg(long, short)]
+        fee_rate: u64,
+    },
+    /// Check whether a wallet transaction can be replaced with a fee bump (BIP-125)
+    #[command(name = "isreplaceable")]
+    IsReplaceable { txid: Txid },
+    /// Show how many blocks remain until a space in auction can be registered
+    #[command(name = "getclaimstatus")]
+    GetClaimStatus {
+        /// The space name
+        space: String,
+    },
+    /// Show the raw and decoded script pubkey an owned space is held at
+    #[command(name = "getspacescript")]
+    GetSpaceScript {
+        /// The space name
+        space: String,
     },
     /// Get a spaceout - a Bitcoin output relevant to the Spaces protocol.
     #[command(name = "getspaceout")]
@@ -180,6 +425,18 @@ enum Commands {
         /// The OutPoint
         outpoint: OutPoint,
     },
+    /// List spaces whose current output is locked to the given address or script
+    #[command(name = "getspacesbyscript")]
+    GetSpacesByScript {
+        /// An address, a space address or a hex encoded script pubkey
+        address_or_script: String,
+        /// Resume after the space hash returned as the previous page's next cursor
+        #[arg(long)]
+        cursor: Option<String>,
+        /// Maximum number of spaces to examine for this page
+        #[arg(long)]
+        limit: Option<usize>,
+    },
     /// Get the estimated rollout batch for the specified interval
     #[command(name = "getrollout")]
     GetRollout {
@@ -209,25 +466,166 @@ enum Commands {
         #[arg(default_value = "0")]
         skip: usize,
     },
+    /// List fee rates paid by the last confirmed transactions
+    #[command(name = "listfeerates")]
+    ListFeeRates {
+        #[arg(default_value = "10")]
+        count: usize,
+    },
+    /// List the wallet's local chain checkpoints, newest first
+    #[command(name = "listcheckpoints")]
+    ListCheckpoints {
+        #[arg(default_value = "20")]
+        count: usize,
+    },
+    /// Remove unconfirmed transactions that were dropped from the node's mempool
+    #[command(name = "reconcile")]
+    Reconcile,
+    /// Remove all unconfirmed transactions from the wallet, freeing their inputs
+    #[command(name = "abandonallpending")]
+    AbandonAllPending,
+    /// Abandon a stuck open, freeing its inputs
+    #[command(name = "abandonopen")]
+    AbandonOpen {
+        /// The space name
+        space: String,
+        /// Fee rate in sat/vB for spending the committed funds back to the wallet,
+        /// estimated if not given and raised if needed to replace an open still in the mempool
+        #[arg(long)]
+        reclaim_fee_rate: Option<u64>,
+    },
+    /// List coins that cost more in fees to spend than they're worth
+    #[command(name = "listdust")]
+    ListDust {
+        /// Fee rate to evaluate spending costs at in sat/vB
+        #[arg(long, short)]
+        fee_rate: u64,
+    },
+    /// Show which coins would be spent to send an amount and whether change is created
+    #[command(name = "estimateselection")]
+    EstimateSelection {
+        /// Amount to send in sat
+        amount: u64,
+        /// Fee rate to use in sat/vB
+        #[arg(long, short)]
+        fee_rate: u64,
+    },
+    /// Estimate the virtual size of the transactions a request needs before building them
+    #[command(name = "estimatevsize")]
+    EstimateVsize {
+        /// The request as JSON e.g. '{"request":"bid","name":"@example","amount":1000}'
+        request: String,
+    },
+    /// Get space info along with whether this wallet owns it
+    #[command(name = "getspaceinfo")]
+    GetSpaceInfo {
+        /// The space name
+        space: String,
+    },
+    /// Sign a message with the key of a space to prove ownership without spending it
+    #[command(name = "signmessage")]
+    SignMessage {
+        /// The space to sign with e.g. @example
+        space: String,
+        /// The message to sign
+        message: String,
+    },
+    /// Check whether a request would currently be accepted and why not, without building it
+    #[command(name = "checkoperation")]
+    CheckOperation {
+        /// The request as JSON e.g. '{"request":"bid","name":"@example","amount":1000}'
+        request: String,
+    },
+    /// Show the most that can be sent to a recipient after fees by sweeping all spendable coins
+    #[command(name = "getmaxsendable")]
+    GetMaxSendable {
+        /// Recipient space name or address
+        to: String,
+        /// Fee rate to use in sat/vB
+        #[arg(long, short)]
+        fee_rate: u64,
+    },
+    /// Build an unsigned base64 psbt for a single bid, transfer, register or send
+    /// funded only by the given inputs, without broadcasting it
+    #[command(name = "buildpsbt")]
+    BuildPsbt {
+        /// The request as JSON e.g. '{"request":"bid","name":"@example","amount":1000}'
+        request: String,
+        /// Wallet coins to fund the psbt with
+        #[arg(long, required = true, num_args = 1..)]
+        inputs: Vec<OutPoint>,
+        /// Fee rate to use in sat/vB
+        #[arg(long, short)]
+        fee_rate: Option<u64>,
+    },
+    /// Export the wallet transaction history confirmed up to the wallet's tip for accounting
+    #[command(name = "exporthistory")]
+    ExportHistory {
+        /// Output format
+        #[arg(long, default_value = "csv")]
+        format: ExportFormat,
+        /// Destination file [default: stdout]
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
     /// List won spaces including ones
     /// still in auction with a winning bid
     #[command(name = "listspaces")]
     ListSpaces,
+    /// List owned spaces grouped by stage: pre-auction, in auction, claimable and registered
+    #[command(name = "listspacesbystate")]
+    ListSpacesByState {
+        /// Only list spaces in this stage
+        #[arg(long)]
+        state: Option<SpaceStateFilter>,
+    },
     /// List unspent auction outputs i.e. outputs that can be
-    /// auctioned off in the bidding process
+    /// auctioned off in the bidding process, with their confirmation depth
     #[command(name = "listbidouts")]
     ListBidOuts,
     /// List unspent coins owned by wallet
     #[command(name = "listunspent")]
     ListUnspent,
+    /// List unspent outputs grouped into spaces, spendable coins and reserved outputs with totals
+    #[command(name = "listoutputgroups")]
+    ListOutputGroups,
+    /// Show the balance, spaces and unspent outputs read at the same wallet tip
+    #[command(name = "getsnapshot")]
+    GetSnapshot,
     /// Get a new Bitcoin address suitable for receiving spaces and coins
     /// (Spaces compatible bitcoin wallets only)
     #[command(name = "getnewspaceaddress")]
-    GetSpaceAddress,
+    GetSpaceAddress {
+        /// Also return a payment URI that can be rendered as a QR code
+        #[arg(long)]
+        uri: bool,
+        /// Label to include in the payment URI
+        #[arg(long, requires = "uri")]
+        label: Option<String>,
+    },
     /// Get a new Bitcoin address suitable for receiving coins
     /// compatible with most bitcoin wallets
     #[command(name = "getnewaddress")]
-    GetCoinAddress,
+    GetCoinAddress {
+        /// Also return a BIP-21 payment URI that can be rendered as a QR code
+        #[arg(long)]
+        uri: bool,
+        /// Amount in sat to request in the payment URI
+        #[arg(long, requires = "uri")]
+        amount: Option<u64>,
+        /// Label to include in the payment URI
+        #[arg(long, requires = "uri")]
+        label: Option<String>,
+    },
+    /// Derive the address and script at a given index without revealing it
+    #[command(name = "deriveaddress")]
+    DeriveAddress {
+        /// Derivation index
+        index: u32,
+        /// Derive from the internal (change) keychain
+        #[arg(long)]
+        internal: bool,
+    },
     /// Force spend an output owned by wallet (for testing only)
     #[command(name = "forcespend")]
     ForceSpend {
@@ -242,9 +640,19 @@ enum Commands {
 
 struct SpaceCli {
     wallet: String,
+    account: Option<u32>,
+    conf_target: Option<u16>,
+    estimate_mode: Option<EstimateMode>,
     dust: Option<Amount>,
     force: bool,
     skip_tx_check: bool,
+    coin_selection: CoinSelectionStrategy,
+    avoid_address_reuse: bool,
+    donate_dust_change_to_fee: bool,
+    outbid_limits: OutbidLimits,
+    resolve_pending: bool,
+    confirm_token: Option<String>,
+    preview: bool,
     network: ExtendedNetwork,
     rpc_url: String,
     client: HttpClient,
@@ -261,9 +669,24 @@ impl SpaceCli {
         Ok((
             Self {
                 wallet: args.wallet.clone(),
+                account: args.account,
+                conf_target: args.conf_target,
+                estimate_mode: args.estimate_mode,
                 dust: args.dust.map(|d| Amount::from_sat(d)),
                 force: args.force,
                 skip_tx_check: args.skip_tx_check,
+                coin_selection: args.coin_selection,
+                avoid_address_reuse: args.avoid_address_reuse,
+                donate_dust_change_to_fee: args.donate_dust_change_to_fee,
+                outbid_limits: OutbidLimits {
+                    max_outbid_multiplier: args.max_outbid_multiplier,
+                    max_fee_rate: args
+                        .max_outbid_fee_rate
+                        .and_then(FeeRate::from_sat_per_vb),
+                },
+                resolve_pending: args.resolve_pending,
+                confirm_token: args.confirm_token.clone(),
+                preview: args.preview,
                 network: args.chain,
                 rpc_url: args.spaced_rpc_url.clone().unwrap(),
                 client,
@@ -280,24 +703,34 @@ impl SpaceCli {
         confirmed_only: bool,
     ) -> Result<(), ClientError> {
         let fee_rate = fee_rate.map(|fee| FeeRate::from_sat_per_vb(fee).unwrap());
-        let result = self
-            .client
-            .wallet_send_request(
-                &self.wallet,
-                RpcWalletTxBuilder {
-                    bidouts,
-                    requests: match req {
-                        None => vec![],
-                        Some(req) => vec![req],
-                    },
-                    fee_rate,
-                    dust: self.dust,
-                    force: self.force,
-                    confirmed_only,
-                    skip_tx_check: self.skip_tx_check,
-                },
-            )
-            .await?;
+        let request = RpcWalletTxBuilder {
+            bidouts,
+            requests: match req {
+                None => vec![],
+                Some(req) => vec![req],
+            },
+            fee_rate,
+            fee: None,
+            conf_target: self.conf_target,
+            estimate_mode: self.estimate_mode,
+            dust: self.dust,
+            force: self.force,
+            confirmed_only,
+            skip_tx_check: self.skip_tx_check,
+            coin_selection: self.coin_selection,
+            avoid_address_reuse: self.avoid_address_reuse,
+            donate_dust_change_to_fee: self.donate_dust_change_to_fee,
+            outbid_limits: self.outbid_limits.clone(),
+            resolve_pending: self.resolve_pending,
+            expected_tip: None,
+            confirm_token: self.confirm_token.clone(),
+            broadcast: true,
+        };
+        let result = if self.preview {
+            self.client.wallet_preview_request(&self.wallet, request).await?
+        } else {
+            self.client.wallet_send_request(&self.wallet, request).await?
+        };
 
         println!(
             "{}",
@@ -407,15 +840,74 @@ async fn handle_commands(
             let response = cli.client.get_space(&space_hash).await?;
             println!("{}", serde_json::to_string_pretty(&response)?);
         }
+        Commands::GetAuctionStatus { space } => {
+            let space_hash = hash_space(&space).map_err(|e| ClientError::Custom(e.to_string()))?;
+            let response = cli.client.get_auction_status(&space_hash).await?;
+            println!("{}", serde_json::to_string_pretty(&response)?);
+        }
+        Commands::ValidateName { space } => {
+            let response = cli.client.validate_name(&normalize_space(&space)).await?;
+            println!("{}", serde_json::to_string_pretty(&response)?);
+        }
+        Commands::GetSpaceOutStatus { space } => {
+            let space_hash = hash_space(&space).map_err(|e| ClientError::Custom(e.to_string()))?;
+            let response = cli.client.get_spaceout_status(&space_hash).await?;
+            println!("{}", serde_json::to_string_pretty(&response)?);
+        }
+        Commands::VerifySpaceAddress { space, address } => {
+            let response = cli
+                .client
+                .verify_space_address(&normalize_space(&space), &address)
+                .await?;
+            println!("{}", serde_json::to_string_pretty(&response)?);
+        }
+        Commands::GetAuctionTimeline { space } => {
+            let space_hash = hash_space(&space).map_err(|e| ClientError::Custom(e.to_string()))?;
+            let response = cli.client.get_auction_timeline(&space_hash).await?;
+            println!("{}", serde_json::to_string_pretty(&response)?);
+        }
+        Commands::GetOwnershipProof { space } => {
+            let space_hash = hash_space(&space).map_err(|e| ClientError::Custom(e.to_string()))?;
+            let response = cli.client.get_ownership_proof(&space_hash).await?;
+            println!("{}", serde_json::to_string_pretty(&response)?);
+        }
+        Commands::CheckAvailability { spaces } => {
+            let names = spaces.iter().map(|s| normalize_space(s)).collect();
+            let response = cli.client.check_availability(names).await?;
+            println!("{}", serde_json::to_string_pretty(&response)?);
+        }
         Commands::GetSpaceOut { outpoint } => {
             let response = cli.client.get_spaceout(outpoint).await?;
             println!("{}", serde_json::to_string_pretty(&response)?);
         }
+        Commands::GetSpacesByScript {
+            address_or_script,
+            cursor,
+            limit,
+        } => {
+            let response = cli
+                .client
+                .get_spaces_by_script(&address_or_script, cursor, limit)
+                .await?;
+            println!("{}", serde_json::to_string_pretty(&response)?);
+        }
         Commands::CreateWallet => {
             cli.client.wallet_create(&cli.wallet).await?;
         }
-        Commands::LoadWallet => {
-            cli.client.wallet_load(&cli.wallet).await?;
+        Commands::RecoverWallet {
+            mnemonic,
+            passphrase,
+            start_height,
+        } => {
+            cli.client
+                .wallet_recover(&cli.wallet, &mnemonic, passphrase, start_height)
+                .await?;
+        }
+        Commands::LoadWallet { passphrase } => {
+            cli.client.wallet_load(&cli.wallet, passphrase).await?;
+        }
+        Commands::EncryptWallet { passphrase } => {
+            cli.client.wallet_encrypt(&cli.wallet, passphrase).await?;
         }
         Commands::ImportWallet { path } => {
             let content =
@@ -423,8 +915,8 @@ async fn handle_commands(
             let wallet: WalletExport = serde_json::from_str(&content)?;
             cli.client.wallet_import(wallet).await?;
         }
-        Commands::ExportWallet { path } => {
-            let result = cli.client.wallet_export(&cli.wallet).await?;
+        Commands::ExportWallet { path, passphrase } => {
+            let result = cli.client.wallet_export(&cli.wallet, passphrase).await?;
             let content = serde_json::to_string_pretty(&result).expect("result");
             fs::write(path, content).map_err(|e| {
                 ClientError::Custom(format!("Could not save to path: {}", e.to_string()))
@@ -434,10 +926,26 @@ async fn handle_commands(
             let result = cli.client.wallet_get_info(&cli.wallet).await?;
             println!("{}", serde_json::to_string_pretty(&result).expect("result"));
         }
+        Commands::GetWalletMetadata => {
+            let result = cli.client.wallet_get_metadata(&cli.wallet).await?;
+            println!("{}", serde_json::to_string_pretty(&result)?);
+        }
         Commands::GetServerInfo => {
             let result = cli.client.get_server_info().await?;
             println!("{}", serde_json::to_string_pretty(&result).expect("result"));
         }
+        Commands::GetNodeSyncStatus => {
+            let result = cli.client.get_node_sync_status().await?;
+            println!("{}", serde_json::to_string_pretty(&result).expect("result"));
+        }
+        Commands::GetProtocolStatus => {
+            let result = cli.client.get_protocol_status().await?;
+            println!("{}", serde_json::to_string_pretty(&result).expect("result"));
+        }
+        Commands::GetBlockTime { height } => {
+            let result = cli.client.get_block_time(height).await?;
+            println!("{}", serde_json::to_string_pretty(&result).expect("result"));
+        }
         Commands::Open {
             ref space,
             initial_bid,
@@ -510,12 +1018,14 @@ async fn handle_commands(
         Commands::SendCoins {
             amount,
             to,
+            from,
             fee_rate,
         } => {
             cli.send_request(
                 Some(RpcWalletRequest::SendCoins(SendCoinsParams {
-                    amount: Amount::from_sat(amount),
+                    amount: amount.map(Amount::from_sat),
                     to,
+                    from,
                 })),
                 None,
                 fee_rate,
@@ -545,6 +1055,7 @@ async fn handle_commands(
                 Some(RpcWalletRequest::Execute(ExecuteParams {
                     context: vec![space],
                     space_script,
+                    inputs: vec![],
                 })),
                 None,
                 fee_rate,
@@ -553,9 +1064,23 @@ async fn handle_commands(
             .await?;
         }
         Commands::ListUnspent => {
-            let spaces = cli.client.wallet_list_unspent(&cli.wallet).await?;
+            let spaces = cli.client.wallet_list_unspent(&cli.wallet, cli.account).await?;
             println!("{}", serde_json::to_string_pretty(&spaces)?);
         }
+        Commands::ListOutputGroups => {
+            let groups = cli
+                .client
+                .wallet_list_output_groups(&cli.wallet, cli.account)
+                .await?;
+            println!("{}", serde_json::to_string_pretty(&groups)?);
+        }
+        Commands::GetSnapshot => {
+            let snapshot = cli
+                .client
+                .wallet_get_snapshot(&cli.wallet, cli.account)
+                .await?;
+            println!("{}", serde_json::to_string_pretty(&snapshot)?);
+        }
         Commands::ListBidOuts => {
             let spaces = cli.client.wallet_list_bidouts(&cli.wallet).await?;
             println!("{}", serde_json::to_string_pretty(&spaces)?);
@@ -563,37 +1088,314 @@ async fn handle_commands(
         Commands::ListTransactions { count, skip } => {
             let txs = cli
                 .client
-                .wallet_list_transactions(&cli.wallet, count, skip)
+                .wallet_list_transactions(&cli.wallet, count, skip, cli.account)
                 .await?;
             println!("{}", serde_json::to_string_pretty(&txs)?);
         }
+        Commands::ListFeeRates { count } => {
+            let rates = cli.client.wallet_list_fee_rates(&cli.wallet, count).await?;
+            println!("{}", serde_json::to_string_pretty(&rates)?);
+        }
+        Commands::ListCheckpoints { count } => {
+            let checkpoints = cli.client.wallet_list_checkpoints(&cli.wallet, count).await?;
+            println!("{}", serde_json::to_string_pretty(&checkpoints)?);
+        }
+        Commands::Reconcile => {
+            let report = cli.client.wallet_reconcile(&cli.wallet).await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        Commands::AbandonAllPending => {
+            let report = cli.client.wallet_abandon_all_pending(&cli.wallet).await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        Commands::AbandonOpen {
+            space,
+            reclaim_fee_rate,
+        } => {
+            let reclaim_fee_rate = reclaim_fee_rate
+                .map(|rate| FeeRate::from_sat_per_vb(rate).expect("valid fee rate"));
+            let report = cli
+                .client
+                .wallet_abandon_open(&cli.wallet, normalize_space(&space), reclaim_fee_rate)
+                .await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        Commands::ListDust { fee_rate } => {
+            let fee_rate = FeeRate::from_sat_per_vb(fee_rate).expect("valid fee rate");
+            let report = cli.client.wallet_list_dust(&cli.wallet, fee_rate).await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        Commands::EstimateSelection { amount, fee_rate } => {
+            let fee_rate = FeeRate::from_sat_per_vb(fee_rate).expect("valid fee rate");
+            let estimate = cli
+                .client
+                .wallet_estimate_selection(&cli.wallet, Amount::from_sat(amount), fee_rate)
+                .await?;
+            println!("{}", serde_json::to_string_pretty(&estimate)?);
+        }
+        Commands::EstimateVsize { request } => {
+            let request: RpcWalletRequest = serde_json::from_str(&request)?;
+            let estimate = cli.client.wallet_estimate_vsize(&cli.wallet, request).await?;
+            println!("{}", serde_json::to_string_pretty(&estimate)?);
+        }
+        Commands::GetSpaceInfo { space } => {
+            let space = normalize_space(&space);
+            let info = cli.client.wallet_get_space_info(&cli.wallet, space).await?;
+            println!("{}", serde_json::to_string_pretty(&info)?);
+        }
+        Commands::SignMessage { space, message } => {
+            let space = normalize_space(&space);
+            let signed = cli
+                .client
+                .wallet_sign_message(&cli.wallet, space, message)
+                .await?;
+            println!("{}", serde_json::to_string_pretty(&signed)?);
+        }
+        Commands::CheckOperation { request } => {
+            let request: RpcWalletRequest = serde_json::from_str(&request)?;
+            let check = cli
+                .client
+                .wallet_check_operation(&cli.wallet, request)
+                .await?;
+            println!("{}", serde_json::to_string_pretty(&check)?);
+        }
+        Commands::GetMaxSendable { to, fee_rate } => {
+            let fee_rate = FeeRate::from_sat_per_vb(fee_rate).expect("valid fee rate");
+            let max = cli
+                .client
+                .wallet_get_max_sendable(&cli.wallet, fee_rate, to)
+                .await?;
+            println!("{}", serde_json::to_string_pretty(&max)?);
+        }
+        Commands::BuildPsbt {
+            request,
+            inputs,
+            fee_rate,
+        } => {
+            let request: RpcWalletRequest = serde_json::from_str(&request)?;
+            let fee_rate = fee_rate.map(|fee| FeeRate::from_sat_per_vb(fee).unwrap());
+            let psbt = cli
+                .client
+                .wallet_build_psbt(&cli.wallet, request, inputs, fee_rate)
+                .await?;
+            println!("{}", psbt);
+        }
+        Commands::ExportHistory { format, output } => {
+            const PAGE_SIZE: usize = 500;
+
+            let dest: Box<dyn Write> = match output {
+                None => Box::new(std::io::stdout()),
+                Some(path) => Box::new(fs::File::create(path).map_err(|e| {
+                    ClientError::Custom(format!("Could not create file: {}", e))
+                })?),
+            };
+            let mut writer = BufWriter::new(dest);
+            // Every page is read as of the same tip so they don't shift mid-export
+            let as_of = cli.client.wallet_get_info(&cli.wallet).await?.tip;
+            let mut skip = 0;
+            loop {
+                let page = cli
+                    .client
+                    .wallet_export_history(&cli.wallet, format, Some(as_of), skip, PAGE_SIZE)
+                    .await?;
+                if page.is_empty() {
+                    break;
+                }
+                writer
+                    .write_all(page.as_bytes())
+                    .map_err(|e| ClientError::Custom(e.to_string()))?;
+                skip += PAGE_SIZE;
+            }
+            writer
+                .flush()
+                .map_err(|e| ClientError::Custom(e.to_string()))?;
+        }
         Commands::ListSpaces => {
-            let spaces = cli.client.wallet_list_spaces(&cli.wallet).await?;
+            let spaces = cli.client.wallet_list_spaces(&cli.wallet, cli.account).await?;
+            println!("{}", serde_json::to_string_pretty(&spaces)?);
+        }
+        Commands::ListSpacesByState { state } => {
+            let spaces = cli
+                .client
+                .wallet_list_spaces_by_state(&cli.wallet, state)
+                .await?;
             println!("{}", serde_json::to_string_pretty(&spaces)?);
         }
         Commands::Balance => {
-            let balance = cli.client.wallet_get_balance(&cli.wallet).await?;
+            let balance = cli.client.wallet_get_balance(&cli.wallet, cli.account).await?;
             println!("{}", serde_json::to_string_pretty(&balance)?);
         }
-        Commands::GetCoinAddress => {
+        Commands::GetSpacesValueLocked => {
+            let locked = cli.client.wallet_get_spaces_value_locked(&cli.wallet).await?;
+            println!("{}", serde_json::to_string_pretty(&locked)?);
+        }
+        Commands::SetFeePolicy { fixed, target } => {
+            let policy = match (fixed, target) {
+                (Some(fee_rate), _) => FeePolicy::Fixed(
+                    FeeRate::from_sat_per_vb(fee_rate)
+                        .ok_or_else(|| ClientError::Custom("invalid fee rate".to_string()))?,
+                ),
+                (None, Some(target)) => FeePolicy::Target(target),
+                (None, None) => FeePolicy::NodeEstimate,
+            };
+            cli.client.wallet_set_fee_policy(&cli.wallet, policy).await?;
+        }
+        Commands::GetFeePolicy => {
+            let policy = cli.client.wallet_get_fee_policy(&cli.wallet).await?;
+            println!("{}", serde_json::to_string_pretty(&policy)?);
+        }
+        Commands::SetConfirmPolicy { threshold } => {
+            let policy = ConfirmPolicy {
+                confirm_high_value: threshold.map(Amount::from_sat),
+            };
+            cli.client.wallet_set_confirm_policy(&cli.wallet, policy).await?;
+        }
+        Commands::GetConfirmPolicy => {
+            let policy = cli.client.wallet_get_confirm_policy(&cli.wallet).await?;
+            println!("{}", serde_json::to_string_pretty(&policy)?);
+        }
+        Commands::BroadcastBatch { raw_txs } => {
+            let result = cli.client.wallet_broadcast_batch(&cli.wallet, raw_txs).await?;
+            println!("{}", serde_json::to_string_pretty(&result)?);
+        }
+        Commands::SetTxNote { txid, note } => {
+            cli.client.wallet_set_tx_note(&cli.wallet, txid, note).await?;
+        }
+        Commands::GetTxNote { txid } => {
+            let note = cli.client.wallet_get_tx_note(&cli.wallet, txid).await?;
+            println!("{}", serde_json::to_string_pretty(&note)?);
+        }
+        Commands::GetSyncProgress => {
+            let progress = cli.client.wallet_get_sync_progress(&cli.wallet).await?;
+            println!("{}", serde_json::to_string_pretty(&progress)?);
+        }
+        Commands::DeriveAddress { index, internal } => {
+            let response = cli
+                .client
+                .wallet_derive_address(&cli.wallet, internal, index, cli.account)
+                .await?;
+            println!("{}", serde_json::to_string_pretty(&response)?);
+        }
+        Commands::GetCoinAddress {
+            uri: true,
+            amount,
+            label,
+        } => {
+            let response = cli
+                .client
+                .wallet_get_new_address_uri(
+                    &cli.wallet,
+                    AddressKind::Coin,
+                    amount.map(Amount::from_sat),
+                    label,
+                    cli.account,
+                )
+                .await?;
+            println!("{}", serde_json::to_string_pretty(&response)?);
+        }
+        Commands::GetCoinAddress { .. } => {
             let response = cli
                 .client
-                .wallet_get_new_address(&cli.wallet, AddressKind::Coin)
+                .wallet_get_new_address(&cli.wallet, AddressKind::Coin, cli.account)
                 .await?;
             println!("{}", response);
         }
-        Commands::GetSpaceAddress => {
+        Commands::GetSpaceAddress { uri: true, label } => {
+            let response = cli
+                .client
+                .wallet_get_new_address_uri(
+                    &cli.wallet,
+                    AddressKind::Space,
+                    None,
+                    label,
+                    cli.account,
+                )
+                .await?;
+            println!("{}", serde_json::to_string_pretty(&response)?);
+        }
+        Commands::GetSpaceAddress { .. } => {
             let response = cli
                 .client
-                .wallet_get_new_address(&cli.wallet, AddressKind::Space)
+                .wallet_get_new_address(&cli.wallet, AddressKind::Space, cli.account)
                 .await?;
             println!("{}", response);
         }
-        Commands::BumpFee { txid, fee_rate } => {
+        Commands::BumpFee {
+            txid,
+            fee_rate,
+            any_wallet,
+            dry_run,
+        } => {
             let fee_rate = FeeRate::from_sat_per_vb(fee_rate).expect("valid fee rate");
+            let response = if any_wallet {
+                cli.client
+                    .bump_fee(txid, fee_rate, cli.skip_tx_check, Some(dry_run))
+                    .await?
+            } else {
+                cli.client
+                    .wallet_bump_fee(
+                        &cli.wallet,
+                        txid,
+                        fee_rate,
+                        cli.skip_tx_check,
+                        Some(dry_run),
+                    )
+                    .await?
+            };
+            println!("{}", serde_json::to_string_pretty(&response)?);
+        }
+        Commands::BumpFeeToTarget {
+            txid,
+            conf_target,
+            dry_run,
+        } => {
+            let response = cli
+                .client
+                .wallet_bump_fee_to_target(&cli.wallet, txid, conf_target, Some(dry_run))
+                .await?;
+            println!("{}", serde_json::to_string_pretty(&response)?);
+        }
+        Commands::BumpAllStuck { fee_rate, min_age } => {
+            let fee_rate = FeeRate::from_sat_per_vb(fee_rate).expect("valid fee rate");
+            let response = cli
+                .client
+                .wallet_bump_all_stuck(&cli.wallet, min_age, fee_rate)
+                .await?;
+            println!("{}", serde_json::to_string_pretty(&response)?);
+        }
+        Commands::ListBumpCandidates {
+            min_age,
+            conf_target,
+        } => {
+            let response = cli
+                .client
+                .wallet_list_bump_candidates(&cli.wallet, min_age, conf_target)
+                .await?;
+            println!("{}", serde_json::to_string_pretty(&response)?);
+        }
+        Commands::CancelBid { txid, fee_rate } => {
+            let fee_rate = FeeRate::from_sat_per_vb(fee_rate).expect("valid fee rate");
+            let response = cli
+                .client
+                .wallet_cancel_bid(&cli.wallet, txid, fee_rate)
+                .await?;
+            println!("{}", serde_json::to_string_pretty(&response)?);
+        }
+        Commands::IsReplaceable { txid } => {
+            let response = cli.client.wallet_is_replaceable(&cli.wallet, txid).await?;
+            println!("{}", serde_json::to_string_pretty(&response)?);
+        }
+        Commands::GetClaimStatus { space } => {
+            let response = cli
+                .client
+                .wallet_get_claim_status(&cli.wallet, normalize_space(&space))
+                .await?;
+            println!("{}", serde_json::to_string_pretty(&response)?);
+        }
+        Commands::GetSpaceScript { space } => {
             let response = cli
                 .client
-                .wallet_bump_fee(&cli.wallet, txid, fee_rate, cli.skip_tx_check)
+                .wallet_get_space_script(&cli.wallet, normalize_space(&space))
                 .await?;
             println!("{}", serde_json::to_string_pretty(&response)?);
         }
@@ -56,11 +56,21 @@ impl Composer {
     async fn setup_rpc_wallet(&mut self, spaced: &Spaced, rx: mpsc::Receiver<LoadedWallet>) {
         let wallet_service = RpcWallet::service(
             spaced.network,
-            spaced.rpc.clone(),
+            spaced.fee_estimation,
+            // One limiter shared by every wallet's clone of the client
+            spaced
+                .rpc
+                .clone()
+                .with_request_limit(spaced.wallet_rpc_concurrency),
+            spaced.rpc_pool,
             spaced.chain.state.clone(),
             rx,
             self.shutdown.clone(),
             spaced.num_workers,
+            spaced.wallet_catch_up_threshold,
+            spaced.tip_refetch_depth,
+            spaced.wallet_tip_preview_depth,
+            spaced.dropped_tx_grace_period,
         );
 
         self.services.spawn(async move {
@@ -79,6 +89,8 @@ impl Composer {
             rpc: spaced.rpc.clone(),
             wallet_loader: wallet_loader_tx,
             wallets: Arc::new(Default::default()),
+            unlocked: Arc::new(Default::default()),
+            account_loading: Arc::new(Default::default()),
         };
 
         let (async_chain_state, async_chain_state_handle) = create_async_store(
@@ -114,9 +126,10 @@ impl Composer {
 
         let shutdown = self.shutdown.clone();
         let rpc = spaced.rpc.clone();
+        let rpc_pool = spaced.rpc_pool;
 
         std::thread::spawn(move || {
-            let source = BitcoinBlockSource::new(rpc);
+            let source = BitcoinBlockSource::with_pool(rpc, rpc_pool);
             _ = spaced_sender.send(spaced.protocol_sync(source, shutdown));
         });
 
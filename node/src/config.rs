@@ -6,6 +6,7 @@ use std::{
     fs,
     net::{IpAddr, SocketAddr},
     path::PathBuf,
+    time::Duration,
 };
 
 use clap::{
@@ -15,14 +16,19 @@ use clap::{
 use directories::ProjectDirs;
 use jsonrpsee::core::Serialize;
 use log::error;
-use protocol::bitcoin::Network;
+use protocol::bitcoin::{Amount, FeeRate, Network};
 use serde::Deserialize;
 use toml::Value;
 
 use crate::{
-    source::{BitcoinRpc, BitcoinRpcAuth},
+    source::{
+        BitcoinRpc, BitcoinRpcAuth, ClientPool, RetryPolicy, DEFAULT_MAX_BURN_AMOUNT,
+        DEFAULT_MAX_FEE_RATE, DEFAULT_MAX_RETRIES, DEFAULT_POOL_IDLE_TIMEOUT,
+        DEFAULT_POOL_MAX_IDLE, DEFAULT_RETRY_DELAY, DEFAULT_TIP_REFETCH_DEPTH,
+    },
     store::{LiveStore, Store},
     sync::Spaced,
+    wallets::{DEFAULT_DROPPED_TX_GRACE_PERIOD, DEFAULT_WALLET_RPC_CONCURRENCY},
 };
 
 const RPC_OPTIONS: &str = "RPC Server Options";
@@ -47,7 +53,8 @@ pub struct Args {
     /// Network to use
     #[arg(long, env = "SPACED_CHAIN", default_value = "mainnet")]
     chain: ExtendedNetwork,
-    /// Number of concurrent workers allowed during syncing
+    /// Number of concurrent workers allowed during syncing, each fetching
+    /// a few blocks at a time from the bitcoin node
     #[arg(short, long, env = "SPACED_JOBS", default_value = "8")]
     jobs: u8,
     /// Bitcoin RPC URL
@@ -66,6 +73,32 @@ pub struct Args {
     /// Bitcoin RPC password
     #[arg(long, env = "SPACED_BITCOIN_RPC_PASSWORD")]
     bitcoin_rpc_password: Option<String>,
+    /// External endpoint to broadcast raw transactions through (e.g. an esplora `/api/tx` url)
+    /// when the bitcoin node doesn't allow `sendrawtransaction`
+    #[arg(long, env = "SPACED_BITCOIN_BROADCAST_URL")]
+    bitcoin_broadcast_url: Option<String>,
+    /// Fee rate in sat/vB above which the bitcoin node refuses to broadcast a
+    /// wallet transaction, guarding against accidental overpayment (0 disables)
+    #[arg(long, env = "SPACED_BITCOIN_MAX_FEE_RATE", default_value_t = DEFAULT_MAX_FEE_RATE)]
+    bitcoin_max_fee_rate: u64,
+    /// Value in sat of unspendable outputs (e.g. bid burns) above which the bitcoin
+    /// node refuses to broadcast a wallet transaction
+    #[arg(long, env = "SPACED_BITCOIN_MAX_BURN_AMOUNT", default_value_t = DEFAULT_MAX_BURN_AMOUNT)]
+    bitcoin_max_burn_amount: u64,
+    /// Times a request to the bitcoin node failing with a temporary error is retried
+    #[arg(long, env = "SPACED_BITCOIN_RPC_MAX_RETRIES", default_value_t = DEFAULT_MAX_RETRIES)]
+    bitcoin_rpc_max_retries: usize,
+    /// Milliseconds before the first retry of a failed request to the bitcoin node,
+    /// doubled for each retry after it with random jitter
+    #[arg(long, env = "SPACED_BITCOIN_RPC_RETRY_DELAY", default_value_t = DEFAULT_RETRY_DELAY)]
+    bitcoin_rpc_retry_delay: u64,
+    /// Append a JSON line with the txid, tags, fee and raw hex of every
+    /// transaction broadcast by a wallet to this file
+    #[arg(long, env = "SPACED_BROADCAST_LOG")]
+    broadcast_log: Option<PathBuf>,
+    /// Path to bitcoind's blocks directory to read blocks from directly during initial sync
+    #[arg(long, env = "SPACED_BITCOIN_BLOCKS_DIR")]
+    bitcoin_blocks_dir: Option<PathBuf>,
     /// Bind to given address to listen for JSON-RPC connections.
     /// This option can be specified multiple times (default: 127.0.0.1 and ::1 i.e., localhost)
     #[arg(long, help_heading = Some(RPC_OPTIONS), default_values = ["127.0.0.1", "::1"], env = "SPACED_RPC_BIND")]
@@ -76,6 +109,62 @@ pub struct Args {
     /// Index blocks including the full transaction data
     #[arg(long, env = "SPACED_BLOCK_INDEX_FULL", default_value = "false")]
     block_index_full: bool,
+    /// Confirmation target used for fee estimation (defaults depend on the network)
+    #[arg(long, env = "SPACED_FEE_ESTIMATE_TARGET")]
+    fee_estimate_target: Option<u16>,
+    /// Estimate mode passed to bitcoind's `estimatesmartfee`
+    #[arg(long, env = "SPACED_FEE_ESTIMATE_MODE")]
+    fee_estimate_mode: Option<EstimateMode>,
+    /// Fee rate in sat/vB to use when no estimate is available
+    #[arg(long, env = "SPACED_FALLBACK_FEE_RATE")]
+    fallback_fee_rate: Option<u64>,
+    /// Number of blocks a wallet may fall behind the chain tip before it switches to
+    /// catch-up mode, processing blocks back to back and deferring balance updates
+    #[arg(long, env = "SPACED_WALLET_CATCH_UP_THRESHOLD", default_value = "12")]
+    wallet_catch_up_threshold: u32,
+    /// Blocks within this distance of the tip are re-fetched when orphaned by a reorg
+    /// instead of rolling back to an earlier checkpoint (0 disables)
+    #[arg(long, env = "SPACED_TIP_REFETCH_DEPTH", default_value_t = DEFAULT_TIP_REFETCH_DEPTH)]
+    tip_refetch_depth: u32,
+    /// Number of blocks a wallet must be behind the chain tip for the tip block to be
+    /// fetched ahead of the rest, reporting wallet transactions confirmed in it before
+    /// the wallet syncs to it (0 disables)
+    #[arg(long, env = "SPACED_WALLET_TIP_PREVIEW_DEPTH", default_value = "0")]
+    wallet_tip_preview_depth: u32,
+    /// Seconds an unconfirmed wallet transaction must be missing from the node's
+    /// mempool before it's treated as dropped and removed from the wallet
+    #[arg(long, env = "SPACED_DROPPED_TX_GRACE_PERIOD", default_value_t = DEFAULT_DROPPED_TX_GRACE_PERIOD)]
+    dropped_tx_grace_period: u64,
+    /// Maximum number of requests all loaded wallets together may have in flight to the
+    /// bitcoin node, regardless of how many are loaded (0 disables the limit)
+    #[arg(long, env = "SPACED_WALLET_RPC_CONCURRENCY", default_value_t = DEFAULT_WALLET_RPC_CONCURRENCY)]
+    wallet_rpc_concurrency: usize,
+    /// Maximum number of idle connections to the bitcoin node kept open for reuse
+    #[arg(long, env = "SPACED_BITCOIN_RPC_POOL_SIZE", default_value_t = DEFAULT_POOL_MAX_IDLE)]
+    bitcoin_rpc_pool_size: usize,
+    /// Seconds an idle connection to the bitcoin node is kept open for reuse
+    /// (0 keeps them open indefinitely)
+    #[arg(long, env = "SPACED_BITCOIN_RPC_IDLE_TIMEOUT", default_value_t = DEFAULT_POOL_IDLE_TIMEOUT)]
+    bitcoin_rpc_idle_timeout: u64,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EstimateMode {
+    Unset,
+    Economical,
+    Conservative,
+}
+
+/// Fee estimation settings used by wallets when a request doesn't specify a fee rate
+#[derive(Copy, Clone, Debug)]
+pub struct FeeEstimation {
+    pub conf_target: u16,
+    pub mode: EstimateMode,
+    /// Whether the node's estimator should be consulted at all
+    pub use_estimator: bool,
+    /// Used when the estimator is disabled or has no estimate yet
+    pub fallback: Option<FeeRate>,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, ValueEnum, Serialize, Deserialize)]
@@ -150,7 +239,9 @@ impl Args {
             .collect();
 
         let bitcoin_rpc_auth = if let Some(cookie) = args.bitcoin_rpc_cookie {
-            let cookie = std::fs::read_to_string(cookie)?;
+            // Fail early on a missing cookie file, it's re-read whenever the
+            // node rejects a request after a restart
+            std::fs::read_to_string(&cookie)?;
             BitcoinRpcAuth::Cookie(cookie)
         } else if let Some(user) = args.bitcoin_rpc_user {
             BitcoinRpcAuth::UserPass(user, args.bitcoin_rpc_password.expect("password"))
@@ -158,10 +249,45 @@ impl Args {
             BitcoinRpcAuth::None
         };
 
-        let rpc = BitcoinRpc::new(
+        if args.jobs == 0 {
+            return Err(anyhow::anyhow!("jobs must be at least 1"));
+        }
+
+        let mut fee_estimation = default_fee_estimation(&args.chain);
+        if let Some(target) = args.fee_estimate_target {
+            if target == 0 {
+                return Err(anyhow::anyhow!("fee estimate target must be at least 1"));
+            }
+            fee_estimation.conf_target = target;
+        }
+        if let Some(mode) = args.fee_estimate_mode {
+            fee_estimation.mode = mode;
+        }
+        if let Some(fallback) = args.fallback_fee_rate {
+            fee_estimation.fallback = Some(
+                FeeRate::from_sat_per_vb(fallback)
+                    .ok_or_else(|| anyhow::anyhow!("fallback fee rate is too high"))?,
+            );
+        }
+
+        let max_fee_rate = FeeRate::from_sat_per_vb(args.bitcoin_max_fee_rate)
+            .ok_or_else(|| anyhow::anyhow!("bitcoin max fee rate is too high"))?;
+        let mut rpc = BitcoinRpc::new(
             &args.bitcoin_rpc_url.expect("bitcoin rpc url"),
             bitcoin_rpc_auth,
-        );
+            RetryPolicy {
+                max_retries: args.bitcoin_rpc_max_retries,
+                base_delay: Duration::from_millis(args.bitcoin_rpc_retry_delay),
+            },
+        )
+        .with_max_fee_rate(max_fee_rate)
+        .with_max_burn_amount(Amount::from_sat(args.bitcoin_max_burn_amount));
+        if let Some(url) = args.bitcoin_broadcast_url.as_ref() {
+            rpc = rpc.with_broadcast_url(url);
+        }
+        if let Some(path) = args.broadcast_log.clone() {
+            rpc = rpc.with_broadcast_log(path);
+        }
 
         let genesis = Spaced::genesis(&rpc, args.chain).await?;
 
@@ -206,12 +332,26 @@ impl Args {
         Ok(Spaced {
             network: args.chain,
             rpc,
+            rpc_pool: ClientPool {
+                max_idle: args.bitcoin_rpc_pool_size,
+                idle_timeout: match args.bitcoin_rpc_idle_timeout {
+                    0 => None,
+                    secs => Some(Duration::from_secs(secs)),
+                },
+            },
             data_dir,
             bind: rpc_bind_addresses,
             chain,
             block_index,
             block_index_full: args.block_index_full,
             num_workers: args.jobs as usize,
+            blocks_dir: args.bitcoin_blocks_dir,
+            fee_estimation,
+            wallet_catch_up_threshold: args.wallet_catch_up_threshold,
+            tip_refetch_depth: args.tip_refetch_depth,
+            wallet_tip_preview_depth: args.wallet_tip_preview_depth,
+            dropped_tx_grace_period: Duration::from_secs(args.dropped_tx_grace_period),
+            wallet_rpc_concurrency: args.wallet_rpc_concurrency,
         })
     }
 
@@ -277,6 +417,43 @@ pub fn default_bitcoin_rpc_url(network: &ExtendedNetwork) -> &'static str {
     }
 }
 
+impl EstimateMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EstimateMode::Unset => "unset",
+            EstimateMode::Economical => "economical",
+            EstimateMode::Conservative => "conservative",
+        }
+    }
+}
+
+pub fn default_fee_estimation(network: &ExtendedNetwork) -> FeeEstimation {
+    match network {
+        ExtendedNetwork::Mainnet | ExtendedNetwork::MainnetAlpha => FeeEstimation {
+            conf_target: 2,
+            mode: EstimateMode::Unset,
+            use_estimator: true,
+            fallback: None,
+        },
+        // Test networks have sparse mempools so estimates are often missing or erratic
+        ExtendedNetwork::Testnet | ExtendedNetwork::Testnet4 | ExtendedNetwork::Signet => {
+            FeeEstimation {
+                conf_target: 6,
+                mode: EstimateMode::Economical,
+                use_estimator: true,
+                fallback: Some(FeeRate::from_sat_per_vb_unchecked(2)),
+            }
+        }
+        // Regtest has no fee estimation data unless transactions are generated for it
+        ExtendedNetwork::Regtest => FeeEstimation {
+            conf_target: 2,
+            mode: EstimateMode::Unset,
+            use_estimator: false,
+            fallback: Some(FeeRate::from_sat_per_vb_unchecked(1)),
+        },
+    }
+}
+
 fn toml_value_to_string(value: Value) -> Option<String> {
     match value {
         Value::String(v) => Some(v),
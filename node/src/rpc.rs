@@ -13,33 +13,45 @@ use bdk::{
     miniscript::Tap,
     KeychainKind,
 };
-use jsonrpsee::{core::async_trait, proc_macros::rpc, server::Server, types::ErrorObjectOwned};
+use jsonrpsee::{
+    core::{async_trait, SubscriptionResult},
+    proc_macros::rpc,
+    server::Server,
+    types::ErrorObjectOwned,
+    PendingSubscriptionSink, SubscriptionMessage,
+};
 use log::info;
 use protocol::{bitcoin, bitcoin::{
     bip32::Xpriv,
     Network::{Regtest, Testnet},
-    OutPoint,
-}, constants::ChainAnchor, hasher::{BaseHash, KeyHasher, SpaceKey}, prepare::DataSource, slabel::SLabel, FullSpaceOut, SpaceOut};
+    OutPoint, ScriptBuf,
+}, constants::{ChainAnchor, AUCTION_DURATION, RENEWAL_INTERVAL, ROLLOUT_BATCH_SIZE, ROLLOUT_BLOCK_INTERVAL, RULES_VERSION}, hasher::{BaseHash, KeyHasher, SpaceKey}, prepare::DataSource, slabel::{NameErrorKind, SLabel}, Covenant, FullSpaceOut, SpaceOut};
 use serde::{Deserialize, Serialize};
 use tokio::{
     select,
-    sync::{broadcast, mpsc, oneshot, RwLock},
+    sync::{broadcast, mpsc, oneshot, Mutex, RwLock},
     task::JoinSet,
 };
-use protocol::validate::TxChangeSet;
+use protocol::{
+    script::SpaceScript,
+    validate::{TxChangeSet, UpdateKind},
+};
 use wallet::{
-    bdk_wallet as bdk, bdk_wallet::template::Bip86, bitcoin::hashes::Hash, export::WalletExport,
-    DoubleUtxo, SpacesWallet, WalletConfig, WalletDescriptors, WalletInfo,
+    address::SpaceAddress, bdk_wallet as bdk, bdk_wallet::template::Bip86, bitcoin::hashes::Hash,
+    builder::CoinSelectionStrategy, encryption::{shred_file, WalletCipher}, export::WalletExport, DoubleUtxo, SpacesWallet, WalletConfig,
+    WalletDescriptors, WalletInfo, WalletMetadata,
 };
 
 use crate::{
-    config::ExtendedNetwork,
+    config::{EstimateMode, ExtendedNetwork},
     node::{BlockMeta, TxEntry},
     source::BitcoinRpc,
     store::{ChainState, LiveSnapshot, RolloutEntry, Sha256},
+    sync::Spaced,
     wallets::{
-        AddressKind, Balance, RpcWallet, TxInfo, TxResponse, WalletCommand, WalletOutput,
-        WalletResponse,
+        payment_uri, AddressKind, AddressUri, Balance, BumpCandidate, ClaimStatus, OperationCheck, SpaceStateFilter, SpacesByState, SignedMessage, WalletSpaceInfo, DerivedAddress, ConfirmPolicy, ExportFormat, FeePolicy, FeeSpec, OutbidLimits, ReplaceableInfo, RpcWallet,
+        AbandonOpenReport, AbandonReport, DustReport, MaxSendable, ReconcileReport, SelectionEstimate, OutputGroups, SpaceScriptInfo, SpacesValueLocked, TargetFeeBump, VsizeEstimate, WalletSnapshot, StuckTxReport, TxFeeRate, TxInfo, TxResponse, WalletCommand, WalletOutput,
+        WalletResponse, WalletSyncProgress,
     },
 };
 use crate::checker::TxChecker;
@@ -52,6 +64,236 @@ pub struct ServerInfo {
     pub tip: ChainAnchor,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProtocolStatus {
+    pub chain: ExtendedNetwork,
+    /// Height the spaces protocol activates at on this network
+    pub activation_height: u32,
+    /// Whether the bitcoin node's chain has reached the activation height
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Version of the consensus rules enforced by this node
+    pub rules_version: u32,
+    /// Number of blocks validated by the bitcoin node
+    pub blocks: u32,
+    pub tip: ChainAnchor,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeSyncStatus {
+    /// Number of blocks validated by the bitcoin node
+    pub blocks: u32,
+    /// Number of headers known to the bitcoin node
+    pub headers: u32,
+    /// Estimate of verification progress [0..1]
+    pub verification_progress: f64,
+    pub initial_block_download: bool,
+    pub pruned: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockTime {
+    pub height: u32,
+    pub hash: BlockHash,
+    /// Block header timestamp
+    pub time: u64,
+    /// Median time of the past 11 blocks (including this one)
+    /// as used for time-based lock times
+    pub median_time: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuctionStatus {
+    pub outpoint: OutPoint,
+    /// Whether the space is still being auctioned
+    pub in_auction: bool,
+    /// Whether the space is waiting to be picked by a rollout
+    pub pre_auction: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_burned: Option<Amount>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub claim_height: Option<u32>,
+    /// Blocks left until the winning bidder may register the space (0 if it can be claimed now)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocks_until_claim: Option<u32>,
+    pub tip: ChainAnchor,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NameValidation {
+    pub name: String,
+    pub valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Hex encoded space hash if the name is valid
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// Whether the space already exists on chain
+    pub exists: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpacePhase {
+    /// Opened and waiting to be picked by a rollout
+    PreAuction,
+    InAuction,
+    /// Auction closed, the winning bidder may register the space
+    Claimable,
+    Registered,
+    /// Locked until a future upgrade
+    Reserved,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimelineEvent {
+    pub height: u32,
+    /// Block time if mined, otherwise estimated from the tip's median time
+    /// assuming 10 minute blocks
+    pub time: u64,
+    /// Whether the height may still change e.g. when a bid extends the auction
+    pub estimated: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuctionTimeline {
+    pub outpoint: OutPoint,
+    pub phase: SpacePhase,
+    /// When a rollout picks the space and its auction opens, only known
+    /// ahead of time while the space is in pre-auction
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auction_open: Option<TimelineEvent>,
+    /// When the auction closes and the winning bidder may register the space
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub claim: Option<TimelineEvent>,
+    /// When the registration expires unless renewed, assuming the space is
+    /// registered at its claim height if it isn't yet
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiry: Option<TimelineEvent>,
+    pub tip: ChainAnchor,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OwnershipStep {
+    /// Opened the auction, attaching the space to its first bid output
+    Open,
+    /// Outbid the previous bid, attaching the space to a new bid output
+    Bid,
+    /// Claimed the space by spending the winning bid output
+    Register,
+    Transfer,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OwnershipLink {
+    pub step: OwnershipStep,
+    pub txid: Txid,
+    pub height: u32,
+    pub block_hash: BlockHash,
+    /// Output holding the space before this transaction, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spent: Option<OutPoint>,
+    /// Output holding the space after this transaction
+    pub output: OutPoint,
+    pub raw: String,
+    /// Proof the transaction is in the block as returned by `gettxoutproof`
+    pub txout_proof: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OwnershipProof {
+    pub space: String,
+    pub outpoint: OutPoint,
+    /// Transactions establishing the current ownership, oldest first
+    pub chain: Vec<OwnershipLink>,
+    /// Whether the chain goes back to the transaction that opened the space
+    pub complete: bool,
+}
+
+/// Expected seconds between blocks used to estimate future timestamps
+const TARGET_BLOCK_SPACING: u64 = 10 * 60;
+
+/// Blocks searched for each bid of an ownership proof, as each block takes a round
+/// trip to the node. Bids attached to older bid outputs leave the proof incomplete.
+const MAX_BID_SEARCH_BLOCKS: u32 = 2016;
+
+/// Spaces examined for each page of spaces by script, as there is no index by
+/// script and the search holds up the chain state
+const DEFAULT_SPACES_BY_SCRIPT_LIMIT: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputSpendStatus {
+    Unspent,
+    /// Spent by a transaction in the node's mempool
+    SpentInMempool,
+    Spent,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpacesByScript {
+    pub spaces: Vec<FullSpaceOut>,
+    /// Hash of the last space examined, to pass as the cursor for the next page
+    /// if there are spaces left to examine
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpaceAddressVerification {
+    pub space: String,
+    pub address: String,
+    /// Whether the address is the space's current on-chain address
+    pub valid: bool,
+    /// The space's current address, if the space exists
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outpoint: Option<OutPoint>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpaceOutStatus {
+    pub outpoint: OutPoint,
+    pub status: OutputSpendStatus,
+    /// Height the output was confirmed at, if it's still unspent on chain
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_height: Option<u32>,
+    /// Spaces tip the outpoint was resolved at
+    pub tip: ChainAnchor,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "status")]
+pub enum NameAvailability {
+    Invalid {
+        error: String,
+    },
+    /// Not yet opened, anyone may open an auction for it
+    Available,
+    /// Opened and waiting to be picked by a rollout
+    PreAuction {
+        outpoint: OutPoint,
+        total_burned: Amount,
+    },
+    InAuction {
+        outpoint: OutPoint,
+        total_burned: Amount,
+        claim_height: u32,
+    },
+    Registered {
+        outpoint: OutPoint,
+        owner: bitcoin::ScriptBuf,
+        expire_height: u32,
+    },
+    /// Locked until a future protocol upgrade
+    Reserved {
+        outpoint: OutPoint,
+    },
+}
+
 pub enum ChainStateCommand {
     CheckPackage {
         txs: Vec<String>,
@@ -89,6 +331,40 @@ pub enum ChainStateCommand {
         target: usize,
         resp: Responder<anyhow::Result<Vec<RolloutEntry>>>,
     },
+    GetNodeSyncStatus {
+        resp: Responder<anyhow::Result<NodeSyncStatus>>,
+    },
+    GetBlockTime {
+        height: u32,
+        resp: Responder<anyhow::Result<BlockTime>>,
+    },
+    CheckAvailability {
+        names: Vec<String>,
+        resp: Responder<anyhow::Result<BTreeMap<String, NameAvailability>>>,
+    },
+    GetSpaceOutStatus {
+        hash: SpaceKey,
+        resp: Responder<anyhow::Result<Option<SpaceOutStatus>>>,
+    },
+    VerifySpaceAddress {
+        name: SLabel,
+        address: SpaceAddress,
+        network: bitcoin::Network,
+        resp: Responder<anyhow::Result<SpaceAddressVerification>>,
+    },
+    GetAuctionTimeline {
+        hash: SpaceKey,
+        resp: Responder<anyhow::Result<Option<AuctionTimeline>>>,
+    },
+    HasBlockIndex {
+        resp: Responder<anyhow::Result<bool>>,
+    },
+    GetSpacesByScript {
+        script: ScriptBuf,
+        after: Option<SpaceKey>,
+        limit: usize,
+        resp: Responder<anyhow::Result<(Vec<FullSpaceOut>, Option<SpaceKey>)>>,
+    },
 }
 
 #[derive(Clone)]
@@ -101,12 +377,61 @@ pub trait Rpc {
     #[method(name = "getserverinfo")]
     async fn get_server_info(&self) -> Result<ServerInfo, ErrorObjectOwned>;
 
+    #[method(name = "getnodesyncstatus")]
+    async fn get_node_sync_status(&self) -> Result<NodeSyncStatus, ErrorObjectOwned>;
+
+    #[method(name = "getblocktime")]
+    async fn get_block_time(&self, height: u32) -> Result<BlockTime, ErrorObjectOwned>;
+
+    #[method(name = "getprotocolstatus")]
+    async fn get_protocol_status(&self) -> Result<ProtocolStatus, ErrorObjectOwned>;
+
     #[method(name = "getspace")]
     async fn get_space(
         &self,
         space_or_hash: &str,
     ) -> Result<Option<FullSpaceOut>, ErrorObjectOwned>;
 
+    #[method(name = "getauctionstatus")]
+    async fn get_auction_status(
+        &self,
+        space_or_hash: &str,
+    ) -> Result<Option<AuctionStatus>, ErrorObjectOwned>;
+
+    #[method(name = "getauctiontimeline")]
+    async fn get_auction_timeline(
+        &self,
+        space_or_hash: &str,
+    ) -> Result<Option<AuctionTimeline>, ErrorObjectOwned>;
+
+    #[method(name = "getownershipproof")]
+    async fn get_ownership_proof(
+        &self,
+        space_or_hash: &str,
+    ) -> Result<Option<OwnershipProof>, ErrorObjectOwned>;
+
+    #[method(name = "validatename")]
+    async fn validate_name(&self, name: &str) -> Result<NameValidation, ErrorObjectOwned>;
+
+    #[method(name = "getspaceoutstatus")]
+    async fn get_spaceout_status(
+        &self,
+        space_or_hash: &str,
+    ) -> Result<Option<SpaceOutStatus>, ErrorObjectOwned>;
+
+    #[method(name = "verifyspaceaddress")]
+    async fn verify_space_address(
+        &self,
+        name: &str,
+        address: &str,
+    ) -> Result<SpaceAddressVerification, ErrorObjectOwned>;
+
+    #[method(name = "checkavailability")]
+    async fn check_availability(
+        &self,
+        names: Vec<String>,
+    ) -> Result<BTreeMap<String, NameAvailability>, ErrorObjectOwned>;
+
     #[method(name = "getspaceowner")]
     async fn get_space_owner(
         &self,
@@ -116,6 +441,14 @@ pub trait Rpc {
     #[method(name = "getspaceout")]
     async fn get_spaceout(&self, outpoint: OutPoint) -> Result<Option<SpaceOut>, ErrorObjectOwned>;
 
+    #[method(name = "getspacesbyscript")]
+    async fn get_spaces_by_script(
+        &self,
+        address_or_script: &str,
+        cursor: Option<String>,
+        limit: Option<usize>,
+    ) -> Result<SpacesByScript, ErrorObjectOwned>;
+
     #[method(name = "checkpackage")]
     async fn check_package(&self, txs: Vec<String>) -> Result<Vec<Option<TxChangeSet>>, ErrorObjectOwned>;
 
@@ -135,7 +468,11 @@ pub trait Rpc {
     async fn get_tx_meta(&self, txid: Txid) -> Result<Option<TxEntry>, ErrorObjectOwned>;
 
     #[method(name = "walletload")]
-    async fn wallet_load(&self, name: &str) -> Result<(), ErrorObjectOwned>;
+    async fn wallet_load(
+        &self,
+        name: &str,
+        passphrase: Option<String>,
+    ) -> Result<(), ErrorObjectOwned>;
 
     #[method(name = "walletimport")]
     async fn wallet_import(&self, wallet: WalletExport) -> Result<(), ErrorObjectOwned>;
@@ -143,12 +480,32 @@ pub trait Rpc {
     #[method(name = "walletgetinfo")]
     async fn wallet_get_info(&self, name: &str) -> Result<WalletInfo, ErrorObjectOwned>;
 
+    #[method(name = "walletgetmetadata")]
+    async fn wallet_get_metadata(&self, name: &str) -> Result<WalletMetadata, ErrorObjectOwned>;
+
     #[method(name = "walletexport")]
-    async fn wallet_export(&self, name: &str) -> Result<WalletExport, ErrorObjectOwned>;
+    async fn wallet_export(
+        &self,
+        name: &str,
+        passphrase: Option<String>,
+    ) -> Result<WalletExport, ErrorObjectOwned>;
+
+    #[method(name = "walletencrypt")]
+    async fn wallet_encrypt(&self, name: &str, passphrase: String)
+        -> Result<(), ErrorObjectOwned>;
 
     #[method(name = "walletcreate")]
     async fn wallet_create(&self, name: &str) -> Result<(), ErrorObjectOwned>;
 
+    #[method(name = "walletrecover")]
+    async fn wallet_recover(
+        &self,
+        name: &str,
+        mnemonic: &str,
+        passphrase: Option<String>,
+        start_height: Option<u32>,
+    ) -> Result<(), ErrorObjectOwned>;
+
     #[method(name = "walletsendrequest")]
     async fn wallet_send_request(
         &self,
@@ -156,13 +513,56 @@ pub trait Rpc {
         request: RpcWalletTxBuilder,
     ) -> Result<WalletResponse, ErrorObjectOwned>;
 
+    #[method(name = "walletpreviewrequest")]
+    async fn wallet_preview_request(
+        &self,
+        wallet: &str,
+        request: RpcWalletTxBuilder,
+    ) -> Result<WalletResponse, ErrorObjectOwned>;
+
+    #[method(name = "walletbuildpsbt")]
+    async fn wallet_build_psbt(
+        &self,
+        wallet: &str,
+        request: RpcWalletRequest,
+        inputs: Vec<OutPoint>,
+        fee_rate: Option<FeeRate>,
+    ) -> Result<String, ErrorObjectOwned>;
+
+    #[method(name = "walletbroadcastbatch")]
+    async fn wallet_broadcast_batch(
+        &self,
+        wallet: &str,
+        raw_txs: Vec<String>,
+    ) -> Result<WalletResponse, ErrorObjectOwned>;
+
     #[method(name = "walletgetnewaddress")]
     async fn wallet_get_new_address(
         &self,
         wallet: &str,
         kind: AddressKind,
+        account: Option<u32>,
     ) -> Result<String, ErrorObjectOwned>;
 
+    #[method(name = "walletderiveaddress")]
+    async fn wallet_derive_address(
+        &self,
+        wallet: &str,
+        internal: bool,
+        index: u32,
+        account: Option<u32>,
+    ) -> Result<DerivedAddress, ErrorObjectOwned>;
+
+    #[method(name = "walletgetnewaddressuri")]
+    async fn wallet_get_new_address_uri(
+        &self,
+        wallet: &str,
+        kind: AddressKind,
+        amount: Option<Amount>,
+        label: Option<String>,
+        account: Option<u32>,
+    ) -> Result<AddressUri, ErrorObjectOwned>;
+
     #[method(name = "walletbumpfee")]
     async fn wallet_bump_fee(
         &self,
@@ -170,16 +570,174 @@ pub trait Rpc {
         txid: Txid,
         fee_rate: FeeRate,
         skip_tx_check: bool,
+        dry_run: Option<bool>,
+    ) -> Result<Vec<TxResponse>, ErrorObjectOwned>;
+
+    #[method(name = "walletbumpfeetotarget")]
+    async fn wallet_bump_fee_to_target(
+        &self,
+        wallet: &str,
+        txid: Txid,
+        conf_target: u16,
+        dry_run: Option<bool>,
+    ) -> Result<TargetFeeBump, ErrorObjectOwned>;
+
+    #[method(name = "bumpfee")]
+    async fn bump_fee(
+        &self,
+        txid: Txid,
+        fee_rate: FeeRate,
+        skip_tx_check: bool,
+        dry_run: Option<bool>,
     ) -> Result<Vec<TxResponse>, ErrorObjectOwned>;
 
+    #[method(name = "walletcancelbid")]
+    async fn wallet_cancel_bid(
+        &self,
+        wallet: &str,
+        txid: Txid,
+        fee_rate: FeeRate,
+    ) -> Result<TxResponse, ErrorObjectOwned>;
+
+    #[method(name = "walletisreplaceable")]
+    async fn wallet_is_replaceable(
+        &self,
+        wallet: &str,
+        txid: Txid,
+    ) -> Result<ReplaceableInfo, ErrorObjectOwned>;
+
+    #[method(name = "walletgetclaimstatus")]
+    async fn wallet_get_claim_status(
+        &self,
+        wallet: &str,
+        space: String,
+    ) -> Result<ClaimStatus, ErrorObjectOwned>;
+
+    #[method(name = "walletgetspacescript")]
+    async fn wallet_get_space_script(
+        &self,
+        wallet: &str,
+        space: String,
+    ) -> Result<SpaceScriptInfo, ErrorObjectOwned>;
+
     #[method(name = "walletlisttransactions")]
     async fn wallet_list_transactions(
         &self,
         wallet: &str,
         count: usize,
         skip: usize,
+        account: Option<u32>,
     ) -> Result<Vec<TxInfo>, ErrorObjectOwned>;
 
+    #[method(name = "walletlistfeerates")]
+    async fn wallet_list_fee_rates(
+        &self,
+        wallet: &str,
+        count: usize,
+    ) -> Result<Vec<TxFeeRate>, ErrorObjectOwned>;
+
+    #[method(name = "walletlistcheckpoints")]
+    async fn wallet_list_checkpoints(
+        &self,
+        wallet: &str,
+        count: usize,
+    ) -> Result<Vec<ChainAnchor>, ErrorObjectOwned>;
+
+    #[method(name = "walletreconcile")]
+    async fn wallet_reconcile(&self, wallet: &str) -> Result<ReconcileReport, ErrorObjectOwned>;
+
+    #[method(name = "walletabandonallpending")]
+    async fn wallet_abandon_all_pending(
+        &self,
+        wallet: &str,
+    ) -> Result<AbandonReport, ErrorObjectOwned>;
+
+    #[method(name = "walletabandonopen")]
+    async fn wallet_abandon_open(
+        &self,
+        wallet: &str,
+        name: String,
+        reclaim_fee_rate: Option<FeeRate>,
+    ) -> Result<AbandonOpenReport, ErrorObjectOwned>;
+
+    #[method(name = "walletbumpallstuck")]
+    async fn wallet_bump_all_stuck(
+        &self,
+        wallet: &str,
+        min_age_blocks: u32,
+        target_fee_rate: FeeRate,
+    ) -> Result<Vec<StuckTxReport>, ErrorObjectOwned>;
+
+    #[method(name = "walletlistbumpcandidates")]
+    async fn wallet_list_bump_candidates(
+        &self,
+        wallet: &str,
+        min_age_blocks: u32,
+        conf_target: Option<u16>,
+    ) -> Result<Vec<BumpCandidate>, ErrorObjectOwned>;
+
+    #[method(name = "walletlistdust")]
+    async fn wallet_list_dust(
+        &self,
+        wallet: &str,
+        fee_rate: FeeRate,
+    ) -> Result<DustReport, ErrorObjectOwned>;
+
+    #[method(name = "walletestimateselection")]
+    async fn wallet_estimate_selection(
+        &self,
+        wallet: &str,
+        amount: Amount,
+        fee_rate: FeeRate,
+    ) -> Result<SelectionEstimate, ErrorObjectOwned>;
+
+    #[method(name = "walletestimatevsize")]
+    async fn wallet_estimate_vsize(
+        &self,
+        wallet: &str,
+        request: RpcWalletRequest,
+    ) -> Result<VsizeEstimate, ErrorObjectOwned>;
+
+    #[method(name = "walletgetspaceinfo")]
+    async fn wallet_get_space_info(
+        &self,
+        wallet: &str,
+        space: String,
+    ) -> Result<WalletSpaceInfo, ErrorObjectOwned>;
+
+    #[method(name = "walletsignmessage")]
+    async fn wallet_sign_message(
+        &self,
+        wallet: &str,
+        space: String,
+        message: String,
+    ) -> Result<SignedMessage, ErrorObjectOwned>;
+
+    #[method(name = "walletcheckoperation")]
+    async fn wallet_check_operation(
+        &self,
+        wallet: &str,
+        request: RpcWalletRequest,
+    ) -> Result<OperationCheck, ErrorObjectOwned>;
+
+    #[method(name = "walletgetmaxsendable")]
+    async fn wallet_get_max_sendable(
+        &self,
+        wallet: &str,
+        fee_rate: FeeRate,
+        to: String,
+    ) -> Result<MaxSendable, ErrorObjectOwned>;
+
+    #[method(name = "walletexporthistory")]
+    async fn wallet_export_history(
+        &self,
+        wallet: &str,
+        format: ExportFormat,
+        as_of: Option<u32>,
+        skip: usize,
+        count: usize,
+    ) -> Result<String, ErrorObjectOwned>;
+
     #[method(name = "walletforcespend")]
     async fn wallet_force_spend(
         &self,
@@ -189,42 +747,198 @@ pub trait Rpc {
     ) -> Result<TxResponse, ErrorObjectOwned>;
 
     #[method(name = "walletlistspaces")]
-    async fn wallet_list_spaces(&self, wallet: &str)
-                                -> Result<Vec<WalletOutput>, ErrorObjectOwned>;
+    async fn wallet_list_spaces(
+        &self,
+        wallet: &str,
+        account: Option<u32>,
+    ) -> Result<Vec<WalletOutput>, ErrorObjectOwned>;
+
+    #[method(name = "walletlistspacesbystate")]
+    async fn wallet_list_spaces_by_state(
+        &self,
+        wallet: &str,
+        state: Option<SpaceStateFilter>,
+    ) -> Result<SpacesByState, ErrorObjectOwned>;
 
     #[method(name = "walletlistunspent")]
     async fn wallet_list_unspent(
         &self,
         wallet: &str,
+        account: Option<u32>,
     ) -> Result<Vec<WalletOutput>, ErrorObjectOwned>;
 
+    #[method(name = "walletlistoutputgroups")]
+    async fn wallet_list_output_groups(
+        &self,
+        wallet: &str,
+        account: Option<u32>,
+    ) -> Result<OutputGroups, ErrorObjectOwned>;
+
+    #[method(name = "walletgetsnapshot")]
+    async fn wallet_get_snapshot(
+        &self,
+        wallet: &str,
+        account: Option<u32>,
+    ) -> Result<WalletSnapshot, ErrorObjectOwned>;
+
     #[method(name = "walletlistbidouts")]
     async fn wallet_list_bidouts(&self, wallet: &str) -> Result<Vec<DoubleUtxo>, ErrorObjectOwned>;
 
+    #[method(name = "walletgetspacesvaluelocked")]
+    async fn wallet_get_spaces_value_locked(
+        &self,
+        wallet: &str,
+    ) -> Result<SpacesValueLocked, ErrorObjectOwned>;
+
     #[method(name = "walletgetbalance")]
-    async fn wallet_get_balance(&self, wallet: &str) -> Result<Balance, ErrorObjectOwned>;
-}
+    async fn wallet_get_balance(
+        &self,
+        wallet: &str,
+        account: Option<u32>,
+    ) -> Result<Balance, ErrorObjectOwned>;
 
-#[derive(Clone, Serialize, Deserialize)]
-pub struct RpcWalletTxBuilder {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub bidouts: Option<u8>,
-    pub requests: Vec<RpcWalletRequest>,
-    pub fee_rate: Option<FeeRate>,
-    pub dust: Option<Amount>,
-    pub force: bool,
-    pub confirmed_only: bool,
-    pub skip_tx_check: bool,
-}
+    #[method(name = "walletsetfeepolicy")]
+    async fn wallet_set_fee_policy(
+        &self,
+        wallet: &str,
+        policy: FeePolicy,
+    ) -> Result<(), ErrorObjectOwned>;
 
-#[derive(Clone, Serialize, Deserialize)]
-#[serde(tag = "request")]
-pub enum RpcWalletRequest {
-    #[serde(rename = "open")]
-    Open(OpenParams),
-    #[serde(rename = "bid")]
-    Bid(BidParams),
-    #[serde(rename = "register")]
+    #[method(name = "walletgetfeepolicy")]
+    async fn wallet_get_fee_policy(&self, wallet: &str) -> Result<FeePolicy, ErrorObjectOwned>;
+
+    #[method(name = "walletsetconfirmpolicy")]
+    async fn wallet_set_confirm_policy(
+        &self,
+        wallet: &str,
+        policy: ConfirmPolicy,
+    ) -> Result<(), ErrorObjectOwned>;
+
+    #[method(name = "walletgetconfirmpolicy")]
+    async fn wallet_get_confirm_policy(
+        &self,
+        wallet: &str,
+    ) -> Result<ConfirmPolicy, ErrorObjectOwned>;
+
+    #[method(name = "walletsettxnote")]
+    async fn wallet_set_tx_note(
+        &self,
+        wallet: &str,
+        txid: Txid,
+        note: String,
+    ) -> Result<(), ErrorObjectOwned>;
+
+    #[method(name = "walletgettxnote")]
+    async fn wallet_get_tx_note(
+        &self,
+        wallet: &str,
+        txid: Txid,
+    ) -> Result<Option<String>, ErrorObjectOwned>;
+
+    #[method(name = "walletgetsyncprogress")]
+    async fn wallet_get_sync_progress(
+        &self,
+        wallet: &str,
+    ) -> Result<WalletSyncProgress, ErrorObjectOwned>;
+}
+
+/// Subscriptions need a websocket connection, so they're kept out of [Rpc]
+/// whose client is also used over http
+#[rpc(server)]
+pub trait StreamRpc {
+    /// Like `walletsendrequest` but notifies each transaction's result as soon as
+    /// it's built and broadcast, ending with whatever wasn't streamed
+    #[subscription(
+        name = "walletsendrequeststream",
+        unsubscribe = "walletsendrequeststreamunsubscribe",
+        item = WalletSendEvent
+    )]
+    async fn wallet_send_request_stream(
+        &self,
+        wallet: String,
+        request: RpcWalletTxBuilder,
+    ) -> SubscriptionResult;
+}
+
+/// Commands feeding constructed data to wallets, only served by test builds
+#[cfg(feature = "testing")]
+#[rpc(server, client)]
+pub trait TestingRpc {
+    #[method(name = "walletapplytestblock")]
+    async fn wallet_apply_test_block(
+        &self,
+        wallet: &str,
+        block_hex: String,
+        height: u32,
+    ) -> Result<(), ErrorObjectOwned>;
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WalletSendEvent {
+    Tx(TxResponse),
+    Done(WalletResponse),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RpcWalletTxBuilder {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bidouts: Option<u8>,
+    pub requests: Vec<RpcWalletRequest>,
+    pub fee_rate: Option<FeeRate>,
+    /// Alternative to `fee_rate` accepting sat/vB or BTC/kvB
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fee: Option<FeeSpec>,
+    /// Confirmation target for the node's fee estimate when no fee rate is given,
+    /// overriding the wallet's fee policy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conf_target: Option<u16>,
+    /// Mode for the node's fee estimate, defaults to the one the node is configured with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimate_mode: Option<EstimateMode>,
+    pub dust: Option<Amount>,
+    pub force: bool,
+    pub confirmed_only: bool,
+    pub skip_tx_check: bool,
+    #[serde(default)]
+    pub coin_selection: CoinSelectionStrategy,
+    /// Avoid spending outputs of the same address together unless needed to fund the transaction
+    #[serde(default)]
+    pub avoid_address_reuse: bool,
+    /// Add change below the dust limit to the fee instead of failing the transaction
+    #[serde(default = "default_true")]
+    pub donate_dust_change_to_fee: bool,
+    #[serde(default)]
+    pub outbid_limits: OutbidLimits,
+    /// Resolve space names to the address of a pending update in the node's mempool,
+    /// e.g. a registration that hasn't confirmed yet. Less safe since the update may
+    /// be replaced or reorged out
+    #[serde(default)]
+    pub resolve_pending: bool,
+    /// Fail unless the wallet is still at this tip e.g. the one returned by a snapshot
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_tip: Option<ChainAnchor>,
+    /// Token returned by a previous call that required confirmation of a high value operation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confirm_token: Option<String>,
+    /// Set to false to only build and sign the transactions, returning them raw
+    /// without broadcasting or changing the wallet
+    #[serde(default = "default_true")]
+    pub broadcast: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "request")]
+pub enum RpcWalletRequest {
+    #[serde(rename = "open")]
+    Open(OpenParams),
+    #[serde(rename = "bid")]
+    Bid(BidParams),
+    #[serde(rename = "register")]
     Register(RegisterParams),
     #[serde(rename = "execute")]
     Execute(ExecuteParams),
@@ -242,14 +956,24 @@ pub struct TransferSpacesParams {
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct SendCoinsParams {
-    pub amount: Amount,
+    /// Required unless `to` is a `bitcoin:` URI specifying an amount
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount: Option<Amount>,
+    /// Space name, address or a BIP-21 `bitcoin:` URI
     pub to: String,
+    /// Confirmed wallet coins to fund the transaction with, selected automatically if empty.
+    /// Only allowed when every request in the batch is a sendcoins request.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub from: Vec<OutPoint>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct ExecuteParams {
     pub context: Vec<String>,
     pub space_script: Vec<u8>,
+    /// Wallet coins to fund the transaction with, selected automatically if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inputs: Vec<OutPoint>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -291,6 +1015,10 @@ pub struct WalletManager {
     pub rpc: BitcoinRpc,
     pub wallet_loader: mpsc::Sender<LoadedWallet>,
     pub wallets: Arc<RwLock<BTreeMap<String, RpcWallet>>>,
+    /// Ciphers of encrypted wallets unlocked since startup
+    pub unlocked: Arc<RwLock<BTreeMap<String, WalletCipher>>>,
+    /// Held while loading an account so concurrent first requests load it only once
+    pub account_loading: Arc<Mutex<()>>,
 }
 
 pub struct LoadedWallet {
@@ -307,6 +1035,23 @@ impl LoadedWallet {
 }
 
 impl WalletManager {
+    /// Finds the loaded wallet that owns the given transaction
+    pub async fn find_tx_wallet(&self, txid: Txid) -> anyhow::Result<(String, RpcWallet)> {
+        let wallets: Vec<(String, RpcWallet)> = {
+            let wallets = self.wallets.read().await;
+            wallets
+                .iter()
+                .map(|(name, wallet)| (name.clone(), wallet.clone()))
+                .collect()
+        };
+        for (name, wallet) in wallets {
+            if wallet.send_has_tx(txid).await? {
+                return Ok((name, wallet));
+            }
+        }
+        Err(anyhow!("transaction {} not found in any loaded wallet", txid))
+    }
+
     pub async fn import_wallet(
         &self,
         client: &reqwest::Client,
@@ -325,18 +1070,96 @@ impl WalletManager {
         let mut file = fs::File::create(wallet_export_path)?;
         file.write_all(wallet.to_string().as_bytes())?;
 
-        self.load_wallet(client, &wallet.label).await?;
+        self.load_wallet(client, &wallet.label, None).await?;
         Ok(())
     }
 
-    pub async fn export_wallet(&self, name: &str) -> anyhow::Result<WalletExport> {
+    pub async fn export_wallet(
+        &self,
+        name: &str,
+        passphrase: Option<&str>,
+    ) -> anyhow::Result<WalletExport> {
+        let (export, _) = self.read_export(name, passphrase).await?;
+        Ok(export)
+    }
+
+    /// Reads the wallet's descriptors along with the cipher its files are encrypted
+    /// with, if any. Encrypted wallets need the passphrase unless already unlocked,
+    /// giving one for an unencrypted wallet is an error.
+    async fn read_export(
+        &self,
+        name: &str,
+        passphrase: Option<&str>,
+    ) -> anyhow::Result<(WalletExport, Option<WalletCipher>)> {
         let wallet_dir = self.data_dir.join(name);
         if !wallet_dir.exists() {
             return Err(anyhow!("Wallet does not exist"));
         }
-        let wallet = fs::read_to_string(wallet_dir.join("wallet.json"))?;
-        let export: WalletExport = serde_json::from_str(&wallet)?;
-        Ok(export)
+
+        let sealed_path = wallet_dir.join("wallet.enc");
+        if !sealed_path.exists() {
+            if passphrase.is_some() {
+                return Err(anyhow!(
+                    "wallet `{}` is not encrypted, a passphrase can't be used with it",
+                    name
+                ));
+            }
+            let wallet = fs::read_to_string(wallet_dir.join("wallet.json"))?;
+            return Ok((serde_json::from_str(&wallet)?, None));
+        }
+
+        let sealed = fs::read(sealed_path)?;
+        let (cipher, raw) = match passphrase {
+            Some(passphrase) => WalletCipher::unseal(passphrase, &sealed)?,
+            None => {
+                let unlocked = self.unlocked.read().await;
+                let cipher = unlocked.get(name).cloned().ok_or_else(|| {
+                    anyhow!("wallet `{}` is encrypted, a passphrase is required", name)
+                })?;
+                let raw = cipher.open(&sealed)?;
+                (cipher, raw)
+            }
+        };
+        Ok((serde_json::from_slice(&raw)?, Some(cipher)))
+    }
+
+    /// Encrypts a loaded wallet's files at rest with a key derived from the passphrase.
+    /// Its accounts not loaded yet are encrypted the next time they're loaded.
+    /// Transaction notes and fee and confirmation policies are encrypted as well.
+    /// The unencrypted files are overwritten before being removed, see [shred_file]
+    /// for the limits of that.
+    pub async fn encrypt_wallet(&self, name: &str, passphrase: &str) -> anyhow::Result<()> {
+        let wallet_dir = self.data_dir.join(name);
+        if wallet_dir.join("wallet.enc").exists() {
+            return Err(anyhow!("wallet `{}` is already encrypted", name));
+        }
+        let accounts: Vec<RpcWallet> = {
+            let wallets = self.wallets.read().await;
+            if !wallets.contains_key(name) {
+                return Err(anyhow!("wallet `{}` must be loaded to be encrypted", name));
+            }
+            let prefix = format!("{}#", name);
+            wallets
+                .iter()
+                .filter(|(loaded, _)| loaded.as_str() == name || loaded.starts_with(&prefix))
+                .map(|(_, wallet)| wallet.clone())
+                .collect()
+        };
+
+        let (export, _) = self.read_export(name, None).await?;
+        let cipher = WalletCipher::new(passphrase)?;
+        // Once the sealed descriptors are written the wallet loads as encrypted,
+        // so an interruption from here on is finished the next time it's loaded
+        cipher.write(&wallet_dir.join("wallet.enc"), export.to_string().as_bytes())?;
+        self.unlocked
+            .write()
+            .await
+            .insert(name.to_string(), cipher.clone());
+        for account in accounts {
+            account.send_encrypt(cipher.clone()).await?;
+        }
+        shred_file(&wallet_dir.join("wallet.json"))?;
+        Ok(())
     }
 
     pub async fn create_wallet(&self, client: &reqwest::Client, name: &str) -> anyhow::Result<()> {
@@ -345,8 +1168,39 @@ impl WalletManager {
                 .map_err(|_| anyhow!("Mnemonic generation error"))?;
 
         let start_block = self.get_wallet_start_block(client).await?;
-        self.setup_new_wallet(name.to_string(), mnemonic.to_string(), start_block)?;
-        self.load_wallet(client, name).await?;
+        self.setup_new_wallet(name.to_string(), mnemonic.to_string(), None, start_block)?;
+        self.load_wallet(client, name, None).await?;
+        Ok(())
+    }
+
+    /// Restores a wallet from a BIP-39 mnemonic. Without a start height the wallet
+    /// rescans from the protocol's activation block.
+    pub async fn recover_wallet(
+        &self,
+        client: &reqwest::Client,
+        name: &str,
+        mnemonic: &str,
+        passphrase: Option<String>,
+        start_height: Option<u32>,
+    ) -> anyhow::Result<()> {
+        let start_block = match start_height {
+            Some(height) => {
+                let hash = self
+                    .rpc
+                    .send_json(&client, &self.rpc.get_block_hash(height))
+                    .await?;
+                BlockId { height, hash }
+            }
+            None => {
+                let activation = Spaced::genesis(&self.rpc, self.network).await?;
+                BlockId {
+                    height: activation.height,
+                    hash: activation.hash,
+                }
+            }
+        };
+        self.setup_new_wallet(name.to_string(), mnemonic.to_string(), passphrase, start_block)?;
+        self.load_wallet(client, name, None).await?;
         Ok(())
     }
 
@@ -354,6 +1208,7 @@ impl WalletManager {
         &self,
         name: String,
         mnemonic: String,
+        passphrase: Option<String>,
         start_block: BlockId,
     ) -> anyhow::Result<()> {
         let wallet_path = self.data_dir.join(&name);
@@ -361,7 +1216,7 @@ impl WalletManager {
             return Err(anyhow!(format!("Wallet `{}` already exists", name)));
         }
 
-        let export = self.wallet_from_mnemonic(name.clone(), mnemonic.to_string(), start_block)?;
+        let export = self.wallet_from_mnemonic(name.clone(), mnemonic, passphrase, start_block)?;
         fs::create_dir_all(&wallet_path)?;
         let wallet_export_path = wallet_path.join("wallet.json");
         let mut file = fs::File::create(wallet_export_path)?;
@@ -373,10 +1228,11 @@ impl WalletManager {
         &self,
         name: String,
         mnemonic: String,
+        passphrase: Option<String>,
         start_block: BlockId,
     ) -> anyhow::Result<WalletExport> {
         let (network, _) = self.fallback_network();
-        let xpriv = Self::descriptor_from_mnemonic(network, &mnemonic.to_string())?;
+        let xpriv = Self::descriptor_from_mnemonic(network, &mnemonic, passphrase)?;
 
         let (external, internal) = Self::default_descriptors(xpriv);
         let tmp = bdk::wallet::Wallet::new_or_load(external, internal, None, network)?;
@@ -424,35 +1280,112 @@ impl WalletManager {
         (network, genesis_hash)
     }
 
-    pub async fn load_wallet(&self, client: &reqwest::Client, name: &str) -> anyhow::Result<()> {
+    pub async fn load_wallet(
+        &self,
+        client: &reqwest::Client,
+        name: &str,
+        passphrase: Option<&str>,
+    ) -> anyhow::Result<()> {
         let wallet_dir = self.data_dir.join(name);
-        if !wallet_dir.exists() {
-            return Err(anyhow!("Wallet does not exist"));
+        let (export, cipher) = self.read_export(name, passphrase).await?;
+        let descriptors = WalletDescriptors {
+            external: export.descriptor(),
+            internal: export
+                .change_descriptor()
+                .expect("expected a change descriptor"),
+        };
+
+        self.start_wallet(
+            client,
+            name.to_string(),
+            wallet_dir.clone(),
+            export.blockheight,
+            descriptors,
+            cipher.clone(),
+        )
+        .await?;
+        if let Some(cipher) = cipher {
+            // Left behind if encrypting the wallet was interrupted, its stores
+            // were migrated when opened
+            let plain_path = wallet_dir.join("wallet.json");
+            if plain_path.exists() {
+                shred_file(&plain_path)?;
+            }
+            self.unlocked.write().await.insert(name.to_string(), cipher);
+        }
+        Ok(())
+    }
+
+    /// Loads an additional account of an already existing wallet. Accounts share
+    /// the wallet's seed but use keychains derived at a different account index.
+    pub async fn load_account(
+        &self,
+        client: &reqwest::Client,
+        name: &str,
+        account: u32,
+    ) -> anyhow::Result<RpcWallet> {
+        let _loading = self.account_loading.lock().await;
+        // Another request may have loaded it while we waited
+        if let Some(loaded) = self
+            .wallets
+            .read()
+            .await
+            .get(&account_wallet_name(name, account))
+        {
+            return Ok(loaded.clone());
         }
 
-        let file = fs::File::open(wallet_dir.join("wallet.json"))?;
+        let (export, cipher) = self.read_export(name, None).await?;
+        let descriptors = WalletDescriptors {
+            external: account_descriptor(&export.descriptor(), account)?,
+            internal: account_descriptor(
+                &export
+                    .change_descriptor()
+                    .expect("expected a change descriptor"),
+                account,
+            )?,
+        };
 
-        let (network, genesis_hash) = self.fallback_network();
-        let export: WalletExport = serde_json::from_reader(file)?;
+        let account_dir = self
+            .data_dir
+            .join(name)
+            .join("accounts")
+            .join(account.to_string());
+        self.start_wallet(
+            client,
+            account_wallet_name(name, account),
+            account_dir,
+            export.blockheight,
+            descriptors,
+            cipher,
+        )
+        .await
+    }
 
+    async fn start_wallet(
+        &self,
+        client: &reqwest::Client,
+        name: String,
+        data_dir: PathBuf,
+        start_block: u32,
+        descriptors: WalletDescriptors,
+        cipher: Option<WalletCipher>,
+    ) -> anyhow::Result<RpcWallet> {
+        let (network, genesis_hash) = self.fallback_network();
         let mut wallet = SpacesWallet::new(WalletConfig {
-            start_block: export.blockheight,
-            data_dir: wallet_dir,
-            name: name.to_string(),
+            start_block,
+            data_dir,
+            name: name.clone(),
             network,
             genesis_hash,
-            space_descriptors: WalletDescriptors {
-                external: export.descriptor(),
-                internal: export
-                    .change_descriptor()
-                    .expect("expected a change descriptor"),
-            },
+            space_descriptors: descriptors,
+            cipher,
         })?;
 
         let wallet_tip = wallet.spaces.local_chain().tip().height();
 
-        if wallet_tip < export.blockheight {
-            let block_id = self.get_block_hash(client, export.blockheight).await?;
+        if wallet_tip < start_block {
+            let block_id = self.get_block_hash(client, start_block).await?;
             wallet.spaces.insert_checkpoint(block_id)?;
             wallet.commit()?;
         }
@@ -462,8 +1395,8 @@ impl WalletManager {
 
         self.wallet_loader.send(loaded_wallet).await?;
         let mut wallets = self.wallets.write().await;
-        wallets.insert(name.to_string(), rpc_wallet);
-        Ok(())
+        wallets.insert(name, rpc_wallet.clone());
+        Ok(rpc_wallet)
     }
 
     async fn get_block_hash(
@@ -494,10 +1427,22 @@ impl WalletManager {
         Ok(BlockId { height, hash })
     }
 
-    fn descriptor_from_mnemonic(network: Network, m: &str) -> anyhow::Result<Xpriv> {
-        let mnemonic = Mnemonic::parse(m).unwrap();
-        let xkey: ExtendedKey = mnemonic.clone().into_extended_key()?;
-        Ok(xkey.into_xprv(network).expect("xpriv"))
+    fn descriptor_from_mnemonic(
+        network: Network,
+        m: &str,
+        passphrase: Option<String>,
+    ) -> anyhow::Result<Xpriv> {
+        let mnemonic = Mnemonic::parse_in(Language::English, m)
+            .map_err(|e| anyhow!("invalid mnemonic: {}", e))?;
+        if ![12, 15, 18, 21, 24].contains(&mnemonic.word_count()) {
+            return Err(anyhow!(
+                "invalid mnemonic: expected 12, 15, 18, 21 or 24 words, got {}",
+                mnemonic.word_count()
+            ));
+        }
+        let xkey: ExtendedKey = (mnemonic, passphrase).into_extended_key()?;
+        xkey.into_xprv(network)
+            .ok_or_else(|| anyhow!("could not derive extended private key"))
     }
 
     fn default_descriptors(x: Xpriv) -> (Bip86<Xpriv>, Bip86<Xpriv>) {
@@ -508,6 +1453,24 @@ impl WalletManager {
     }
 }
 
+fn account_wallet_name(name: &str, account: u32) -> String {
+    format!("{}#{}", name, account)
+}
+
+/// Rewrites a BIP-86 descriptor (`.../86'/coin'/0'/...`) to use the given account index
+fn account_descriptor(descriptor: &str, account: u32) -> anyhow::Result<String> {
+    // the checksum no longer matches once the path changes
+    let descriptor = descriptor.split('#').next().unwrap_or(descriptor);
+    for coin_type in [0, 1] {
+        let path = format!("/86'/{}'/0'/", coin_type);
+        if descriptor.contains(&path) {
+            let account_path = format!("/86'/{}'/{}'/", coin_type, account);
+            return Ok(descriptor.replacen(&path, &account_path, 1));
+        }
+    }
+    Err(anyhow!("Wallet descriptors do not support multiple accounts"))
+}
+
 impl RpcServerImpl {
     pub fn new(store: AsyncChainState, wallet_manager: WalletManager) -> Self {
         RpcServerImpl {
@@ -528,6 +1491,34 @@ impl RpcServerImpl {
         })
     }
 
+    /// Returns the given account of a loaded wallet, loading it on first use.
+    /// Account 0 is the wallet itself.
+    async fn wallet_account(
+        &self,
+        wallet: &str,
+        account: Option<u32>,
+    ) -> Result<RpcWallet, ErrorObjectOwned> {
+        let account = match account {
+            None | Some(0) => return self.wallet(wallet).await,
+            Some(account) => account,
+        };
+
+        // The wallet itself must be loaded before any of its accounts
+        self.wallet(wallet).await?;
+        let loaded = {
+            let wallets = self.wallet_manager.wallets.read().await;
+            wallets.get(&account_wallet_name(wallet, account)).cloned()
+        };
+        match loaded {
+            Some(rpc_wallet) => Ok(rpc_wallet),
+            None => self
+                .wallet_manager
+                .load_account(&self.client, wallet, account)
+                .await
+                .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>)),
+        }
+    }
+
     pub async fn listen(
         self,
         addrs: Vec<SocketAddr>,
@@ -544,7 +1535,11 @@ impl RpcServerImpl {
             let addr = listener.local_addr()?;
             info!("Listening at {addr}");
 
-            let handle = listener.start(self.clone().into_rpc());
+            let mut module = RpcServer::into_rpc(self.clone());
+            module.merge(StreamRpcServer::into_rpc(self.clone()))?;
+            #[cfg(feature = "testing")]
+            module.merge(TestingRpcServer::into_rpc(self.clone()))?;
+            let handle = listener.start(module);
 
             let mut signal = signal.subscribe();
             set.spawn(async move {
@@ -584,6 +1579,53 @@ impl RpcServer for RpcServerImpl {
         Ok(ServerInfo { chain, tip })
     }
 
+    async fn get_block_time(&self, height: u32) -> Result<BlockTime, ErrorObjectOwned> {
+        self.store
+            .get_block_time(height)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn get_node_sync_status(&self) -> Result<NodeSyncStatus, ErrorObjectOwned> {
+        self.store
+            .get_node_sync_status()
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn get_protocol_status(&self) -> Result<ProtocolStatus, ErrorObjectOwned> {
+        let chain = self.wallet_manager.network;
+        let activation_height = Spaced::activation(chain).height;
+        let sync = self
+            .store
+            .get_node_sync_status()
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))?;
+        let tip = self
+            .store
+            .get_tip()
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))?;
+
+        let active = sync.blocks >= activation_height;
+        let message = (!active).then(|| {
+            format!(
+                "spaces not active until height {} ({} blocks left)",
+                activation_height,
+                activation_height - sync.blocks
+            )
+        });
+        Ok(ProtocolStatus {
+            chain,
+            activation_height,
+            active,
+            message,
+            rules_version: RULES_VERSION,
+            blocks: sync.blocks,
+            tip,
+        })
+    }
+
     async fn get_space(
         &self,
         space_or_hash: &str,
@@ -598,6 +1640,151 @@ impl RpcServer for RpcServerImpl {
         Ok(info)
     }
 
+    async fn get_auction_status(
+        &self,
+        space_or_hash: &str,
+    ) -> Result<Option<AuctionStatus>, ErrorObjectOwned> {
+        let space_hash = get_space_key(space_or_hash)?;
+        let space = self
+            .store
+            .get_space(space_hash)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))?;
+        let space = match space {
+            None => return Ok(None),
+            Some(space) => space,
+        };
+        let tip = self
+            .store
+            .get_tip()
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))?;
+
+        let covenant = space.spaceout.space.as_ref().map(|s| &s.covenant);
+        let status = match covenant {
+            Some(Covenant::Bid {
+                total_burned,
+                claim_height,
+                ..
+            }) => AuctionStatus {
+                outpoint: space.outpoint(),
+                in_auction: true,
+                pre_auction: claim_height.is_none(),
+                total_burned: Some(*total_burned),
+                claim_height: *claim_height,
+                blocks_until_claim: claim_height.map(|h| h.saturating_sub(tip.height)),
+                tip,
+            },
+            _ => AuctionStatus {
+                outpoint: space.outpoint(),
+                in_auction: false,
+                pre_auction: false,
+                total_burned: None,
+                claim_height: None,
+                blocks_until_claim: None,
+                tip,
+            },
+        };
+        Ok(Some(status))
+    }
+
+    async fn get_auction_timeline(
+        &self,
+        space_or_hash: &str,
+    ) -> Result<Option<AuctionTimeline>, ErrorObjectOwned> {
+        let space_hash = get_space_key(space_or_hash)?;
+        self.store
+            .get_auction_timeline(space_hash)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn get_ownership_proof(
+        &self,
+        space_or_hash: &str,
+    ) -> Result<Option<OwnershipProof>, ErrorObjectOwned> {
+        let space_hash = get_space_key(space_or_hash)?;
+        self.store
+            .get_ownership_proof(&self.client, &self.wallet_manager.rpc, space_hash)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn validate_name(&self, name: &str) -> Result<NameValidation, ErrorObjectOwned> {
+        let label = match SLabel::try_from(name) {
+            Ok(label) => label,
+            Err(protocol::errors::Error::Name(kind)) => {
+                return Ok(NameValidation {
+                    name: name.to_string(),
+                    valid: false,
+                    error: Some(name_error_reason(&kind).to_string()),
+                    hash: None,
+                    exists: false,
+                })
+            }
+            Err(e) => return Err(ErrorObjectOwned::owned(-1, e.to_string(), None::<String>)),
+        };
+        let hash = SpaceKey::from(Sha256::hash(label.as_ref()));
+        let exists = self
+            .store
+            .get_space_outpoint(hash)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))?
+            .is_some();
+
+        Ok(NameValidation {
+            name: label.to_string(),
+            valid: true,
+            error: None,
+            hash: Some(hex::encode(hash.as_slice())),
+            exists,
+        })
+    }
+
+    async fn get_spaceout_status(
+        &self,
+        space_or_hash: &str,
+    ) -> Result<Option<SpaceOutStatus>, ErrorObjectOwned> {
+        let space_hash = get_space_key(space_or_hash)?;
+        self.store
+            .get_spaceout_status(space_hash)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn verify_space_address(
+        &self,
+        name: &str,
+        address: &str,
+    ) -> Result<SpaceAddressVerification, ErrorObjectOwned> {
+        let name = SLabel::try_from(name)
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))?;
+        let address = SpaceAddress::from_str(address)
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))?;
+        let network = self.wallet_manager.network.fallback_network();
+        if !address.is_valid_for_network(network) {
+            return Err(ErrorObjectOwned::owned(
+                -1,
+                format!("space address is not valid for network {}", network),
+                None::<String>,
+            ));
+        }
+        self.store
+            .verify_space_address(name, address, network)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn check_availability(
+        &self,
+        names: Vec<String>,
+    ) -> Result<BTreeMap<String, NameAvailability>, ErrorObjectOwned> {
+        self.store
+            .check_availability(names)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
     async fn get_space_owner(
         &self,
         space_or_hash: &str,
@@ -621,6 +1808,33 @@ impl RpcServer for RpcServerImpl {
         Ok(spaceout)
     }
 
+    async fn get_spaces_by_script(
+        &self,
+        address_or_script: &str,
+        cursor: Option<String>,
+        limit: Option<usize>,
+    ) -> Result<SpacesByScript, ErrorObjectOwned> {
+        let script = get_script_pubkey(self.wallet_manager.network, address_or_script)?;
+        let after = cursor.as_deref().map(get_space_key).transpose()?;
+        let limit = limit.unwrap_or(DEFAULT_SPACES_BY_SCRIPT_LIMIT);
+        if limit == 0 {
+            return Err(ErrorObjectOwned::owned(
+                -1,
+                "limit must be greater than zero",
+                None::<String>,
+            ));
+        }
+        let (spaces, next) = self
+            .store
+            .get_spaces_by_script(script, after, limit)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))?;
+        Ok(SpacesByScript {
+            spaces,
+            next_cursor: next.map(|key| hex::encode(key.as_slice())),
+        })
+    }
+
     async fn check_package(&self, txs: Vec<String>) -> Result<Vec<Option<TxChangeSet>>, ErrorObjectOwned> {
         let spaceout = self
             .store
@@ -670,9 +1884,13 @@ impl RpcServer for RpcServerImpl {
         Ok(data)
     }
 
-    async fn wallet_load(&self, name: &str) -> Result<(), ErrorObjectOwned> {
+    async fn wallet_load(
+        &self,
+        name: &str,
+        passphrase: Option<String>,
+    ) -> Result<(), ErrorObjectOwned> {
         self.wallet_manager
-            .load_wallet(&self.client, name)
+            .load_wallet(&self.client, name, passphrase.as_deref())
             .await
             .map_err(|error| {
                 ErrorObjectOwned::owned(RPC_WALLET_NOT_LOADED, error.to_string(), None::<String>)
@@ -696,15 +1914,34 @@ impl RpcServer for RpcServerImpl {
             .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
     }
 
-    async fn wallet_export(&self, name: &str) -> Result<WalletExport, ErrorObjectOwned> {
+    async fn wallet_get_metadata(&self, wallet: &str) -> Result<WalletMetadata, ErrorObjectOwned> {
+        self.wallet(&wallet)
+            .await?
+            .send_get_wallet_metadata()
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_export(
+        &self,
+        name: &str,
+        passphrase: Option<String>,
+    ) -> Result<WalletExport, ErrorObjectOwned> {
         self.wallet_manager
-            .export_wallet(name)
+            .export_wallet(name, passphrase.as_deref())
             .await
             .map_err(|error| {
                 ErrorObjectOwned::owned(RPC_WALLET_NOT_LOADED, error.to_string(), None::<String>)
             })
     }
 
+    async fn wallet_encrypt(&self, name: &str, passphrase: String) -> Result<(), ErrorObjectOwned> {
+        self.wallet_manager
+            .encrypt_wallet(name, &passphrase)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
     async fn wallet_create(&self, name: &str) -> Result<(), ErrorObjectOwned> {
         self.wallet_manager
             .create_wallet(&self.client, name)
@@ -713,6 +1950,21 @@ impl RpcServer for RpcServerImpl {
                 ErrorObjectOwned::owned(RPC_WALLET_NOT_LOADED, error.to_string(), None::<String>)
             })
     }
+
+    async fn wallet_recover(
+        &self,
+        name: &str,
+        mnemonic: &str,
+        passphrase: Option<String>,
+        start_height: Option<u32>,
+    ) -> Result<(), ErrorObjectOwned> {
+        self.wallet_manager
+            .recover_wallet(&self.client, name, mnemonic, passphrase, start_height)
+            .await
+            .map_err(|error| {
+                ErrorObjectOwned::owned(RPC_WALLET_NOT_LOADED, error.to_string(), None::<String>)
+            })
+    }
     async fn wallet_send_request(
         &self,
         wallet: &str,
@@ -721,98 +1973,642 @@ impl RpcServer for RpcServerImpl {
         let result = self
             .wallet(&wallet)
             .await?
-            .send_batch_tx(request)
+            .send_batch_tx(request)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))?;
+        Ok(result)
+    }
+
+    async fn wallet_preview_request(
+        &self,
+        wallet: &str,
+        request: RpcWalletTxBuilder,
+    ) -> Result<WalletResponse, ErrorObjectOwned> {
+        self.wallet(&wallet)
+            .await?
+            .send_preview_batch(request)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_build_psbt(
+        &self,
+        wallet: &str,
+        request: RpcWalletRequest,
+        inputs: Vec<OutPoint>,
+        fee_rate: Option<FeeRate>,
+    ) -> Result<String, ErrorObjectOwned> {
+        self.wallet(&wallet)
+            .await?
+            .send_build_psbt(request, inputs, fee_rate)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_broadcast_batch(
+        &self,
+        wallet: &str,
+        raw_txs: Vec<String>,
+    ) -> Result<WalletResponse, ErrorObjectOwned> {
+        self.wallet(&wallet)
+            .await?
+            .send_broadcast_batch(raw_txs)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_get_new_address(
+        &self,
+        wallet: &str,
+        kind: AddressKind,
+        account: Option<u32>,
+    ) -> Result<String, ErrorObjectOwned> {
+        self.wallet_account(&wallet, account)
+            .await?
+            .send_get_new_address(kind)
+            .await
+            .map(|new| new.address)
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_derive_address(
+        &self,
+        wallet: &str,
+        internal: bool,
+        index: u32,
+        account: Option<u32>,
+    ) -> Result<DerivedAddress, ErrorObjectOwned> {
+        self.wallet_account(&wallet, account)
+            .await?
+            .send_derive_address(internal, index)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_get_new_address_uri(
+        &self,
+        wallet: &str,
+        kind: AddressKind,
+        amount: Option<Amount>,
+        label: Option<String>,
+        account: Option<u32>,
+    ) -> Result<AddressUri, ErrorObjectOwned> {
+        if amount.is_some() && matches!(kind, AddressKind::Space) {
+            return Err(ErrorObjectOwned::owned(
+                -1,
+                "an amount can only be requested for coin addresses",
+                None::<String>,
+            ));
+        }
+        let new = self
+            .wallet_account(&wallet, account)
+            .await?
+            .send_get_new_address(kind)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))?;
+        let uri = payment_uri(kind, &new.address, amount, label.as_deref());
+        Ok(AddressUri {
+            address: new.address,
+            uri,
+            warnings: new.warnings,
+        })
+    }
+
+    async fn wallet_bump_fee(
+        &self,
+        wallet: &str,
+        txid: Txid,
+        fee_rate: FeeRate,
+        skip_tx_check: bool,
+        dry_run: Option<bool>,
+    ) -> Result<Vec<TxResponse>, ErrorObjectOwned> {
+        self.wallet(&wallet)
+            .await?
+            .send_fee_bump(txid, fee_rate, skip_tx_check, dry_run.unwrap_or(false))
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_bump_fee_to_target(
+        &self,
+        wallet: &str,
+        txid: Txid,
+        conf_target: u16,
+        dry_run: Option<bool>,
+    ) -> Result<TargetFeeBump, ErrorObjectOwned> {
+        self.wallet(&wallet)
+            .await?
+            .send_fee_bump_to_target(txid, conf_target, dry_run.unwrap_or(false))
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn bump_fee(
+        &self,
+        txid: Txid,
+        fee_rate: FeeRate,
+        skip_tx_check: bool,
+        dry_run: Option<bool>,
+    ) -> Result<Vec<TxResponse>, ErrorObjectOwned> {
+        let (name, wallet) = self
+            .wallet_manager
+            .find_tx_wallet(txid)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))?;
+        info!("Bumping fee of {} in wallet `{}`", txid, name);
+        wallet
+            .send_fee_bump(txid, fee_rate, skip_tx_check, dry_run.unwrap_or(false))
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_cancel_bid(
+        &self,
+        wallet: &str,
+        txid: Txid,
+        fee_rate: FeeRate,
+    ) -> Result<TxResponse, ErrorObjectOwned> {
+        self.wallet(&wallet)
+            .await?
+            .send_cancel_bid(txid, fee_rate)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_is_replaceable(
+        &self,
+        wallet: &str,
+        txid: Txid,
+    ) -> Result<ReplaceableInfo, ErrorObjectOwned> {
+        self.wallet(&wallet)
+            .await?
+            .send_is_replaceable(txid)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_get_claim_status(
+        &self,
+        wallet: &str,
+        space: String,
+    ) -> Result<ClaimStatus, ErrorObjectOwned> {
+        self.wallet(&wallet)
+            .await?
+            .send_get_claim_status(space)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_get_space_script(
+        &self,
+        wallet: &str,
+        space: String,
+    ) -> Result<SpaceScriptInfo, ErrorObjectOwned> {
+        self.wallet(&wallet)
+            .await?
+            .send_get_space_script(space)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_list_transactions(
+        &self,
+        wallet: &str,
+        count: usize,
+        skip: usize,
+        account: Option<u32>,
+    ) -> Result<Vec<TxInfo>, ErrorObjectOwned> {
+        self.wallet_account(&wallet, account)
+            .await?
+            .send_list_transactions(count, skip)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_list_fee_rates(
+        &self,
+        wallet: &str,
+        count: usize,
+    ) -> Result<Vec<TxFeeRate>, ErrorObjectOwned> {
+        self.wallet(&wallet)
+            .await?
+            .send_list_fee_rates(count)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_list_checkpoints(
+        &self,
+        wallet: &str,
+        count: usize,
+    ) -> Result<Vec<ChainAnchor>, ErrorObjectOwned> {
+        self.wallet(&wallet)
+            .await?
+            .send_list_checkpoints(count)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_reconcile(&self, wallet: &str) -> Result<ReconcileReport, ErrorObjectOwned> {
+        self.wallet(&wallet)
+            .await?
+            .send_reconcile()
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_abandon_all_pending(
+        &self,
+        wallet: &str,
+    ) -> Result<AbandonReport, ErrorObjectOwned> {
+        self.wallet(&wallet)
+            .await?
+            .send_abandon_all_pending()
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_abandon_open(
+        &self,
+        wallet: &str,
+        name: String,
+        reclaim_fee_rate: Option<FeeRate>,
+    ) -> Result<AbandonOpenReport, ErrorObjectOwned> {
+        self.wallet(&wallet)
+            .await?
+            .send_abandon_open(name, reclaim_fee_rate)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_bump_all_stuck(
+        &self,
+        wallet: &str,
+        min_age_blocks: u32,
+        target_fee_rate: FeeRate,
+    ) -> Result<Vec<StuckTxReport>, ErrorObjectOwned> {
+        self.wallet(&wallet)
+            .await?
+            .send_bump_all_stuck(min_age_blocks, target_fee_rate)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_list_bump_candidates(
+        &self,
+        wallet: &str,
+        min_age_blocks: u32,
+        conf_target: Option<u16>,
+    ) -> Result<Vec<BumpCandidate>, ErrorObjectOwned> {
+        self.wallet(&wallet)
+            .await?
+            .send_list_bump_candidates(min_age_blocks, conf_target)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_list_dust(
+        &self,
+        wallet: &str,
+        fee_rate: FeeRate,
+    ) -> Result<DustReport, ErrorObjectOwned> {
+        self.wallet(&wallet)
+            .await?
+            .send_list_dust(fee_rate)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_estimate_selection(
+        &self,
+        wallet: &str,
+        amount: Amount,
+        fee_rate: FeeRate,
+    ) -> Result<SelectionEstimate, ErrorObjectOwned> {
+        self.wallet(&wallet)
+            .await?
+            .send_estimate_selection(amount, fee_rate)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_get_max_sendable(
+        &self,
+        wallet: &str,
+        fee_rate: FeeRate,
+        to: String,
+    ) -> Result<MaxSendable, ErrorObjectOwned> {
+        self.wallet(&wallet)
+            .await?
+            .send_get_max_sendable(fee_rate, to)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_estimate_vsize(
+        &self,
+        wallet: &str,
+        request: RpcWalletRequest,
+    ) -> Result<VsizeEstimate, ErrorObjectOwned> {
+        self.wallet(&wallet)
+            .await?
+            .send_estimate_vsize(request)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_get_space_info(
+        &self,
+        wallet: &str,
+        space: String,
+    ) -> Result<WalletSpaceInfo, ErrorObjectOwned> {
+        self.wallet(&wallet)
+            .await?
+            .send_get_space_info(space)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_sign_message(
+        &self,
+        wallet: &str,
+        space: String,
+        message: String,
+    ) -> Result<SignedMessage, ErrorObjectOwned> {
+        self.wallet(&wallet)
+            .await?
+            .send_sign_message(space, message)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_check_operation(
+        &self,
+        wallet: &str,
+        request: RpcWalletRequest,
+    ) -> Result<OperationCheck, ErrorObjectOwned> {
+        self.wallet(&wallet)
+            .await?
+            .send_check_operation(request)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_export_history(
+        &self,
+        wallet: &str,
+        format: ExportFormat,
+        as_of: Option<u32>,
+        skip: usize,
+        count: usize,
+    ) -> Result<String, ErrorObjectOwned> {
+        self.wallet(&wallet)
+            .await?
+            .send_export_history(format, as_of, skip, count)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_force_spend(
+        &self,
+        wallet: &str,
+        outpoint: OutPoint,
+        fee_rate: FeeRate,
+    ) -> Result<TxResponse, ErrorObjectOwned> {
+        self.wallet(&wallet)
+            .await?
+            .send_force_spend(outpoint, fee_rate)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_list_spaces(
+        &self,
+        wallet: &str,
+        account: Option<u32>,
+    ) -> Result<Vec<WalletOutput>, ErrorObjectOwned> {
+        self.wallet_account(&wallet, account)
+            .await?
+            .send_list_spaces()
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_list_spaces_by_state(
+        &self,
+        wallet: &str,
+        state: Option<SpaceStateFilter>,
+    ) -> Result<SpacesByState, ErrorObjectOwned> {
+        self.wallet(&wallet)
+            .await?
+            .send_list_spaces_by_state(state)
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_list_unspent(
+        &self,
+        wallet: &str,
+        account: Option<u32>,
+    ) -> Result<Vec<WalletOutput>, ErrorObjectOwned> {
+        self.wallet_account(&wallet, account)
+            .await?
+            .send_list_unspent()
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_list_output_groups(
+        &self,
+        wallet: &str,
+        account: Option<u32>,
+    ) -> Result<OutputGroups, ErrorObjectOwned> {
+        self.wallet_account(&wallet, account)
+            .await?
+            .send_list_output_groups()
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_get_snapshot(
+        &self,
+        wallet: &str,
+        account: Option<u32>,
+    ) -> Result<WalletSnapshot, ErrorObjectOwned> {
+        self.wallet_account(&wallet, account)
+            .await?
+            .send_get_snapshot()
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_list_bidouts(&self, wallet: &str) -> Result<Vec<DoubleUtxo>, ErrorObjectOwned> {
+        self.wallet(&wallet)
+            .await?
+            .send_list_bidouts()
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_get_spaces_value_locked(
+        &self,
+        wallet: &str,
+    ) -> Result<SpacesValueLocked, ErrorObjectOwned> {
+        self.wallet(&wallet)
+            .await?
+            .send_get_spaces_value_locked()
+            .await
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
+    }
+
+    async fn wallet_get_balance(
+        &self,
+        wallet: &str,
+        account: Option<u32>,
+    ) -> Result<Balance, ErrorObjectOwned> {
+        self.wallet_account(&wallet, account)
+            .await?
+            .send_get_balance()
             .await
-            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))?;
-        Ok(result)
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
     }
 
-    async fn wallet_get_new_address(
+    async fn wallet_set_fee_policy(
         &self,
         wallet: &str,
-        kind: AddressKind,
-    ) -> Result<String, ErrorObjectOwned> {
+        policy: FeePolicy,
+    ) -> Result<(), ErrorObjectOwned> {
         self.wallet(&wallet)
             .await?
-            .send_get_new_address(kind)
+            .send_set_fee_policy(policy)
             .await
             .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
     }
 
-    async fn wallet_bump_fee(
-        &self,
-        wallet: &str,
-        txid: Txid,
-        fee_rate: FeeRate,
-        skip_tx_check: bool
-    ) -> Result<Vec<TxResponse>, ErrorObjectOwned> {
+    async fn wallet_get_fee_policy(&self, wallet: &str) -> Result<FeePolicy, ErrorObjectOwned> {
         self.wallet(&wallet)
             .await?
-            .send_fee_bump(txid, fee_rate, skip_tx_check)
+            .send_get_fee_policy()
             .await
             .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
     }
 
-    async fn wallet_list_transactions(
+    async fn wallet_set_confirm_policy(
         &self,
         wallet: &str,
-        count: usize,
-        skip: usize,
-    ) -> Result<Vec<TxInfo>, ErrorObjectOwned> {
+        policy: ConfirmPolicy,
+    ) -> Result<(), ErrorObjectOwned> {
         self.wallet(&wallet)
             .await?
-            .send_list_transactions(count, skip)
+            .send_set_confirm_policy(policy)
             .await
             .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
     }
 
-    async fn wallet_force_spend(
+    async fn wallet_get_confirm_policy(
         &self,
         wallet: &str,
-        outpoint: OutPoint,
-        fee_rate: FeeRate,
-    ) -> Result<TxResponse, ErrorObjectOwned> {
+    ) -> Result<ConfirmPolicy, ErrorObjectOwned> {
         self.wallet(&wallet)
             .await?
-            .send_force_spend(outpoint, fee_rate)
+            .send_get_confirm_policy()
             .await
             .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
     }
 
-    async fn wallet_list_spaces(
+    async fn wallet_set_tx_note(
         &self,
         wallet: &str,
-    ) -> Result<Vec<WalletOutput>, ErrorObjectOwned> {
+        txid: Txid,
+        note: String,
+    ) -> Result<(), ErrorObjectOwned> {
         self.wallet(&wallet)
             .await?
-            .send_list_spaces()
+            .send_set_tx_note(txid, note)
             .await
             .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
     }
 
-    async fn wallet_list_unspent(
+    async fn wallet_get_tx_note(
         &self,
         wallet: &str,
-    ) -> Result<Vec<WalletOutput>, ErrorObjectOwned> {
+        txid: Txid,
+    ) -> Result<Option<String>, ErrorObjectOwned> {
         self.wallet(&wallet)
             .await?
-            .send_list_unspent()
+            .send_get_tx_note(txid)
             .await
             .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
     }
 
-    async fn wallet_list_bidouts(&self, wallet: &str) -> Result<Vec<DoubleUtxo>, ErrorObjectOwned> {
+    async fn wallet_get_sync_progress(
+        &self,
+        wallet: &str,
+    ) -> Result<WalletSyncProgress, ErrorObjectOwned> {
         self.wallet(&wallet)
             .await?
-            .send_list_bidouts()
+            .send_get_sync_progress()
             .await
             .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
     }
+}
+
+#[async_trait]
+impl StreamRpcServer for RpcServerImpl {
+    async fn wallet_send_request_stream(
+        &self,
+        pending: PendingSubscriptionSink,
+        wallet: String,
+        request: RpcWalletTxBuilder,
+    ) -> SubscriptionResult {
+        let wallet = match self.wallet(&wallet).await {
+            Ok(wallet) => wallet,
+            Err(error) => {
+                pending.reject(error).await;
+                return Ok(());
+            }
+        };
+        let (mut stream, resp) = match wallet.send_batch_tx_stream(request).await {
+            Ok(receivers) => receivers,
+            Err(error) => {
+                pending
+                    .reject(ErrorObjectOwned::owned(
+                        -1,
+                        error.to_string(),
+                        None::<String>,
+                    ))
+                    .await;
+                return Ok(());
+            }
+        };
+
+        // Dropping the stream on disconnect doesn't stop the batch
+        let sink = pending.accept().await?;
+        while let Some(tx) = stream.recv().await {
+            sink.send(SubscriptionMessage::from_json(&WalletSendEvent::Tx(tx))?)
+                .await?;
+        }
+        let response = resp.await??;
+        sink.send(SubscriptionMessage::from_json(&WalletSendEvent::Done(
+            response,
+        ))?)
+        .await?;
+        Ok(())
+    }
+}
 
-    async fn wallet_get_balance(&self, wallet: &str) -> Result<Balance, ErrorObjectOwned> {
+#[cfg(feature = "testing")]
+#[async_trait]
+impl TestingRpcServer for RpcServerImpl {
+    async fn wallet_apply_test_block(
+        &self,
+        wallet: &str,
+        block_hex: String,
+        height: u32,
+    ) -> Result<(), ErrorObjectOwned> {
         self.wallet(&wallet)
             .await?
-            .send_get_balance()
+            .send_apply_test_block(block_hex, height)
             .await
             .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>))
     }
@@ -929,6 +2725,17 @@ impl AsyncChainState {
                     .context("could not fetch spaceout");
                 let _ = resp.send(result);
             }
+            ChainStateCommand::GetSpacesByScript {
+                script,
+                after,
+                limit,
+                resp,
+            } => {
+                let result = chain_state
+                    .get_spaces_by_script(&script, after, limit)
+                    .context("could not fetch spaces by script");
+                let _ = resp.send(result);
+            }
             ChainStateCommand::GetSpaceOutpoint { hash, resp } => {
                 let result = chain_state
                     .get_space_outpoint(&hash)
@@ -953,7 +2760,343 @@ impl AsyncChainState {
                 let rollouts = chain_state.get_rollout(target);
                 _ = resp.send(rollouts);
             }
+            ChainStateCommand::GetNodeSyncStatus { resp } => {
+                _ = resp.send(Self::get_node_sync_status(client, rpc).await);
+            }
+            ChainStateCommand::GetBlockTime { height, resp } => {
+                _ = resp.send(Self::get_block_time(client, rpc, height).await);
+            }
+            ChainStateCommand::CheckAvailability { names, resp } => {
+                _ = resp.send(Self::check_availability(chain_state, names));
+            }
+            ChainStateCommand::GetSpaceOutStatus { hash, resp } => {
+                _ = resp.send(Self::get_spaceout_status(client, rpc, chain_state, hash).await);
+            }
+            ChainStateCommand::VerifySpaceAddress {
+                name,
+                address,
+                network,
+                resp,
+            } => {
+                _ = resp.send(Self::verify_space_address(chain_state, name, address, network));
+            }
+            ChainStateCommand::GetAuctionTimeline { hash, resp } => {
+                _ = resp.send(Self::get_auction_timeline(client, rpc, chain_state, hash).await);
+            }
+            ChainStateCommand::HasBlockIndex { resp } => {
+                _ = resp.send(Ok(block_index.is_some()));
+            }
+        }
+    }
+
+    fn check_availability(
+        chain_state: &mut LiveSnapshot,
+        names: Vec<String>,
+    ) -> anyhow::Result<BTreeMap<String, NameAvailability>> {
+        let mut statuses = BTreeMap::new();
+        for name in names {
+            let label = match SLabel::try_from(name.as_str()) {
+                Ok(label) => label,
+                Err(protocol::errors::Error::Name(kind)) => {
+                    let error = name_error_reason(&kind).to_string();
+                    statuses.insert(name, NameAvailability::Invalid { error });
+                    continue;
+                }
+                Err(e) => return Err(anyhow!("could not parse name `{}`: {}", name, e)),
+            };
+            let hash = SpaceKey::from(Sha256::hash(label.as_ref()));
+            let space = chain_state.get_space_info(&hash)?;
+
+            let status = match space {
+                None => NameAvailability::Available,
+                Some(space) => {
+                    let outpoint = space.outpoint();
+                    match space.spaceout.space.map(|s| s.covenant) {
+                        Some(Covenant::Bid {
+                            total_burned,
+                            claim_height: None,
+                            ..
+                        }) => NameAvailability::PreAuction {
+                            outpoint,
+                            total_burned,
+                        },
+                        Some(Covenant::Bid {
+                            total_burned,
+                            claim_height: Some(claim_height),
+                            ..
+                        }) => NameAvailability::InAuction {
+                            outpoint,
+                            total_burned,
+                            claim_height,
+                        },
+                        Some(Covenant::Transfer { expire_height, .. }) => {
+                            NameAvailability::Registered {
+                                outpoint,
+                                owner: space.spaceout.script_pubkey,
+                                expire_height,
+                            }
+                        }
+                        Some(Covenant::Reserved) | None => NameAvailability::Reserved { outpoint },
+                    }
+                }
+            };
+            statuses.insert(name, status);
+        }
+        Ok(statuses)
+    }
+
+    async fn get_node_sync_status(
+        client: &reqwest::Client,
+        rpc: &BitcoinRpc,
+    ) -> anyhow::Result<NodeSyncStatus> {
+        let info: serde_json::Value = rpc.send_json(client, &rpc.get_blockchain_info()).await?;
+        let field = |name: &str| {
+            info.get(name)
+                .ok_or_else(|| anyhow!("getblockchaininfo response is missing `{}`", name))
+        };
+
+        Ok(NodeSyncStatus {
+            blocks: field("blocks")?.as_u64().unwrap_or_default() as u32,
+            headers: field("headers")?.as_u64().unwrap_or_default() as u32,
+            verification_progress: field("verificationprogress")?
+                .as_f64()
+                .unwrap_or_default(),
+            initial_block_download: field("initialblockdownload")?
+                .as_bool()
+                .unwrap_or_default(),
+            pruned: field("pruned")?.as_bool().unwrap_or_default(),
+        })
+    }
+
+    /// Checks the space's current outpoint against the bitcoin node's utxo set
+    async fn get_spaceout_status(
+        client: &reqwest::Client,
+        rpc: &BitcoinRpc,
+        chain_state: &mut LiveSnapshot,
+        hash: SpaceKey,
+    ) -> anyhow::Result<Option<SpaceOutStatus>> {
+        let tip = chain_state.tip.read().expect("read meta").clone();
+        let outpoint = match chain_state.get_space_outpoint(&hash)? {
+            None => return Ok(None),
+            Some(outpoint) => outpoint,
+        };
+
+        // gettxout returns null for spent outputs
+        let with_mempool: Option<serde_json::Value> =
+            rpc.send_json(client, &rpc.get_tx_out(&outpoint, true)).await?;
+        let (status, created_height) = match with_mempool {
+            Some(txout) => {
+                let confirmations = txout
+                    .get("confirmations")
+                    .and_then(|c| c.as_u64())
+                    .unwrap_or_default() as u32;
+                let created_height = if confirmations > 0 {
+                    let height: u32 = rpc.send_json(client, &rpc.get_block_count()).await?;
+                    Some(height + 1 - confirmations)
+                } else {
+                    None
+                };
+                (OutputSpendStatus::Unspent, created_height)
+            }
+            None => {
+                let on_chain: Option<serde_json::Value> =
+                    rpc.send_json(client, &rpc.get_tx_out(&outpoint, false)).await?;
+                match on_chain {
+                    Some(_) => (OutputSpendStatus::SpentInMempool, None),
+                    None => (OutputSpendStatus::Spent, None),
+                }
+            }
+        };
+
+        Ok(Some(SpaceOutStatus {
+            outpoint,
+            status,
+            created_height,
+            tip,
+        }))
+    }
+
+    /// Checks whether the address is the one the space is currently held at
+    fn verify_space_address(
+        chain_state: &mut LiveSnapshot,
+        name: SLabel,
+        address: SpaceAddress,
+        network: bitcoin::Network,
+    ) -> anyhow::Result<SpaceAddressVerification> {
+        let hash = SpaceKey::from(Sha256::hash(name.as_ref()));
+        let space = chain_state.get_space_info(&hash)?;
+        let script_pubkey = space.as_ref().map(|space| space.spaceout.script_pubkey.clone());
+        let current_address = match &script_pubkey {
+            Some(script) if script.is_witness_program() => Some(
+                SpaceAddress(bitcoin::Address::from_script(script, network)?).to_string(),
+            ),
+            _ => None,
+        };
+
+        Ok(SpaceAddressVerification {
+            space: name.to_string(),
+            address: address.to_string(),
+            valid: script_pubkey.is_some_and(|script| script == address.script_pubkey()),
+            current_address,
+            outpoint: space.map(|space| space.outpoint()),
+        })
+    }
+
+    /// Computes the heights of a space's auction and registration milestones
+    /// from its covenant and the protocol's rollout and auction rules
+    async fn get_auction_timeline(
+        client: &reqwest::Client,
+        rpc: &BitcoinRpc,
+        chain_state: &mut LiveSnapshot,
+        hash: SpaceKey,
+    ) -> anyhow::Result<Option<AuctionTimeline>> {
+        let tip = chain_state.tip.read().expect("read meta").clone();
+        let space = match chain_state.get_space_info(&hash)? {
+            None => return Ok(None),
+            Some(space) => space,
+        };
+        let covenant = space.spaceout.space.as_ref().map(|s| s.covenant.clone());
+
+        // (height, estimated) of each milestone
+        let (phase, auction_open, claim, expiry) = match covenant {
+            Some(Covenant::Bid {
+                claim_height: None, ..
+            }) => {
+                // Rollouts happen in blocks where (height - 1) is a multiple of the interval
+                let next_rollout = tip.height.div_ceil(ROLLOUT_BLOCK_INTERVAL)
+                    * ROLLOUT_BLOCK_INTERVAL
+                    + 1;
+                let position = chain_state
+                    .get_rollout_entries(None, 0)?
+                    .iter()
+                    .position(|(_, key)| *key == hash)
+                    .unwrap_or_default();
+                let open =
+                    next_rollout + (position / ROLLOUT_BATCH_SIZE) as u32 * ROLLOUT_BLOCK_INTERVAL;
+                let claim = open + AUCTION_DURATION;
+                (
+                    SpacePhase::PreAuction,
+                    Some((open, true)),
+                    Some((claim, true)),
+                    Some((claim + RENEWAL_INTERVAL, true)),
+                )
+            }
+            Some(Covenant::Bid {
+                claim_height: Some(claim),
+                ..
+            }) => {
+                let phase = match claim > tip.height {
+                    true => SpacePhase::InAuction,
+                    false => SpacePhase::Claimable,
+                };
+                let expiry = std::cmp::max(claim, tip.height + 1) + RENEWAL_INTERVAL;
+                (
+                    phase,
+                    None,
+                    Some((claim, claim > tip.height)),
+                    Some((expiry, true)),
+                )
+            }
+            Some(Covenant::Transfer { expire_height, .. }) => (
+                SpacePhase::Registered,
+                None,
+                None,
+                Some((expire_height, false)),
+            ),
+            Some(Covenant::Reserved) | None => (SpacePhase::Reserved, None, None, None),
+        };
+
+        let tip_time = Self::get_block_time(client, rpc, tip.height).await?;
+        let mut events = Vec::with_capacity(3);
+        for milestone in [auction_open, claim, expiry] {
+            let event = match milestone {
+                None => None,
+                Some((height, estimated)) => Some(
+                    Self::get_timeline_event(client, rpc, &tip_time, height, estimated).await?,
+                ),
+            };
+            events.push(event);
         }
+        let mut events = events.into_iter();
+
+        Ok(Some(AuctionTimeline {
+            outpoint: space.outpoint(),
+            phase,
+            auction_open: events.next().flatten(),
+            claim: events.next().flatten(),
+            expiry: events.next().flatten(),
+            tip,
+        }))
+    }
+
+    async fn ownership_link(
+        client: &reqwest::Client,
+        rpc: &BitcoinRpc,
+        step: OwnershipStep,
+        tx: &bitcoin::Transaction,
+        block: ChainAnchor,
+        spent: Option<OutPoint>,
+        output: OutPoint,
+    ) -> anyhow::Result<OwnershipLink> {
+        let txid = tx.compute_txid();
+        let txout_proof: String = rpc
+            .send_json(
+                client,
+                &rpc.make_request("gettxoutproof", serde_json::json!([[txid], block.hash])),
+            )
+            .await
+            .map_err(|e| anyhow!("Could not retrieve proof for tx {} ({})", txid, e))?;
+        Ok(OwnershipLink {
+            step,
+            txid,
+            height: block.height,
+            block_hash: block.hash,
+            spent,
+            output,
+            raw: bitcoin::consensus::encode::serialize_hex(tx),
+            txout_proof,
+        })
+    }
+
+    async fn get_timeline_event(
+        client: &reqwest::Client,
+        rpc: &BitcoinRpc,
+        tip: &BlockTime,
+        height: u32,
+        estimated: bool,
+    ) -> anyhow::Result<TimelineEvent> {
+        let time = match height <= tip.height {
+            true => Self::get_block_time(client, rpc, height).await?.time,
+            // Median time is less sensitive to miner clock skew than the tip's timestamp
+            false => tip.median_time + (height - tip.height) as u64 * TARGET_BLOCK_SPACING,
+        };
+        Ok(TimelineEvent {
+            height,
+            time,
+            estimated,
+        })
+    }
+
+    async fn get_block_time(
+        client: &reqwest::Client,
+        rpc: &BitcoinRpc,
+        height: u32,
+    ) -> anyhow::Result<BlockTime> {
+        let hash: BlockHash = rpc.send_json(client, &rpc.get_block_hash(height)).await?;
+        let header: serde_json::Value = rpc.send_json(client, &rpc.get_block_header(&hash)).await?;
+        let field = |name: &str| {
+            header
+                .get(name)
+                .and_then(|v| v.as_u64())
+                .ok_or_else(|| anyhow!("getblockheader response is missing `{}`", name))
+        };
+
+        Ok(BlockTime {
+            height,
+            hash,
+            time: field("time")?,
+            median_time: field("mediantime")?,
+        })
     }
 
     pub async fn handler(
@@ -986,6 +3129,286 @@ impl AsyncChainState {
         resp_rx.await?
     }
 
+    pub async fn get_block_time(&self, height: u32) -> anyhow::Result<BlockTime> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(ChainStateCommand::GetBlockTime { height, resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn verify_space_address(
+        &self,
+        name: SLabel,
+        address: SpaceAddress,
+        network: bitcoin::Network,
+    ) -> anyhow::Result<SpaceAddressVerification> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(ChainStateCommand::VerifySpaceAddress {
+                name,
+                address,
+                network,
+                resp,
+            })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn get_auction_timeline(
+        &self,
+        hash: SpaceKey,
+    ) -> anyhow::Result<Option<AuctionTimeline>> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(ChainStateCommand::GetAuctionTimeline { hash, resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    /// Walks a space's history back from its current output, following transfers
+    /// to the registration and bids back to the open. A bid attaches the space to an
+    /// existing output, so the transaction carrying it is found by scanning the block
+    /// index forward from where that output was created.
+    ///
+    /// The walk runs on the caller's task and takes the chain state for one lookup
+    /// at a time, so a long search doesn't hold up other requests. Without the
+    /// block index the transactions carrying the space can't be told apart and the
+    /// proof is left empty.
+    pub async fn get_ownership_proof(
+        &self,
+        client: &reqwest::Client,
+        rpc: &BitcoinRpc,
+        hash: SpaceKey,
+    ) -> anyhow::Result<Option<OwnershipProof>> {
+        let space = match self.get_space(hash).await? {
+            None => return Ok(None),
+            Some(space) => space,
+        };
+        let name = space.spaceout.space.as_ref().expect("space").name.clone();
+        if !self.has_block_index().await? {
+            return Ok(Some(OwnershipProof {
+                space: name.to_string(),
+                outpoint: space.outpoint(),
+                chain: vec![],
+                complete: false,
+            }));
+        }
+        let tip = self.get_tip().await?;
+
+        // Transfers and the registration move the space from input n - 1 to output n
+        let mut transfers = Vec::new();
+        let mut outpoint = space.outpoint();
+        loop {
+            let (tx, block, meta) = self.get_confirmed_tx(client, rpc, outpoint.txid).await?;
+            let created = meta
+                .and_then(|meta| {
+                    meta.tx_meta
+                        .into_iter()
+                        .find(|entry| entry.changeset.txid == outpoint.txid)
+                })
+                .is_some_and(|entry| {
+                    entry.changeset.creates.iter().any(|out| {
+                        out.n == outpoint.vout as usize
+                            && out.space.as_ref().is_some_and(|s| s.name == name)
+                    })
+                });
+            if !created || outpoint.vout == 0 {
+                break;
+            }
+            let spent = tx.input[outpoint.vout as usize - 1].previous_output;
+            transfers.push(
+                Self::ownership_link(
+                    client,
+                    rpc,
+                    OwnershipStep::Transfer,
+                    &tx,
+                    block,
+                    Some(spent),
+                    outpoint,
+                )
+                .await?,
+            );
+            outpoint = spent;
+        }
+        // The earliest one spent the winning bid output
+        if let Some(registration) = transfers.last_mut() {
+            registration.step = OwnershipStep::Register;
+        }
+
+        let open_script = SpaceScript::create_open(name.clone());
+        let mut bids = Vec::new();
+        let mut complete = false;
+        let mut upper = transfers.last().map_or(tip.height, |link| link.height);
+        let mut bid_output = outpoint;
+        loop {
+            let (_, created_at, _) = self.get_confirmed_tx(client, rpc, bid_output.txid).await?;
+            let found = self
+                .find_bid_tx(client, rpc, &name, bid_output, created_at.height, upper)
+                .await?;
+            let (entry, block) = match found {
+                None => break,
+                Some(found) => found,
+            };
+            let (tx, _, _) = self
+                .get_confirmed_tx(client, rpc, entry.changeset.txid)
+                .await?;
+
+            let opens = tx.input.iter().any(|input| {
+                input.witness.tapscript().is_some_and(|script| {
+                    script
+                        .as_bytes()
+                        .windows(open_script.len())
+                        .any(|window| window == open_script.as_slice())
+                })
+            });
+            if opens {
+                bids.push(
+                    Self::ownership_link(
+                        client,
+                        rpc,
+                        OwnershipStep::Open,
+                        &tx,
+                        block,
+                        None,
+                        bid_output,
+                    )
+                    .await?,
+                );
+                complete = true;
+                break;
+            }
+
+            // Outbidding spends the previous bid output
+            let spent = entry
+                .changeset
+                .spends
+                .first()
+                .map(|spend| tx.input[spend.n].previous_output);
+            bids.push(
+                Self::ownership_link(
+                    client,
+                    rpc,
+                    OwnershipStep::Bid,
+                    &tx,
+                    block,
+                    spent,
+                    bid_output,
+                )
+                .await?,
+            );
+            match spent {
+                None => break,
+                Some(spent) => bid_output = spent,
+            }
+            upper = block.height;
+        }
+
+        let chain = bids
+            .into_iter()
+            .rev()
+            .chain(transfers.into_iter().rev())
+            .collect();
+        Ok(Some(OwnershipProof {
+            space: name.to_string(),
+            outpoint: space.outpoint(),
+            chain,
+            complete,
+        }))
+    }
+
+    /// Fetches a confirmed transaction along with its block and the block's
+    /// index entry, if it has one
+    async fn get_confirmed_tx(
+        &self,
+        client: &reqwest::Client,
+        rpc: &BitcoinRpc,
+        txid: Txid,
+    ) -> anyhow::Result<(bitcoin::Transaction, ChainAnchor, Option<BlockMeta>)> {
+        let info: serde_json::Value = rpc
+            .send_json(client, &rpc.get_raw_transaction(&txid, true))
+            .await
+            .map_err(|e| anyhow!("Could not retrieve tx {} ({})", txid, e))?;
+        let raw = info
+            .get("hex")
+            .and_then(|t| t.as_str())
+            .ok_or_else(|| anyhow!("Could not retrieve tx {}", txid))?;
+        let tx: bitcoin::Transaction = bitcoin::consensus::encode::deserialize_hex(raw)?;
+        let hash = BlockHash::from_str(
+            info.get("blockhash")
+                .and_then(|t| t.as_str())
+                .ok_or_else(|| anyhow!("tx {} is not confirmed", txid))?,
+        )?;
+
+        let header: serde_json::Value = rpc
+            .send_json(client, &rpc.get_block_header(&hash))
+            .await
+            .map_err(|e| anyhow!("Could not retrieve block ({})", e))?;
+        let height = header
+            .get("height")
+            .and_then(|t| t.as_u64())
+            .ok_or_else(|| anyhow!("Could not retrieve block height"))? as u32;
+
+        let meta = self.get_block_meta(hash).await?;
+        Ok((tx, ChainAnchor { hash, height }, meta))
+    }
+
+    /// Finds the transaction between the given heights whose bid attached the
+    /// space to `output`, searching back at most [MAX_BID_SEARCH_BLOCKS] from `to`
+    async fn find_bid_tx(
+        &self,
+        client: &reqwest::Client,
+        rpc: &BitcoinRpc,
+        name: &SLabel,
+        output: OutPoint,
+        from: u32,
+        to: u32,
+    ) -> anyhow::Result<Option<(TxEntry, ChainAnchor)>> {
+        let from = from.max(to.saturating_sub(MAX_BID_SEARCH_BLOCKS));
+        for height in (from..=to).rev() {
+            let hash: BlockHash = rpc
+                .send_json(client, &rpc.get_block_hash(height))
+                .await
+                .map_err(|e| anyhow!("Could not retrieve block hash ({})", e))?;
+            let block = match self.get_block_meta(hash).await? {
+                None => continue,
+                Some(block) => block,
+            };
+            let found = block.tx_meta.into_iter().find(|entry| {
+                entry.changeset.updates.iter().any(|update| {
+                    matches!(update.kind, UpdateKind::Bid)
+                        && update.output.outpoint() == output
+                        && update
+                            .output
+                            .spaceout
+                            .space
+                            .as_ref()
+                            .is_some_and(|s| &s.name == name)
+                })
+            });
+            if let Some(entry) = found {
+                return Ok(Some((entry, ChainAnchor { hash, height })));
+            }
+        }
+        Ok(None)
+    }
+
+    pub async fn has_block_index(&self) -> anyhow::Result<bool> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(ChainStateCommand::HasBlockIndex { resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn get_node_sync_status(&self) -> anyhow::Result<NodeSyncStatus> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(ChainStateCommand::GetNodeSyncStatus { resp })
+            .await?;
+        resp_rx.await?
+    }
+
     pub async fn get_rollout(&self, target: usize) -> anyhow::Result<Vec<RolloutEntry>> {
         let (resp, resp_rx) = oneshot::channel();
         self.sender
@@ -1002,6 +3425,28 @@ impl AsyncChainState {
         resp_rx.await?
     }
 
+    pub async fn get_spaceout_status(
+        &self,
+        hash: SpaceKey,
+    ) -> anyhow::Result<Option<SpaceOutStatus>> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(ChainStateCommand::GetSpaceOutStatus { hash, resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn check_availability(
+        &self,
+        names: Vec<String>,
+    ) -> anyhow::Result<BTreeMap<String, NameAvailability>> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(ChainStateCommand::CheckAvailability { names, resp })
+            .await?;
+        resp_rx.await?
+    }
+
     pub async fn get_space_outpoint(&self, hash: SpaceKey) -> anyhow::Result<Option<OutPoint>> {
         let (resp, resp_rx) = oneshot::channel();
         self.sender
@@ -1032,6 +3477,24 @@ impl AsyncChainState {
         resp_rx.await?
     }
 
+    pub async fn get_spaces_by_script(
+        &self,
+        script: ScriptBuf,
+        after: Option<SpaceKey>,
+        limit: usize,
+    ) -> anyhow::Result<(Vec<FullSpaceOut>, Option<SpaceKey>)> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(ChainStateCommand::GetSpacesByScript {
+                script,
+                after,
+                limit,
+                resp,
+            })
+            .await?;
+        resp_rx.await?
+    }
+
     pub async fn get_block_meta(&self, block_hash: BlockHash) -> anyhow::Result<Option<BlockMeta>> {
         let (resp, resp_rx) = oneshot::channel();
         self.sender
@@ -1049,6 +3512,49 @@ impl AsyncChainState {
     }
 }
 
+fn name_error_reason(kind: &NameErrorKind) -> &'static str {
+    match kind {
+        NameErrorKind::Empty | NameErrorKind::ZeroLength => "name is empty",
+        NameErrorKind::TooLong => "name is too long",
+        NameErrorKind::EOF => "name is truncated",
+        NameErrorKind::InvalidCharacter => {
+            "name may only contain lowercase letters, digits and single hyphens \
+             not at the start or end"
+        }
+        NameErrorKind::NotCanonical => "name must be prefixed with @",
+    }
+}
+
+fn get_script_pubkey(
+    network: ExtendedNetwork,
+    address_or_script: &str,
+) -> Result<ScriptBuf, ErrorObjectOwned> {
+    let network = network.fallback_network();
+    if let Ok(address) = bitcoin::Address::from_str(address_or_script) {
+        return address
+            .require_network(network)
+            .map(|address| address.script_pubkey())
+            .map_err(|error| ErrorObjectOwned::owned(-1, error.to_string(), None::<String>));
+    }
+    if let Ok(space_address) = SpaceAddress::from_str(address_or_script) {
+        if !space_address.is_valid_for_network(network) {
+            return Err(ErrorObjectOwned::owned(
+                -1,
+                format!("space address is not valid for network {}", network),
+                None::<String>,
+            ));
+        }
+        return Ok(space_address.script_pubkey());
+    }
+    ScriptBuf::from_hex(address_or_script).map_err(|_| {
+        ErrorObjectOwned::owned(
+            -1,
+            "expected an address, a space address or a hex encoded script pubkey",
+            None::<String>,
+        )
+    })
+}
+
 fn get_space_key(space_or_hash: &str) -> Result<SpaceKey, ErrorObjectOwned> {
     if space_or_hash.len() != 64 {
         return Ok(SpaceKey::from(Sha256::hash(
@@ -1,24 +1,36 @@
 use std::{
-    collections::BTreeMap,
-    fmt,
+    collections::{BTreeMap, HashMap},
+    fmt, fs,
+    io::{Read, Seek, SeekFrom},
+    path::PathBuf,
     sync::{
         atomic::{AtomicU64, AtomicUsize, Ordering},
         mpsc::Receiver,
-        Arc,
+        Arc, Condvar, Mutex, RwLock,
     },
     time::Duration,
 };
 
 use base64::Engine;
-use bitcoin::{Block, BlockHash, Txid};
+use bitcoin::{
+    block::Header, hashes::Hash, p2p::Magic, pow::Work, Amount, Block, BlockHash, FeeRate,
+    OutPoint, Txid,
+};
 use hex::FromHexError;
-use log::{error, info};
+use log::{error, info, warn};
 use reqwest::StatusCode;
 use protocol::constants::ChainAnchor;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use threadpool::ThreadPool;
 use tokio::time::Instant;
-use wallet::{bdk_wallet::chain::ConfirmationTime, bitcoin, bitcoin::Transaction};
+use wallet::{
+    bdk_wallet::chain::ConfirmationTime,
+    bitcoin,
+    bitcoin::{
+        key::rand::{thread_rng, Rng},
+        Transaction,
+    },
+};
 
 use crate::node::BlockSource;
 
@@ -27,29 +39,162 @@ const BITCOIN_RPC_CLIENT_NOT_CONNECTED: i32 = -9; // Bitcoin is not connected
 const BITCOIN_RPC_CLIENT_IN_INITIAL_DOWNLOAD: i32 = -10; // Still downloading initial blocks
 
 const RPC_PARSE_ERROR: i32 = -32700;
+const RPC_METHOD_NOT_FOUND: i32 = -32601;
+
+/// Blocks fetched by each worker job, using one batched request to look up
+/// their hashes and another to fetch the blocks.
+const BLOCKS_PER_BATCH: u32 = 4;
+
+/// Blocks emitted between [BlockEvent::Progress] events
+const PROGRESS_INTERVAL: u32 = 100;
+
+/// Blocks this close to the tip are re-fetched on a mismatch by default
+pub const DEFAULT_TIP_REFETCH_DEPTH: u32 = 2;
+
+/// Events the block channel holds before the fetcher waits for the consumer by default
+pub const DEFAULT_BLOCK_CHANNEL_CAPACITY: usize = 12;
+
+/// Retries of a request failing with a temporary error by default
+pub const DEFAULT_MAX_RETRIES: usize = 9;
+
+/// Milliseconds before the first retry of a failed request by default
+pub const DEFAULT_RETRY_DELAY: u64 = 1000;
+
+/// Idle connections to the node kept open for reuse by default
+pub const DEFAULT_POOL_MAX_IDLE: usize = 32;
+
+/// Seconds an idle connection to the node is kept open by default
+pub const DEFAULT_POOL_IDLE_TIMEOUT: u64 = 90;
+
+/// Default `maxfeerate` in sat/vB for broadcasts (0.1 BTC/kvB)
+pub const DEFAULT_MAX_FEE_RATE: u64 = 10_000;
+
+/// Default `maxburnamount` in sat for broadcasts, high enough to allow any bid
+pub const DEFAULT_MAX_BURN_AMOUNT: u64 = 21_000_000 * 100_000_000;
 
 #[derive(Clone)]
 pub struct BitcoinRpc {
     id: Arc<AtomicU64>,
-    auth_token: Option<String>,
+    auth: Arc<BitcoinRpcAuth>,
+    /// Cached by all clones so a reloaded cookie is picked up by each of them
+    auth_token: Arc<RwLock<Option<String>>>,
     url: String,
+    /// External endpoint accepting raw transaction hex (esplora style `POST /tx`)
+    /// used when the node doesn't allow `sendrawtransaction`
+    broadcast_url: Option<String>,
+    /// File wallets append a JSON line to for every transaction they broadcast
+    broadcast_log: Option<PathBuf>,
+    /// Fee rate above which the node refuses to broadcast a transaction (zero disables)
+    max_fee_rate: FeeRate,
+    /// Value of unspendable outputs above which the node refuses to broadcast
+    max_burn_amount: Amount,
+    /// Shared by all clones to bound the number of blocking requests in flight
+    limiter: Option<Arc<RequestLimiter>>,
+    retry: RetryPolicy,
+}
+
+/// How requests failing with a temporary error (e.g. the node restarting) are retried
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt before giving up
+    pub max_retries: usize,
+    /// Delay before the first retry, doubled for each one after it
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: DEFAULT_MAX_RETRIES,
+            base_delay: Duration::from_millis(DEFAULT_RETRY_DELAY),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before the given retry, randomized by up to half of it either way so
+    /// clients failing at the same time don't all retry in lockstep
+    fn delay(&self, attempt: usize) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt as u32));
+        backoff.mul_f64(thread_rng().gen_range(0.5..1.5))
+    }
+}
+
+/// A counting semaphore bounding concurrent requests to the bitcoin node
+struct RequestLimiter {
+    available: Mutex<usize>,
+    released: Condvar,
+}
+
+struct RequestPermit<'a> {
+    limiter: &'a RequestLimiter,
+}
+
+impl RequestLimiter {
+    fn new(limit: usize) -> Self {
+        Self {
+            available: Mutex::new(limit),
+            released: Condvar::new(),
+        }
+    }
+
+    fn acquire(&self) -> RequestPermit<'_> {
+        let mut available = self.available.lock().expect("lock");
+        while *available == 0 {
+            available = self.released.wait(available).expect("lock");
+        }
+        *available -= 1;
+        RequestPermit { limiter: self }
+    }
+}
+
+impl Drop for RequestPermit<'_> {
+    fn drop(&mut self) {
+        *self.limiter.available.lock().expect("lock") += 1;
+        self.limiter.released.notify_one();
+    }
 }
 
 pub struct BlockFetcher {
     src: BitcoinBlockSource,
     job_id: Arc<AtomicUsize>,
     sender: std::sync::mpsc::SyncSender<BlockEvent>,
+    /// Capacity of the block channel, kept for when it's re-established
+    channel_capacity: usize,
     num_workers: usize,
+    /// Thread running the current job along with the job's id
+    thread: Mutex<Option<(usize, std::thread::JoinHandle<()>)>>,
+    /// How close to the tip a block mismatch may be to be resolved by re-fetching
+    /// the block instead of reporting [BlockFetchError::BlockMismatch]
+    tip_refetch_depth: u32,
+    /// How far behind the tip the fetcher must be to send the tip block ahead of
+    /// the ones before it as a [BlockEvent::TipPreview]
+    tip_preview_depth: u32,
+    /// Last block handed to the consumer, used to resume a dead fetcher
+    last_emitted: Arc<Mutex<Option<ChainAnchor>>>,
+    /// Best chain tip last seen by the fetcher
+    remote_tip: Arc<Mutex<Option<ChainAnchor>>>,
 }
 
 pub enum BlockEvent {
     Block(ChainAnchor, Block),
+    /// The tip block fetched out of order while catching up. It's not connected
+    /// to the blocks emitted so far and is emitted again as a [BlockEvent::Block]
+    /// once they reach it.
+    TipPreview(ChainAnchor, Block),
+    /// Sent every few blocks and after the last one of a run, once the block at
+    /// `height` was emitted. Purely informational for rendering sync progress.
+    Progress { height: u32, tip: u32 },
     Error(BlockFetchError),
 }
 
 pub enum BitcoinRpcAuth {
     UserPass(String, String),
-    Cookie(String),
+    /// Path of the node's cookie file, re-read when the node rejects a request
+    /// as unauthorized since it rotates the cookie on every restart
+    Cookie(PathBuf),
     None,
 }
 
@@ -64,7 +209,10 @@ pub enum BitcoinRpcError {
 pub enum BlockFetchError {
     RpcError(BitcoinRpcError),
     BlockMismatch,
+    /// The receiving end of the block channel was dropped
     ChannelClosed,
+    /// The job was stopped or superseded by a newer one
+    Stopped,
 }
 
 impl fmt::Display for BlockFetchError {
@@ -73,6 +221,7 @@ impl fmt::Display for BlockFetchError {
             BlockFetchError::RpcError(e) => write!(f, "RPC error: {}", e),
             BlockFetchError::BlockMismatch => write!(f, "Block mismatch detected"),
             BlockFetchError::ChannelClosed => write!(f, "Channel closed"),
+            BlockFetchError::Stopped => write!(f, "Fetch job stopped"),
         }
     }
 }
@@ -110,6 +259,12 @@ pub struct BitcoinRpcRequest {
     body: serde_json::Value,
 }
 
+/// Requests sent together as a single JSON-RPC batch
+pub struct BitcoinRpcBatch {
+    ids: Vec<u64>,
+    body: serde_json::Value,
+}
+
 trait ErrorForRpc {
     async fn error_for_rpc<T: DeserializeOwned>(self) -> Result<T, BitcoinRpcError>;
 }
@@ -119,14 +274,59 @@ trait ErrorForRpcBlocking {
 }
 
 impl BitcoinRpc {
-    pub fn new(url: &str, auth: BitcoinRpcAuth) -> Self {
+    pub fn new(url: &str, auth: BitcoinRpcAuth, retry: RetryPolicy) -> Self {
         Self {
             id: Default::default(),
-            auth_token: auth.to_token(),
+            auth_token: Arc::new(RwLock::new(auth.to_token().unwrap_or_else(|e| {
+                error!("Rpc: {}", e);
+                None
+            }))),
+            auth: Arc::new(auth),
             url: url.to_string(),
+            broadcast_url: None,
+            broadcast_log: None,
+            max_fee_rate: FeeRate::from_sat_per_vb_unchecked(DEFAULT_MAX_FEE_RATE),
+            max_burn_amount: Amount::from_sat(DEFAULT_MAX_BURN_AMOUNT),
+            limiter: None,
+            retry,
         }
     }
 
+    /// Limits the number of blocking requests in flight across this client and
+    /// all of its clones to `limit`, requests beyond it wait for one to finish.
+    /// Use 0 for no limit.
+    pub fn with_request_limit(mut self, limit: usize) -> Self {
+        self.limiter = match limit {
+            0 => None,
+            limit => Some(Arc::new(RequestLimiter::new(limit))),
+        };
+        self
+    }
+
+    pub fn with_broadcast_url(mut self, url: &str) -> Self {
+        self.broadcast_url = Some(url.to_string());
+        self
+    }
+
+    pub fn with_broadcast_log(mut self, path: PathBuf) -> Self {
+        self.broadcast_log = Some(path);
+        self
+    }
+
+    pub fn broadcast_log(&self) -> Option<&PathBuf> {
+        self.broadcast_log.as_ref()
+    }
+
+    pub fn with_max_fee_rate(mut self, fee_rate: FeeRate) -> Self {
+        self.max_fee_rate = fee_rate;
+        self
+    }
+
+    pub fn with_max_burn_amount(mut self, amount: Amount) -> Self {
+        self.max_burn_amount = amount;
+        self
+    }
+
     pub fn make_request(&self, method: &str, params: serde_json::Value) -> BitcoinRpcRequest {
         let id = self.id.fetch_add(1, Ordering::Relaxed);
         let body = serde_json::json!({
@@ -138,6 +338,17 @@ impl BitcoinRpc {
         return BitcoinRpcRequest { id, body };
     }
 
+    pub fn make_batch(&self, requests: Vec<BitcoinRpcRequest>) -> BitcoinRpcBatch {
+        let (ids, bodies) = requests
+            .into_iter()
+            .map(|request| (request.id, request.body))
+            .unzip();
+        BitcoinRpcBatch {
+            ids,
+            body: serde_json::Value::Array(bodies),
+        }
+    }
+
     pub fn get_block_count(&self) -> BitcoinRpcRequest {
         let params = serde_json::json!([]);
 
@@ -177,10 +388,31 @@ impl BitcoinRpc {
         self.make_request("getmempoolentry", params)
     }
 
+    pub fn get_tx_out(&self, outpoint: &OutPoint, include_mempool: bool) -> BitcoinRpcRequest {
+        let params = serde_json::json!([outpoint.txid, outpoint.vout, include_mempool]);
+        self.make_request("gettxout", params)
+    }
+
+    pub fn get_tx_spending_prevout(&self, outpoint: &OutPoint) -> BitcoinRpcRequest {
+        let params = serde_json::json!([[{ "txid": outpoint.txid, "vout": outpoint.vout }]]);
+        self.make_request("gettxspendingprevout", params)
+    }
+
+    pub fn get_raw_mempool(&self) -> BitcoinRpcRequest {
+        let params = serde_json::json!([]);
+        self.make_request("getrawmempool", params)
+    }
+
+    pub fn get_mempool_info(&self) -> BitcoinRpcRequest {
+        let params = serde_json::json!([]);
+        self.make_request("getmempoolinfo", params)
+    }
+
     pub fn send_raw_transaction(&self, tx: &Transaction) -> BitcoinRpcRequest {
         let raw_hex = bitcoin::consensus::encode::serialize_hex(&tx);
-        let params =
-            serde_json::json!([raw_hex, /* max fee rate */ 0, /* max burn amount */ 21_000_000]);
+        // Both are passed in BTC, the fee rate per kvB
+        let max_fee_rate = Amount::from_sat(self.max_fee_rate.to_sat_per_kwu() * 4).to_btc();
+        let params = serde_json::json!([raw_hex, max_fee_rate, self.max_burn_amount.to_btc()]);
 
         self.make_request("sendrawtransaction", params)
     }
@@ -202,12 +434,57 @@ impl BitcoinRpc {
             .and_then(|res| res.error_for_rpc())
     }
 
+    /// Sends a batch in a single round trip, returning the result of each request
+    /// in the order they were added to it
+    pub fn send_batch_blocking<T: DeserializeOwned>(
+        &self,
+        client: &reqwest::blocking::Client,
+        batch: &BitcoinRpcBatch,
+    ) -> Result<Vec<Result<T, BitcoinRpcError>>, BitcoinRpcError> {
+        let responses: Vec<JsonRpcResponse<T>> =
+            self.send_body_blocking(client, &batch.body)?.json()?;
+        Ok(batch_results(&batch.ids, responses))
+    }
+
     pub fn broadcast_tx(
         &self,
         client: &reqwest::blocking::Client,
         tx: &Transaction,
     ) -> Result<ConfirmationTime, BitcoinRpcError> {
-        let txid: String = self.send_json_blocking(client, &self.send_raw_transaction(tx))?;
+        let txid: String = match self.send_json_blocking(client, &self.send_raw_transaction(tx)) {
+            Ok(txid) => txid,
+            Err(e) if e.is_method_restricted() => {
+                return match self.broadcast_url.as_ref() {
+                    Some(url) => self.broadcast_tx_external(client, url, tx),
+                    None => Err(BitcoinRpcError::Other(format!(
+                        "the bitcoin node refused `sendrawtransaction` ({}): the method may be \
+                        disabled or restricted by `rpcwhitelist` for this rpc user. Allow it in \
+                        the node configuration or set an external broadcast endpoint with \
+                        --bitcoin-broadcast-url",
+                        e
+                    ))),
+                };
+            }
+            Err(e) if e.is_max_fee_exceeded() => {
+                return Err(BitcoinRpcError::Other(format!(
+                    "the bitcoin node refused the transaction because its fee rate is above the \
+                    configured maximum of {} sat/vB ({}), raise --bitcoin-max-fee-rate if this \
+                    fee is intended",
+                    self.max_fee_rate.to_sat_per_vb_ceil(),
+                    e
+                )))
+            }
+            Err(e) if e.is_max_burn_exceeded() => {
+                return Err(BitcoinRpcError::Other(format!(
+                    "the bitcoin node refused the transaction because it burns more than the \
+                    configured maximum of {} sat ({}), raise --bitcoin-max-burn-amount if this \
+                    is intended",
+                    self.max_burn_amount.to_sat(),
+                    e
+                )))
+            }
+            Err(e) => return Err(e),
+        };
 
         const MAX_RETRIES: usize = 10;
         let mut retry_count = 0;
@@ -231,34 +508,68 @@ impl BitcoinRpc {
         Err(last_error.expect("an error"))
     }
 
+    fn broadcast_tx_external(
+        &self,
+        client: &reqwest::blocking::Client,
+        url: &str,
+        tx: &Transaction,
+    ) -> Result<ConfirmationTime, BitcoinRpcError> {
+        let raw_hex = bitcoin::consensus::encode::serialize_hex(tx);
+        let res = client
+            .post(url)
+            .header("Content-Type", "text/plain")
+            .body(raw_hex)
+            .send()?;
+
+        let status = res.status();
+        let body = res.text()?;
+        if !status.is_success() {
+            return Err(BitcoinRpcError::Other(format!(
+                "external broadcast endpoint rejected transaction (HTTP code: {}): {}",
+                status, body
+            )));
+        }
+        info!("Broadcasted tx {} via external endpoint", body.trim());
+
+        let last_seen = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        Ok(ConfirmationTime::Unconfirmed { last_seen })
+    }
+
     async fn send_request(
         &self,
         client: &reqwest::Client,
         request: &BitcoinRpcRequest,
     ) -> Result<reqwest::Response, BitcoinRpcError> {
-        let mut delay = Duration::from_millis(1000);
-        let max_retries = 10;
-        let mut last_error = None;
+        let mut attempt = 0;
+        let mut reauthenticated = false;
 
-        for attempt in 0..max_retries {
+        loop {
             let mut builder = client.post(&self.url);
-            if let Some(auth) = self.auth_token.as_ref() {
+            if let Some(auth) = self.auth_token() {
                 builder = builder.header("Authorization", format!("Basic {}", auth));
             }
 
             match builder.json(&request.body).send().await.map_err(BitcoinRpcError::from) {
+                Ok(res)
+                    if res.status() == StatusCode::UNAUTHORIZED
+                        && !reauthenticated
+                        && self.reload_auth() =>
+                {
+                    reauthenticated = true;
+                }
                 Ok(res) => return Self::clean_rpc_response(res).await,
-                Err(e) if e.is_temporary() && attempt < max_retries - 1 => {
+                Err(e) if e.is_temporary() && attempt < self.retry.max_retries => {
+                    let delay = self.retry.delay(attempt);
                     error!("Rpc: {} - retrying in {:?}...", e, delay);
-                    last_error = Some(e.into());
                     tokio::time::sleep(delay).await;
-                    delay *= 2;
+                    attempt += 1;
                 }
                 Err(e) => return Err(e),
             }
         }
-
-        Err(last_error.expect("an error"))
     }
 
     fn send_request_blocking(
@@ -266,29 +577,71 @@ impl BitcoinRpc {
         client: &reqwest::blocking::Client,
         request: &BitcoinRpcRequest,
     ) -> Result<reqwest::blocking::Response, BitcoinRpcError> {
-        let mut delay = Duration::from_millis(1000);
-        let max_retries = 10;
-        let mut last_error = None;
+        self.send_body_blocking(client, &request.body)
+    }
+
+    fn send_body_blocking(
+        &self,
+        client: &reqwest::blocking::Client,
+        body: &serde_json::Value,
+    ) -> Result<reqwest::blocking::Response, BitcoinRpcError> {
+        let mut attempt = 0;
+        let mut reauthenticated = false;
 
-        for attempt in 0..max_retries {
+        loop {
             let mut builder = client.post(&self.url);
-            if let Some(auth) = self.auth_token.as_ref() {
+            if let Some(auth) = self.auth_token() {
                 builder = builder.header("Authorization", format!("Basic {}", auth));
             }
 
-            match builder.json(&request.body).send().map_err(BitcoinRpcError::from) {
+            let permit = self.limiter.as_ref().map(|limiter| limiter.acquire());
+            let result = builder.json(body).send().map_err(BitcoinRpcError::from);
+            drop(permit);
+            match result {
+                Ok(res)
+                    if res.status() == StatusCode::UNAUTHORIZED
+                        && !reauthenticated
+                        && self.reload_auth() =>
+                {
+                    reauthenticated = true;
+                }
                 Ok(res) => return Self::clean_rpc_response_blocking(res),
-                Err(e) if e.is_temporary() && attempt < max_retries - 1 => {
+                Err(e) if e.is_temporary() && attempt < self.retry.max_retries => {
+                    let delay = self.retry.delay(attempt);
                     error!("Rpc: {} - retrying in {:?}...", e, delay);
-                    last_error = Some(e.into());
                     std::thread::sleep(delay);
-                    delay *= 2;
+                    attempt += 1;
                 }
                 Err(e) => return Err(e),
             }
         }
+    }
 
-        Err(last_error.expect("an error"))
+    fn auth_token(&self) -> Option<String> {
+        self.auth_token.read().expect("auth token").clone()
+    }
+
+    /// Re-reads the cookie file after the node rejected a request, returning whether
+    /// the token changed and the request is worth retrying. `UserPass` and `None`
+    /// credentials never change so they're not reloaded.
+    fn reload_auth(&self) -> bool {
+        if !matches!(self.auth.as_ref(), BitcoinRpcAuth::Cookie(_)) {
+            return false;
+        }
+        let token = match self.auth.to_token() {
+            Ok(token) => token,
+            Err(e) => {
+                error!("Rpc: {}", e);
+                return false;
+            }
+        };
+        let mut current = self.auth_token.write().expect("auth token");
+        if *current == token {
+            return false;
+        }
+        info!("Rpc: reloaded bitcoin node cookie");
+        *current = token;
+        true
     }
 
     pub async fn clean_rpc_response(res: reqwest::Response) -> Result<reqwest::Response, BitcoinRpcError> {
@@ -351,13 +704,22 @@ impl BitcoinRpc {
 }
 
 impl BitcoinRpcAuth {
-    fn to_token(&self) -> Option<String> {
+    fn to_token(&self) -> Result<Option<String>, BitcoinRpcError> {
         match self {
-            BitcoinRpcAuth::UserPass(user, pass) => {
-                Some(base64::prelude::BASE64_STANDARD.encode(format!("{user}:{pass}")))
+            BitcoinRpcAuth::UserPass(user, pass) => Ok(Some(
+                base64::prelude::BASE64_STANDARD.encode(format!("{user}:{pass}")),
+            )),
+            BitcoinRpcAuth::Cookie(path) => {
+                let cookie = fs::read_to_string(path).map_err(|e| {
+                    BitcoinRpcError::Other(format!(
+                        "could not read cookie file {}: {}",
+                        path.display(),
+                        e
+                    ))
+                })?;
+                Ok(Some(base64::prelude::BASE64_STANDARD.encode(cookie.trim_end())))
             }
-            BitcoinRpcAuth::Cookie(cookie) => Some(cookie.clone()),
-            BitcoinRpcAuth::None => None,
+            BitcoinRpcAuth::None => Ok(None),
         }
     }
 }
@@ -367,13 +729,29 @@ impl BlockFetcher {
         src: BitcoinBlockSource,
         num_workers: usize,
     ) -> (Self, std::sync::mpsc::Receiver<BlockEvent>) {
-        let (tx, rx) = std::sync::mpsc::sync_channel(12);
+        Self::with_capacity(src, num_workers, DEFAULT_BLOCK_CHANNEL_CAPACITY)
+    }
+
+    /// Like [BlockFetcher::new] with a block channel holding up to `channel_capacity`
+    /// events before the fetcher waits for the consumer
+    pub fn with_capacity(
+        src: BitcoinBlockSource,
+        num_workers: usize,
+        channel_capacity: usize,
+    ) -> (Self, std::sync::mpsc::Receiver<BlockEvent>) {
+        let (tx, rx) = std::sync::mpsc::sync_channel(channel_capacity);
         (
             Self {
                 src,
                 job_id: Arc::new(AtomicUsize::new(0)),
                 sender: tx,
+                channel_capacity,
                 num_workers,
+                thread: Mutex::new(None),
+                tip_refetch_depth: DEFAULT_TIP_REFETCH_DEPTH,
+                tip_preview_depth: 0,
+                last_emitted: Arc::new(Mutex::new(None)),
+                remote_tip: Arc::new(Mutex::new(None)),
             },
             rx,
         )
@@ -383,6 +761,58 @@ impl BlockFetcher {
         self.job_id.fetch_add(1, Ordering::SeqCst);
     }
 
+    /// Sets how many blocks from the tip a mismatching block is re-fetched rather
+    /// than forcing the consumer to roll back. Use 0 to always report mismatches.
+    /// Takes effect the next time the fetcher is started.
+    pub fn set_tip_refetch_depth(&mut self, depth: u32) {
+        self.tip_refetch_depth = depth;
+    }
+
+    /// Sets how many blocks behind the tip the fetcher must be for the tip block to
+    /// be sent ahead of the others. Use 0 to never send it early. Takes effect the
+    /// next time the fetcher is started.
+    pub fn set_tip_preview_depth(&mut self, depth: u32) {
+        self.tip_preview_depth = depth;
+    }
+
+    /// The last block emitted by the fetcher (or the checkpoint it was started from)
+    pub fn last_emitted(&self) -> Option<ChainAnchor> {
+        *self.last_emitted.lock().expect("lock")
+    }
+
+    /// The best chain tip as of the fetcher's last check, if it has checked yet
+    pub fn remote_tip(&self) -> Option<ChainAnchor> {
+        *self.remote_tip.lock().expect("lock")
+    }
+
+    /// Whether the current job's thread exited without being stopped or reporting
+    /// an error, which happens if its receiver was dropped or it panicked.
+    /// [BlockFetcher::reconnect] resumes it.
+    pub fn is_dead(&self) -> bool {
+        match &*self.thread.lock().expect("lock") {
+            Some((job_id, thread)) => {
+                *job_id == self.job_id.load(Ordering::SeqCst) && thread.is_finished()
+            }
+            None => false,
+        }
+    }
+
+    /// Re-establishes the block channel and restarts fetching from the last
+    /// emitted block. Events not received from the previous channel are lost.
+    pub fn reconnect(&mut self) -> std::sync::mpsc::Receiver<BlockEvent> {
+        self.stop();
+        let (tx, rx) = std::sync::mpsc::sync_channel(self.channel_capacity);
+        self.sender = tx;
+        if let Some(checkpoint) = self.last_emitted() {
+            info!(
+                "Resuming block fetcher from block={} height={}",
+                checkpoint.hash, checkpoint.height
+            );
+            self.start(checkpoint);
+        }
+        rx
+    }
+
     fn should_sync(
         source: &BitcoinBlockSource,
         start: ChainAnchor,
@@ -414,8 +844,13 @@ impl BlockFetcher {
         let current_task = self.job_id.clone();
         let task_sender = self.sender.clone();
         let num_workers = self.num_workers;
+        let tip_refetch_depth = self.tip_refetch_depth;
+        let tip_preview_depth = self.tip_preview_depth;
+        let last_emitted = self.last_emitted.clone();
+        *last_emitted.lock().expect("lock") = Some(checkpoint);
+        let remote_tip = self.remote_tip.clone();
 
-        _ = std::thread::spawn(move || {
+        let thread = std::thread::spawn(move || {
             let mut last_check = Instant::now() - Duration::from_secs(2);
 
             loop {
@@ -430,37 +865,78 @@ impl BlockFetcher {
                 last_check = Instant::now();
 
                 let tip = match BlockFetcher::should_sync(&task_src, checkpoint) {
-                    Ok(t) => t,
+                    Ok(t) => {
+                        *remote_tip.lock().expect("lock") = Some(t.unwrap_or(checkpoint));
+                        t
+                    }
                     Err(e) => {
+                        Self::retire_job(&current_task, job_id);
                         _ = task_sender.send(BlockEvent::Error(e));
                         return;
                     }
                 };
 
                 if let Some(tip) = tip {
+                    if tip_preview_depth != 0
+                        && tip.height - checkpoint.height >= tip_preview_depth
+                    {
+                        match Self::fetch_block(&task_src, &tip.hash) {
+                            Ok(block) => {
+                                _ = task_sender.send(BlockEvent::TipPreview(tip, block));
+                            }
+                            Err(e) => warn!("Could not fetch tip block {}: {}", tip.hash, e),
+                        }
+                    }
+
                     let res = Self::run_workers(
                         job_id,
                         current_task.clone(),
                         task_src.clone(),
                         task_sender.clone(),
+                        last_emitted.clone(),
                         checkpoint,
                         tip.height,
                         num_workers,
+                        tip_refetch_depth,
                     );
 
                     match res {
                         Ok(new_tip) => {
                             checkpoint = new_tip;
                         }
+                        Err(BlockFetchError::Stopped) => {
+                            info!("Shutting down block fetcher");
+                            return;
+                        }
+                        Err(BlockFetchError::ChannelClosed) => {
+                            // Only reachable while this job is still current, so the
+                            // consumer went away without stopping us. Leave the job id
+                            // untouched and let the caller reconnect to resume.
+                            if let Some(at) = *last_emitted.lock().expect("lock") {
+                                warn!(
+                                    "Block fetcher channel closed unexpectedly at height {}, \
+                                     waiting for reconnect",
+                                    at.height
+                                );
+                            }
+                            return;
+                        }
                         Err(e) => {
+                            Self::retire_job(&current_task, job_id);
                             _ = task_sender.send(BlockEvent::Error(e));
-                            current_task.fetch_add(1, Ordering::SeqCst);
                             return;
                         }
                     }
                 }
             }
         });
+        *self.thread.lock().expect("lock") = Some((job_id, thread));
+    }
+
+    /// Ends a job that's reporting an error so it isn't taken for a dead one.
+    /// Done before the error is sent as the consumer may restart on receiving it.
+    fn retire_job(current_job: &AtomicUsize, job_id: usize) {
+        _ = current_job.compare_exchange(job_id, job_id + 1, Ordering::SeqCst, Ordering::SeqCst);
     }
 
     fn run_workers(
@@ -468,20 +944,24 @@ impl BlockFetcher {
         current_job: Arc<AtomicUsize>,
         src: BitcoinBlockSource,
         sender: std::sync::mpsc::SyncSender<BlockEvent>,
+        emitted: Arc<Mutex<Option<ChainAnchor>>>,
         start_block: ChainAnchor,
         end_height: u32,
         num_workers: usize,
+        tip_refetch_depth: u32,
     ) -> Result<ChainAnchor, BlockFetchError> {
         let mut workers = Workers {
             current_job,
             job_id,
             out_of_order: Default::default(),
             last_emitted: start_block,
+            emitted,
             queued_height: start_block.height + 1,
             end_height,
             ordered_sender: sender,
             src,
             num_workers,
+            tip_refetch_depth,
             pool: ThreadPool::new(num_workers),
         };
 
@@ -519,6 +999,42 @@ impl BlockFetcher {
                 hex_block.result.unwrap().into_bytes()
             };
 
+        Self::decode_block(hex_block)
+    }
+
+    /// Fetches several blocks in a single round trip, in the order of `hashes`
+    pub fn fetch_blocks(
+        source: &BitcoinBlockSource,
+        hashes: &[BlockHash],
+    ) -> Result<Vec<Block>, BitcoinRpcError> {
+        let batch = source.rpc.make_batch(
+            hashes
+                .iter()
+                .map(|hash| source.rpc.get_block(hash))
+                .collect(),
+        );
+        let raw = source
+            .rpc
+            .send_body_blocking(&source.client, &batch.body)?
+            .bytes()?;
+
+        // Check if we can quickly extract blocks
+        let hex_blocks = match split_batch_hex(&raw, &batch.ids) {
+            Some(hex_blocks) => hex_blocks.into_iter().map(|hex| hex.to_vec()).collect(),
+            None => {
+                // fallback to decoding json
+                let responses: Vec<JsonRpcResponse<String>> = serde_json::from_slice(&raw)
+                    .map_err(|e| BitcoinRpcError::Other(e.to_string()))?;
+                batch_results(&batch.ids, responses)
+                    .into_iter()
+                    .map(|hex_block| hex_block.map(String::into_bytes))
+                    .collect::<Result<Vec<_>, _>>()?
+            }
+        };
+        hex_blocks.into_iter().map(Self::decode_block).collect()
+    }
+
+    fn decode_block(hex_block: Vec<u8>) -> Result<Block, BitcoinRpcError> {
         if hex_block.len() % 2 != 0 {
             return Err(BitcoinRpcError::Other(
                 "Parse error: could not hex decode block".to_string(),
@@ -542,11 +1058,13 @@ struct Workers {
     job_id: usize,
     out_of_order: BTreeMap<u32, (ChainAnchor, Block)>,
     last_emitted: ChainAnchor,
+    emitted: Arc<Mutex<Option<ChainAnchor>>>,
     queued_height: u32,
     end_height: u32,
     ordered_sender: std::sync::mpsc::SyncSender<BlockEvent>,
     src: BitcoinBlockSource,
     num_workers: usize,
+    tip_refetch_depth: u32,
     pool: ThreadPool,
 }
 
@@ -559,7 +1077,7 @@ impl Workers {
     ) -> Result<bool, BlockFetchError> {
         while let Ok(unordered_block) = unordered.try_recv() {
             if self.should_stop() {
-                return Err(BlockFetchError::ChannelClosed);
+                return Err(BlockFetchError::Stopped);
             }
             let (id, block) = unordered_block?;
             self.out_of_order.insert(id.height, (id, block));
@@ -572,22 +1090,72 @@ impl Workers {
 
         if let Some((id, block)) = self.out_of_order.remove(&next) {
             if self.should_stop() {
-                return Err(BlockFetchError::ChannelClosed);
+                return Err(BlockFetchError::Stopped);
             }
 
-            if block.header.prev_blockhash != self.last_emitted.hash {
-                return Err(BlockFetchError::BlockMismatch);
-            }
+            let (id, block) = if block.header.prev_blockhash != self.last_emitted.hash {
+                self.refetch_tip_block(id)?
+            } else {
+                (id, block)
+            };
 
+            self.send(BlockEvent::Block(id.clone(), block))?;
             self.last_emitted = id;
-            self.ordered_sender
-                .send(BlockEvent::Block(id.clone(), block))
-                .map_err(|_| BlockFetchError::ChannelClosed)?;
+            *self.emitted.lock().expect("lock") = Some(id);
+            if id.height % PROGRESS_INTERVAL == 0 || id.height == self.end_height {
+                self.send(BlockEvent::Progress {
+                    height: id.height,
+                    tip: self.end_height,
+                })?;
+            }
             return Ok(true);
         }
         Ok(false)
     }
 
+    fn send(&self, event: BlockEvent) -> Result<(), BlockFetchError> {
+        self.ordered_sender.send(event).map_err(|_| {
+            if self.should_stop() {
+                BlockFetchError::Stopped
+            } else {
+                BlockFetchError::ChannelClosed
+            }
+        })
+    }
+
+    /// A block near the tip may have been orphaned between fetching and emitting it.
+    /// If the block currently at that height builds on the last emitted one, it's
+    /// used instead so a shallow tip reorg doesn't force the consumer to roll back.
+    fn refetch_tip_block(
+        &self,
+        stale: ChainAnchor,
+    ) -> Result<(ChainAnchor, Block), BlockFetchError> {
+        if self.end_height.saturating_sub(stale.height) >= self.tip_refetch_depth {
+            return Err(BlockFetchError::BlockMismatch);
+        }
+        let hash = match self.src.get_block_hash(stale.height) {
+            Ok(hash) if hash != stale.hash => hash,
+            // Same block, so the last emitted block is the one that got orphaned
+            _ => return Err(BlockFetchError::BlockMismatch),
+        };
+        let block = BlockFetcher::fetch_block(&self.src, &hash)
+            .map_err(|_| BlockFetchError::BlockMismatch)?;
+        if block.header.prev_blockhash != self.last_emitted.hash {
+            return Err(BlockFetchError::BlockMismatch);
+        }
+        info!(
+            "Re-fetched block={} height={} after a tip reorg",
+            hash, stale.height
+        );
+        Ok((
+            ChainAnchor {
+                height: stale.height,
+                hash,
+            },
+            block,
+        ))
+    }
+
     #[inline(always)]
     fn can_add_workers(&self) -> bool {
         self.out_of_order.len() < self.num_workers
@@ -605,31 +1173,56 @@ impl Workers {
 
         'queue_blocks: while !self.queued_all() {
             if self.should_stop() {
-                return Err(BlockFetchError::ChannelClosed);
+                return Err(BlockFetchError::Stopped);
             }
 
             while self.can_add_workers() {
                 if self.should_stop() {
-                    return Err(BlockFetchError::ChannelClosed);
+                    return Err(BlockFetchError::Stopped);
                 }
                 let tx = tx.clone();
                 let rpc = self.src.clone();
                 let task_sigterm = self.current_job.clone();
-                let height = self.queued_height;
+                let start = self.queued_height;
+                let end = (start + BLOCKS_PER_BATCH - 1).min(self.end_height);
                 let job_id = self.job_id;
 
                 self.pool.execute(move || {
                     if task_sigterm.load(Ordering::SeqCst) != job_id {
                         return;
                     }
-                    let result: Result<_, BitcoinRpcError> = (move || {
-                        let hash: BlockHash = rpc.get_block_hash(height)?;
-                        let block = BlockFetcher::fetch_block(&rpc, &hash)?;
-                        Ok((ChainAnchor { height, hash }, block))
+                    let result: Result<_, BitcoinRpcError> = (|| {
+                        let heights: Vec<u32> = (start..=end).collect();
+                        let hash_batch = rpc.rpc.make_batch(
+                            heights
+                                .iter()
+                                .map(|height| rpc.rpc.get_block_hash(*height))
+                                .collect(),
+                        );
+                        let hashes = rpc
+                            .rpc
+                            .send_batch_blocking::<BlockHash>(&rpc.client, &hash_batch)?
+                            .into_iter()
+                            .collect::<Result<Vec<_>, _>>()?;
+                        if task_sigterm.load(Ordering::SeqCst) != job_id {
+                            return Ok(());
+                        }
+
+                        // Blocks are checked to connect when emitted, so a reorg
+                        // between the two batches is caught there
+                        let blocks = BlockFetcher::fetch_blocks(&rpc, &hashes)?;
+                        for ((height, hash), block) in heights.into_iter().zip(hashes).zip(blocks) {
+                            if tx.send(Ok((ChainAnchor { height, hash }, block))).is_err() {
+                                break;
+                            }
+                        }
+                        Ok(())
                     })();
-                    _ = tx.send(result);
+                    if let Err(e) = result {
+                        _ = tx.send(Err(e));
+                    }
                 });
-                self.queued_height += 1;
+                self.queued_height = end + 1;
             }
 
             // Emits any completed blocks while workers are processing
@@ -684,10 +1277,43 @@ fn val(c: u8, idx: usize) -> Result<u8, FromHexError> {
 }
 
 impl BitcoinRpcError {
+    /// Whether the node refused the call due to its configuration
+    /// (method disabled or not whitelisted for this user)
+    fn is_method_restricted(&self) -> bool {
+        match self {
+            BitcoinRpcError::Rpc(e) => {
+                e.code == RPC_METHOD_NOT_FOUND || e.message.contains("HTTP code: 403")
+            }
+            BitcoinRpcError::Transport(e) => e.status() == Some(StatusCode::FORBIDDEN),
+            _ => false,
+        }
+    }
+
+    /// Whether the node refused a broadcast for exceeding `maxfeerate`
+    fn is_max_fee_exceeded(&self) -> bool {
+        match self {
+            BitcoinRpcError::Rpc(e) => {
+                e.message.contains("max-fee-exceeded") || e.message.contains("maxfeerate")
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the node refused a broadcast for exceeding `maxburnamount`
+    fn is_max_burn_exceeded(&self) -> bool {
+        match self {
+            BitcoinRpcError::Rpc(e) => e.message.contains("maxburnamount"),
+            _ => false,
+        }
+    }
+
     fn is_temporary(&self) -> bool {
         match self {
             BitcoinRpcError::Transport(e) => {
-                if e.is_timeout() || e.is_connect() {
+                if e.is_timeout() || e.is_connect() || e.is_request() || e.is_body() {
+                    return true;
+                }
+                if is_connection_dropped(e) {
                     return true;
                 }
                 if let Some(status) = e.status() {
@@ -716,6 +1342,27 @@ impl BitcoinRpcError {
     }
 }
 
+/// Whether the error was caused by the connection being reset or closed mid-request
+/// e.g. when bitcoind restarts or a proxy drops the connection
+fn is_connection_dropped(e: &reqwest::Error) -> bool {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(e);
+    while let Some(err) = source {
+        if let Some(io) = err.downcast_ref::<std::io::Error>() {
+            if matches!(
+                io.kind(),
+                std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::BrokenPipe
+                    | std::io::ErrorKind::UnexpectedEof
+            ) {
+                return true;
+            }
+        }
+        source = err.source();
+    }
+    false
+}
+
 impl From<reqwest::Error> for BitcoinRpcError {
     fn from(value: reqwest::Error) -> Self {
         Self::Transport(value)
@@ -752,7 +1399,7 @@ impl ErrorForRpc for reqwest::Response {
             return Err(BitcoinRpcError::Rpc(e));
         }
 
-        Ok(rpc_res.result.unwrap())
+        rpc_result(rpc_res.result)
     }
 }
 
@@ -763,21 +1410,115 @@ impl ErrorForRpcBlocking for reqwest::blocking::Response {
             return Err(BitcoinRpcError::Rpc(e));
         }
 
-        Ok(rpc_res.result.unwrap())
+        rpc_result(rpc_res.result)
+    }
+}
+
+/// Matches batch responses to the requests by id, bitcoind may answer in any order
+fn batch_results<T: DeserializeOwned>(
+    ids: &[u64],
+    responses: Vec<JsonRpcResponse<T>>,
+) -> Vec<Result<T, BitcoinRpcError>> {
+    let mut by_id: HashMap<String, JsonRpcResponse<T>> = responses
+        .into_iter()
+        .map(|response| (response.id.clone(), response))
+        .collect();
+    ids.iter()
+        .map(|id| {
+            let response = by_id.remove(&id.to_string()).ok_or_else(|| {
+                BitcoinRpcError::Other(format!("no response to batched request {}", id))
+            })?;
+            if let Some(e) = response.error {
+                return Err(BitcoinRpcError::Rpc(e));
+            }
+            rpc_result(response.result)
+        })
+        .collect()
+}
+
+/// Extracts the hex results of a batch of `getblock` requests without decoding
+/// the response as json, if it's laid out exactly as bitcoind writes it
+fn split_batch_hex<'a>(raw: &'a [u8], ids: &[u64]) -> Option<Vec<&'a [u8]>> {
+    let mut rest = raw.strip_prefix(b"[")?;
+    let mut hex_blocks = Vec::with_capacity(ids.len());
+    for (n, id) in ids.iter().enumerate() {
+        if n > 0 {
+            rest = rest.strip_prefix(b",")?;
+        }
+        rest = rest.strip_prefix(b"{\"result\":\"")?;
+        let end = rest.iter().position(|byte| *byte == b'"')?;
+        hex_blocks.push(&rest[..end]);
+        let end_needle = format!("\",\"error\":null,\"id\":\"{}\"}}", id);
+        rest = rest[end..].strip_prefix(end_needle.as_bytes())?;
+    }
+    matches!(rest, b"]" | b"]\n").then_some(hex_blocks)
+}
+
+/// A null result (e.g. `gettxout` for a spent output) is only valid
+/// if the caller expects one such as an `Option<T>`
+fn rpc_result<T: DeserializeOwned>(result: Option<T>) -> Result<T, BitcoinRpcError> {
+    match result {
+        Some(result) => Ok(result),
+        None => serde_json::from_value(serde_json::Value::Null)
+            .map_err(|_| BitcoinRpcError::Other("unexpected null rpc result".to_string())),
     }
 }
 
 #[derive(Clone)]
 pub struct BitcoinBlockSource {
+    /// Shared by all clones of the source, including block fetcher workers,
+    /// so connections to the node are kept alive and reused
     pub client: reqwest::blocking::Client,
     pub rpc: BitcoinRpc,
 }
 
+/// Connection pool settings of the client used to reach the node
+#[derive(Debug, Clone, Copy)]
+pub struct ClientPool {
+    /// Maximum number of idle connections kept open
+    pub max_idle: usize,
+    /// How long an idle connection is kept open, forever if not set
+    pub idle_timeout: Option<Duration>,
+}
+
+impl Default for ClientPool {
+    fn default() -> Self {
+        Self {
+            max_idle: DEFAULT_POOL_MAX_IDLE,
+            idle_timeout: Some(Duration::from_secs(DEFAULT_POOL_IDLE_TIMEOUT)),
+        }
+    }
+}
+
 impl BitcoinBlockSource {
     pub fn new(rpc: BitcoinRpc) -> Self {
-        let client = reqwest::blocking::Client::new();
+        Self::with_pool(rpc, ClientPool::default())
+    }
+
+    /// Creates a source whose client pools connections with the given settings.
+    /// Must not be called from within an async runtime.
+    pub fn with_pool(rpc: BitcoinRpc, pool: ClientPool) -> Self {
+        let client = reqwest::blocking::Client::builder()
+            .pool_max_idle_per_host(pool.max_idle)
+            .pool_idle_timeout(pool.idle_timeout)
+            .build()
+            .expect("could not create http client");
         Self { client, rpc }
     }
+
+    /// Whether the node is still in initial block download
+    pub fn in_initial_block_download(&self) -> Result<bool, BitcoinRpcError> {
+        let info: serde_json::Value = self
+            .rpc
+            .send_json_blocking(&self.client, &self.rpc.get_blockchain_info())?;
+        info.get("initialblockdownload")
+            .and_then(|ibd| ibd.as_bool())
+            .ok_or_else(|| {
+                BitcoinRpcError::Other(
+                    "getblockchaininfo response is missing `initialblockdownload`".to_string(),
+                )
+            })
+    }
 }
 
 impl BlockSource for BitcoinBlockSource {
@@ -829,3 +1570,311 @@ impl BlockSource for BitcoinBlockSource {
         })
     }
 }
+
+/// Reads blocks directly from bitcoind's `blocks/` directory (blk*.dat files)
+/// which is much faster than fetching them over rpc during initial sync.
+pub struct FileBlockSource {
+    blocks_dir: PathBuf,
+    xor_key: Option<[u8; 8]>,
+    locations: HashMap<BlockHash, BlockLocation>,
+    chain: Vec<BlockHash>,
+}
+
+#[derive(Clone, Copy)]
+struct BlockLocation {
+    file: u32,
+    offset: u64,
+    size: u32,
+    prev: BlockHash,
+    time: u32,
+    work: Work,
+}
+
+impl FileBlockSource {
+    /// Indexes all block headers found in `blocks_dir` and selects the chain with the most work
+    pub fn new(blocks_dir: PathBuf, magic: Magic) -> Result<Self, BitcoinRpcError> {
+        let xor_key = match fs::read(blocks_dir.join("xor.dat")) {
+            Ok(key) if key.len() == 8 && key.iter().any(|b| *b != 0) => {
+                Some(key.try_into().expect("8 bytes"))
+            }
+            _ => None,
+        };
+
+        let mut source = Self {
+            blocks_dir,
+            xor_key,
+            locations: HashMap::new(),
+            chain: Vec::new(),
+        };
+
+        let mut file = 0;
+        while source.block_file_path(file).exists() {
+            source.index_file(file, magic)?;
+            file += 1;
+        }
+        source.select_best_chain();
+
+        info!(
+            "Indexed {} blocks from {} block files (best height: {})",
+            source.locations.len(),
+            file,
+            source.chain.len().saturating_sub(1)
+        );
+        Ok(source)
+    }
+
+    fn block_file_path(&self, file: u32) -> PathBuf {
+        self.blocks_dir.join(format!("blk{:05}.dat", file))
+    }
+
+    fn read_at(&self, file: &mut fs::File, offset: u64, buf: &mut [u8]) -> std::io::Result<()> {
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(buf)?;
+        if let Some(key) = self.xor_key {
+            for (i, byte) in buf.iter_mut().enumerate() {
+                *byte ^= key[((offset + i as u64) % 8) as usize];
+            }
+        }
+        Ok(())
+    }
+
+    fn index_file(&mut self, file_num: u32, magic: Magic) -> Result<(), BitcoinRpcError> {
+        let path = self.block_file_path(file_num);
+        let mut file = fs::File::open(&path).map_err(file_error)?;
+        let len = file.metadata().map_err(file_error)?.len();
+
+        let mut offset = 0;
+        let mut record = [0u8; 8 + 80];
+        while offset + record.len() as u64 <= len {
+            self.read_at(&mut file, offset, &mut record)
+                .map_err(file_error)?;
+            // the rest of the file is pre-allocated space
+            if record[..4] != magic.to_bytes() {
+                break;
+            }
+            let size = u32::from_le_bytes(record[4..8].try_into().expect("4 bytes"));
+            let header: Header = bitcoin::consensus::deserialize(&record[8..])
+                .map_err(|e| BitcoinRpcError::Other(format!("invalid block header: {}", e)))?;
+
+            self.locations.insert(
+                header.block_hash(),
+                BlockLocation {
+                    file: file_num,
+                    offset: offset + 8,
+                    size,
+                    prev: header.prev_blockhash,
+                    time: header.time,
+                    work: header.work(),
+                },
+            );
+            offset += 8 + size as u64;
+        }
+        Ok(())
+    }
+
+    fn select_best_chain(&mut self) {
+        let mut children: HashMap<BlockHash, Vec<BlockHash>> = HashMap::new();
+        let mut genesis = None;
+        for (hash, location) in self.locations.iter() {
+            if location.prev == BlockHash::all_zeros() {
+                genesis = Some(*hash);
+                continue;
+            }
+            children.entry(location.prev).or_default().push(*hash);
+        }
+        let genesis = match genesis {
+            None => return,
+            Some(genesis) => genesis,
+        };
+
+        // Walk down from genesis finding the block with the most cumulative work
+        let genesis_work = self.locations[&genesis].work;
+        let mut best = (0, genesis_work, genesis);
+        let mut queue = vec![(0u32, genesis_work, genesis)];
+        while let Some((height, work, hash)) = queue.pop() {
+            if work > best.1 {
+                best = (height, work, hash);
+            }
+            if let Some(next) = children.get(&hash) {
+                queue.extend(
+                    next.iter()
+                        .map(|child| (height + 1, work + self.locations[child].work, *child)),
+                );
+            }
+        }
+
+        let mut chain = Vec::with_capacity(best.0 as usize + 1);
+        let mut hash = best.2;
+        loop {
+            chain.push(hash);
+            if hash == genesis {
+                break;
+            }
+            hash = self.locations[&hash].prev;
+        }
+        chain.reverse();
+        self.chain = chain;
+    }
+
+    fn tip_height(&self) -> Result<u32, BitcoinRpcError> {
+        match self.chain.len() {
+            0 => Err(BitcoinRpcError::Other("no blocks found in block files".to_string())),
+            len => Ok(len as u32 - 1),
+        }
+    }
+}
+
+fn file_error(e: std::io::Error) -> BitcoinRpcError {
+    BitcoinRpcError::Other(format!("block file error: {}", e))
+}
+
+impl BlockSource for FileBlockSource {
+    fn get_block_hash(&self, height: u32) -> Result<BlockHash, BitcoinRpcError> {
+        self.chain.get(height as usize).cloned().ok_or_else(|| {
+            BitcoinRpcError::Other(format!("block height {} not found in block files", height))
+        })
+    }
+
+    fn get_block(&self, hash: &BlockHash) -> Result<Block, BitcoinRpcError> {
+        let location = self.locations.get(hash).ok_or_else(|| {
+            BitcoinRpcError::Other(format!("block {} not found in block files", hash))
+        })?;
+        let mut file =
+            fs::File::open(self.block_file_path(location.file)).map_err(file_error)?;
+        let mut raw = vec![0u8; location.size as usize];
+        self.read_at(&mut file, location.offset, &mut raw)
+            .map_err(file_error)?;
+
+        bitcoin::consensus::deserialize(&raw)
+            .map_err(|e| BitcoinRpcError::Other(format!("invalid block {}: {}", hash, e)))
+    }
+
+    fn get_median_time(&self) -> Result<u64, BitcoinRpcError> {
+        let mut times: Vec<_> = self
+            .chain
+            .iter()
+            .rev()
+            .take(11)
+            .map(|hash| self.locations[hash].time)
+            .collect();
+        if times.is_empty() {
+            return Err(BitcoinRpcError::Other(
+                "Could not fetch median time".to_string(),
+            ));
+        }
+        times.sort();
+        Ok(times[times.len() / 2] as u64)
+    }
+
+    fn get_block_count(&self) -> Result<u64, BitcoinRpcError> {
+        Ok(self.tip_height()? as u64)
+    }
+
+    fn get_best_chain(&self) -> Result<ChainAnchor, BitcoinRpcError> {
+        let height = self.tip_height()?;
+        Ok(ChainAnchor {
+            height,
+            hash: self.chain[height as usize],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn responses(raw: &str) -> Vec<JsonRpcResponse<String>> {
+        serde_json::from_str(raw).expect("json-rpc responses")
+    }
+
+    #[test]
+    fn test_batch_results_by_id() {
+        let results = batch_results(
+            &[1, 2, 3],
+            responses(
+                r#"[{"result":"c","error":null,"id":"3"},
+                    {"result":"a","error":null,"id":"1"},
+                    {"result":"b","error":null,"id":"2"}]"#,
+            ),
+        );
+        let results: Vec<_> = results.into_iter().map(|r| r.ok()).collect();
+        assert_eq!(
+            results,
+            vec![
+                Some("a".to_string()),
+                Some("b".to_string()),
+                Some("c".to_string())
+            ],
+            "results must follow the order of the requests"
+        );
+    }
+
+    #[test]
+    fn test_batch_results_missing_and_failed() {
+        let results = batch_results(
+            &[1, 2, 3],
+            responses(
+                r#"[{"result":"a","error":null,"id":"1"},
+                    {"result":null,"error":{"code":-5,"message":"Block not found"},"id":"3"}]"#,
+            ),
+        );
+        assert_eq!(results.len(), 3);
+        assert!(matches!(&results[0], Ok(result) if result == "a"));
+        assert!(
+            matches!(&results[1], Err(BitcoinRpcError::Other(e)) if e.contains("request 2")),
+            "a missing response must fail its own request"
+        );
+        assert!(
+            matches!(&results[2], Err(BitcoinRpcError::Rpc(e)) if e.code == -5),
+            "an error response must fail its own request"
+        );
+    }
+
+    #[test]
+    fn test_split_batch_hex() {
+        let raw =
+            br#"[{"result":"00ab","error":null,"id":"7"},{"result":"ff","error":null,"id":"8"}]"#;
+        let expected: Vec<&[u8]> = vec![b"00ab", b"ff"];
+        assert_eq!(split_batch_hex(raw, &[7, 8]), Some(expected.clone()));
+
+        let mut with_newline = raw.to_vec();
+        with_newline.push(b'\n');
+        assert_eq!(split_batch_hex(&with_newline, &[7, 8]), Some(expected));
+    }
+
+    #[test]
+    fn test_split_batch_hex_falls_back() {
+        let raw =
+            br#"[{"result":"00ab","error":null,"id":"7"},{"result":"ff","error":null,"id":"8"}]"#;
+        assert_eq!(
+            split_batch_hex(raw, &[8, 7]),
+            None,
+            "out of order responses must be decoded as json"
+        );
+        assert_eq!(
+            split_batch_hex(raw, &[7, 8, 9]),
+            None,
+            "a missing response must be decoded as json"
+        );
+        assert_eq!(split_batch_hex(raw, &[7]), None);
+
+        let failed = br#"[{"result":"00ab","error":null,"id":"7"},{"result":null,"error":{"code":-5,"message":"Block not found"},"id":"8"}]"#;
+        assert_eq!(
+            split_batch_hex(failed, &[7, 8]),
+            None,
+            "an error response must be decoded as json"
+        );
+
+        let spaced = r#"[ {"result": "00ab", "error": null, "id": "7"} ]"#;
+        assert_eq!(split_batch_hex(spaced.as_bytes(), &[7]), None);
+        let results: Vec<_> = batch_results(&[7], responses(spaced))
+            .into_iter()
+            .map(|r| r.ok())
+            .collect();
+        assert_eq!(
+            results,
+            vec![Some("00ab".to_string())],
+            "other layouts must decode as json"
+        );
+    }
+}
@@ -12,7 +12,7 @@ use anyhow::Result;
 use bincode::{config, Decode, Encode};
 use jsonrpsee::core::Serialize;
 use protocol::{
-    bitcoin::OutPoint,
+    bitcoin::{OutPoint, Script},
     constants::{ChainAnchor, ROLLOUT_BATCH_SIZE},
     hasher::{BidKey, KeyHash, OutpointKey, SpaceKey},
     prepare::DataSource,
@@ -367,6 +367,64 @@ impl LiveSnapshot {
         Ok(data)
     }
 
+    /// Finds spaces whose current output is locked to `script`, examining at most
+    /// `limit` spaces in key order starting after the `after` key.
+    ///
+    /// There is no index by script so this walks the space keys in the snapshot
+    /// including any staged changes. Returns the last key examined if there are
+    /// spaces left to resume from.
+    pub fn get_spaces_by_script(
+        &mut self,
+        script: &Script,
+        after: Option<SpaceKey>,
+        limit: usize,
+    ) -> Result<(Vec<FullSpaceOut>, Option<SpaceKey>)> {
+        let rlock = self.staged.read().expect("acquire lock");
+        let mut keys = BTreeSet::new();
+        let mut deleted = BTreeSet::new();
+        for (key, value) in rlock.memory.iter() {
+            if let Ok(space_key) = SpaceKey::from_raw(*key) {
+                if value.is_some() {
+                    keys.insert(space_key);
+                } else {
+                    deleted.insert(space_key);
+                }
+            }
+        }
+        drop(rlock);
+
+        let snapshot = self.inner()?;
+        for result in snapshot.iter() {
+            let (key, _) = result?;
+            if let Ok(space_key) = SpaceKey::from_raw(key) {
+                if !deleted.contains(&space_key) {
+                    keys.insert(space_key);
+                }
+            }
+        }
+
+        let mut remaining = match after {
+            Some(after) => keys.split_off(&after),
+            None => keys,
+        };
+        if let Some(after) = after {
+            remaining.remove(&after);
+        }
+
+        let mut spaces = Vec::new();
+        let mut last = None;
+        for key in remaining.iter().take(limit) {
+            if let Some(space) = self.get_space_info(key)? {
+                if space.spaceout.script_pubkey.as_script() == script {
+                    spaces.push(space);
+                }
+            }
+            last = Some(*key);
+        }
+        let next = if remaining.len() > limit { last } else { None };
+        Ok((spaces, next))
+    }
+
     pub fn get_rollout_entries(
         &mut self,
         limit: Option<usize>,
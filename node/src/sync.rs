@@ -1,18 +1,21 @@
 use std::{net::SocketAddr, path::PathBuf, time::Duration};
 
 use anyhow::{anyhow, Context};
-use log::info;
+use log::{info, warn};
 use protocol::{
-    bitcoin::{hashes::Hash, Block, BlockHash},
+    bitcoin::{hashes::Hash, p2p::Magic, Block, BlockHash},
     constants::ChainAnchor,
     hasher::BaseHash,
 };
 use tokio::sync::broadcast;
 
 use crate::{
-    config::ExtendedNetwork,
+    config::{ExtendedNetwork, FeeEstimation},
     node::{BlockMeta, BlockSource, Node},
-    source::{BitcoinBlockSource, BitcoinRpc, BlockEvent, BlockFetchError, BlockFetcher},
+    source::{
+        BitcoinBlockSource, BitcoinRpc, BlockEvent, BlockFetchError, BlockFetcher, ClientPool,
+        FileBlockSource,
+    },
     store::LiveStore,
 };
 
@@ -35,9 +38,18 @@ pub struct Spaced {
     pub block_index: Option<LiveStore>,
     pub block_index_full: bool,
     pub rpc: BitcoinRpc,
+    /// Connection pool settings for clients of the bitcoin node
+    pub rpc_pool: ClientPool,
     pub data_dir: PathBuf,
     pub bind: Vec<SocketAddr>,
     pub num_workers: usize,
+    pub blocks_dir: Option<PathBuf>,
+    pub fee_estimation: FeeEstimation,
+    pub wallet_catch_up_threshold: u32,
+    pub tip_refetch_depth: u32,
+    pub wallet_tip_preview_depth: u32,
+    pub dropped_tx_grace_period: Duration,
+    pub wallet_rpc_concurrency: usize,
 }
 
 impl Spaced {
@@ -142,20 +154,84 @@ impl Spaced {
         Ok(())
     }
 
+    /// Applies blocks read from bitcoind's block files up to the highest block
+    /// that is also part of the rpc node's best chain
+    pub fn file_sync(
+        &mut self,
+        node: &mut Node,
+        source: &BitcoinBlockSource,
+        blocks_dir: PathBuf,
+        shutdown: &broadcast::Sender<()>,
+    ) -> anyhow::Result<()> {
+        let magic = match self.network {
+            ExtendedNetwork::Testnet4 => Magic::from_bytes([0x1c, 0x16, 0x3f, 0x28]),
+            network => network.fallback_network().magic(),
+        };
+        info!("Indexing block files in {}", blocks_dir.display());
+        // Missing, empty or pruned block files only mean syncing over rpc instead
+        let files = match FileBlockSource::new(blocks_dir, magic) {
+            Ok(files) => files,
+            Err(e) => {
+                warn!("Could not index block files - skipping file sync: {}", e);
+                return Ok(());
+            }
+        };
+        let files_best = match files.get_best_chain() {
+            Ok(best) => best,
+            Err(e) => {
+                warn!("No usable chain in block files - skipping file sync: {}", e);
+                return Ok(());
+            }
+        };
+
+        let tip = self.chain.state.tip.read().expect("read").clone();
+        let rpc_best = source.get_best_chain()?;
+        let target = std::cmp::min(files_best.height, rpc_best.height);
+        if target <= tip.height {
+            return Ok(());
+        }
+
+        if files.get_block_hash(target)? != source.get_block_hash(target)? {
+            warn!("Block files do not match the bitcoin rpc best chain - skipping file sync");
+            return Ok(());
+        }
+        if files.get_block_hash(tip.height).ok() != Some(tip.hash) {
+            warn!("Block files do not contain the current tip - skipping file sync");
+            return Ok(());
+        }
+
+        let mut shutdown_signal = shutdown.subscribe();
+        for height in tip.height + 1..=target {
+            if shutdown_signal.try_recv().is_ok() {
+                break;
+            }
+            let hash = files.get_block_hash(height)?;
+            let block = files.get_block(&hash)?;
+            self.handle_block(node, ChainAnchor { height, hash }, block)?;
+            info!("block={} height={} (from file)", hash, height);
+        }
+        Ok(())
+    }
+
     pub fn protocol_sync(
         &mut self,
         source: BitcoinBlockSource,
         shutdown: broadcast::Sender<()>,
     ) -> anyhow::Result<()> {
-        let start_block: ChainAnchor = { self.chain.state.tip.read().expect("read").clone() };
         let mut node = Node::new(self.block_index_full);
+        if let Some(blocks_dir) = self.blocks_dir.clone() {
+            self.file_sync(&mut node, &source, blocks_dir, &shutdown)?;
+        }
+
+        let start_block: ChainAnchor = { self.chain.state.tip.read().expect("read").clone() };
 
         info!(
             "Start block={} height={}",
             start_block.hash, start_block.height
         );
 
-        let (fetcher, receiver) = BlockFetcher::new(source.clone(), self.num_workers);
+        let (mut fetcher, receiver) = BlockFetcher::new(source.clone(), self.num_workers);
+        fetcher.set_tip_refetch_depth(self.tip_refetch_depth);
         fetcher.start(start_block);
 
         let mut shutdown_signal = shutdown.subscribe();
@@ -169,6 +245,7 @@ impl Spaced {
                         self.handle_block(&mut node, id, block)?;
                         info!("block={} height={}", id.hash, id.height);
                     }
+                    BlockEvent::TipPreview(..) | BlockEvent::Progress { .. } => {}
                     BlockEvent::Error(e) if matches!(e, BlockFetchError::BlockMismatch) => {
                         self.restore(&source)?;
                         let new_tip = self.chain.state.tip.read().expect("read").clone();
@@ -191,18 +268,24 @@ impl Spaced {
         Ok(())
     }
 
-    pub async fn genesis(
-        rpc: &BitcoinRpc,
-        network: ExtendedNetwork,
-    ) -> anyhow::Result<ChainAnchor> {
-        let mut anchor = match network {
+    /// The protocol's activation block for the network, the hash may be unset
+    /// if the block wasn't mined when the height was chosen
+    pub fn activation(network: ExtendedNetwork) -> ChainAnchor {
+        match network {
             ExtendedNetwork::Testnet => ChainAnchor::TESTNET(),
             ExtendedNetwork::Testnet4 => ChainAnchor::TESTNET4(),
             ExtendedNetwork::Regtest => ChainAnchor::REGTEST(),
             ExtendedNetwork::Mainnet => ChainAnchor::MAINNET(),
             ExtendedNetwork::MainnetAlpha => ChainAnchor::MAINNET_ALPHA(),
             _ => panic!("unsupported network"),
-        };
+        }
+    }
+
+    pub async fn genesis(
+        rpc: &BitcoinRpc,
+        network: ExtendedNetwork,
+    ) -> anyhow::Result<ChainAnchor> {
+        let mut anchor = Self::activation(network);
 
         if anchor.hash == BlockHash::all_zeros() {
             let client = reqwest::Client::new();
@@ -1,17 +1,24 @@
-use std::{collections::BTreeMap, str::FromStr, time::Duration};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fmt::Write,
+    mem,
+    str::FromStr,
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
 
 use anyhow::anyhow;
 use clap::ValueEnum;
 use futures::{stream::FuturesUnordered, StreamExt};
 use log::{info, warn};
 use protocol::{
-    bitcoin::Txid,
+    bitcoin::{BlockHash, Txid},
     constants::ChainAnchor,
     hasher::{KeyHasher, SpaceKey},
-    prepare::DataSource,
-    script::SpaceScript,
+    prepare::{is_magic_amount, DataSource, TxContext},
+    script::{OpenHistory, SpaceScript},
     slabel::SLabel,
-    FullSpaceOut, Space,
+    Covenant, FullSpaceOut, Space, SpaceOut,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::json;
@@ -28,20 +35,27 @@ use wallet::{
         KeychainKind, LocalOutput,
     },
     bitcoin,
-    bitcoin::{Address, Amount, FeeRate, OutPoint},
+    bitcoin::{
+        hashes::{sha256, Hash},
+        psbt, Address, Amount, FeeRate, OutPoint, ScriptBuf, SignedAmount, Transaction,
+    },
     builder::{
         CoinTransfer, SelectionOutput, SpaceTransfer, SpacesAwareCoinSelection, TransactionTag,
         TransferRequest,
     },
-    DoubleUtxo, SpacesWallet, WalletInfo,
+    encryption::WalletCipher,
+    DoubleUtxo, SpacesWallet, WalletInfo, WalletMetadata,
 };
 use wallet::bdk_wallet::chain::ConfirmationTime;
 use crate::{
-    config::ExtendedNetwork,
+    config::{EstimateMode, ExtendedNetwork, FeeEstimation},
     node::BlockSource,
-    rpc::{LoadedWallet, RpcWalletRequest, RpcWalletTxBuilder},
+    rpc::{
+        LoadedWallet, RpcWalletRequest, RpcWalletTxBuilder, SendCoinsParams, TransferSpacesParams,
+    },
     source::{
         BitcoinBlockSource, BitcoinRpc, BitcoinRpcError, BlockEvent, BlockFetchError, BlockFetcher,
+        ClientPool,
     },
     store::{ChainState, LiveSnapshot, Sha256},
 };
@@ -55,20 +69,620 @@ pub struct TxResponse {
     pub tags: Vec<TransactionTag>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub raw: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// Outputs paying back to this wallet, only reported by previews
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub change: Vec<ChangeOutput>,
+    /// Change below the dust limit that was added to the fee instead of creating an output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dust_change: Option<Amount>,
+    /// Fee paid, unknown if the value of an input isn't known to the wallet
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fee: Option<Amount>,
+    /// Virtual size in vbytes, unknown until the transaction is fully signed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vsize: Option<u64>,
+    /// Cost of a fee bump, only reported by dry runs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fee_bump: Option<FeeBumpCost>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeeBumpCost {
+    pub fee: Amount,
+    pub fee_rate: FeeRate,
+    pub original_fee: Amount,
+    /// Fee paid on top of the original transaction's fee
+    pub additional_fee: Amount,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangeOutput {
+    pub vout: u32,
+    pub address: String,
+    pub value: Amount,
+    /// Whether the address is from the internal (change) keychain
+    pub internal: bool,
+    pub index: u32,
+}
+
+/// Caps on how far a bid may be escalated to outbid a competing bid in the mempool
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OutbidLimits {
+    /// Maximum factor over the intended fee rate that outbidding may require
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_outbid_multiplier: Option<f64>,
+    /// Absolute fee rate ceiling for outbidding
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_fee_rate: Option<FeeRate>,
+}
+
+impl OutbidLimits {
+    /// Checks whether replacing a competing bid at `required` fee rate stays within limits
+    pub fn check(&self, intended: FeeRate, required: FeeRate) -> anyhow::Result<()> {
+        if let Some(multiplier) = self.max_outbid_multiplier {
+            let cap = intended.to_sat_per_kwu() as f64 * multiplier;
+            if required.to_sat_per_kwu() as f64 > cap {
+                return Err(anyhow!(
+                    "the competing bid is too high for the configured limits: outbidding requires \
+                    a feerate > {} sat/vB which is more than {}x the intended {} sat/vB",
+                    required.to_sat_per_vb_ceil(),
+                    multiplier,
+                    intended.to_sat_per_vb_ceil()
+                ));
+            }
+        }
+        if let Some(max_fee_rate) = self.max_fee_rate {
+            if required > max_fee_rate {
+                return Err(anyhow!(
+                    "the competing bid is too high for the configured limits: outbidding requires \
+                    a feerate > {} sat/vB which is above the ceiling of {} sat/vB",
+                    required.to_sat_per_vb_ceil(),
+                    max_fee_rate.to_sat_per_vb_ceil()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks whether paying `required` in absolute fees to replace a competing bid
+    /// stays within the multiplier of the `intended` fee
+    pub fn check_fee(&self, intended: Amount, required: Amount) -> anyhow::Result<()> {
+        if let Some(multiplier) = self.max_outbid_multiplier {
+            if required.to_sat() as f64 > intended.to_sat() as f64 * multiplier {
+                return Err(anyhow!(
+                    "the competing bid is too high for the configured limits: outbidding requires \
+                    a fee > {} sats which is more than {}x the intended {} sats",
+                    required.to_sat(),
+                    multiplier,
+                    intended.to_sat()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn is_unlimited(&self) -> bool {
+        self.max_outbid_multiplier.is_none() && self.max_fee_rate.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StandardnessViolation {
+    /// Policy rule being violated, named after bitcoind's reject reason
+    pub rule: String,
+    /// The offending output, if the violation is specific to one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<usize>,
+    pub message: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TxInfo {
     pub txid: Txid,
     pub confirmed: bool,
+    /// Number of confirmations (tip height - confirmation height + 1), 0 if unconfirmed
+    pub depth: u32,
     pub sent: Amount,
     pub received: Amount,
     pub fee: Option<Amount>,
+    /// Height of the block the transaction confirmed in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_height: Option<u32>,
+    /// Hash of the block the transaction confirmed in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_hash: Option<BlockHash>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TxFeeRate {
+    pub txid: Txid,
+    /// Number of confirmations (tip height - confirmation height + 1)
+    pub depth: u32,
+    pub fee: Amount,
+    pub vsize: u64,
+    /// Effective fee rate in sat/vB
+    pub fee_rate: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReconcileReport {
+    /// Number of unconfirmed wallet transactions checked against the mempool
+    pub checked: usize,
+    /// Transactions removed from the wallet, children before parents
+    pub removed: Vec<Txid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AbandonReport {
+    /// Transactions removed from the wallet, children before parents
+    pub abandoned: Vec<Txid>,
+    /// Transactions kept because the node already has them in a block
+    pub confirmed: Vec<Txid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TargetFeeBump {
+    /// Fee rate the replacement was built with
+    pub fee_rate: FeeRate,
+    pub result: Vec<TxResponse>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AbandonOpenReport {
+    /// The open transaction that was abandoned
+    pub txid: Txid,
+    /// The commitment output the open was spending
+    pub commitment: OutPoint,
+    /// Transactions removed from the wallet, children before parents
+    pub abandoned: Vec<Txid>,
+    /// Transaction returning the committed funds to the wallet
+    pub reclaim: Txid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StuckTxReport {
+    pub txid: Txid,
+    /// Blocks since the transaction entered the node's mempool
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub age: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fee_rate: Option<FeeRate>,
+    #[serde(flatten)]
+    pub outcome: StuckTxOutcome,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum StuckTxOutcome {
+    /// Replaced with a higher fee version (RBF)
+    Replaced { replacement: Txid },
+    /// A child spending one of its outputs pays for the package (CPFP)
+    ChildPaysForParent { child: Txid },
+    Skipped { reason: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BumpCandidate {
+    pub txid: Txid,
+    /// Blocks since the transaction entered the node's mempool
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub age: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fee_rate: Option<FeeRate>,
+    /// Fee rate to bump to for confirmation within the target
+    pub suggested_fee_rate: FeeRate,
+    pub method: BumpMethod,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BumpMethod {
+    /// Replace the transaction with a higher fee version
+    Rbf,
+    /// Spend one of its wallet outputs with a higher fee child
+    Cpfp,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DustOutput {
+    pub outpoint: OutPoint,
+    pub value: Amount,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DustReport {
+    pub fee_rate: FeeRate,
+    /// Fee needed to spend a single wallet input at `fee_rate`
+    pub input_cost: Amount,
+    pub outputs: Vec<DustOutput>,
+    /// Total value of outputs that cost more to spend than they're worth
+    pub total: Amount,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelectionEstimate {
+    pub inputs: Vec<OutPoint>,
+    pub input_total: Amount,
+    pub fee: Amount,
+    /// Value of the change output, `None` if the selection doesn't create change
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub change: Option<Amount>,
+}
+
+/// Whether a request would currently be accepted, without building it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperationCheck {
+    pub allowed: bool,
+    /// Why the request would be rejected, empty when allowed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reasons: Vec<String>,
+}
+
+/// A space as known to the node along with whether this wallet owns it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalletSpaceInfo {
+    /// Not set if the space doesn't exist
+    pub space: Option<FullSpaceOut>,
+    pub is_mine: bool,
+}
+
+/// BIP-322 signature over a message by the key controlling a space
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedMessage {
+    pub space: String,
+    pub message: String,
+    /// Script the space is currently held by and the signature is bound to
+    pub script_pubkey: ScriptBuf,
+    /// Base64 encoded simple signature
+    pub signature: String,
+}
+
+/// Approximate size of the transactions a request needs before building them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VsizeEstimate {
+    /// Virtual size of each transaction in the order they're built
+    pub vsizes: Vec<u64>,
+    /// Total virtual size across all transactions
+    pub vsize: u64,
+    /// Number of wallet coins expected to fund the request
+    pub funding_inputs: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaxSendable {
+    pub fee_rate: FeeRate,
+    /// Amount the recipient receives when sweeping all spendable coins
+    pub amount: Amount,
+    pub fee: Amount,
+    pub inputs: Vec<OutPoint>,
+    /// Value of coins left out because they cost more to spend than they're worth
+    pub uneconomical: Amount,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpacesValueLocked {
+    /// Total coin value held in space outputs
+    pub total: Amount,
+    pub spaces: Vec<SpaceValueLocked>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpaceValueLocked {
+    pub space: SLabel,
+    pub outpoint: OutPoint,
+    pub value: Amount,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClaimStatus {
+    pub space: SLabel,
+    pub outpoint: OutPoint,
+    /// Whether this wallet holds the current highest bid
+    pub winning: bool,
+    pub claim_height: u32,
+    pub tip: u32,
+    /// Blocks until the space can be registered, zero once it's claimable
+    pub blocks_remaining: u32,
+}
+
+/// Stage of an owned space in its auction lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum SpaceStateFilter {
+    /// Opened but not yet rolled out into an auction
+    PreAuction,
+    /// Winning bid in an auction that hasn't reached its claim height
+    InAuction,
+    /// Won and ready to register
+    Claimable,
+    Registered,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SpacesByState {
+    pub pre_auction: Vec<WalletOutput>,
+    pub in_auction: Vec<WalletOutput>,
+    pub claimable: Vec<WalletOutput>,
+    pub registered: Vec<WalletOutput>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpaceScriptInfo {
+    pub space: SLabel,
+    pub outpoint: OutPoint,
+    pub value: Amount,
+    /// Hex encoded script pubkey of the output holding the space
+    pub script_pubkey: String,
+    pub asm: String,
+    pub script_type: String,
+    pub address: String,
+    /// The script encoded as a space address, only taproot scripts have one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub space_address: Option<String>,
+    /// Whether the output value marks it as trackable by the protocol,
+    /// keeping the space bound to it when spent alongside other outputs
+    pub trackable: bool,
+    pub keychain: KeychainKind,
+    pub derivation_index: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplaceableInfo {
+    pub replaceable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalletSyncProgress {
+    pub wallet_tip: u32,
+    pub node_tip: u32,
+    pub blocks_remaining: u32,
+    /// Recent block processing rate in blocks per second
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocks_per_second: Option<f64>,
+    /// Estimated number of seconds until the wallet is synced
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eta_seconds: Option<u64>,
+    /// Wallet transactions, or ones paying to the wallet, already confirmed in the
+    /// node's tip block while the wallet is still syncing towards it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tip_confirmed: Vec<Txid>,
+}
+
+/// Published to subscribers every few blocks while a wallet syncs
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SyncProgress {
+    /// Height the wallet has synced to
+    pub height: u32,
+    /// Height of the node's tip the wallet is syncing towards
+    pub tip: u32,
+}
+
+/// Wallet relevant transactions in a tip block fetched ahead of the in-order sync
+#[derive(Default)]
+struct TipPreview {
+    tip: Option<ChainAnchor>,
+    confirmed: Vec<Txid>,
+}
+
+impl TipPreview {
+    fn inspect(&mut self, wallet: &SpacesWallet, tip: ChainAnchor, block: &bitcoin::Block) {
+        self.tip = Some(tip);
+        self.confirmed = block
+            .txdata
+            .iter()
+            .filter(|tx| {
+                wallet.spaces.get_tx(tx.compute_txid()).is_some()
+                    || tx
+                        .output
+                        .iter()
+                        .any(|out| wallet.spaces.is_mine(out.script_pubkey.as_script()))
+            })
+            .map(|tx| tx.compute_txid())
+            .collect();
+        for txid in self.confirmed.iter() {
+            info!(
+                "Wallet `{}`: tx {} confirmed in tip block {} at height {}",
+                wallet.name(),
+                txid,
+                tip.hash,
+                tip.height
+            );
+        }
+    }
+
+    /// Drops the preview once the wallet synced up to it
+    fn synced_to(&mut self, height: u32) {
+        if self.tip.is_some_and(|tip| tip.height <= height) {
+            *self = Self::default();
+        }
+    }
+}
+
+/// Tracks how long unconfirmed wallet transactions have been missing from the
+/// node's mempool so briefly absent ones aren't treated as dropped
+struct DroppedTxTracker {
+    grace_period: Duration,
+    missing_since: BTreeMap<Txid, Instant>,
+}
+
+impl DroppedTxTracker {
+    fn new(grace_period: Duration) -> Self {
+        Self {
+            grace_period,
+            missing_since: BTreeMap::new(),
+        }
+    }
+}
+
+/// Hashes of blocks wallet transactions confirmed in. Entries above a reorg's
+/// restore point are dropped so they're looked up again once the new chain syncs.
+struct BlockHashCache {
+    hashes: BTreeMap<u32, BlockHash>,
+}
+
+impl BlockHashCache {
+    fn new() -> Self {
+        Self {
+            hashes: BTreeMap::new(),
+        }
+    }
+
+    fn get(
+        &mut self,
+        source: &BitcoinBlockSource,
+        wallet: &SpacesWallet,
+        height: u32,
+    ) -> anyhow::Result<BlockHash> {
+        if let Some(hash) = self.hashes.get(&height) {
+            return Ok(*hash);
+        }
+        let hash = match wallet.spaces.local_chain().get(height) {
+            Some(cp) => cp.hash(),
+            None => source.get_block_hash(height)?,
+        };
+        self.hashes.insert(height, hash);
+        Ok(hash)
+    }
+
+    fn connect(&mut self, id: &ChainAnchor) {
+        self.hashes.insert(id.height, id.hash);
+    }
+
+    fn disconnect_above(&mut self, height: u32) {
+        self.hashes.split_off(&(height + 1));
+    }
+}
+
+/// Moving average of the time between blocks applied by a wallet
+#[derive(Default)]
+struct SyncRate {
+    intervals: std::collections::VecDeque<Duration>,
+    last_block: Option<Instant>,
+}
+
+impl SyncRate {
+    const WINDOW: usize = 100;
+    /// Gaps longer than this mean the wallet was idle at the tip
+    const IDLE: Duration = Duration::from_secs(60);
+
+    fn record_block(&mut self) {
+        let now = Instant::now();
+        if let Some(last) = self.last_block {
+            let interval = now - last;
+            if interval < Self::IDLE {
+                if self.intervals.len() == Self::WINDOW {
+                    self.intervals.pop_front();
+                }
+                self.intervals.push_back(interval);
+            }
+        }
+        self.last_block = Some(now);
+    }
+
+    fn blocks_per_second(&self) -> Option<f64> {
+        if self.intervals.is_empty() {
+            return None;
+        }
+        let total: Duration = self.intervals.iter().sum();
+        if total.is_zero() {
+            return None;
+        }
+        Some(self.intervals.len() as f64 / total.as_secs_f64())
+    }
+}
+
+/// Default fee rate used by a wallet when a request doesn't specify one
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeePolicy {
+    Fixed(FeeRate),
+    /// Estimate a fee rate for confirmation within the given number of blocks
+    Target(u16),
+    #[default]
+    NodeEstimate,
+}
+
+/// Fee rate given in either sat/vB or bitcoind's BTC/kvB
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeSpec {
+    SatPerVb(u64),
+    BtcPerKvb(f64),
+}
+
+impl FeeSpec {
+    pub fn to_fee_rate(self) -> anyhow::Result<FeeRate> {
+        match self {
+            FeeSpec::SatPerVb(rate) => FeeRate::from_sat_per_vb(rate),
+            FeeSpec::BtcPerKvb(rate) => fee_rate_from_btc_per_kvb(rate),
+        }
+        .ok_or_else(|| anyhow!("invalid fee rate {:?}", self))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// One JSON object per line
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryEntryKind {
+    Send,
+    Receive,
+    Open,
+    Bid,
+    Register,
+    Transfer,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Block time if confirmed, otherwise the time the wallet last saw the transaction
+    pub timestamp: u64,
+    pub txid: Txid,
+    #[serde(rename = "type")]
+    pub kind: HistoryEntryKind,
+    pub space: Option<String>,
+    /// Net change in the wallet's balance in satoshi
+    #[serde(with = "bitcoin::amount::serde::as_sat")]
+    pub amount: SignedAmount,
+    pub fee: Option<Amount>,
+    pub confirmation_height: Option<u32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WalletResponse {
     pub result: Vec<TxResponse>,
+    /// Set instead of a result when the request must be resubmitted with a confirmation token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confirmation: Option<ConfirmationRequired>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfirmationRequired {
+    /// Token to pass as `confirm_token` when resubmitting the same request
+    pub token: String,
+    /// Largest amount committed by a single operation in the request
+    pub amount: Amount,
+    pub threshold: Amount,
+}
+
+/// Guards against high value operations being executed without an explicit confirmation
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct ConfirmPolicy {
+    /// Operations committing more than this amount require a confirmation token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confirm_high_value: Option<Amount>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -79,46 +693,258 @@ pub struct WalletOutput {
     pub is_spaceout: bool,
 }
 
+/// Balance, spaces and unspent outputs read at a single wallet tip
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalletSnapshot {
+    pub tip: ChainAnchor,
+    pub balance: Balance,
+    pub spaces: Vec<WalletOutput>,
+    pub unspent: Vec<WalletOutput>,
+}
+
+/// The wallet's unspent outputs split by what their value can be used for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputGroups {
+    /// Outputs carrying a space
+    pub spaces: OutputGroup,
+    /// Coins available to fund transactions
+    pub coins: OutputGroup,
+    /// Bidouts and other dust sized outputs held back from coin selection
+    pub reserved: OutputGroup,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OutputGroup {
+    pub total: Amount,
+    pub outputs: Vec<WalletOutput>,
+}
+
+impl OutputGroup {
+    fn push(&mut self, output: WalletOutput) {
+        self.total += output.output.txout.value;
+        self.outputs.push(output);
+    }
+}
+
 pub enum WalletCommand {
     GetInfo {
         resp: crate::rpc::Responder<anyhow::Result<WalletInfo>>,
     },
+    GetWalletMetadata {
+        resp: crate::rpc::Responder<anyhow::Result<WalletMetadata>>,
+    },
     BatchTx {
         request: RpcWalletTxBuilder,
         resp: crate::rpc::Responder<anyhow::Result<WalletResponse>>,
     },
+    BatchTxStream {
+        request: RpcWalletTxBuilder,
+        stream: mpsc::Sender<TxResponse>,
+        resp: crate::rpc::Responder<anyhow::Result<WalletResponse>>,
+    },
+    PreviewBatch {
+        request: RpcWalletTxBuilder,
+        resp: crate::rpc::Responder<anyhow::Result<WalletResponse>>,
+    },
+    BuildPsbt {
+        request: RpcWalletRequest,
+        inputs: Vec<OutPoint>,
+        fee_rate: Option<FeeRate>,
+        resp: crate::rpc::Responder<anyhow::Result<String>>,
+    },
+    BroadcastBatch {
+        raw_txs: Vec<String>,
+        resp: crate::rpc::Responder<anyhow::Result<WalletResponse>>,
+    },
     GetNewAddress {
         kind: AddressKind,
-        resp: crate::rpc::Responder<anyhow::Result<String>>,
+        resp: crate::rpc::Responder<anyhow::Result<NewAddress>>,
+    },
+    DeriveAddress {
+        internal: bool,
+        index: u32,
+        resp: crate::rpc::Responder<anyhow::Result<DerivedAddress>>,
     },
     BumpFee {
         txid: Txid,
         fee_rate: FeeRate,
         skip_tx_check: bool,
+        dry_run: bool,
         resp: crate::rpc::Responder<anyhow::Result<Vec<TxResponse>>>,
     },
+    BumpFeeToTarget {
+        txid: Txid,
+        conf_target: u16,
+        dry_run: bool,
+        resp: crate::rpc::Responder<anyhow::Result<TargetFeeBump>>,
+    },
     ListTransactions {
         count: usize,
         skip: usize,
         resp: crate::rpc::Responder<anyhow::Result<Vec<TxInfo>>>,
     },
+    ListFeeRates {
+        count: usize,
+        resp: crate::rpc::Responder<anyhow::Result<Vec<TxFeeRate>>>,
+    },
+    Reconcile {
+        resp: crate::rpc::Responder<anyhow::Result<ReconcileReport>>,
+    },
+    AbandonAllPending {
+        resp: crate::rpc::Responder<anyhow::Result<AbandonReport>>,
+    },
+    AbandonOpen {
+        name: String,
+        reclaim_fee_rate: Option<FeeRate>,
+        resp: crate::rpc::Responder<anyhow::Result<AbandonOpenReport>>,
+    },
+    BumpAllStuck {
+        min_age_blocks: u32,
+        target_fee_rate: FeeRate,
+        resp: crate::rpc::Responder<anyhow::Result<Vec<StuckTxReport>>>,
+    },
+    ListBumpCandidates {
+        min_age_blocks: u32,
+        conf_target: Option<u16>,
+        resp: crate::rpc::Responder<anyhow::Result<Vec<BumpCandidate>>>,
+    },
+    ListCheckpoints {
+        count: usize,
+        resp: crate::rpc::Responder<anyhow::Result<Vec<ChainAnchor>>>,
+    },
+    ListDust {
+        fee_rate: FeeRate,
+        resp: crate::rpc::Responder<anyhow::Result<DustReport>>,
+    },
+    EstimateSelection {
+        amount: Amount,
+        fee_rate: FeeRate,
+        resp: crate::rpc::Responder<anyhow::Result<SelectionEstimate>>,
+    },
+    EstimateVsize {
+        request: RpcWalletRequest,
+        resp: crate::rpc::Responder<anyhow::Result<VsizeEstimate>>,
+    },
+    CheckOperation {
+        request: RpcWalletRequest,
+        resp: crate::rpc::Responder<anyhow::Result<OperationCheck>>,
+    },
+    SignMessage {
+        space: String,
+        message: String,
+        resp: crate::rpc::Responder<anyhow::Result<SignedMessage>>,
+    },
+    GetSpaceInfo {
+        space: String,
+        resp: crate::rpc::Responder<anyhow::Result<WalletSpaceInfo>>,
+    },
+    GetMaxSendable {
+        fee_rate: FeeRate,
+        to: String,
+        resp: crate::rpc::Responder<anyhow::Result<MaxSendable>>,
+    },
+    ExportHistory {
+        format: ExportFormat,
+        as_of: Option<u32>,
+        skip: usize,
+        count: usize,
+        resp: crate::rpc::Responder<anyhow::Result<String>>,
+    },
     ListSpaces {
         resp: crate::rpc::Responder<anyhow::Result<Vec<WalletOutput>>>,
     },
+    ListSpacesByState {
+        state: Option<SpaceStateFilter>,
+        resp: crate::rpc::Responder<anyhow::Result<SpacesByState>>,
+    },
     ListBidouts {
         resp: crate::rpc::Responder<anyhow::Result<Vec<DoubleUtxo>>>,
     },
+    GetSpacesValueLocked {
+        resp: crate::rpc::Responder<anyhow::Result<SpacesValueLocked>>,
+    },
     ListUnspent {
         resp: crate::rpc::Responder<anyhow::Result<Vec<WalletOutput>>>,
     },
-    ForceSpendOutput {
-        outpoint: OutPoint,
-        fee_rate: FeeRate,
-        resp: crate::rpc::Responder<anyhow::Result<TxResponse>>,
+    ListOutputGroups {
+        resp: crate::rpc::Responder<anyhow::Result<OutputGroups>>,
+    },
+    GetSnapshot {
+        resp: crate::rpc::Responder<anyhow::Result<WalletSnapshot>>,
+    },
+    CancelBid {
+        txid: Txid,
+        fee_rate: FeeRate,
+        resp: crate::rpc::Responder<anyhow::Result<TxResponse>>,
+    },
+    IsReplaceable {
+        txid: Txid,
+        resp: crate::rpc::Responder<anyhow::Result<ReplaceableInfo>>,
+    },
+    GetClaimStatus {
+        space: String,
+        resp: crate::rpc::Responder<anyhow::Result<ClaimStatus>>,
+    },
+    GetSpaceScript {
+        space: String,
+        resp: crate::rpc::Responder<anyhow::Result<SpaceScriptInfo>>,
+    },
+    Encrypt {
+        cipher: WalletCipher,
+        resp: crate::rpc::Responder<anyhow::Result<()>>,
+    },
+    /// Applies a constructed block on top of the wallet's tip as if it was synced
+    /// from the node, so wallet logic can be tested without one
+    #[cfg(feature = "testing")]
+    ApplyTestBlock {
+        block_hex: String,
+        height: u32,
+        resp: crate::rpc::Responder<anyhow::Result<()>>,
+    },
+    ForceSpendOutput {
+        outpoint: OutPoint,
+        fee_rate: FeeRate,
+        resp: crate::rpc::Responder<anyhow::Result<TxResponse>>,
     },
     GetBalance {
         resp: crate::rpc::Responder<anyhow::Result<Balance>>,
     },
+    SubscribeBalance {
+        resp: crate::rpc::Responder<anyhow::Result<broadcast::Receiver<Balance>>>,
+    },
+    SubscribeSyncProgress {
+        resp: crate::rpc::Responder<anyhow::Result<broadcast::Receiver<SyncProgress>>>,
+    },
+    GetSyncProgress {
+        resp: crate::rpc::Responder<anyhow::Result<WalletSyncProgress>>,
+    },
+    SetFeePolicy {
+        policy: FeePolicy,
+        resp: crate::rpc::Responder<anyhow::Result<()>>,
+    },
+    GetFeePolicy {
+        resp: crate::rpc::Responder<anyhow::Result<FeePolicy>>,
+    },
+    SetConfirmPolicy {
+        policy: ConfirmPolicy,
+        resp: crate::rpc::Responder<anyhow::Result<()>>,
+    },
+    GetConfirmPolicy {
+        resp: crate::rpc::Responder<anyhow::Result<ConfirmPolicy>>,
+    },
+    SetTxNote {
+        txid: Txid,
+        note: String,
+        resp: crate::rpc::Responder<anyhow::Result<()>>,
+    },
+    GetTxNote {
+        txid: Txid,
+        resp: crate::rpc::Responder<anyhow::Result<Option<String>>>,
+    },
+    HasTx {
+        txid: Txid,
+        resp: crate::rpc::Responder<anyhow::Result<bool>>,
+    },
     UnloadWallet,
 }
 
@@ -129,12 +955,159 @@ pub enum AddressKind {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DerivedAddress {
+    pub internal: bool,
+    pub index: u32,
+    pub script_pubkey: bitcoin::ScriptBuf,
+    pub address: String,
+    pub space_address: String,
+    /// Whether the wallet has revealed addresses up to this index
+    pub revealed: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewAddress {
+    pub address: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddressUri {
+    pub address: String,
+    /// BIP-21 style payment URI suitable for rendering as a QR code
+    pub uri: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// Builds a BIP-21 style URI for the given address. Space addresses use the
+/// `spaces:` scheme since regular bitcoin wallets can't pay to them.
+pub fn payment_uri(
+    kind: AddressKind,
+    address: &str,
+    amount: Option<Amount>,
+    label: Option<&str>,
+) -> String {
+    let scheme = match kind {
+        AddressKind::Coin => "bitcoin",
+        AddressKind::Space => "spaces",
+    };
+    let mut params = Vec::new();
+    if let Some(amount) = amount {
+        params.push(format!(
+            "amount={}",
+            amount.to_string_in(bitcoin::Denomination::Bitcoin)
+        ));
+    }
+    if let Some(label) = label {
+        params.push(format!("label={}", uri_encode(label)));
+    }
+    if params.is_empty() {
+        return format!("{}:{}", scheme, address);
+    }
+    format!("{}:{}?{}", scheme, address, params.join("&"))
+}
+
+/// A parsed BIP-21 `bitcoin:` URI
+#[derive(Debug, Clone, PartialEq)]
+pub struct BitcoinUri {
+    pub address: String,
+    pub amount: Option<Amount>,
+    pub label: Option<String>,
+}
+
+/// Parses a BIP-21 URI, returns `None` if `uri` doesn't use the `bitcoin:` scheme
+pub fn parse_bitcoin_uri(uri: &str) -> anyhow::Result<Option<BitcoinUri>> {
+    let rest = match uri.split_once(':') {
+        Some((scheme, rest)) if scheme.eq_ignore_ascii_case("bitcoin") => rest,
+        _ => return Ok(None),
+    };
+    let (address, query) = match rest.split_once('?') {
+        Some((address, query)) => (address, Some(query)),
+        None => (rest, None),
+    };
+    if address.is_empty() {
+        return Err(anyhow!("bitcoin uri is missing an address"));
+    }
+
+    let mut parsed = BitcoinUri {
+        address: address.to_string(),
+        amount: None,
+        label: None,
+    };
+    for param in query.into_iter().flat_map(|q| q.split('&')) {
+        if param.is_empty() {
+            continue;
+        }
+        let (key, value) = param.split_once('=').unwrap_or((param, ""));
+        match key {
+            "amount" => {
+                let amount = Amount::from_str_in(value, bitcoin::Denomination::Bitcoin)
+                    .map_err(|e| anyhow!("bitcoin uri has an invalid amount '{}': {}", value, e))?;
+                parsed.amount = Some(amount);
+            }
+            "label" => parsed.label = Some(uri_decode(value)?),
+            key if key.starts_with("req-") => {
+                return Err(anyhow!(
+                    "bitcoin uri requires unsupported parameter '{}'",
+                    key
+                ));
+            }
+            _ => {}
+        }
+    }
+    Ok(Some(parsed))
+}
+
+fn uri_decode(value: &str) -> anyhow::Result<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = value
+                    .get(i + 1..i + 3)
+                    .ok_or_else(|| anyhow!("bad percent encoding in '{}'", value))?;
+                let byte = u8::from_str_radix(hex, 16)
+                    .map_err(|_| anyhow!("bad percent encoding in '{}'", value))?;
+                decoded.push(byte);
+                i += 3;
+            }
+            b'+' => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            byte => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    Ok(String::from_utf8(decoded)?)
+}
+
+fn uri_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => write!(encoded, "%{:02X}", byte).expect("write to string"),
+        }
+    }
+    encoded
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Balance {
     pub balance: Amount,
     pub details: BalanceDetails,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BalanceDetails {
     #[serde(flatten)]
     pub balance: bdk_wallet::wallet::Balance,
@@ -152,22 +1125,162 @@ impl RpcWallet {
         (Self { sender }, receiver)
     }
 
-    fn estimate_fee_rate(source: &BitcoinBlockSource) -> Option<FeeRate> {
-        let params = json!([/* conf_target= */ 2, "unset"]);
+    fn estimate_fee_rate(
+        source: &BitcoinBlockSource,
+        fee_estimation: &FeeEstimation,
+        conf_target: Option<u16>,
+        mode: Option<EstimateMode>,
+    ) -> anyhow::Result<FeeRate> {
+        let estimate = if fee_estimation.use_estimator {
+            Self::estimate_smart_fee(
+                source,
+                conf_target.unwrap_or(fee_estimation.conf_target),
+                mode.unwrap_or(fee_estimation.mode).as_str(),
+            )
+        } else {
+            Err("fee estimation is disabled for this network".to_string())
+        };
+        match (estimate, fee_estimation.fallback) {
+            (Ok(fee_rate), _) => Ok(fee_rate),
+            (Err(_), Some(fallback)) => Ok(fallback),
+            (Err(reason), None) => Err(anyhow!("could not estimate fee rate: {}", reason)),
+        }
+    }
+
+    /// Queries `estimatesmartfee`, returning the reason reported by the node
+    /// when no estimate is available
+    fn estimate_smart_fee(
+        source: &BitcoinBlockSource,
+        conf_target: u16,
+        mode: &str,
+    ) -> Result<FeeRate, String> {
+        let params = json!([conf_target, mode]);
 
         let estimate_req = source.rpc.make_request("estimatesmartfee", params);
-        if let Ok(res) = source
+        let res = source
             .rpc
             .send_json_blocking::<serde_json::Value>(&source.client, &estimate_req)
-        {
-            if let Some(fee_rate) = res["feerate"].as_f64() {
-                // Convert BTC/kB to sat/vB
-                let fee_rate_sat_vb = (fee_rate * 100_000.0).ceil() as u64;
-                return FeeRate::from_sat_per_vb(fee_rate_sat_vb);
-            }
+            .map_err(|e| e.to_string())?;
+
+        if let Some(fee_rate) = res["feerate"].as_f64() {
+            // Convert BTC/kB to sat/vB
+            let fee_rate_sat_vb = (fee_rate * 100_000.0).ceil() as u64;
+            return FeeRate::from_sat_per_vb(fee_rate_sat_vb)
+                .ok_or_else(|| format!("estimated fee rate {} BTC/kvB is out of range", fee_rate));
+        }
+
+        let errors: Vec<&str> = res["errors"]
+            .as_array()
+            .map(|errors| errors.iter().filter_map(|e| e.as_str()).collect())
+            .unwrap_or_default();
+        if errors.is_empty() {
+            return Err("node returned no fee rate".to_string());
+        }
+        Err(errors.join("; "))
+    }
+
+    fn get_sync_progress(
+        source: &BitcoinBlockSource,
+        wallet: &SpacesWallet,
+        sync_rate: &SyncRate,
+        tip_preview: &TipPreview,
+    ) -> anyhow::Result<WalletSyncProgress> {
+        let wallet_tip = wallet.spaces.local_chain().tip().height();
+        let node_tip = source.get_best_chain()?.height;
+        let blocks_remaining = node_tip.saturating_sub(wallet_tip);
+        let blocks_per_second = sync_rate.blocks_per_second();
+        let eta_seconds = match blocks_per_second {
+            _ if blocks_remaining == 0 => Some(0),
+            Some(rate) => Some((blocks_remaining as f64 / rate).ceil() as u64),
+            None => None,
+        };
+        Ok(WalletSyncProgress {
+            wallet_tip,
+            node_tip,
+            blocks_remaining,
+            blocks_per_second,
+            eta_seconds,
+            tip_confirmed: tip_preview.confirmed.clone(),
+        })
+    }
+
+    fn load_fee_policy(wallet: &SpacesWallet) -> anyhow::Result<FeePolicy> {
+        match wallet.read_data_file(FEE_POLICY_FILE)? {
+            None => Ok(FeePolicy::default()),
+            Some(content) => Ok(serde_json::from_slice(&content)?),
+        }
+    }
+
+    fn set_fee_policy(wallet: &SpacesWallet, policy: FeePolicy) -> anyhow::Result<()> {
+        match policy {
+            FeePolicy::Fixed(fee_rate) => check_fee_rate(fee_rate)?,
+            FeePolicy::Target(0) => return Err(anyhow!("confirmation target must be at least 1")),
+            _ => {}
+        }
+        wallet.write_data_file(FEE_POLICY_FILE, &serde_json::to_vec(&policy)?)
+    }
+
+    fn load_confirm_policy(wallet: &SpacesWallet) -> anyhow::Result<ConfirmPolicy> {
+        match wallet.read_data_file(CONFIRM_POLICY_FILE)? {
+            None => Ok(ConfirmPolicy::default()),
+            Some(content) => Ok(serde_json::from_slice(&content)?),
+        }
+    }
+
+    fn set_confirm_policy(wallet: &SpacesWallet, policy: ConfirmPolicy) -> anyhow::Result<()> {
+        wallet.write_data_file(CONFIRM_POLICY_FILE, &serde_json::to_vec(&policy)?)
+    }
+
+    /// Token binding a confirmation to the exact requests and wallet tip it was
+    /// issued for, so it can't be reused for a different request
+    fn confirmation_token(
+        wallet: &SpacesWallet,
+        requests: &[RpcWalletRequest],
+    ) -> anyhow::Result<String> {
+        let mut data = serde_json::to_vec(requests)?;
+        data.extend_from_slice(&wallet.spaces.local_chain().tip().hash().to_byte_array());
+        let hash = sha256::Hash::hash(&data).to_string();
+        Ok(hash[..16].to_string())
+    }
+
+    fn get_spaces_value_locked(
+        wallet: &mut SpacesWallet,
+        state: &mut LiveSnapshot,
+    ) -> anyhow::Result<SpacesValueLocked> {
+        let spaces: Vec<_> = Self::list_unspent(wallet, state)?
+            .into_iter()
+            .filter_map(|out| {
+                let space = out.space?;
+                Some(SpaceValueLocked {
+                    space: space.name,
+                    outpoint: out.output.outpoint,
+                    value: out.output.txout.value,
+                })
+            })
+            .collect();
+        let total = spaces.iter().map(|s| s.value).sum();
+        Ok(SpacesValueLocked { total, spaces })
+    }
+
+    fn load_tx_notes(wallet: &SpacesWallet) -> anyhow::Result<BTreeMap<Txid, String>> {
+        match wallet.read_data_file(TX_NOTES_FILE)? {
+            None => Ok(BTreeMap::new()),
+            Some(content) => Ok(serde_json::from_slice(&content)?),
         }
+    }
 
-        None
+    /// Attaches a note to a transaction, an empty note removes it
+    fn set_tx_note(wallet: &SpacesWallet, txid: Txid, note: String) -> anyhow::Result<()> {
+        let mut notes = Self::load_tx_notes(wallet)?;
+        if note.is_empty() {
+            notes.remove(&txid);
+        } else {
+            if wallet.spaces.get_tx(txid).is_none() {
+                return Err(anyhow!("no wallet transaction with txid {}", txid));
+            }
+            notes.insert(txid, note);
+        }
+        wallet.write_data_file(TX_NOTES_FILE, &serde_json::to_vec(&notes)?)
     }
 
     fn get_balance(state: &mut LiveSnapshot, wallet: &mut SpacesWallet) -> anyhow::Result<Balance> {
@@ -193,6 +1306,8 @@ impl RpcWallet {
         })
     }
 
+    /// Replaces `txid` with a version paying `fee_rate`. A dry run builds and signs
+    /// the replacement in the in-memory wallet only and discards it afterward.
     fn handle_fee_bump(
         source: &BitcoinBlockSource,
         state: &mut LiveSnapshot,
@@ -200,14 +1315,37 @@ impl RpcWallet {
         txid: Txid,
         skip_tx_check: bool,
         fee_rate: FeeRate,
+        dry_run: bool,
+    ) -> anyhow::Result<Vec<TxResponse>> {
+        if !dry_run {
+            return Self::fee_bump(source, state, wallet, txid, skip_tx_check, fee_rate, false);
+        }
+        wallet.commit()?;
+        let preview = Self::fee_bump(source, state, wallet, txid, skip_tx_check, fee_rate, true);
+        wallet.discard_staged()?;
+        preview
+    }
+
+    fn fee_bump(
+        source: &BitcoinBlockSource,
+        state: &mut LiveSnapshot,
+        wallet: &mut SpacesWallet,
+        txid: Txid,
+        skip_tx_check: bool,
+        fee_rate: FeeRate,
+        dry_run: bool,
     ) -> anyhow::Result<Vec<TxResponse>> {
+        check_fee_rate(fee_rate)?;
         let coin_selection = Self::get_spaces_coin_selection(
             wallet, state,
             false, /* generally bdk won't use unconfirmed for replacements anyways */
         )?;
-        let previous_tx_lock_time = match wallet.spaces.get_tx(txid) {
+        let (previous_tx_lock_time, original_fee) = match wallet.spaces.get_tx(txid) {
             None => return Err(anyhow::anyhow!("No wallet tx {} found", txid)),
-            Some(tx) => tx.tx_node.lock_time
+            Some(tx) => (
+                tx.tx_node.lock_time,
+                wallet.spaces.calculate_fee(&tx.tx_node.tx),
+            ),
         };
 
         let mut builder = wallet
@@ -222,6 +1360,7 @@ impl RpcWallet {
             .fee_rate(fee_rate);
 
         let psbt = builder.finish()?;
+        let fee = psbt.fee()?;
         let tx = wallet.sign(psbt, None)?;
 
         if !skip_tx_check {
@@ -231,308 +1370,2547 @@ impl RpcWallet {
         }
 
         let new_txid = tx.compute_txid();
-        let confirmation = source.rpc.broadcast_tx(&source.client, &tx)?;
+        let tags = vec![TransactionTag::FeeBump];
+        if dry_run {
+            let original_fee = original_fee?;
+            let fee_bump = FeeBumpCost {
+                fee,
+                fee_rate: FeeRate::from_sat_per_kwu(fee.to_sat() * 1000 / tx.weight().to_wu()),
+                original_fee,
+                additional_fee: fee.checked_sub(original_fee).unwrap_or(Amount::ZERO),
+            };
+            return Ok(vec![TxResponse {
+                txid: new_txid,
+                tags,
+                error: None,
+                raw: Some(bitcoin::consensus::encode::serialize_hex(&tx)),
+                warnings: vec![],
+                change: vec![],
+                dust_change: None,
+                fee: Some(fee),
+                vsize: Some(tx.vsize() as u64),
+                fee_bump: Some(fee_bump),
+            }]);
+        }
+        let vsize = tx.vsize() as u64;
+        Self::log_broadcast(source, wallet, &tx, &tags)?;
+        let confirmation = Self::broadcast_logged(source, wallet, &tx)?;
         wallet.insert_tx(tx, confirmation)?;
         wallet.commit()?;
 
         Ok(vec![TxResponse {
             txid: new_txid,
-            tags: vec![TransactionTag::FeeBump],
+            tags,
             error: None,
             raw: None,
+            warnings: vec![],
+            change: vec![],
+            dust_change: None,
+            fee: Some(fee),
+            vsize: Some(vsize),
+            fee_bump: None,
         }])
     }
 
-    fn handle_force_spend_output(
+    /// Bumps a transaction to the fee rate estimated to confirm within `conf_target`
+    /// blocks, raised if needed so it still replaces the original
+    fn bump_fee_to_target(
         source: &BitcoinBlockSource,
+        fee_estimation: &FeeEstimation,
         state: &mut LiveSnapshot,
         wallet: &mut SpacesWallet,
-        output: OutPoint,
+        txid: Txid,
+        conf_target: u16,
+        dry_run: bool,
+    ) -> anyhow::Result<TargetFeeBump> {
+        let original = match wallet.spaces.get_tx(txid) {
+            None => return Err(anyhow!("No wallet tx {} found", txid)),
+            Some(tx) if tx.chain_position.is_confirmed() => {
+                return Err(anyhow!("transaction {} is already confirmed", txid))
+            }
+            Some(tx) => tx.tx_node.tx.clone(),
+        };
+        let estimated = Self::estimate_fee_rate(source, fee_estimation, Some(conf_target), None)?;
+
+        let fee_rate = match wallet.spaces.calculate_fee_rate(&original) {
+            Ok(current) => estimated.max(min_replacement_fee_rate(current)),
+            Err(_) => estimated,
+        };
+        info!(
+            "Bumping {} to {} sat/vB for confirmation within {} blocks",
+            txid,
+            fee_rate.to_sat_per_vb_ceil(),
+            conf_target
+        );
+
+        let result = Self::handle_fee_bump(source, state, wallet, txid, false, fee_rate, dry_run)?;
+        Ok(TargetFeeBump { fee_rate, result })
+    }
+
+    /// Replaces a pending bid with a transaction sending the wallet's own
+    /// inputs back to itself so the bid never confirms
+    fn cancel_bid(
+        source: &BitcoinBlockSource,
+        state: &mut LiveSnapshot,
+        wallet: &mut SpacesWallet,
+        txid: Txid,
         fee_rate: FeeRate,
     ) -> anyhow::Result<TxResponse> {
-        let coin_selection = Self::get_spaces_coin_selection(wallet, state, true)?;
-        let addre = wallet.spaces.next_unused_address(KeychainKind::External);
-        let mut builder = wallet.spaces.build_tx().coin_selection(coin_selection);
+        check_fee_rate(fee_rate)?;
+        let original = match wallet.spaces.get_tx(txid) {
+            None => return Err(anyhow!("No wallet tx {} found", txid)),
+            Some(tx) if tx.chain_position.is_confirmed() => {
+                return Err(anyhow!("bid {} is already confirmed", txid))
+            }
+            Some(tx) => tx.tx_node.tx.clone(),
+        };
+        if !original
+            .output
+            .first()
+            .is_some_and(|out| out.script_pubkey.is_op_return())
+        {
+            return Err(anyhow!("{} is not a bid transaction", txid));
+        }
+        let replaceable = Self::is_replaceable(source, wallet, txid)?;
+        if !replaceable.replaceable {
+            return Err(anyhow!(
+                "cannot cancel bid {}: {}",
+                txid,
+                replaceable.reason.unwrap_or_default()
+            ));
+        }
 
-        builder.ordering(TxOrdering::Untouched);
-        builder.fee_rate(fee_rate);
-        builder.enable_rbf();
-        builder.add_utxo(output)?;
-        builder.add_recipient(addre.script_pubkey(), Amount::from_sat(5000));
+        // Only our own inputs are reclaimed, the auctioned space input is dropped
+        let mut inputs = Vec::new();
+        for input in original.input.iter() {
+            if let Some(txout) = wallet.spaces.tx_graph().get_txout(input.previous_output) {
+                if wallet.spaces.is_mine(txout.script_pubkey.as_script()) {
+                    inputs.push((input.previous_output, txout.clone()));
+                }
+            }
+        }
+        if inputs.is_empty() {
+            return Err(anyhow!("bid {} has no inputs owned by this wallet", txid));
+        }
 
-        let psbt = builder.finish()?;
-        let tx = wallet.sign(psbt, None)?;
+        // BIP-125 requires the replacement to pay at least the original absolute fee
+        let original_fee = wallet.spaces.calculate_fee(&original).ok();
+        let coin_selection = Self::get_spaces_coin_selection(wallet, state, true)?;
+        let change = wallet
+            .spaces
+            .next_unused_address(KeychainKind::Internal)
+            .script_pubkey();
 
-        let txid = tx.compute_txid();
-        let confirmation = source.rpc.broadcast_tx(&source.client, &tx)?;
+        let mut fee_absolute = None;
+        let tx = loop {
+            let mut builder = wallet.spaces.build_tx().coin_selection(coin_selection.clone());
+            builder
+                .ordering(TxOrdering::Untouched)
+                .enable_rbf()
+                .manually_selected_only()
+                .drain_to(change.clone());
+            match fee_absolute {
+                None => builder.fee_rate(fee_rate),
+                Some(fee) => builder.fee_absolute(fee),
+            };
+            for (outpoint, txout) in inputs.iter() {
+                builder.add_foreign_utxo(
+                    *outpoint,
+                    psbt::Input {
+                        witness_utxo: Some(txout.clone()),
+                        ..Default::default()
+                    },
+                    66,
+                )?;
+            }
+            let psbt = builder.finish()?;
+            let fee = psbt.fee()?;
+            let vsize = psbt.unsigned_tx.vsize() as u64;
+            if let (None, Some(original_fee)) = (fee_absolute, original_fee) {
+                let required = original_fee + Amount::from_sat(vsize);
+                if fee < required {
+                    fee_absolute = Some(required);
+                    continue;
+                }
+            }
+            break wallet.sign(psbt, None)?;
+        };
+
+        let new_txid = tx.compute_txid();
+        let tags = vec![TransactionTag::CancelBid];
+        let fee = wallet.spaces.calculate_fee(&tx).ok();
+        let vsize = tx.vsize() as u64;
+        Self::log_broadcast(source, wallet, &tx, &tags)?;
+        let confirmation = Self::broadcast_logged(source, wallet, &tx)?;
         wallet.insert_tx(tx, confirmation)?;
         wallet.commit()?;
 
         Ok(TxResponse {
-            txid,
-            tags: vec![TransactionTag::ForceSpendTestOnly],
+            txid: new_txid,
+            tags,
             error: None,
             raw: None,
+            warnings: vec![],
+            change: vec![],
+            dust_change: None,
+            fee,
+            vsize: Some(vsize),
+            fee_bump: None,
         })
     }
 
-    fn wallet_handle_commands(
-        network: ExtendedNetwork,
+    /// Bumps every unconfirmed wallet transaction that spent at least `min_age_blocks`
+    /// in the mempool to `fee_rate`, replacing it when it signals RBF and spending
+    /// one of its outputs with a higher fee child otherwise
+    fn bump_all_stuck(
         source: &BitcoinBlockSource,
-        mut state: &mut LiveSnapshot,
+        state: &mut LiveSnapshot,
         wallet: &mut SpacesWallet,
-        command: WalletCommand,
-    ) -> anyhow::Result<()> {
-        match command {
-            WalletCommand::GetInfo { resp } => _ = resp.send(Ok(wallet.get_info())),
-            WalletCommand::BatchTx { request, resp } => {
-                let batch_result = Self::batch_tx(network, &source, wallet, &mut state, request);
-                _ = resp.send(batch_result);
-            }
-            WalletCommand::BumpFee {
+        min_age_blocks: u32,
+        fee_rate: FeeRate,
+    ) -> anyhow::Result<Vec<StuckTxReport>> {
+        check_fee_rate(fee_rate)?;
+        let tip = wallet.spaces.local_chain().tip().height();
+        let mut pending: Vec<_> = wallet
+            .spaces
+            .transactions()
+            .filter(|ctx| !ctx.chain_position.is_confirmed())
+            .collect();
+        pending.sort();
+        // Parents must be handled before their children to notice evicted descendants
+        let pending = parents_first(
+            pending
+                .into_iter()
+                .map(|ctx| ctx.tx_node.tx.as_ref().clone())
+                .collect(),
+        );
+
+        let mut reports = Vec::new();
+        let mut replaced = HashSet::new();
+        for tx in pending {
+            let txid = tx.compute_txid();
+            let current = wallet.spaces.calculate_fee_rate(&tx).ok();
+            let mut report = StuckTxReport {
                 txid,
-                fee_rate,
-                skip_tx_check,
-                resp,
-            } => {
-                let result = Self::handle_fee_bump(source, &mut state, wallet, txid, skip_tx_check, fee_rate);
-                _ = resp.send(result);
-            }
-            WalletCommand::ForceSpendOutput {
-                outpoint,
-                fee_rate,
-                resp,
-            } => {
-                let result =
-                    Self::handle_force_spend_output(source, &mut state, wallet, outpoint, fee_rate);
-                _ = resp.send(result);
-            }
-            WalletCommand::GetNewAddress { kind, resp } => {
-                let address = match kind {
-                    AddressKind::Coin => wallet
-                        .spaces
-                        .next_unused_address(KeychainKind::External)
-                        .address
-                        .to_string(),
-                    AddressKind::Space => wallet.next_unused_space_address().to_string(),
-                };
-                _ = resp.send(Ok(address));
+                age: None,
+                fee_rate: current,
+                outcome: StuckTxOutcome::Skipped {
+                    reason: String::new(),
+                },
+            };
+            let skip = |report: &mut StuckTxReport, reason: &str| {
+                report.outcome = StuckTxOutcome::Skipped {
+                    reason: reason.to_string(),
+                }
+            };
+
+            // A replaced parent evicts its descendants from the mempool
+            if tx
+                .input
+                .iter()
+                .any(|input| replaced.contains(&input.previous_output.txid))
+            {
+                replaced.insert(txid);
+                skip(&mut report, "a parent transaction was replaced");
+                reports.push(report);
+                continue;
             }
-            WalletCommand::ListUnspent { resp } => {
-                _ = resp.send(Self::list_unspent(wallet, state));
+
+            let entry_req = source.rpc.get_mempool_entry(txid);
+            let entry = match source
+                .rpc
+                .send_json_blocking::<serde_json::Value>(&source.client, &entry_req)
+            {
+                Ok(entry) => entry,
+                Err(_) => {
+                    skip(&mut report, "transaction is not in the node's mempool");
+                    reports.push(report);
+                    continue;
+                }
+            };
+            report.age = entry["height"]
+                .as_u64()
+                .map(|height| tip.saturating_sub(height as u32));
+            if report.age.unwrap_or_default() < min_age_blocks {
+                skip(&mut report, "transaction is not old enough");
+                reports.push(report);
+                continue;
             }
-            WalletCommand::ListTransactions { count, skip, resp } => {
-                let transactions = Self::list_transactions(wallet, count, skip);
-                _ = resp.send(transactions);
+            if current.is_some_and(|current| min_replacement_fee_rate(current) > fee_rate) {
+                skip(
+                    &mut report,
+                    "transaction already pays close to or above the target fee rate",
+                );
+                reports.push(report);
+                continue;
             }
-            WalletCommand::ListSpaces { resp } => {
-                let result = Self::list_unspent(wallet, state);
-                match result {
-                    Ok(unspent) => {
-                        _ = resp.send(Ok(unspent
-                            .into_iter()
-                            .filter(|s| s.space.is_some())
-                            .collect()));
-                    }
-                    Err(error) => {
-                        _ = resp.send(Err(error));
+
+            let replaceable = Self::is_replaceable(source, wallet, txid)?;
+            let result = if replaceable.replaceable {
+                Self::handle_fee_bump(source, state, wallet, txid, false, fee_rate, false).map(
+                    |responses| StuckTxOutcome::Replaced {
+                        replacement: responses[0].txid,
+                    },
+                )
+            } else {
+                Self::child_pays_for_parent(source, state, wallet, &tx, fee_rate)
+                    .map(|child| StuckTxOutcome::ChildPaysForParent { child: child.txid })
+            };
+            match result {
+                Ok(outcome) => {
+                    if matches!(outcome, StuckTxOutcome::Replaced { .. }) {
+                        replaced.insert(txid);
                     }
+                    report.outcome = outcome;
                 }
+                Err(e) => skip(&mut report, &e.to_string()),
             }
-            WalletCommand::ListBidouts { resp } => {
-                let sel = Self::get_spaces_coin_selection(wallet, state, false)?;
-                let result = wallet.list_bidouts(&sel);
-                _ = resp.send(result);
-            }
-            WalletCommand::GetBalance { resp } => {
-                let balance = Self::get_balance(state, wallet);
-                _ = resp.send(balance);
-            }
-            WalletCommand::UnloadWallet => {
-                info!("Unloading wallet '{}' ...", wallet.name());
-            }
+            reports.push(report);
         }
-        Ok(())
+        Ok(reports)
     }
 
-    fn wallet_sync(
-        network: ExtendedNetwork,
-        source: BitcoinBlockSource,
-        mut state: LiveSnapshot,
-        mut wallet: SpacesWallet,
-        mut commands: Receiver<WalletCommand>,
-        mut shutdown: broadcast::Receiver<()>,
-        num_workers: usize,
+    /// Lists unconfirmed wallet transactions that spent at least `min_age_blocks` in
+    /// the mempool paying less than the rate estimated to confirm within `conf_target`
+    /// blocks, along with how each can be bumped
+    fn list_bump_candidates(
+        source: &BitcoinBlockSource,
+        fee_estimation: &FeeEstimation,
+        state: &mut LiveSnapshot,
+        wallet: &mut SpacesWallet,
+        min_age_blocks: u32,
+        conf_target: Option<u16>,
+    ) -> anyhow::Result<Vec<BumpCandidate>> {
+        let estimated = Self::estimate_fee_rate(source, fee_estimation, conf_target, None)?;
+        let tip = wallet.spaces.local_chain().tip().height();
+        let pending: Vec<_> = wallet
+            .spaces
+            .transactions()
+            .filter(|ctx| !ctx.chain_position.is_confirmed())
+            .map(|ctx| ctx.tx_node.tx.clone())
+            .collect();
+
+        let mut candidates = Vec::new();
+        for tx in pending {
+            let txid = tx.compute_txid();
+            let entry_req = source.rpc.get_mempool_entry(txid);
+            let entry = match source
+                .rpc
+                .send_json_blocking::<serde_json::Value>(&source.client, &entry_req)
+            {
+                Ok(entry) => entry,
+                Err(_) => continue,
+            };
+            let age = entry["height"]
+                .as_u64()
+                .map(|height| tip.saturating_sub(height as u32));
+            if age.unwrap_or_default() < min_age_blocks {
+                continue;
+            }
+
+            let fee_rate = wallet.spaces.calculate_fee_rate(&tx).ok();
+            if fee_rate.is_some_and(|current| current >= estimated) {
+                continue;
+            }
+            let suggested_fee_rate = match fee_rate {
+                Some(current) => estimated.max(min_replacement_fee_rate(current)),
+                None => estimated,
+            };
+
+            let method = if Self::is_replaceable(source, wallet, txid)?.replaceable {
+                BumpMethod::Rbf
+            } else {
+                let mut spendable = false;
+                for utxo in wallet.spaces.list_unspent() {
+                    if utxo.outpoint.txid == txid
+                        && utxo.txout.value > SpacesAwareCoinSelection::DUST_THRESHOLD
+                        && state.get_spaceout(&utxo.outpoint)?.is_none()
+                    {
+                        spendable = true;
+                        break;
+                    }
+                }
+                if !spendable {
+                    continue;
+                }
+                BumpMethod::Cpfp
+            };
+
+            candidates.push(BumpCandidate {
+                txid,
+                age,
+                fee_rate,
+                suggested_fee_rate,
+                method,
+            });
+        }
+        Ok(candidates)
+    }
+
+    /// Spends the largest wallet output of an unconfirmed transaction with
+    /// enough fee for the parent and child together to pay `fee_rate`
+    fn child_pays_for_parent(
+        source: &BitcoinBlockSource,
+        state: &mut LiveSnapshot,
+        wallet: &mut SpacesWallet,
+        parent: &Transaction,
+        fee_rate: FeeRate,
+    ) -> anyhow::Result<TxResponse> {
+        let txid = parent.compute_txid();
+        let parent_fee = wallet.spaces.calculate_fee(parent)?;
+        let parent_deficit = fee_rate
+            .fee_vb(parent.vsize() as u64)
+            .ok_or_else(|| anyhow!("fee rate is too high"))?
+            .checked_sub(parent_fee)
+            .unwrap_or(Amount::ZERO);
+
+        let mut output: Option<LocalOutput> = None;
+        for utxo in wallet.spaces.list_unspent() {
+            if utxo.outpoint.txid != txid
+                || utxo.txout.value <= SpacesAwareCoinSelection::DUST_THRESHOLD
+                || state.get_spaceout(&utxo.outpoint)?.is_some()
+            {
+                continue;
+            }
+            if output
+                .as_ref()
+                .map_or(true, |o| o.txout.value < utxo.txout.value)
+            {
+                output = Some(utxo);
+            }
+        }
+        let output = output
+            .ok_or_else(|| anyhow!("not replaceable and has no wallet output to spend for CPFP"))?;
+
+        let coin_selection = Self::get_spaces_coin_selection(wallet, state, false)?;
+        let change = wallet
+            .spaces
+            .next_unused_address(KeychainKind::Internal)
+            .script_pubkey();
+
+        let mut fee_absolute = None;
+        let tx = loop {
+            let mut builder = wallet.spaces.build_tx().coin_selection(coin_selection.clone());
+            builder
+                .ordering(TxOrdering::Untouched)
+                .enable_rbf()
+                .drain_to(change.clone())
+                .add_utxo(output.outpoint)?;
+            match fee_absolute {
+                None => builder.fee_rate(fee_rate),
+                Some(fee) => builder.fee_absolute(fee),
+            };
+            let psbt = builder.finish()?;
+            if fee_absolute.is_none() && parent_deficit > Amount::ZERO {
+                fee_absolute = Some(psbt.fee()? + parent_deficit);
+                continue;
+            }
+            break wallet.sign(psbt, None)?;
+        };
+
+        let child_txid = tx.compute_txid();
+        let tags = vec![TransactionTag::FeeBump];
+        let fee = wallet.spaces.calculate_fee(&tx).ok();
+        let vsize = tx.vsize() as u64;
+        Self::log_broadcast(source, wallet, &tx, &tags)?;
+        let confirmation = Self::broadcast_logged(source, wallet, &tx)?;
+        wallet.insert_tx(tx, confirmation)?;
+        wallet.commit()?;
+
+        Ok(TxResponse {
+            txid: child_txid,
+            tags,
+            error: None,
+            raw: None,
+            warnings: vec![],
+            change: vec![],
+            dust_change: None,
+            fee,
+            vsize: Some(vsize),
+            fee_bump: None,
+        })
+    }
+
+    fn is_replaceable(
+        source: &BitcoinBlockSource,
+        wallet: &SpacesWallet,
+        txid: Txid,
+    ) -> anyhow::Result<ReplaceableInfo> {
+        let tx = match wallet.spaces.get_tx(txid) {
+            None => return Err(anyhow!("No wallet tx {} found", txid)),
+            Some(tx) => tx,
+        };
+        if tx.chain_position.is_confirmed() {
+            return Ok(ReplaceableInfo {
+                replaceable: false,
+                reason: Some("transaction is already confirmed".to_string()),
+            });
+        }
+        if tx.tx_node.input.iter().any(|input| input.sequence.is_rbf()) {
+            return Ok(ReplaceableInfo {
+                replaceable: true,
+                reason: None,
+            });
+        }
+
+        // May still be replaceable by inheriting signaling from an unconfirmed ancestor
+        let entry_req = source.rpc.get_mempool_entry(txid);
+        if let Ok(entry) = source
+            .rpc
+            .send_json_blocking::<serde_json::Value>(&source.client, &entry_req)
+        {
+            if entry["bip125-replaceable"].as_bool().unwrap_or(false) {
+                return Ok(ReplaceableInfo {
+                    replaceable: true,
+                    reason: None,
+                });
+            }
+        }
+
+        Ok(ReplaceableInfo {
+            replaceable: false,
+            reason: Some(
+                "transaction does not signal replaceability (BIP-125); use CPFP instead"
+                    .to_string(),
+            ),
+        })
+    }
+
+    fn handle_force_spend_output(
+        source: &BitcoinBlockSource,
+        state: &mut LiveSnapshot,
+        wallet: &mut SpacesWallet,
+        output: OutPoint,
+        fee_rate: FeeRate,
+    ) -> anyhow::Result<TxResponse> {
+        let coin_selection = Self::get_spaces_coin_selection(wallet, state, true)?;
+        let addre = wallet.spaces.next_unused_address(KeychainKind::External);
+        let mut builder = wallet.spaces.build_tx().coin_selection(coin_selection);
+
+        builder.ordering(TxOrdering::Untouched);
+        builder.fee_rate(fee_rate);
+        builder.enable_rbf();
+        builder.add_utxo(output)?;
+        builder.add_recipient(addre.script_pubkey(), Amount::from_sat(5000));
+
+        let psbt = builder.finish()?;
+        let tx = wallet.sign(psbt, None)?;
+
+        let txid = tx.compute_txid();
+        let tags = vec![TransactionTag::ForceSpendTestOnly];
+        let fee = wallet.spaces.calculate_fee(&tx).ok();
+        let vsize = tx.vsize() as u64;
+        Self::log_broadcast(source, wallet, &tx, &tags)?;
+        let confirmation = Self::broadcast_logged(source, wallet, &tx)?;
+        wallet.insert_tx(tx, confirmation)?;
+        wallet.commit()?;
+
+        Ok(TxResponse {
+            txid,
+            tags,
+            error: None,
+            raw: None,
+            warnings: vec![],
+            change: vec![],
+            dust_change: None,
+            fee,
+            vsize: Some(vsize),
+            fee_bump: None,
+        })
+    }
+
+    #[cfg(feature = "testing")]
+    fn apply_test_block(
+        wallet: &mut SpacesWallet,
+        block_hashes: &mut BlockHashCache,
+        block_hex: &str,
+        height: u32,
+    ) -> anyhow::Result<()> {
+        let block: bitcoin::Block = bitcoin::consensus::encode::deserialize_hex(block_hex)?;
+        let tip = wallet.spaces.local_chain().tip().block_id();
+        if height != tip.height + 1 || block.header.prev_blockhash != tip.hash {
+            return Err(anyhow!(
+                "block at height {} does not connect to wallet tip {} at height {}",
+                height,
+                tip.hash,
+                tip.height
+            ));
+        }
+        wallet.apply_block_connected_to(height, &block, tip)?;
+        block_hashes.connect(&ChainAnchor {
+            height,
+            hash: block.block_hash(),
+        });
+        wallet.commit()?;
+        Ok(())
+    }
+
+    fn wallet_handle_commands(
+        network: ExtendedNetwork,
+        fee_estimation: &FeeEstimation,
+        source: &BitcoinBlockSource,
+        mut state: &mut LiveSnapshot,
+        wallet: &mut SpacesWallet,
+        balance_updates: &broadcast::Sender<Balance>,
+        progress_updates: &broadcast::Sender<SyncProgress>,
+        sync_rate: &SyncRate,
+        tip_preview: &TipPreview,
+        dropped_txs: &mut DroppedTxTracker,
+        block_hashes: &mut BlockHashCache,
+        command: WalletCommand,
     ) -> anyhow::Result<()> {
-        let (fetcher, receiver) = BlockFetcher::new(source.clone(), num_workers);
+        match command {
+            WalletCommand::GetInfo { resp } => _ = resp.send(Ok(wallet.get_info())),
+            WalletCommand::GetWalletMetadata { resp } => {
+                _ = resp.send(Ok(wallet.get_metadata()))
+            }
+            WalletCommand::BatchTx { request, resp } if !request.broadcast => {
+                let preview = Self::preview_batch(
+                    network,
+                    fee_estimation,
+                    &source,
+                    wallet,
+                    &mut state,
+                    request,
+                    None,
+                );
+                _ = resp.send(preview);
+            }
+            WalletCommand::BatchTx { request, resp } => {
+                let batch_result = Self::batch_tx(
+                    network,
+                    fee_estimation,
+                    &source,
+                    wallet,
+                    &mut state,
+                    request,
+                    false,
+                    None,
+                    None,
+                );
+                _ = resp.send(batch_result);
+            }
+            WalletCommand::BatchTxStream {
+                request,
+                stream,
+                resp,
+            } if !request.broadcast => {
+                let preview = Self::preview_batch(
+                    network,
+                    fee_estimation,
+                    &source,
+                    wallet,
+                    &mut state,
+                    request,
+                    Some(&stream),
+                );
+                _ = resp.send(preview);
+            }
+            WalletCommand::BatchTxStream {
+                request,
+                stream,
+                resp,
+            } => {
+                let batch_result = Self::batch_tx(
+                    network,
+                    fee_estimation,
+                    &source,
+                    wallet,
+                    &mut state,
+                    request,
+                    false,
+                    None,
+                    Some(&stream),
+                );
+                _ = resp.send(batch_result);
+            }
+            WalletCommand::PreviewBatch { request, resp } => {
+                let preview = Self::preview_batch(
+                    network,
+                    fee_estimation,
+                    &source,
+                    wallet,
+                    &mut state,
+                    request,
+                    None,
+                );
+                _ = resp.send(preview);
+            }
+            WalletCommand::BuildPsbt {
+                request,
+                inputs,
+                fee_rate,
+                resp,
+            } => {
+                let psbt = Self::build_operation_psbt(
+                    network,
+                    fee_estimation,
+                    &source,
+                    wallet,
+                    &mut state,
+                    request,
+                    inputs,
+                    fee_rate,
+                );
+                _ = resp.send(psbt);
+            }
+            WalletCommand::BroadcastBatch { raw_txs, resp } => {
+                _ = resp.send(Self::broadcast_batch(&source, wallet, raw_txs));
+            }
+            WalletCommand::BumpFee {
+                txid,
+                fee_rate,
+                skip_tx_check,
+                dry_run,
+                resp,
+            } => {
+                let result = Self::handle_fee_bump(
+                    source,
+                    &mut state,
+                    wallet,
+                    txid,
+                    skip_tx_check,
+                    fee_rate,
+                    dry_run,
+                );
+                _ = resp.send(result);
+            }
+            WalletCommand::BumpFeeToTarget {
+                txid,
+                conf_target,
+                dry_run,
+                resp,
+            } => {
+                _ = resp.send(Self::bump_fee_to_target(
+                    source,
+                    fee_estimation,
+                    &mut state,
+                    wallet,
+                    txid,
+                    conf_target,
+                    dry_run,
+                ));
+            }
+            WalletCommand::CancelBid {
+                txid,
+                fee_rate,
+                resp,
+            } => {
+                _ = resp.send(Self::cancel_bid(source, &mut state, wallet, txid, fee_rate));
+            }
+            WalletCommand::IsReplaceable { txid, resp } => {
+                _ = resp.send(Self::is_replaceable(source, wallet, txid));
+            }
+            WalletCommand::GetClaimStatus { space, resp } => {
+                _ = resp.send(Self::get_claim_status(wallet, state, &space));
+            }
+            WalletCommand::GetSpaceScript { space, resp } => {
+                _ = resp.send(Self::get_space_script(wallet, state, &space));
+            }
+            WalletCommand::Encrypt { cipher, resp } => {
+                _ = resp.send(wallet.encrypt(
+                    cipher,
+                    &[TX_NOTES_FILE, FEE_POLICY_FILE, CONFIRM_POLICY_FILE],
+                ));
+            }
+            #[cfg(feature = "testing")]
+            WalletCommand::ApplyTestBlock {
+                block_hex,
+                height,
+                resp,
+            } => {
+                _ = resp.send(Self::apply_test_block(
+                    wallet,
+                    block_hashes,
+                    &block_hex,
+                    height,
+                ));
+            }
+            WalletCommand::ForceSpendOutput {
+                outpoint,
+                fee_rate,
+                resp,
+            } => {
+                let result =
+                    Self::handle_force_spend_output(source, &mut state, wallet, outpoint, fee_rate);
+                _ = resp.send(result);
+            }
+            WalletCommand::GetNewAddress { kind, resp } => {
+                _ = resp.send(Self::new_address(wallet, kind));
+            }
+            WalletCommand::DeriveAddress {
+                internal,
+                index,
+                resp,
+            } => {
+                _ = resp.send(Self::derive_address(wallet, internal, index));
+            }
+            WalletCommand::ListUnspent { resp } => {
+                _ = resp.send(Self::list_unspent(wallet, state));
+            }
+            WalletCommand::ListOutputGroups { resp } => {
+                _ = resp.send(Self::list_output_groups(wallet, state));
+            }
+            WalletCommand::GetSnapshot { resp } => {
+                _ = resp.send(Self::get_snapshot(wallet, state));
+            }
+            WalletCommand::ListTransactions { count, skip, resp } => {
+                let transactions =
+                    Self::list_transactions(source, wallet, block_hashes, count, skip);
+                _ = resp.send(transactions);
+            }
+            WalletCommand::ListFeeRates { count, resp } => {
+                _ = resp.send(Self::list_fee_rates(wallet, count));
+            }
+            WalletCommand::Reconcile { resp } => {
+                _ = resp.send(Self::reconcile(source, wallet, dropped_txs));
+            }
+            WalletCommand::AbandonAllPending { resp } => {
+                _ = resp.send(Self::abandon_all_pending(source, wallet));
+            }
+            WalletCommand::AbandonOpen {
+                name,
+                reclaim_fee_rate,
+                resp,
+            } => {
+                _ = resp.send(Self::abandon_open(
+                    source,
+                    fee_estimation,
+                    wallet,
+                    &name,
+                    reclaim_fee_rate,
+                ));
+            }
+            WalletCommand::BumpAllStuck {
+                min_age_blocks,
+                target_fee_rate,
+                resp,
+            } => {
+                _ = resp.send(Self::bump_all_stuck(
+                    source,
+                    &mut state,
+                    wallet,
+                    min_age_blocks,
+                    target_fee_rate,
+                ));
+            }
+            WalletCommand::ListBumpCandidates {
+                min_age_blocks,
+                conf_target,
+                resp,
+            } => {
+                _ = resp.send(Self::list_bump_candidates(
+                    source,
+                    fee_estimation,
+                    &mut state,
+                    wallet,
+                    min_age_blocks,
+                    conf_target,
+                ));
+            }
+            WalletCommand::ListCheckpoints { count, resp } => {
+                // Newest first, the order restore points are searched in after a mismatch
+                let checkpoints = wallet
+                    .spaces
+                    .local_chain()
+                    .iter_checkpoints()
+                    .take(count)
+                    .map(|cp| ChainAnchor {
+                        height: cp.height(),
+                        hash: cp.hash(),
+                    })
+                    .collect();
+                _ = resp.send(Ok(checkpoints));
+            }
+            WalletCommand::ListDust { fee_rate, resp } => {
+                _ = resp.send(Self::list_dust(wallet, state, fee_rate));
+            }
+            WalletCommand::EstimateSelection {
+                amount,
+                fee_rate,
+                resp,
+            } => {
+                _ = resp.send(Self::estimate_selection(wallet, state, amount, fee_rate));
+            }
+            WalletCommand::EstimateVsize { request, resp } => {
+                _ = resp.send(Self::estimate_vsize(wallet, state, request));
+            }
+            WalletCommand::CheckOperation { request, resp } => {
+                _ = resp.send(Self::check_operation(network, source, wallet, state, request));
+            }
+            WalletCommand::SignMessage {
+                space,
+                message,
+                resp,
+            } => {
+                _ = resp.send(Self::sign_message(wallet, state, space, message));
+            }
+            WalletCommand::GetSpaceInfo { space, resp } => {
+                _ = resp.send(Self::get_space_info(wallet, state, &space));
+            }
+            WalletCommand::GetMaxSendable { fee_rate, to, resp } => {
+                _ = resp.send(Self::get_max_sendable(network, wallet, state, fee_rate, &to));
+            }
+            WalletCommand::ExportHistory {
+                format,
+                as_of,
+                skip,
+                count,
+                resp,
+            } => {
+                _ = resp.send(Self::export_history(
+                    wallet, state, format, as_of, skip, count,
+                ));
+            }
+            WalletCommand::GetSpacesValueLocked { resp } => {
+                _ = resp.send(Self::get_spaces_value_locked(wallet, state));
+            }
+            WalletCommand::ListSpaces { resp } => {
+                let result = Self::list_unspent(wallet, state);
+                match result {
+                    Ok(unspent) => {
+                        _ = resp.send(Ok(unspent
+                            .into_iter()
+                            .filter(|s| s.space.is_some())
+                            .collect()));
+                    }
+                    Err(error) => {
+                        _ = resp.send(Err(error));
+                    }
+                }
+            }
+            WalletCommand::ListSpacesByState {
+                state: filter,
+                resp,
+            } => {
+                _ = resp.send(Self::list_spaces_by_state(wallet, state, filter));
+            }
+            WalletCommand::ListBidouts { resp } => {
+                let sel = Self::get_spaces_coin_selection(wallet, state, false)?;
+                let result = wallet.list_bidouts(&sel);
+                _ = resp.send(result);
+            }
+            WalletCommand::GetBalance { resp } => {
+                let balance = Self::get_balance(state, wallet);
+                _ = resp.send(balance);
+            }
+            WalletCommand::SubscribeBalance { resp } => {
+                _ = resp.send(Ok(balance_updates.subscribe()));
+            }
+            WalletCommand::SubscribeSyncProgress { resp } => {
+                _ = resp.send(Ok(progress_updates.subscribe()));
+            }
+            WalletCommand::GetSyncProgress { resp } => {
+                _ = resp.send(Self::get_sync_progress(source, wallet, sync_rate, tip_preview));
+            }
+            WalletCommand::SetFeePolicy { policy, resp } => {
+                _ = resp.send(Self::set_fee_policy(wallet, policy));
+            }
+            WalletCommand::GetFeePolicy { resp } => {
+                _ = resp.send(Self::load_fee_policy(wallet));
+            }
+            WalletCommand::SetConfirmPolicy { policy, resp } => {
+                _ = resp.send(Self::set_confirm_policy(wallet, policy));
+            }
+            WalletCommand::GetConfirmPolicy { resp } => {
+                _ = resp.send(Self::load_confirm_policy(wallet));
+            }
+            WalletCommand::SetTxNote { txid, note, resp } => {
+                _ = resp.send(Self::set_tx_note(wallet, txid, note));
+            }
+            WalletCommand::GetTxNote { txid, resp } => {
+                let note = Self::load_tx_notes(wallet).map(|mut notes| notes.remove(&txid));
+                _ = resp.send(note);
+            }
+            WalletCommand::HasTx { txid, resp } => {
+                _ = resp.send(Ok(wallet.spaces.get_tx(txid).is_some()));
+            }
+            WalletCommand::UnloadWallet => {
+                info!("Unloading wallet '{}' ...", wallet.name());
+            }
+        }
+        Ok(())
+    }
+
+    fn wallet_sync(
+        network: ExtendedNetwork,
+        fee_estimation: FeeEstimation,
+        source: BitcoinBlockSource,
+        mut state: LiveSnapshot,
+        mut wallet: SpacesWallet,
+        mut commands: Receiver<WalletCommand>,
+        mut shutdown: broadcast::Receiver<()>,
+        num_workers: usize,
+        catch_up_threshold: u32,
+        tip_refetch_depth: u32,
+        tip_preview_depth: u32,
+        dropped_tx_grace_period: Duration,
+    ) -> anyhow::Result<()> {
+        let (mut fetcher, mut receiver) = BlockFetcher::new(source.clone(), num_workers);
+        fetcher.set_tip_refetch_depth(tip_refetch_depth);
+        fetcher.set_tip_preview_depth(tip_preview_depth);
+
+        let mut wallet_tip = {
+            let tip = wallet.spaces.local_chain().tip();
+            ChainAnchor {
+                height: tip.height(),
+                hash: tip.hash(),
+            }
+        };
+
+        // Syncing against a node still in initial block download would leave the
+        // wallet thinking it's caught up to a partial chain, so the fetcher is
+        // only started once the node is out of it
+        let mut waiting_for_ibd = true;
+        let mut last_ibd_check: Option<Instant> = None;
+
+        let (balance_updates, _) = broadcast::channel(16);
+        let (progress_updates, _) = broadcast::channel(16);
+        let mut last_balance: Option<Balance> = None;
+        let mut balance_stale = false;
+        let mut balance_failing = false;
+        let mut sync_rate = SyncRate::default();
+        let mut catching_up = false;
+        let mut last_reconcile = Instant::now();
+        let mut dropped_txs = DroppedTxTracker::new(dropped_tx_grace_period);
+        let mut block_hashes = BlockHashCache::new();
+        let mut tip_preview = TipPreview::default();
+
+        loop {
+            if shutdown.try_recv().is_ok() {
+                info!("Shutting down wallet sync");
+                break;
+            }
+
+            if waiting_for_ibd
+                && last_ibd_check.map_or(true, |last| last.elapsed() >= IBD_POLL_INTERVAL)
+            {
+                let first_check = last_ibd_check.is_none();
+                last_ibd_check = Some(Instant::now());
+                match source.in_initial_block_download() {
+                    Ok(false) => {
+                        if !first_check {
+                            info!(
+                                "Bitcoin node finished initial block download, syncing wallet `{}`",
+                                wallet.name()
+                            );
+                        }
+                        waiting_for_ibd = false;
+                        fetcher.start(wallet_tip);
+                    }
+                    Ok(true) if first_check => info!(
+                        "Bitcoin node is in initial block download, \
+                         waiting before syncing wallet `{}`",
+                        wallet.name()
+                    ),
+                    Ok(true) => {}
+                    Err(e) => warn!(
+                        "Could not check if the bitcoin node is in initial block download: {}",
+                        e
+                    ),
+                }
+            }
+
+            // When far behind the tip, process blocks back to back and hold off on
+            // work that only matters once the wallet is caught up
+            let behind = fetcher
+                .remote_tip()
+                .map(|tip| tip.height.saturating_sub(wallet_tip.height))
+                .unwrap_or(0);
+            if (behind > catch_up_threshold) != catching_up {
+                catching_up = !catching_up;
+                if catching_up {
+                    info!(
+                        "Wallet `{}` is {} blocks behind, catching up",
+                        wallet.name(),
+                        behind
+                    );
+                } else {
+                    info!("Wallet `{}` caught up", wallet.name());
+                }
+            }
+
+            // Only recompute the balance for subscribers when the wallet may have changed
+            if balance_stale && !catching_up && balance_updates.receiver_count() > 0 {
+                match Self::get_balance(&mut state, &mut wallet) {
+                    Ok(balance) => {
+                        if last_balance.as_ref() != Some(&balance) {
+                            _ = balance_updates.send(balance.clone());
+                            last_balance = Some(balance);
+                        }
+                        balance_stale = false;
+                        balance_failing = false;
+                    }
+                    // Left stale so it's retried on the next iteration
+                    Err(e) => {
+                        if !balance_failing {
+                            warn!("Could not compute balance of `{}`: {}", wallet.name(), e);
+                            balance_failing = true;
+                        }
+                    }
+                }
+            }
+            if let Ok(command) = commands.try_recv() {
+                balance_stale = true;
+                Self::wallet_handle_commands(
+                    network,
+                    &fee_estimation,
+                    &source,
+                    &mut state,
+                    &mut wallet,
+                    &balance_updates,
+                    &progress_updates,
+                    &sync_rate,
+                    &tip_preview,
+                    &mut dropped_txs,
+                    &mut block_hashes,
+                    command,
+                )?;
+            }
+            // Checked before receiving so events sent before the fetcher died are
+            // consumed before it's resumed
+            let fetcher_dead = fetcher.is_dead();
+            let event = if catching_up {
+                receiver.recv_timeout(Duration::from_millis(10)).ok()
+            } else {
+                receiver.try_recv().ok()
+            };
+            if let Some(event) = event {
+                balance_stale = true;
+                match event {
+                    BlockEvent::Block(id, block) => {
+                        wallet.apply_block_connected_to(
+                            id.height,
+                            &block,
+                            wallet::bdk_wallet::chain::BlockId {
+                                height: wallet_tip.height,
+                                hash: wallet_tip.hash,
+                            },
+                        )?;
+
+                        // Temporary fix for https://github.com/bitcoindevkit/bdk/issues/1740
+                        for tx in block.txdata {
+                            for input in tx.input.iter() {
+                                if wallet.watch_bid_spends.contains(&input.previous_output) {
+                                    if wallet.spaces.get_tx(tx.compute_txid()).is_some() {
+                                        break;
+                                    }
+                                    wallet.insert_tx(tx, ConfirmationTime::Confirmed {
+                                        height: id.height,
+                                        time: block.header.time as _,
+                                    })?;
+                                    break;
+                                }
+                            }
+                        }
+
+                        block_hashes.connect(&id);
+                        wallet_tip.height = id.height;
+                        wallet_tip.hash = id.hash;
+                        sync_rate.record_block();
+                        tip_preview.synced_to(id.height);
+
+                        if id.height % 12 == 0 {
+                            wallet.commit()?;
+                        }
+                    }
+                    BlockEvent::TipPreview(id, block) => {
+                        tip_preview.inspect(&wallet, id, &block);
+                    }
+                    BlockEvent::Progress { height, tip } => {
+                        _ = progress_updates.send(SyncProgress { height, tip });
+                    }
+                    BlockEvent::Error(e) if matches!(e, BlockFetchError::BlockMismatch) => {
+                        let mut checkpoint_in_chain = None;
+                        let best_chain = source.get_best_chain()?;
+                        for cp in wallet.spaces.local_chain().iter_checkpoints() {
+                            if cp.height() > best_chain.height {
+                                continue;
+                            }
+
+                            let hash = source.get_block_hash(cp.height())?;
+                            if cp.height() != 0 && hash == cp.hash() {
+                                checkpoint_in_chain = Some(cp);
+                                break;
+                            }
+                        }
+
+                        let restore_point = match checkpoint_in_chain {
+                            None => {
+                                // We couldn't find a restore point
+                                warn!("Rebuilding wallet `{}`", wallet.config.name);
+                                let birthday = wallet.config.start_block;
+                                let hash = source.get_block_hash(birthday)?;
+                                let cp = CheckPoint::new(BlockId {
+                                    height: birthday,
+                                    hash,
+                                });
+                                wallet = wallet.rebuild()?;
+                                wallet.spaces.insert_checkpoint(cp.block_id())?;
+                                cp
+                            }
+                            Some(cp) => cp,
+                        };
+
+                        wallet_tip.height = restore_point.block_id().height;
+                        wallet_tip.hash = restore_point.block_id().hash;
+                        block_hashes.disconnect_above(wallet_tip.height);
+
+                        info!(
+                            "Restore wallet `{}` to block={} height={}",
+                            wallet.name(),
+                            wallet_tip.hash,
+                            wallet_tip.height
+                        );
+                        fetcher.start(wallet_tip);
+                    }
+                    BlockEvent::Error(e) => return Err(e.into()),
+                }
+
+                continue;
+            }
+
+            if fetcher_dead {
+                warn!(
+                    "Block fetcher of wallet `{}` stopped unexpectedly, restarting",
+                    wallet.name()
+                );
+                receiver = fetcher.reconnect();
+                continue;
+            }
+
+            if !catching_up && !waiting_for_ibd && last_reconcile.elapsed() >= RECONCILE_INTERVAL {
+                last_reconcile = Instant::now();
+                match Self::reconcile(&source, &mut wallet, &mut dropped_txs) {
+                    Ok(report) => balance_stale |= !report.removed.is_empty(),
+                    Err(e) => warn!("Could not reconcile wallet `{}`: {}", wallet.name(), e),
+                }
+            }
+
+            // TODO: update wallet mempool
+            if !catching_up {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        }
+
+        fetcher.stop();
+        Ok(())
+    }
+
+    fn get_spaces_coin_selection(
+        wallet: &mut SpacesWallet,
+        state: &mut LiveSnapshot,
+        confirmed_only: bool,
+    ) -> anyhow::Result<SpacesAwareCoinSelection> {
+        // Filters out all "spaceouts" with value higher than DUST threshold && all spaceouts representing
+        // spaces.
+        //
+        // Note: This exclusion only applies to confirmed spaceouts; unconfirmed ones are not excluded
+        // as we cannot easily detect them for now.
+        // In practice, this should be fine since Spaces coin selection skips dust by default,
+        // so explicitly excluding spaceouts may be redundant.
+        let excluded = Self::list_unspent(wallet, state)?
+            .into_iter()
+            .filter(|out| out.space.is_some() ||
+                (out.is_spaceout && out.output.txout.value <= SpacesAwareCoinSelection::DUST_THRESHOLD)
+            )
+            .map(|out| SelectionOutput {
+                outpoint: out.output.outpoint,
+                is_space: out.space.is_some(),
+                is_spaceout: out.is_spaceout,
+            })
+            .collect::<Vec<_>>();
+
+        Ok(SpacesAwareCoinSelection::new(excluded, confirmed_only))
+    }
+
+    fn list_transactions(
+        source: &BitcoinBlockSource,
+        wallet: &mut SpacesWallet,
+        block_hashes: &mut BlockHashCache,
+        count: usize,
+        skip: usize,
+    ) -> anyhow::Result<Vec<TxInfo>> {
+        let mut notes = Self::load_tx_notes(wallet)?;
+        let mut transactions: Vec<_> = wallet.spaces.transactions().collect();
+        transactions.sort();
+        let tip_height = wallet.spaces.local_chain().tip().height();
+
+        transactions
+            .iter()
+            .rev()
+            .skip(skip)
+            .take(count)
+            .map(|ctx| {
+                let tx = ctx.tx_node.tx.clone();
+                let txid = ctx.tx_node.txid.clone();
+                let confirmed = ctx.chain_position.is_confirmed();
+                let confirmation = ConfirmationTime::from(ctx.chain_position.cloned());
+                let depth = tx_depth(tip_height, confirmation);
+                let block_height = match confirmation {
+                    ConfirmationTime::Confirmed { height, .. } => Some(height),
+                    ConfirmationTime::Unconfirmed { .. } => None,
+                };
+                let block_hash = match block_height {
+                    None => None,
+                    Some(height) => Some(block_hashes.get(source, wallet, height)?),
+                };
+                let (sent, received) = wallet.spaces.sent_and_received(&tx);
+                let fee = wallet.spaces.calculate_fee(&tx).ok();
+                Ok(TxInfo {
+                    txid,
+                    confirmed,
+                    depth,
+                    sent,
+                    received,
+                    fee,
+                    block_height,
+                    block_hash,
+                    note: notes.remove(&txid),
+                })
+            })
+            .collect()
+    }
+
+    /// Hands out the next receive address no output known to the wallet ever paid,
+    /// skipping any that were paid despite being marked unused. Warns when the
+    /// unused gap approaches the lookahead a restore from seed scans with.
+    fn new_address(wallet: &mut SpacesWallet, kind: AddressKind) -> anyhow::Result<NewAddress> {
+        let keychain = KeychainKind::External;
+        let lookahead = wallet.spaces.spk_index().lookahead();
+        let mut skipped = 0;
+        let info = loop {
+            let info = wallet.spaces.next_unused_address(keychain);
+            let paid = wallet
+                .spaces
+                .tx_graph()
+                .all_txouts()
+                .any(|(_, txout)| txout.script_pubkey == info.address.script_pubkey());
+            if !paid {
+                break info;
+            }
+            wallet.spaces.mark_used(keychain, info.index);
+            skipped += 1;
+            if skipped > lookahead {
+                return Err(anyhow!(
+                    "could not find an address that never received funds within {} addresses",
+                    lookahead
+                ));
+            }
+        };
+
+        let mut warnings = Vec::new();
+        if skipped > 0 {
+            warnings.push(format!(
+                "skipped {} address(es) that already received funds",
+                skipped
+            ));
+        }
+        let gap = match wallet.spaces.spk_index().last_used_index(keychain) {
+            Some(used) => info.index.saturating_sub(used),
+            None => info.index + 1,
+        };
+        if gap + GAP_LIMIT_WARNING_MARGIN >= lookahead {
+            let warning = format!(
+                "{} addresses were handed out since the last one that received funds, \
+                 a wallet restored from seed only looks {} addresses ahead and may miss \
+                 payments to later ones",
+                gap, lookahead
+            );
+            warn!("Wallet `{}`: {}", wallet.name(), warning);
+            warnings.push(warning);
+        }
+
+        let address = match kind {
+            AddressKind::Coin => info.address.to_string(),
+            AddressKind::Space => SpaceAddress(info.address).to_string(),
+        };
+        Ok(NewAddress { address, warnings })
+    }
+
+    /// Derives the address at `index` without revealing it
+    fn derive_address(
+        wallet: &SpacesWallet,
+        internal: bool,
+        index: u32,
+    ) -> anyhow::Result<DerivedAddress> {
+        if index >= (1 << 31) {
+            return Err(anyhow!("derivation index {} is out of range", index));
+        }
+        let keychain = if internal {
+            KeychainKind::Internal
+        } else {
+            KeychainKind::External
+        };
+        let info = wallet.spaces.peek_address(keychain, index);
+        let revealed = wallet
+            .spaces
+            .derivation_index(keychain)
+            .is_some_and(|last| index <= last);
+        Ok(DerivedAddress {
+            internal,
+            index,
+            script_pubkey: info.address.script_pubkey(),
+            address: info.address.to_string(),
+            space_address: SpaceAddress(info.address).to_string(),
+            revealed,
+        })
+    }
+
+    /// Removes unconfirmed wallet transactions that are no longer in the node's
+    /// mempool, e.g. after being evicted or double spent, once they've been missing
+    /// for the tracker's grace period.
+    fn reconcile(
+        source: &BitcoinBlockSource,
+        wallet: &mut SpacesWallet,
+        dropped_txs: &mut DroppedTxTracker,
+    ) -> anyhow::Result<ReconcileReport> {
+        // An empty mempool doesn't tell us anything until the node finished loading it
+        let info: serde_json::Value = source
+            .rpc
+            .send_json_blocking(&source.client, &source.rpc.get_mempool_info())?;
+        if !info.get("loaded").and_then(|l| l.as_bool()).unwrap_or(true) {
+            return Err(anyhow!("the bitcoin node is still loading its mempool"));
+        }
+        let mempool: HashSet<Txid> = source
+            .rpc
+            .send_json_blocking(&source.client, &source.rpc.get_raw_mempool())?;
+
+        let now = Instant::now();
+        let mut checked = 0;
+        let mut missing = BTreeMap::new();
+        let mut expired = Vec::new();
+        for ctx in wallet.spaces.transactions() {
+            if ctx.chain_position.is_confirmed() {
+                continue;
+            }
+            checked += 1;
+            let txid = ctx.tx_node.txid;
+            if mempool.contains(&txid) {
+                continue;
+            }
+            let since = dropped_txs
+                .missing_since
+                .get(&txid)
+                .copied()
+                .unwrap_or(now);
+            if now.duration_since(since) >= dropped_txs.grace_period {
+                expired.push((txid, ctx.tx_node.tx.output.len() as u32));
+            } else {
+                missing.insert(txid, since);
+            }
+        }
+        // Forget transactions that reappeared, confirmed or are being removed
+        dropped_txs.missing_since = missing;
+
+        // Transactions mined after the mempool was fetched are missing from it too
+        let mut dropped = HashSet::new();
+        for (txid, outputs) in expired {
+            if !Self::is_mined(source, txid, outputs)? {
+                dropped.insert(txid);
+            }
+        }
+
+        let removed = wallet.remove_unconfirmed_txs(&dropped)?;
+        if !removed.is_empty() {
+            info!(
+                "Removed {} dropped transaction(s) from wallet `{}`",
+                removed.len(),
+                wallet.name()
+            );
+        }
+        Ok(ReconcileReport { checked, removed })
+    }
+
+    /// Whether any of the transaction's `outputs` are unspent in the node's chain
+    /// state, meaning it was mined even if the wallet hasn't synced that block yet
+    fn is_mined(source: &BitcoinBlockSource, txid: Txid, outputs: u32) -> anyhow::Result<bool> {
+        for vout in 0..outputs {
+            let request = source.rpc.get_tx_out(&OutPoint { txid, vout }, false);
+            let txout: Option<serde_json::Value> =
+                source.rpc.send_json_blocking(&source.client, &request)?;
+            if txout.is_some() {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Removes every unconfirmed transaction from the wallet, freeing their inputs.
+    /// Transactions may still confirm if they're in the node's mempool, in which
+    /// case the wallet picks them up again when syncing the block.
+    fn abandon_all_pending(
+        source: &BitcoinBlockSource,
+        wallet: &mut SpacesWallet,
+    ) -> anyhow::Result<AbandonReport> {
+        let pending: Vec<_> = wallet
+            .spaces
+            .transactions()
+            .filter(|ctx| !ctx.chain_position.is_confirmed())
+            .map(|ctx| (ctx.tx_node.txid, ctx.tx_node.tx.output.len() as u32))
+            .collect();
+
+        // The wallet may lag behind the node, skip anything already mined
+        let mut confirmed = Vec::new();
+        let mut abandon = HashSet::new();
+        for (txid, outputs) in pending {
+            if Self::is_mined(source, txid, outputs)? {
+                confirmed.push(txid);
+            } else {
+                abandon.insert(txid);
+            }
+        }
+
+        let abandoned = wallet.remove_unconfirmed_txs(&abandon)?;
+        if !abandoned.is_empty() {
+            info!(
+                "Abandoned {} pending transaction(s) in wallet `{}`",
+                abandoned.len(),
+                wallet.name()
+            );
+        }
+        Ok(AbandonReport {
+            abandoned,
+            confirmed,
+        })
+    }
+
+    /// Removes the unconfirmed open for `name` from the wallet and spends its
+    /// commitment back to the wallet together with the open's own inputs,
+    /// replacing the open if it's still in the node's mempool.
+    fn abandon_open(
+        source: &BitcoinBlockSource,
+        fee_estimation: &FeeEstimation,
+        wallet: &mut SpacesWallet,
+        name: &str,
+        reclaim_fee_rate: Option<FeeRate>,
+    ) -> anyhow::Result<AbandonOpenReport> {
+        let label = SLabel::from_str(name)?;
+        let prefix = SpaceScript::nop_script(SpaceScript::create_open(label)).into_script();
+        let (txid, commitment) = wallet
+            .spaces
+            .transactions()
+            .filter(|ctx| !ctx.chain_position.is_confirmed())
+            .find_map(|ctx| {
+                ctx.tx_node
+                    .tx
+                    .input
+                    .iter()
+                    .find(|input| {
+                        input.witness.tapscript().is_some_and(|script| {
+                            script.as_bytes().starts_with(prefix.as_bytes())
+                        })
+                    })
+                    .map(|input| (ctx.tx_node.txid, input.previous_output))
+            })
+            .ok_or_else(|| anyhow!("no unconfirmed open for {} in this wallet", name))?;
+
+        let open = wallet.spaces.get_tx(txid).expect("wallet tx").tx_node.tx;
+
+        // The wallet may lag behind the node
+        if Self::is_mined(source, txid, open.output.len() as u32)? {
+            return Err(anyhow!("open {} is already confirmed", txid));
+        }
+
+        let entry_req = source.rpc.get_mempool_entry(txid);
+        let in_mempool = source
+            .rpc
+            .send_json_blocking::<serde_json::Value>(&source.client, &entry_req)
+            .is_ok();
+
+        let fee_rate = match reclaim_fee_rate {
+            Some(fee_rate) => fee_rate,
+            None => Self::estimate_fee_rate(source, fee_estimation, None, None)?,
+        };
+        let commitment_txout = wallet
+            .spaces
+            .get_tx(commitment.txid)
+            .and_then(|commit| {
+                commit
+                    .tx_node
+                    .tx
+                    .output
+                    .get(commitment.vout as usize)
+                    .cloned()
+            })
+            .ok_or_else(|| anyhow!("commitment {} not found in wallet", commitment))?;
+
+        // Spending the open's wallet inputs too conflicts with the open and pays for
+        // its replacement, the commitment alone is only worth a couple of dust outputs
+        let mut inputs = Vec::new();
+        for input in open.input.iter() {
+            if let Some(txout) = wallet.spaces.tx_graph().get_txout(input.previous_output) {
+                if wallet.spaces.is_mine(txout.script_pubkey.as_script()) {
+                    inputs.push((input.previous_output, txout.clone()));
+                }
+            }
+        }
+
+        let replaced_fee = match in_mempool {
+            true => wallet.spaces.calculate_fee(&open).ok(),
+            false => None,
+        };
+        let tx = wallet.reclaim_commitment(
+            commitment,
+            commitment_txout,
+            &inputs,
+            fee_rate,
+            replaced_fee,
+        )?;
+        Self::log_broadcast(source, wallet, &tx, &[])?;
+        let confirmation = source
+            .rpc
+            .broadcast_tx(&source.client, &tx)
+            .map_err(|e| anyhow!("could not broadcast reclaim transaction: {}", e))?;
+
+        let abandoned = wallet.remove_unconfirmed_txs(&HashSet::from([txid]))?;
+        info!(
+            "Abandoned open {} for {} in wallet `{}`",
+            txid,
+            name,
+            wallet.name()
+        );
+
+        let reclaim = tx.compute_txid();
+        wallet.insert_tx(tx, confirmation)?;
+        wallet.commit()?;
+        Ok(AbandonOpenReport {
+            txid,
+            commitment,
+            abandoned,
+            reclaim,
+        })
+    }
+
+    /// Finds coins that cost more in fees to spend at the given fee rate than
+    /// they're worth. Space outputs and bidouts are not considered.
+    fn list_dust(
+        wallet: &mut SpacesWallet,
+        state: &mut LiveSnapshot,
+        fee_rate: FeeRate,
+    ) -> anyhow::Result<DustReport> {
+        let input_cost = fee_rate
+            .fee_wu(bitcoin::Weight::from_wu(TAPROOT_KEY_SPEND_INPUT_WEIGHT))
+            .ok_or_else(|| anyhow!("fee rate is too high"))?;
+
+        let selection = Self::get_spaces_coin_selection(wallet, state, false)?;
+        let mut reserved = BTreeSet::new();
+        for bidout in wallet.list_bidouts(&selection)? {
+            reserved.insert(bidout.spend.outpoint);
+            reserved.insert(bidout.auction.outpoint);
+        }
+
+        let outputs: Vec<_> = Self::list_unspent(wallet, state)?
+            .into_iter()
+            .filter(|out| !out.is_spaceout && out.space.is_none())
+            .filter(|out| !reserved.contains(&out.output.outpoint))
+            .filter(|out| out.output.txout.value <= input_cost)
+            .map(|out| DustOutput {
+                outpoint: out.output.outpoint,
+                value: out.output.txout.value,
+            })
+            .collect();
+        let total = outputs.iter().map(|out| out.value).sum();
+
+        Ok(DustReport {
+            fee_rate,
+            input_cost,
+            outputs,
+            total,
+        })
+    }
+
+    /// Runs coin selection for sending `amount` without signing or broadcasting
+    /// to find out which coins would be spent and whether change is created
+    fn estimate_selection(
+        wallet: &mut SpacesWallet,
+        state: &mut LiveSnapshot,
+        amount: Amount,
+        fee_rate: FeeRate,
+    ) -> anyhow::Result<SelectionEstimate> {
+        check_fee_rate(fee_rate)?;
+        let coin_selection = Self::get_spaces_coin_selection(wallet, state, false)?;
+        // Any script of the same type works as a placeholder recipient
+        let recipient = wallet
+            .spaces
+            .peek_address(KeychainKind::External, 0)
+            .script_pubkey();
+
+        let mut builder = wallet.spaces.build_tx().coin_selection(coin_selection);
+        builder
+            .ordering(TxOrdering::Untouched)
+            .fee_rate(fee_rate)
+            .add_recipient(recipient, amount);
+        let psbt = builder.finish()?;
+        // Release the change address reserved for this transaction
+        wallet.spaces.cancel_tx(&psbt.unsigned_tx);
+
+        let inputs = psbt
+            .unsigned_tx
+            .input
+            .iter()
+            .map(|input| input.previous_output)
+            .collect();
+        let input_total = psbt
+            .inputs
+            .iter()
+            .filter_map(|input| input.witness_utxo.as_ref())
+            .map(|utxo| utxo.value)
+            .sum();
+        let change = psbt
+            .unsigned_tx
+            .output
+            .iter()
+            .skip(1)
+            .map(|out| out.value)
+            .reduce(|a, b| a + b);
+
+        Ok(SelectionEstimate {
+            inputs,
+            input_total,
+            fee: psbt.fee()?,
+            change,
+        })
+    }
+
+    /// Runs the checks [Self::batch_tx] makes on a request without building it,
+    /// collecting every reason it would currently be rejected
+    fn check_operation(
+        network: ExtendedNetwork,
+        source: &BitcoinBlockSource,
+        wallet: &mut SpacesWallet,
+        state: &mut LiveSnapshot,
+        request: RpcWalletRequest,
+    ) -> anyhow::Result<OperationCheck> {
+        let mut reasons = Self::check_request(network, wallet, state, &request, false, None)?;
+
+        let needed = match &request {
+            RpcWalletRequest::Open(params) => Amount::from_sat(params.amount),
+            RpcWalletRequest::Bid(params) => Amount::from_sat(params.amount),
+            RpcWalletRequest::SendCoins(params) => Self::send_coins_target(params)
+                .map(|(_, amount, _)| amount)
+                .unwrap_or(Amount::ZERO),
+            _ => Amount::ZERO,
+        };
+
+        // Spaces the request spends, checked for conflicting spends in the mempool
+        let spending: Vec<&String> = match &request {
+            RpcWalletRequest::Open(_) | RpcWalletRequest::SendCoins(_) => vec![],
+            RpcWalletRequest::Bid(params) => vec![&params.name],
+            RpcWalletRequest::Register(params) => vec![&params.name],
+            RpcWalletRequest::Transfer(params) => params.spaces.iter().collect(),
+            RpcWalletRequest::Execute(params) => params.context.iter().collect(),
+        };
+        let mut seen = BTreeSet::new();
+        for space in spending {
+            let full = match Self::check_space(state, space)? {
+                Err(_) => continue,
+                Ok(full) => full,
+            };
+            // Outbidding our own pending bid replaces it
+            if matches!(request, RpcWalletRequest::Bid(_))
+                && Self::replaces_unconfirmed_bid(wallet, &full)
+            {
+                continue;
+            }
+            if !seen.insert(full.outpoint()) {
+                continue;
+            }
+            let spends_req = source.rpc.get_tx_spending_prevout(&full.outpoint());
+            let spends: Vec<serde_json::Value> =
+                source.rpc.send_json_blocking(&source.client, &spends_req)?;
+            if let Some(txid) = spends
+                .first()
+                .and_then(|spend| spend.get("spendingtxid"))
+                .and_then(|txid| txid.as_str())
+            {
+                reasons.push(format!(
+                    "`{}` is already being spent by {} in the mempool",
+                    space, txid
+                ));
+            }
+        }
+
+        if needed > Amount::ZERO {
+            let balance = Self::get_balance(state, wallet)?.balance;
+            if needed > balance {
+                reasons.push(format!(
+                    "insufficient funds: needs {} sats before fees but only {} sats are spendable",
+                    needed.to_sat(),
+                    balance.to_sat()
+                ));
+            }
+        }
+
+        Ok(OperationCheck {
+            allowed: reasons.is_empty(),
+            reasons,
+        })
+    }
+
+    /// Validates a single request against the wallet and chain state, returning every
+    /// reason it can't be carried out. With `force`, checks that may be deliberately
+    /// overridden are skipped. Used by both [Self::batch_tx] and [Self::check_operation]
+    /// so they agree on what's rejected and why.
+    fn check_request(
+        network: ExtendedNetwork,
+        wallet: &SpacesWallet,
+        state: &mut LiveSnapshot,
+        request: &RpcWalletRequest,
+        force: bool,
+        pending: Option<&BitcoinBlockSource>,
+    ) -> anyhow::Result<Vec<String>> {
+        let tip_height = wallet.spaces.local_chain().tip().height();
+        let mut reasons = Vec::new();
+
+        match request {
+            RpcWalletRequest::Open(params) => match SLabel::from_str(&params.name) {
+                Err(_) => reasons.push(format!("'{}' is not a valid space name", params.name)),
+                Ok(_) if force => {}
+                Ok(name) => match Self::find_pending_open(wallet, state, &name)? {
+                    Some(pending) if pending.output[0].value != Amount::from_sat(params.amount) => {
+                        reasons.push(
+                            "a pending open from this wallet has a different amount; \
+                             wait for it to confirm and bid instead"
+                                .to_string(),
+                        )
+                    }
+                    Some(_) => {}
+                    None => {
+                        let spacehash = SpaceKey::from(Sha256::hash(name.as_ref()));
+                        if state.get_space_info(&spacehash)?.is_some() {
+                            reasons.push("space already exists".to_string());
+                        }
+                    }
+                },
+            },
+            RpcWalletRequest::Bid(params) => match Self::check_space(state, &params.name)? {
+                Err(reason) => reasons.push(reason),
+                Ok(_) if force => {}
+                Ok(spaceout) => match spaceout.spaceout.space.as_ref().map(|s| &s.covenant) {
+                    Some(Covenant::Bid {
+                        total_burned,
+                        claim_height,
+                        ..
+                    }) => {
+                        if claim_height.is_some_and(|height| height <= tip_height) {
+                            reasons.push("auction has closed".to_string());
+                        }
+                        let min_bid = *total_burned + Amount::from_sat(1);
+                        if Amount::from_sat(params.amount) < min_bid {
+                            reasons.push(format!("minimum bid is {} sats", min_bid.to_sat()));
+                        }
+                    }
+                    _ => reasons.push("space is not in auction".to_string()),
+                },
+            },
+            RpcWalletRequest::Register(params) => match Self::check_space(state, &params.name)? {
+                Err(reason) => reasons.push(reason),
+                Ok(spaceout) => {
+                    if !wallet.spaces.is_mine(&spaceout.spaceout.script_pubkey) {
+                        reasons.push("you don't own this space".to_string());
+                    }
+                    if !force {
+                        match spaceout
+                            .spaceout
+                            .space
+                            .as_ref()
+                            .and_then(|s| s.claim_height())
+                        {
+                            None => reasons.push(
+                                "space may be in pre-auctions or already registered".to_string(),
+                            ),
+                            Some(claim_height) if claim_height > tip_height => reasons.push(
+                                format!("cannot register until claim height {}", claim_height),
+                            ),
+                            Some(_) => {}
+                        }
+                    }
+                    if let Some(to) = params.to.as_ref() {
+                        match SpaceAddress::from_str(to) {
+                            Err(_) => {
+                                reasons.push("recipient must be a valid space address".to_string())
+                            }
+                            Ok(address)
+                                if !address.is_valid_for_network(network.fallback_network()) =>
+                            {
+                                reasons.push(format!(
+                                    "space address {} is not valid for network {}",
+                                    to, network
+                                ))
+                            }
+                            Ok(_) => {}
+                        }
+                    }
+                }
+            },
+            RpcWalletRequest::Transfer(params) => {
+                if let Err(reason) =
+                    Self::check_recipient(network, state, &params.to, true, pending)
+                {
+                    reasons.push(reason);
+                }
+                for space in params.spaces.iter() {
+                    match Self::check_space(state, space)? {
+                        Err(reason) => reasons.push(format!("{}: {}", space, reason)),
+                        Ok(full)
+                            if !full.spaceout.space.as_ref().is_some_and(|s| s.is_owned())
+                                || !wallet
+                                    .spaces
+                                    .is_mine(full.spaceout.script_pubkey.as_script()) =>
+                        {
+                            reasons.push(format!("you don't own `{}`", space))
+                        }
+                        Ok(_) => {}
+                    }
+                }
+            }
+            RpcWalletRequest::SendCoins(params) => {
+                match Self::send_coins_target(params) {
+                    Err(e) => reasons.push(e.to_string()),
+                    Ok((to, _, _)) => {
+                        if let Err(reason) =
+                            Self::check_recipient(network, state, &to, false, pending)
+                        {
+                            reasons.push(reason);
+                        }
+                    }
+                }
+                let mut funding = BTreeSet::new();
+                for outpoint in params.from.iter() {
+                    if !funding.insert(*outpoint) {
+                        reasons.push(format!("coin {} is listed more than once", outpoint));
+                    } else if let Err(reason) = Self::check_coin_input(wallet, state, *outpoint)? {
+                        reasons.push(reason);
+                    }
+                }
+            }
+            RpcWalletRequest::Execute(params) => {
+                let mut seen = BTreeSet::new();
+                for space in params.context.iter() {
+                    match Self::check_space(state, space)? {
+                        Err(reason) => reasons.push(format!("{}: {}", space, reason)),
+                        Ok(full) if !seen.insert(full.outpoint()) => {
+                            reasons.push(format!("`{}` is listed more than once in context", space))
+                        }
+                        Ok(full) if !wallet.spaces.is_mine(&full.spaceout.script_pubkey) => {
+                            reasons.push(format!("you don't own `{}`", space))
+                        }
+                        Ok(_) => {}
+                    }
+                }
+                let mut funding = BTreeSet::new();
+                for outpoint in params.inputs.iter() {
+                    if !funding.insert(*outpoint) {
+                        reasons.push(format!("input {} is listed more than once", outpoint));
+                    } else if wallet.spaces.get_utxo(*outpoint).is_none() {
+                        reasons.push(format!(
+                            "input {} is not an unspent output of this wallet",
+                            outpoint
+                        ));
+                    } else if state.get_spaceout(outpoint)?.is_some() {
+                        reasons.push(format!(
+                            "input {} carries a space and cannot be used for funding",
+                            outpoint
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(reasons)
+    }
+
+    /// Names a request in errors about it
+    fn describe_request(request: &RpcWalletRequest) -> String {
+        match request {
+            RpcWalletRequest::Open(params) => format!("open '{}'", params.name),
+            RpcWalletRequest::Bid(params) => format!("bid '{}'", params.name),
+            RpcWalletRequest::Register(params) => format!("register '{}'", params.name),
+            RpcWalletRequest::Transfer(_) => "sendspaces".to_string(),
+            RpcWalletRequest::SendCoins(_) => "sendcoins".to_string(),
+            RpcWalletRequest::Execute(_) => "execute".to_string(),
+        }
+    }
+
+    fn check_space(
+        state: &mut LiveSnapshot,
+        name: &str,
+    ) -> anyhow::Result<Result<FullSpaceOut, String>> {
+        let label = match SLabel::from_str(name) {
+            Err(_) => return Ok(Err("not a valid space name".to_string())),
+            Ok(label) => label,
+        };
+        let spacehash = SpaceKey::from(Sha256::hash(label.as_ref()));
+        Ok(state
+            .get_space_info(&spacehash)?
+            .ok_or_else(|| "space does not exist".to_string()))
+    }
+
+    /// Checks `outpoint` is a confirmed coin of this wallet that can fund a send
+    fn check_coin_input(
+        wallet: &SpacesWallet,
+        state: &mut LiveSnapshot,
+        outpoint: OutPoint,
+    ) -> anyhow::Result<Result<(), String>> {
+        let utxo = match wallet.spaces.get_utxo(outpoint) {
+            None => {
+                return Ok(Err(format!(
+                    "coin {} is not an unspent output of this wallet",
+                    outpoint
+                )))
+            }
+            Some(utxo) => utxo,
+        };
+        if !utxo.confirmation_time.is_confirmed() {
+            return Ok(Err(format!("coin {} is not confirmed", outpoint)));
+        }
+        if state.get_spaceout(&outpoint)?.is_some() {
+            return Ok(Err(format!(
+                "coin {} carries a space and cannot be used for funding",
+                outpoint
+            )));
+        }
+        Ok(Ok(()))
+    }
+
+    fn check_recipient(
+        network: ExtendedNetwork,
+        state: &mut LiveSnapshot,
+        to: &str,
+        require_space_address: bool,
+        pending: Option<&BitcoinBlockSource>,
+    ) -> Result<(), String> {
+        match Self::resolve(network, state, to, require_space_address, pending) {
+            Err(e) => Err(e.to_string()),
+            Ok(None) => Err(format!("could not resolve '{}'", to)),
+            Ok(Some(_)) => Ok(()),
+        }
+    }
+
+    fn get_space_info(
+        wallet: &SpacesWallet,
+        state: &mut LiveSnapshot,
+        space: &str,
+    ) -> anyhow::Result<WalletSpaceInfo> {
+        let name =
+            SLabel::from_str(space).map_err(|_| anyhow!("'{}' is not a valid space name", space))?;
+        let spacehash = SpaceKey::from(Sha256::hash(name.as_ref()));
+        let space = state.get_space_info(&spacehash)?;
+        let is_mine = space
+            .as_ref()
+            .is_some_and(|full| wallet.spaces.is_mine(&full.spaceout.script_pubkey));
+        Ok(WalletSpaceInfo { space, is_mine })
+    }
 
-        let mut wallet_tip = {
-            let tip = wallet.spaces.local_chain().tip();
-            ChainAnchor {
-                height: tip.height(),
-                hash: tip.hash(),
+    /// Signs a message with the key of a space owned by this wallet, proving
+    /// control of it without spending it
+    fn sign_message(
+        wallet: &mut SpacesWallet,
+        state: &mut LiveSnapshot,
+        space: String,
+        message: String,
+    ) -> anyhow::Result<SignedMessage> {
+        let name = SLabel::from_str(&space)?;
+        let spacehash = SpaceKey::from(Sha256::hash(name.as_ref()));
+        let full = state
+            .get_space_info(&spacehash)?
+            .ok_or_else(|| anyhow!("sign message: space '{}' does not exist", space))?;
+        if !wallet.spaces.is_mine(&full.spaceout.script_pubkey) {
+            return Err(anyhow!("sign message: you don't own `{}`", space));
+        }
+
+        let signature = wallet.sign_message(full.outpoint(), message.as_bytes())?;
+        Ok(SignedMessage {
+            space,
+            message,
+            script_pubkey: full.spaceout.script_pubkey,
+            signature,
+        })
+    }
+
+    /// Estimates the size of the transactions a request needs from the shape
+    /// the builder gives them, assuming the largest spendable coins fund it
+    fn estimate_vsize(
+        wallet: &mut SpacesWallet,
+        state: &mut LiveSnapshot,
+        request: RpcWalletRequest,
+    ) -> anyhow::Result<VsizeEstimate> {
+        let funding_input = (32 + 4 + 4 + 1) * 4
+            + wallet
+                .spaces
+                .public_descriptor(KeychainKind::External)
+                .max_weight_to_satisfy()?
+                .to_wu();
+        let output = |script_len: u64| (8 + 1 + script_len) * 4;
+        let p2tr_output = output(34);
+        // Compressed bid psbt pushed after OP_RETURN
+        let carrier_output = output(2 + 65);
+
+        let coin_selection = Self::get_spaces_coin_selection(wallet, state, false)?;
+        let mut coins: Vec<Amount> = wallet
+            .spaces
+            .list_unspent()
+            .filter(|utxo| {
+                utxo.txout.value > SpacesAwareCoinSelection::DUST_THRESHOLD
+                    && !coin_selection
+                        .exclude_outputs
+                        .iter()
+                        .any(|o| o.outpoint == utxo.outpoint)
+            })
+            .map(|utxo| utxo.txout.value)
+            .collect();
+        coins.sort_by(|a, b| b.cmp(a));
+        let funding_inputs = |amount: Amount| -> anyhow::Result<usize> {
+            let mut total = Amount::ZERO;
+            for (n, value) in coins.iter().enumerate() {
+                total += *value;
+                if total >= amount {
+                    return Ok(n + 1);
+                }
             }
+            Err(anyhow!(
+                "not enough spendable coins to fund {} sat",
+                amount.to_sat()
+            ))
         };
 
-        fetcher.start(wallet_tip);
+        // Each transaction as its input and output weights
+        let mut txs: Vec<(Vec<u64>, Vec<u64>)> = Vec::new();
+        let funding = match request {
+            RpcWalletRequest::SendCoins(params) => {
+                let (_, amount, _) = Self::send_coins_target(&params)?;
+                let n = funding_inputs(amount)?;
+                txs.push((vec![funding_input; n], vec![p2tr_output; 2]));
+                n
+            }
+            RpcWalletRequest::Transfer(TransferSpacesParams { spaces, .. }) => {
+                // Space inputs are paired with outputs after a padding output
+                let mut inputs = vec![TAPROOT_KEY_SPEND_INPUT_WEIGHT; spaces.len()];
+                inputs.push(funding_input);
+                txs.push((inputs, vec![p2tr_output; spaces.len() + 2]));
+                1
+            }
+            RpcWalletRequest::Register(_) => {
+                txs.push((
+                    vec![TAPROOT_KEY_SPEND_INPUT_WEIGHT, funding_input],
+                    vec![p2tr_output; 3],
+                ));
+                1
+            }
+            RpcWalletRequest::Bid(params) => {
+                let bidouts = wallet.list_bidouts(&coin_selection)?;
+                if bidouts.is_empty() {
+                    txs.push((vec![funding_input], vec![p2tr_output; 3]));
+                }
+                let n = funding_inputs(Amount::from_sat(params.amount))?;
+                // Bid outpoint spend and the refund of the previous bid
+                let mut inputs = vec![TAPROOT_KEY_SPEND_INPUT_WEIGHT; 2];
+                inputs.extend(vec![funding_input; n]);
+                txs.push((inputs, vec![carrier_output, p2tr_output, p2tr_output]));
+                n
+            }
+            RpcWalletRequest::Open(params) => {
+                let name = SLabel::from_str(&params.name)?;
+                let bidouts = wallet.list_bidouts(&coin_selection)?;
+                // Commitment to the open script, with a bid outpoint pair if none are available
+                let commit_outputs = if bidouts.is_empty() { 4 } else { 2 };
+                txs.push((vec![funding_input], vec![p2tr_output; commit_outputs]));
 
-        loop {
-            if shutdown.try_recv().is_ok() {
-                info!("Shutting down wallet sync");
-                break;
+                // Script, 32-byte key push and OP_CHECKSIG revealed with a single leaf
+                // control block and a signature, see `SpaceScriptSigningInfo`
+                let script_len = SpaceScript::nop_script(SpaceScript::create_open(name))
+                    .into_script()
+                    .len() as u64
+                    + 33
+                    + 1;
+                let reveal_input = (32 + 4 + 4 + 1) * 4 + 1 + 33 + 1 + script_len + 1 + 65;
+                let n = funding_inputs(Amount::from_sat(params.amount))?;
+                let mut inputs = vec![TAPROOT_KEY_SPEND_INPUT_WEIGHT, reveal_input];
+                inputs.extend(vec![funding_input; n]);
+                txs.push((inputs, vec![carrier_output, p2tr_output]));
+                n + 1
             }
-            if let Ok(command) = commands.try_recv() {
-                Self::wallet_handle_commands(network, &source, &mut state, &mut wallet, command)?;
+            RpcWalletRequest::Execute(_) => {
+                return Err(anyhow!("estimating script executions is not supported"))
             }
-            if let Ok(event) = receiver.try_recv() {
-                match event {
-                    BlockEvent::Block(id, block) => {
-                        wallet.apply_block_connected_to(
-                            id.height,
-                            &block,
-                            wallet::bdk_wallet::chain::BlockId {
-                                height: wallet_tip.height,
-                                hash: wallet_tip.hash,
-                            },
-                        )?;
+        };
 
-                        // Temporary fix for https://github.com/bitcoindevkit/bdk/issues/1740
-                        for tx in block.txdata {
-                            for input in tx.input.iter() {
-                                if wallet.watch_bid_spends.contains(&input.previous_output) {
-                                    if wallet.spaces.get_tx(tx.compute_txid()).is_some() {
-                                        break;
-                                    }
-                                    wallet.insert_tx(tx, ConfirmationTime::Confirmed {
-                                        height: id.height,
-                                        time: block.header.time as _,
-                                    })?;
-                                    break;
-                                }
-                            }
-                        }
+        let vsizes: Vec<u64> = txs
+            .iter()
+            .map(|(inputs, outputs)| {
+                // Version, lock time, input and output counts plus the segwit marker and flag
+                let weight = (4 + 4 + 1 + 1) * 4
+                    + 2
+                    + inputs.iter().sum::<u64>()
+                    + outputs.iter().sum::<u64>();
+                bitcoin::Weight::from_wu(weight).to_vbytes_ceil()
+            })
+            .collect();
+        Ok(VsizeEstimate {
+            vsize: vsizes.iter().sum(),
+            vsizes,
+            funding_inputs: funding,
+        })
+    }
 
-                        wallet_tip.height = id.height;
-                        wallet_tip.hash = id.hash;
+    /// Sweeps every spendable coin to a single recipient without signing or
+    /// broadcasting to find the most that can be sent at the given fee rate
+    fn get_max_sendable(
+        network: ExtendedNetwork,
+        wallet: &mut SpacesWallet,
+        state: &mut LiveSnapshot,
+        fee_rate: FeeRate,
+        to: &str,
+    ) -> anyhow::Result<MaxSendable> {
+        check_fee_rate(fee_rate)?;
+        let recipient = match Self::resolve(network, state, to, false, None)? {
+            None => return Err(anyhow!("could not resolve '{}'", to)),
+            Some(r) => r,
+        };
+        let input_cost = fee_rate
+            .fee_wu(bitcoin::Weight::from_wu(TAPROOT_KEY_SPEND_INPUT_WEIGHT))
+            .ok_or_else(|| anyhow!("fee rate is too high"))?;
 
-                        if id.height % 12 == 0 {
-                            wallet.commit()?;
-                        }
-                    }
-                    BlockEvent::Error(e) if matches!(e, BlockFetchError::BlockMismatch) => {
-                        let mut checkpoint_in_chain = None;
-                        let best_chain = source.get_best_chain()?;
-                        for cp in wallet.spaces.local_chain().iter_checkpoints() {
-                            if cp.height() > best_chain.height {
-                                continue;
-                            }
+        // Same candidates coin selection would consider: no space outputs
+        // and nothing below the dust threshold, which covers bidouts
+        let coin_selection = Self::get_spaces_coin_selection(wallet, state, false)?;
+        let mut inputs = Vec::new();
+        let mut uneconomical = Amount::ZERO;
+        for utxo in wallet.spaces.list_unspent() {
+            let value = utxo.txout.value;
+            if value <= SpacesAwareCoinSelection::DUST_THRESHOLD
+                || coin_selection
+                    .exclude_outputs
+                    .iter()
+                    .any(|o| o.outpoint == utxo.outpoint)
+            {
+                continue;
+            }
+            if value <= input_cost {
+                uneconomical += value;
+                continue;
+            }
+            inputs.push(utxo.outpoint);
+        }
+        if inputs.is_empty() {
+            return Err(anyhow!("no spendable coins"));
+        }
 
-                            let hash = source.get_block_hash(cp.height())?;
-                            if cp.height() != 0 && hash == cp.hash() {
-                                checkpoint_in_chain = Some(cp);
-                                break;
-                            }
-                        }
+        let mut builder = wallet.spaces.build_tx().coin_selection(coin_selection);
+        builder
+            .ordering(TxOrdering::Untouched)
+            .fee_rate(fee_rate)
+            .add_utxos(&inputs)?
+            .manually_selected_only()
+            .drain_to(recipient.script_pubkey());
+        let psbt = builder
+            .finish()
+            .map_err(|e| anyhow!("could not sweep spendable coins: {}", e))?;
 
-                        let restore_point = match checkpoint_in_chain {
-                            None => {
-                                // We couldn't find a restore point
-                                warn!("Rebuilding wallet `{}`", wallet.config.name);
-                                let birthday = wallet.config.start_block;
-                                let hash = source.get_block_hash(birthday)?;
-                                let cp = CheckPoint::new(BlockId {
-                                    height: birthday,
-                                    hash,
-                                });
-                                wallet = wallet.rebuild()?;
-                                wallet.spaces.insert_checkpoint(cp.block_id())?;
-                                cp
-                            }
-                            Some(cp) => cp,
-                        };
+        Ok(MaxSendable {
+            fee_rate,
+            amount: psbt.unsigned_tx.output.iter().map(|out| out.value).sum(),
+            fee: psbt.fee()?,
+            inputs,
+            uneconomical,
+        })
+    }
 
-                        wallet_tip.height = restore_point.block_id().height;
-                        wallet_tip.hash = restore_point.block_id().hash;
+    /// Reports how many blocks remain until a space in auction can be registered
+    fn get_claim_status(
+        wallet: &mut SpacesWallet,
+        state: &mut LiveSnapshot,
+        space: &str,
+    ) -> anyhow::Result<ClaimStatus> {
+        let name = SLabel::from_str(space)?;
+        let spacehash = SpaceKey::from(Sha256::hash(name.as_ref()));
+        let full = match state.get_space_info(&spacehash)? {
+            None => return Err(anyhow!("'{}': space does not exist", space)),
+            Some(full) => full,
+        };
+        let claim_height = match full.spaceout.space.as_ref().and_then(|s| s.claim_height()) {
+            None => {
+                return Err(anyhow!(
+                    "'{}': space may be in pre-auctions or already registered",
+                    space
+                ))
+            }
+            Some(height) => height,
+        };
+        let tip = wallet.spaces.local_chain().tip().height();
 
-                        info!(
-                            "Restore wallet `{}` to block={} height={}",
-                            wallet.name(),
-                            wallet_tip.hash,
-                            wallet_tip.height
-                        );
-                        fetcher.start(wallet_tip);
-                    }
-                    BlockEvent::Error(e) => return Err(e.into()),
-                }
+        Ok(ClaimStatus {
+            space: name,
+            outpoint: full.outpoint(),
+            winning: wallet.spaces.is_mine(&full.spaceout.script_pubkey),
+            claim_height,
+            tip,
+            blocks_remaining: claim_height.saturating_sub(tip),
+        })
+    }
 
+    /// Groups owned spaces by their stage at the wallet tip. With a filter,
+    /// only the matching group is filled.
+    fn list_spaces_by_state(
+        wallet: &mut SpacesWallet,
+        state: &mut LiveSnapshot,
+        filter: Option<SpaceStateFilter>,
+    ) -> anyhow::Result<SpacesByState> {
+        let tip = wallet.spaces.local_chain().tip().height();
+        let mut grouped = SpacesByState::default();
+        for output in Self::list_unspent(wallet, state)? {
+            let stage = match output.space.as_ref() {
+                None => continue,
+                Some(space) if space.is_owned() => SpaceStateFilter::Registered,
+                Some(space) => match (&space.covenant, space.claim_height()) {
+                    (Covenant::Bid { .. }, None) => SpaceStateFilter::PreAuction,
+                    (Covenant::Bid { .. }, Some(claim_height)) if claim_height > tip => {
+                        SpaceStateFilter::InAuction
+                    }
+                    (Covenant::Bid { .. }, Some(_)) => SpaceStateFilter::Claimable,
+                    _ => continue,
+                },
+            };
+            if filter.is_some_and(|filter| filter != stage) {
                 continue;
             }
-
-            // TODO: update wallet mempool
-            std::thread::sleep(Duration::from_millis(10));
+            match stage {
+                SpaceStateFilter::PreAuction => grouped.pre_auction.push(output),
+                SpaceStateFilter::InAuction => grouped.in_auction.push(output),
+                SpaceStateFilter::Claimable => grouped.claimable.push(output),
+                SpaceStateFilter::Registered => grouped.registered.push(output),
+            }
         }
-
-        fetcher.stop();
-        Ok(())
+        Ok(grouped)
     }
 
-    fn get_spaces_coin_selection(
+    /// Describes the script of the output an owned space is currently held at
+    fn get_space_script(
         wallet: &mut SpacesWallet,
         state: &mut LiveSnapshot,
-        confirmed_only: bool,
-    ) -> anyhow::Result<SpacesAwareCoinSelection> {
-        // Filters out all "spaceouts" with value higher than DUST threshold && all spaceouts representing
-        // spaces.
-        //
-        // Note: This exclusion only applies to confirmed spaceouts; unconfirmed ones are not excluded
-        // as we cannot easily detect them for now.
-        // In practice, this should be fine since Spaces coin selection skips dust by default,
-        // so explicitly excluding spaceouts may be redundant.
-        let excluded = Self::list_unspent(wallet, state)?
-            .into_iter()
-            .filter(|out| out.space.is_some() ||
-                (out.is_spaceout && out.output.txout.value <= SpacesAwareCoinSelection::DUST_THRESHOLD)
-            )
-            .map(|out| SelectionOutput {
-                outpoint: out.output.outpoint,
-                is_space: out.space.is_some(),
-                is_spaceout: out.is_spaceout,
-            })
-            .collect::<Vec<_>>();
+        space: &str,
+    ) -> anyhow::Result<SpaceScriptInfo> {
+        let name = SLabel::from_str(space)?;
+        let spacehash = SpaceKey::from(Sha256::hash(name.as_ref()));
+        let full = match state.get_space_info(&spacehash)? {
+            None => return Err(anyhow!("'{}': space does not exist", space)),
+            Some(full) => full,
+        };
+        let script = full.spaceout.script_pubkey.as_script();
+        let (keychain, derivation_index) = match wallet.spaces.derivation_of_spk(script) {
+            None => return Err(anyhow!("'{}': space is not owned by this wallet", space)),
+            Some(derivation) => derivation,
+        };
 
-        Ok(SpacesAwareCoinSelection::new(excluded, confirmed_only))
+        let script_type = if script.is_p2tr() {
+            "p2tr"
+        } else if script.is_p2wpkh() {
+            "p2wpkh"
+        } else if script.is_p2wsh() {
+            "p2wsh"
+        } else if script.is_p2pkh() {
+            "p2pkh"
+        } else if script.is_p2sh() {
+            "p2sh"
+        } else {
+            "nonstandard"
+        };
+        let address = Address::from_script(script, wallet.config.network)?;
+        let space_address = script
+            .is_p2tr()
+            .then(|| SpaceAddress(address.clone()).to_string());
+
+        Ok(SpaceScriptInfo {
+            space: name,
+            outpoint: full.outpoint(),
+            value: full.spaceout.value,
+            script_pubkey: script.to_hex_string(),
+            asm: script.to_asm_string(),
+            script_type: script_type.to_string(),
+            address: address.to_string(),
+            space_address,
+            trackable: is_magic_amount(full.spaceout.value),
+            keychain,
+            derivation_index,
+        })
     }
 
-    fn list_transactions(
-        wallet: &mut SpacesWallet,
-        count: usize,
-        skip: usize,
-    ) -> anyhow::Result<Vec<TxInfo>> {
-        let mut transactions: Vec<_> = wallet.spaces.transactions().collect();
+    /// Returns the effective fee rate of the last `count` confirmed transactions
+    /// funded by this wallet
+    fn list_fee_rates(wallet: &mut SpacesWallet, count: usize) -> anyhow::Result<Vec<TxFeeRate>> {
+        let mut transactions: Vec<_> = wallet
+            .spaces
+            .transactions()
+            .filter(|ctx| ctx.chain_position.is_confirmed())
+            .collect();
         transactions.sort();
+        let tip_height = wallet.spaces.local_chain().tip().height();
 
         Ok(transactions
             .iter()
             .rev()
-            .skip(skip)
-            .take(count)
-            .map(|ctx| {
-                let tx = ctx.tx_node.tx.clone();
-                let txid = ctx.tx_node.txid.clone();
-                let confirmed = ctx.chain_position.is_confirmed();
-                let (sent, received) = wallet.spaces.sent_and_received(&tx);
-                let fee = wallet.spaces.calculate_fee(&tx).ok();
-                TxInfo {
-                    txid,
-                    confirmed,
-                    sent,
-                    received,
+            .filter_map(|ctx| {
+                let tx = ctx.tx_node.tx.as_ref();
+                // Only transactions whose inputs are all known to the wallet have a fee
+                let fee = wallet.spaces.calculate_fee(tx).ok()?;
+                let vsize = tx.vsize() as u64;
+                let confirmation = ConfirmationTime::from(ctx.chain_position.cloned());
+                Some(TxFeeRate {
+                    txid: ctx.tx_node.txid,
+                    depth: tx_depth(tip_height, confirmation),
                     fee,
-                }
+                    vsize,
+                    fee_rate: fee.to_sat() as f64 / vsize as f64,
+                })
             })
+            .take(count)
             .collect())
     }
 
+    /// Formats a page of the wallet history (oldest first) in the given format.
+    /// Pages are meant to be requested one after another so that large
+    /// histories can be streamed by the caller. The CSV header is only
+    /// included in the first page.
+    ///
+    /// With `as_of`, only transactions confirmed at or below that height are
+    /// included, so pages requested with the same height don't shift as
+    /// transactions arrive, confirm or get evicted mid-export. Without it the
+    /// current history including unconfirmed transactions is paged.
+    fn export_history(
+        wallet: &mut SpacesWallet,
+        store: &mut LiveSnapshot,
+        format: ExportFormat,
+        as_of: Option<u32>,
+        skip: usize,
+        count: usize,
+    ) -> anyhow::Result<String> {
+        let mut transactions: Vec<_> = wallet
+            .spaces
+            .transactions()
+            .filter(|ctx| match as_of {
+                None => true,
+                Some(as_of) => matches!(
+                    ConfirmationTime::from(ctx.chain_position.cloned()),
+                    ConfirmationTime::Confirmed { height, .. } if height <= as_of
+                ),
+            })
+            .collect();
+        transactions.sort();
+
+        let mut out = String::new();
+        for ctx in transactions.into_iter().skip(skip).take(count) {
+            let tx = ctx.tx_node.tx.as_ref();
+            let (sent, received) = wallet.spaces.sent_and_received(tx);
+            let (kind, space) = Self::classify_history_tx(wallet, store, tx, sent, received)?;
+            let confirmation = ConfirmationTime::from(ctx.chain_position.cloned());
+            let (timestamp, confirmation_height) = match confirmation {
+                ConfirmationTime::Confirmed { height, time } => (time, Some(height)),
+                ConfirmationTime::Unconfirmed { last_seen } => (last_seen, None),
+            };
+
+            let entry = HistoryEntry {
+                timestamp,
+                txid: ctx.tx_node.txid,
+                kind,
+                space: space.map(|s| s.to_string()),
+                amount: received.to_signed()? - sent.to_signed()?,
+                fee: wallet.spaces.calculate_fee(tx).ok(),
+                confirmation_height,
+            };
+
+            match format {
+                ExportFormat::Json => {
+                    out.push_str(&serde_json::to_string(&entry)?);
+                    out.push('\n');
+                }
+                ExportFormat::Csv => {
+                    if skip == 0 && out.is_empty() {
+                        out.push_str("timestamp,txid,type,space,amount,fee,confirmation_height\n");
+                    }
+                    writeln!(
+                        out,
+                        "{},{},{},{},{},{},{}",
+                        entry.timestamp,
+                        entry.txid,
+                        serde_json::to_value(entry.kind)?.as_str().unwrap_or_default(),
+                        entry.space.unwrap_or_default(),
+                        entry.amount.to_sat(),
+                        entry.fee.map(|f| f.to_sat().to_string()).unwrap_or_default(),
+                        entry
+                            .confirmation_height
+                            .map(|h| h.to_string())
+                            .unwrap_or_default()
+                    )?;
+                }
+            }
+        }
+
+        Ok(out)
+    }
+
+    /// Best effort classification of a wallet transaction. Space names are
+    /// recovered from open scripts or from outputs still holding a space so
+    /// older history may be reported as plain sends/receives.
+    fn classify_history_tx(
+        wallet: &SpacesWallet,
+        store: &mut LiveSnapshot,
+        tx: &Transaction,
+        sent: Amount,
+        received: Amount,
+    ) -> anyhow::Result<(HistoryEntryKind, Option<SLabel>)> {
+        for input in tx.input.iter() {
+            let script = match input.witness.tapscript() {
+                None => continue,
+                Some(script) => script,
+            };
+            if let Some(Ok(SpaceScript::Open(open))) =
+                SpaceScript::eval::<_, Sha256>(store, script)?
+            {
+                let name = match open {
+                    OpenHistory::NewSpace(name) => Some(name),
+                    OpenHistory::ExistingSpace(full) => full.spaceout.space.map(|s| s.name),
+                };
+                return Ok((HistoryEntryKind::Open, name));
+            }
+        }
+
+        let txid = tx.compute_txid();
+        for vout in 0..tx.output.len() as u32 {
+            let spaceout = match store.get_spaceout(&OutPoint { txid, vout })? {
+                None => continue,
+                Some(spaceout) => spaceout,
+            };
+            let space = match spaceout.space {
+                None => continue,
+                Some(space) => space,
+            };
+            let kind = match space.covenant {
+                Covenant::Bid { .. } => HistoryEntryKind::Bid,
+                // A space arriving without the wallet spending anything was
+                // transferred in rather than registered
+                _ if sent > Amount::ZERO && wallet.spaces.is_mine(&spaceout.script_pubkey) => {
+                    HistoryEntryKind::Register
+                }
+                _ => HistoryEntryKind::Transfer,
+            };
+            return Ok((kind, Some(space.name)));
+        }
+
+        // An unconfirmed bid carries its psbt in the first OP_RETURN output
+        if let Some(bid) = TxContext::get_bid_psbt(tx) {
+            let name = store
+                .get_spaceout(&bid.outpoint)?
+                .and_then(|spaceout| spaceout.space)
+                .map(|space| space.name);
+            return Ok((HistoryEntryKind::Bid, name));
+        }
+
+        if sent > received {
+            return Ok((HistoryEntryKind::Send, None));
+        }
+        Ok((HistoryEntryKind::Receive, None))
+    }
+
     fn list_unspent(
         wallet: &mut SpacesWallet,
         store: &mut LiveSnapshot,
@@ -546,14 +3924,67 @@ impl RpcWallet {
                 is_spaceout: false,
             };
 
-            let result = store.get_spaceout(&details.output.outpoint)?;
-            if let Some(spaceout) = result {
-                details.is_spaceout = true;
-                details.space = spaceout.space;
+            let result = store.get_spaceout(&details.output.outpoint)?;
+            if let Some(spaceout) = result {
+                details.is_spaceout = true;
+                details.space = spaceout.space;
+            }
+            wallet_outputs.push(details)
+        }
+        Ok(wallet_outputs)
+    }
+
+    /// Reads everything in one command so no block is applied in between
+    fn get_snapshot(
+        wallet: &mut SpacesWallet,
+        state: &mut LiveSnapshot,
+    ) -> anyhow::Result<WalletSnapshot> {
+        let tip = wallet.spaces.local_chain().tip();
+        let unspent = Self::list_unspent(wallet, state)?;
+        Ok(WalletSnapshot {
+            tip: ChainAnchor {
+                height: tip.height(),
+                hash: tip.hash(),
+            },
+            balance: Self::get_balance(state, wallet)?,
+            spaces: unspent
+                .iter()
+                .filter(|out| out.space.is_some())
+                .cloned()
+                .collect(),
+            unspent,
+        })
+    }
+
+    fn list_output_groups(
+        wallet: &mut SpacesWallet,
+        state: &mut LiveSnapshot,
+    ) -> anyhow::Result<OutputGroups> {
+        let selection = Self::get_spaces_coin_selection(wallet, state, false)?;
+        let mut bidouts = BTreeSet::new();
+        for bidout in wallet.list_bidouts(&selection)? {
+            bidouts.insert(bidout.spend.outpoint);
+            bidouts.insert(bidout.auction.outpoint);
+        }
+
+        let mut groups = OutputGroups {
+            spaces: OutputGroup::default(),
+            coins: OutputGroup::default(),
+            reserved: OutputGroup::default(),
+        };
+        for out in Self::list_unspent(wallet, state)? {
+            if out.space.is_some() {
+                groups.spaces.push(out);
+            } else if out.is_spaceout
+                || bidouts.contains(&out.output.outpoint)
+                || out.output.txout.value <= SpacesAwareCoinSelection::DUST_THRESHOLD
+            {
+                groups.reserved.push(out);
+            } else {
+                groups.coins.push(out);
             }
-            wallet_outputs.push(details)
         }
-        Ok(wallet_outputs)
+        Ok(groups)
     }
 
     fn resolve(
@@ -561,6 +3992,7 @@ impl RpcWallet {
         store: &mut LiveSnapshot,
         to: &str,
         require_space_address: bool,
+        pending: Option<&BitcoinBlockSource>,
     ) -> anyhow::Result<Option<Address>> {
         if let Ok(address) = Address::from_str(to) {
             if require_space_address {
@@ -569,6 +4001,13 @@ impl RpcWallet {
             return Ok(Some(address.require_network(network.fallback_network())?));
         }
         if let Ok(space_address) = SpaceAddress::from_str(to) {
+            if !space_address.is_valid_for_network(network.fallback_network()) {
+                return Err(anyhow!(
+                    "space address {} is not valid for network {}",
+                    to,
+                    network.fallback_network()
+                ));
+            }
             return Ok(Some(space_address.0));
         }
 
@@ -582,9 +4021,15 @@ impl RpcWallet {
         };
 
         let spacehash = SpaceKey::from(Sha256::hash(sname.as_ref()));
-        let script_pubkey = match store.get_space_info(&spacehash)? {
-            None => return Ok(None),
-            Some(fullspaceout) => fullspaceout.spaceout.script_pubkey,
+        let script_pubkey = match pending {
+            None => match store.get_space_info(&spacehash)? {
+                None => return Ok(None),
+                Some(fullspaceout) => fullspaceout.spaceout.script_pubkey,
+            },
+            Some(source) => match Self::pending_spaceout(source, store, &spacehash)? {
+                None => return Ok(None),
+                Some(spaceout) => spaceout.script_pubkey,
+            },
         };
 
         Ok(Some(Address::from_script(
@@ -593,6 +4038,91 @@ impl RpcWallet {
         )?))
     }
 
+    /// Follows spends of the space's output in the node's mempool to find where
+    /// its pending updates, e.g. a registration or transfer, will leave it
+    fn pending_spaceout(
+        source: &BitcoinBlockSource,
+        store: &mut LiveSnapshot,
+        spacehash: &SpaceKey,
+    ) -> anyhow::Result<Option<SpaceOut>> {
+        let tip_height = store.tip.read().expect("read tip").height;
+        let mut checker = TxChecker::new(store);
+        for _ in 0..MAX_PENDING_SPACE_UPDATES {
+            let outpoint = match checker.get_space_outpoint(spacehash)? {
+                None => return Ok(None),
+                Some(outpoint) => outpoint,
+            };
+            let request = source.rpc.get_tx_spending_prevout(&outpoint);
+            let spends: Vec<serde_json::Value> =
+                source.rpc.send_json_blocking(&source.client, &request)?;
+            let spending_txid = match spends
+                .first()
+                .and_then(|spend| spend.get("spendingtxid"))
+                .and_then(|txid| txid.as_str())
+            {
+                None => return Ok(checker.get_spaceout(&outpoint)?),
+                Some(txid) => Txid::from_str(txid)?,
+            };
+
+            let request = source.rpc.get_raw_transaction(&spending_txid, false);
+            let raw: String = source.rpc.send_json_blocking(&source.client, &request)?;
+            let tx: Transaction = bitcoin::consensus::encode::deserialize_hex(&raw)?;
+            checker.apply_tx(tip_height + 1, &tx)?;
+            if checker.get_space_outpoint(spacehash)? == Some(outpoint) {
+                // Spent by something that doesn't update the space
+                return Ok(checker.get_spaceout(&outpoint)?);
+            }
+        }
+        Err(anyhow!(
+            "space has more than {} pending updates in the mempool",
+            MAX_PENDING_SPACE_UPDATES
+        ))
+    }
+
+    /// Returns the recipient, amount and label of a send, taking them from the
+    /// `bitcoin:` URI if one was given. An explicit amount takes priority.
+    fn send_coins_target(
+        params: &SendCoinsParams,
+    ) -> anyhow::Result<(String, Amount, Option<String>)> {
+        let (to, uri_amount, label) = match parse_bitcoin_uri(&params.to)? {
+            None => (params.to.clone(), None, None),
+            Some(uri) => (uri.address, uri.amount, uri.label),
+        };
+        let amount = params
+            .amount
+            .or(uri_amount)
+            .ok_or_else(|| anyhow!("sendcoins: an amount is required to send to '{}'", to))?;
+        Ok((to, amount, label))
+    }
+
+    /// Finds an unconfirmed open reveal for the given space broadcast by this wallet
+    fn find_pending_open(
+        wallet: &SpacesWallet,
+        store: &mut LiveSnapshot,
+        name: &SLabel,
+    ) -> anyhow::Result<Option<Transaction>> {
+        for ctx in wallet
+            .spaces
+            .transactions()
+            .filter(|tx| !tx.chain_position.is_confirmed())
+        {
+            for input in ctx.tx_node.input.iter() {
+                let script = match input.witness.tapscript() {
+                    None => continue,
+                    Some(script) => script,
+                };
+                if let Some(Ok(SpaceScript::Open(OpenHistory::NewSpace(open)))) =
+                    SpaceScript::eval::<_, Sha256>(store, script)?
+                {
+                    if open == *name {
+                        return Ok(Some(ctx.tx_node.tx.as_ref().clone()));
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+
     fn replaces_unconfirmed_bid(wallet: &SpacesWallet, bid_spaceout: &FullSpaceOut) -> bool {
         let outpoint = bid_spaceout.outpoint();
         wallet
@@ -607,14 +4137,216 @@ impl RpcWallet {
             })
     }
 
+    /// Looks up mempool transactions from others spending the same outputs as the bid
+    /// and checks that replacing them stays within the outbid limits
+    fn check_competing_bids(
+        source: &BitcoinBlockSource,
+        wallet: &SpacesWallet,
+        bid: &Transaction,
+        limits: &OutbidLimits,
+        intended: FeeRate,
+    ) -> anyhow::Result<()> {
+        if limits.is_unlimited() {
+            return Ok(());
+        }
+        let intended_fee = wallet.spaces.calculate_fee(bid)?;
+        for input in bid.input.iter() {
+            let request = source.rpc.get_tx_spending_prevout(&input.previous_output);
+            let spends: Vec<serde_json::Value> =
+                source.rpc.send_json_blocking(&source.client, &request)?;
+            let competing = match spends
+                .first()
+                .and_then(|spend| spend.get("spendingtxid"))
+                .and_then(|txid| txid.as_str())
+            {
+                None => continue,
+                Some(txid) => Txid::from_str(txid)?,
+            };
+            // Replacing our own pending bid isn't outbidding anyone
+            if wallet.spaces.get_tx(competing).is_some() {
+                continue;
+            }
+
+            let entry_req = source.rpc.get_mempool_entry(competing);
+            let entry: serde_json::Value =
+                source.rpc.send_json_blocking(&source.client, &entry_req)?;
+            let (fee, vsize) = match (entry["fees"]["base"].as_f64(), entry["vsize"].as_u64()) {
+                (Some(fee), Some(vsize)) if vsize > 0 => (Amount::from_btc(fee)?, vsize),
+                _ => continue,
+            };
+
+            let required = min_replacement_fee_rate(FeeRate::from_sat_per_kwu(
+                (fee.to_sat() * 250).div_ceil(vsize),
+            ));
+            limits.check(intended, required)?;
+            let relay_fee = FeeRate::BROADCAST_MIN
+                .fee_vb(bid.vsize() as u64)
+                .ok_or_else(|| anyhow!("fee rate is too high"))?;
+            limits.check_fee(intended_fee, fee + relay_fee)?;
+        }
+        Ok(())
+    }
+
+    /// Builds and signs a batch without broadcasting it, reporting the outputs
+    /// each transaction pays back to the wallet. Transactions are only added to
+    /// the in-memory wallet so later ones in the batch can spend earlier ones,
+    /// and are discarded afterward.
+    fn preview_batch(
+        network: ExtendedNetwork,
+        fee_estimation: &FeeEstimation,
+        source: &BitcoinBlockSource,
+        wallet: &mut SpacesWallet,
+        store: &mut LiveSnapshot,
+        tx: RpcWalletTxBuilder,
+        stream: Option<&mpsc::Sender<TxResponse>>,
+    ) -> anyhow::Result<WalletResponse> {
+        wallet.commit()?;
+        let preview = Self::batch_tx(
+            network,
+            fee_estimation,
+            source,
+            wallet,
+            store,
+            tx,
+            true,
+            None,
+            stream,
+        );
+        wallet.discard_staged()?;
+        preview
+    }
+
+    /// Builds an unsigned psbt for a single operation funded only by the given
+    /// inputs so it can be combined with other psbts. Nothing is broadcast and
+    /// addresses revealed while building are discarded afterward.
+    fn build_operation_psbt(
+        network: ExtendedNetwork,
+        fee_estimation: &FeeEstimation,
+        source: &BitcoinBlockSource,
+        wallet: &mut SpacesWallet,
+        store: &mut LiveSnapshot,
+        request: RpcWalletRequest,
+        inputs: Vec<OutPoint>,
+        fee_rate: Option<FeeRate>,
+    ) -> anyhow::Result<String> {
+        match request {
+            RpcWalletRequest::Open(_) | RpcWalletRequest::Execute(_) => {
+                return Err(anyhow!(
+                    "opens and script executions need more than one transaction \
+                     and cannot be built as a single psbt"
+                ))
+            }
+            _ => {}
+        }
+        if inputs.is_empty() {
+            return Err(anyhow!("at least one input is required to fund the psbt"));
+        }
+        let mut seen = BTreeSet::new();
+        for outpoint in inputs.iter() {
+            if !seen.insert(*outpoint) {
+                return Err(anyhow!("input {} is listed more than once", outpoint));
+            }
+            if wallet.spaces.get_utxo(*outpoint).is_none() {
+                return Err(anyhow!(
+                    "input {} is not an unspent output of this wallet",
+                    outpoint
+                ));
+            }
+            if store.get_spaceout(outpoint)?.is_some() {
+                return Err(anyhow!(
+                    "input {} carries a space and cannot be used for funding",
+                    outpoint
+                ));
+            }
+        }
+
+        let tx = RpcWalletTxBuilder {
+            bidouts: None,
+            requests: vec![request],
+            fee_rate,
+            fee: None,
+            conf_target: None,
+            estimate_mode: None,
+            dust: None,
+            force: false,
+            confirmed_only: false,
+            skip_tx_check: false,
+            coin_selection: Default::default(),
+            avoid_address_reuse: false,
+            donate_dust_change_to_fee: true,
+            outbid_limits: Default::default(),
+            resolve_pending: false,
+            expected_tip: None,
+            confirm_token: None,
+            broadcast: true,
+        };
+        wallet.commit()?;
+        let response = Self::batch_tx(
+            network,
+            fee_estimation,
+            source,
+            wallet,
+            store,
+            tx,
+            true,
+            Some(inputs),
+            None,
+        );
+        wallet.discard_staged()?;
+        response?
+            .result
+            .pop()
+            .and_then(|tx| tx.raw)
+            .ok_or_else(|| anyhow!("no psbt was built for this request"))
+    }
+
+    fn change_outputs(wallet: &SpacesWallet, tx: &Transaction) -> Vec<ChangeOutput> {
+        let network = wallet.spaces.network();
+        tx.output
+            .iter()
+            .enumerate()
+            // Space and bid outputs are dust sized
+            .filter(|(_, out)| out.value > SpacesAwareCoinSelection::DUST_THRESHOLD)
+            .filter_map(|(vout, out)| {
+                let (keychain, index) = wallet.spaces.derivation_of_spk(&out.script_pubkey)?;
+                Some(ChangeOutput {
+                    vout: vout as u32,
+                    address: Address::from_script(&out.script_pubkey, network)
+                        .map(|address| address.to_string())
+                        .unwrap_or_else(|_| out.script_pubkey.to_hex_string()),
+                    value: out.value,
+                    internal: keychain == KeychainKind::Internal,
+                    index,
+                })
+            })
+            .collect()
+    }
+
     fn batch_tx(
         network: ExtendedNetwork,
+        fee_estimation: &FeeEstimation,
         source: &BitcoinBlockSource,
         wallet: &mut SpacesWallet,
         store: &mut LiveSnapshot,
         tx: RpcWalletTxBuilder,
+        preview: bool,
+        psbt_inputs: Option<Vec<OutPoint>>,
+        stream: Option<&mpsc::Sender<TxResponse>>,
     ) -> anyhow::Result<WalletResponse> {
         let tip_height = wallet.spaces.local_chain().tip().height();
+        if let Some(expected) = tx.expected_tip.as_ref() {
+            let tip = wallet.spaces.local_chain().tip();
+            if tip.height() != expected.height || tip.hash() != expected.hash {
+                return Err(anyhow!(
+                    "wallet tip moved to {} at height {} since the expected tip {} \
+                     at height {}, take a new snapshot",
+                    tip.hash(),
+                    tip.height(),
+                    expected.hash,
+                    expected.height
+                ));
+            }
+        }
 
 
         if let Some(dust) = tx.dust {
@@ -628,15 +4360,82 @@ impl RpcWallet {
             }
         }
 
-        let fee_rate = match tx.fee_rate.as_ref() {
-            None => match Self::estimate_fee_rate(source) {
-                None => return Err(anyhow!("could not estimate fee rate")),
-                Some(r) => r,
+        let requested_fee_rate = match (tx.fee_rate, tx.fee) {
+            (Some(_), Some(_)) => return Err(anyhow!("specify either fee_rate or fee, not both")),
+            (Some(fee_rate), None) => Some(fee_rate),
+            (None, Some(fee)) => Some(fee.to_fee_rate()?),
+            (None, None) => None,
+        };
+        let estimate_requested = tx.conf_target.is_some() || tx.estimate_mode.is_some();
+        if requested_fee_rate.is_some() && estimate_requested {
+            return Err(anyhow!(
+                "conf_target and estimate_mode only apply when no fee rate is given"
+            ));
+        }
+        let fee_rate = match requested_fee_rate.as_ref() {
+            None => match Self::load_fee_policy(wallet)? {
+                FeePolicy::Fixed(r) if !estimate_requested => r,
+                FeePolicy::Target(target) => Self::estimate_fee_rate(
+                    source,
+                    fee_estimation,
+                    tx.conf_target.or(Some(target)),
+                    tx.estimate_mode,
+                )?,
+                _ => Self::estimate_fee_rate(
+                    source,
+                    fee_estimation,
+                    tx.conf_target,
+                    tx.estimate_mode,
+                )?,
             },
-            Some(r) => r.clone(),
+            Some(r) => {
+                if !tx.force {
+                    check_fee_rate(*r)?;
+                }
+                r.clone()
+            }
         };
         info!("Using fee rate: {} sat/vB", fee_rate.to_sat_per_vb_ceil());
 
+        // Previews return signed transactions too, so they're held to the same policy
+        if let Some(threshold) = Self::load_confirm_policy(wallet)?.confirm_high_value {
+            let committed = tx
+                .requests
+                .iter()
+                .filter_map(|req| match req {
+                    RpcWalletRequest::Open(params) => Some(Amount::from_sat(params.amount)),
+                    RpcWalletRequest::Bid(params) => Some(Amount::from_sat(params.amount)),
+                    RpcWalletRequest::SendCoins(params) => {
+                        Self::send_coins_target(params).ok().map(|(_, amount, _)| amount)
+                    }
+                    _ => None,
+                })
+                .max()
+                .unwrap_or(Amount::ZERO);
+            if committed > threshold {
+                let token = Self::confirmation_token(wallet, &tx.requests)?;
+                match tx.confirm_token.as_ref() {
+                    None => {
+                        return Ok(WalletResponse {
+                            result: vec![],
+                            confirmation: Some(ConfirmationRequired {
+                                token,
+                                amount: committed,
+                                threshold,
+                            }),
+                        })
+                    }
+                    Some(given) if *given != token => {
+                        return Err(anyhow!(
+                            "confirmation token does not match this request, \
+                             resubmit without a token to get a new one"
+                        ))
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+
         let mut builder = wallet::builder::Builder::new();
         builder = builder.fee_rate(fee_rate);
 
@@ -646,81 +4445,92 @@ impl RpcWallet {
 
         builder = builder.force(tx.force);
         let mut bid_replacement = tx.confirmed_only;
+        let pending = tx.resolve_pending.then_some(source);
+        let mut replaced_opens = Vec::new();
+        let mut labels = Vec::new();
+        let mut coin_inputs = Vec::new();
+
+        // Listed coins are the only inputs of the transaction carrying the sends,
+        // which would also have to fund anything else batched into it
+        let lists_coins = tx.requests.iter().any(
+            |req| matches!(req, RpcWalletRequest::SendCoins(params) if !params.from.is_empty()),
+        );
+        if lists_coins
+            && tx
+                .requests
+                .iter()
+                .any(|req| !matches!(req, RpcWalletRequest::SendCoins(_)))
+        {
+            return Err(anyhow!(
+                "sendcoins: coins to spend can only be listed in a batch of sendcoins requests"
+            ));
+        }
 
         for req in tx.requests {
+            let reasons = Self::check_request(network, wallet, store, &req, tx.force, pending)?;
+            if let Some(reason) = reasons.first() {
+                return Err(anyhow!("{}: {}", Self::describe_request(&req), reason));
+            }
             match req {
                 RpcWalletRequest::SendCoins(params) => {
-                    let recipient = match Self::resolve(network, store, &params.to, false)? {
-                        None => {
-                            return Err(anyhow!("sendcoins: could not resolve '{}'", params.to))
-                        }
+                    let (to, amount, label) = Self::send_coins_target(&params)?;
+                    let recipient = match Self::resolve(network, store, &to, false, pending)? {
+                        None => return Err(anyhow!("sendcoins: could not resolve '{}'", to)),
                         Some(r) => r,
                     };
+                    labels.extend(label);
+                    for outpoint in params.from {
+                        // Coins may only fund one send of the batch
+                        if coin_inputs.contains(&outpoint) {
+                            return Err(anyhow!(
+                                "sendcoins: coin {} is listed more than once",
+                                outpoint
+                            ));
+                        }
+                        coin_inputs.push(outpoint);
+                    }
                     builder = builder.add_transfer(TransferRequest::Coin(CoinTransfer {
-                        amount: params.amount,
+                        amount,
                         recipient: recipient.clone(),
                     }));
                 }
                 RpcWalletRequest::Transfer(params) => {
-                    let spaces: Vec<_> = params
-                        .spaces
-                        .iter()
-                        .filter_map(|space| SLabel::from_str(space).ok())
-                        .collect();
-                    if spaces.len() != params.spaces.len() {
-                        return Err(anyhow!("sendspaces: some names were malformed"));
-                    }
-                    let recipient = match Self::resolve(network, store, &params.to, true)? {
-                        None => {
-                            return Err(anyhow!("sendspaces: could not resolve '{}'", params.to))
-                        }
-                        Some(r) => r,
-                    };
-                    for space in spaces {
-                        let spacehash = SpaceKey::from(Sha256::hash(space.as_ref()));
-                        match store.get_space_info(&spacehash)? {
-                            None => return Err(anyhow!("sendspaces: you don't own `{}`", space)),
-                            Some(full)
-                            if full.spaceout.space.is_none()
-                                || !full.spaceout.space.as_ref().unwrap().is_owned()
-                                || !wallet
-                                .spaces
-                                .is_mine(full.spaceout.script_pubkey.as_script()) =>
-                                {
-                                    return Err(anyhow!("sendspaces: you don't own `{}`", space));
-                                }
-                            Some(full) => {
-                                builder =
-                                    builder.add_transfer(TransferRequest::Space(SpaceTransfer {
-                                        space: full,
-                                        recipient: recipient.clone(),
-                                    }));
+                    let recipient =
+                        match Self::resolve(network, store, &params.to, true, pending)? {
+                            None => {
+                                return Err(anyhow!(
+                                    "sendspaces: could not resolve '{}'",
+                                    params.to
+                                ))
                             }
+                            Some(r) => r,
                         };
+                    for space in params.spaces {
+                        let full = Self::check_space(store, &space)?
+                            .map_err(|reason| anyhow!("sendspaces: {}: {}", space, reason))?;
+                        // Recipients aren't merged, each space keeps its own output
+                        // even when they all go to the same address
+                        builder = builder.add_transfer(TransferRequest::Space(SpaceTransfer {
+                            space: full,
+                            recipient: recipient.clone(),
+                        }));
                     }
                 }
                 RpcWalletRequest::Open(params) => {
-                    let name = SLabel::from_str(&params.name)?;
                     if !tx.force {
-                        // Warn if already exists
-                        let spacehash = SpaceKey::from(Sha256::hash(name.as_ref()));
-                        let spaceout = store.get_space_info(&spacehash)?;
-                        if spaceout.is_some() {
-                            return Err(anyhow!("open '{}': space already exists", params.name));
+                        // Our own pending open is replaced with a fee bump instead of opening twice
+                        let name = SLabel::from_str(&params.name)?;
+                        if let Some(pending) = Self::find_pending_open(wallet, store, &name)? {
+                            replaced_opens.push(pending.compute_txid());
+                            continue;
                         }
                     }
 
                     builder = builder.add_open(&params.name, Amount::from_sat(params.amount));
                 }
                 RpcWalletRequest::Bid(params) => {
-                    let name = SLabel::from_str(&params.name)?;
-                    let spacehash = SpaceKey::from(Sha256::hash(name.as_ref()));
-                    let spaceout = store.get_space_info(&spacehash)?;
-                    if spaceout.is_none() {
-                        return Err(anyhow!("bid '{}': space does not exist", params.name));
-                    }
-
-                    let spaceout = spaceout.unwrap();
+                    let spaceout = Self::check_space(store, &params.name)?
+                        .map_err(|reason| anyhow!("bid '{}': {}", params.name, reason))?;
                     if Self::replaces_unconfirmed_bid(wallet, &spaceout) {
                         bid_replacement = true;
                     }
@@ -728,177 +4538,661 @@ impl RpcWallet {
                     builder = builder.add_bid(spaceout, Amount::from_sat(params.amount));
                 }
                 RpcWalletRequest::Register(params) => {
-                    let name = SLabel::from_str(&params.name)?;
-                    let spacehash = SpaceKey::from(Sha256::hash(name.as_ref()));
-                    let spaceout = store.get_space_info(&spacehash)?;
-                    if spaceout.is_none() {
-                        return Err(anyhow!("register '{}': space does not exist", params.name));
+                    let utxo = Self::check_space(store, &params.name)?
+                        .map_err(|reason| anyhow!("register '{}': {}", params.name, reason))?;
+                    let address = match params.to {
+                        None => wallet.next_unused_space_address(),
+                        Some(address) => SpaceAddress::from_str(&address)?,
+                    };
+
+                    builder = builder.add_register(utxo, Some(address));
+                }
+                RpcWalletRequest::Execute(params) => {
+                    let mut spaces = Vec::new();
+                    for space in params.context.iter() {
+                        let spaceout = Self::check_space(store, space)?
+                            .map_err(|reason| anyhow!("execute: {}: {}", space, reason))?;
+                        let address = wallet.next_unused_space_address();
+                        spaces.push(SpaceTransfer {
+                            space: spaceout,
+                            recipient: address.0,
+                        });
                     }
-                    let utxo = spaceout.unwrap();
-                    if !wallet.spaces.is_mine(&utxo.spaceout.script_pubkey) {
-                        return Err(anyhow!(
-                            "register '{}': you don't own this space",
-                            params.name
-                        ));
+
+                    let script = SpaceScript::nop_script(params.space_script);
+                    builder = if params.inputs.is_empty() {
+                        builder.add_execute(spaces, script)
+                    } else {
+                        builder.add_execute_with_inputs(spaces, script, params.inputs)
+                    };
+                }
+            }
+        }
+
+        if !coin_inputs.is_empty() {
+            // Only the selected coins fund the transaction carrying the send
+            builder = builder.inputs(coin_inputs.clone());
+        }
+
+        let median_time = source.get_median_time()?;
+        let mut coin_selection =
+            Self::get_spaces_coin_selection(wallet, store, bid_replacement)?;
+        coin_selection.strategy = tx.coin_selection;
+        coin_selection.avoid_address_reuse = tx.avoid_address_reuse;
+        let selection_dust_change = coin_selection.dust_change.clone();
+        let psbt_dust_change = || -> anyhow::Result<Option<Amount>> {
+            let dust_change = Amount::from_sat(selection_dust_change.load(Ordering::Relaxed));
+            if dust_change == Amount::ZERO {
+                return Ok(None);
+            }
+            if !tx.donate_dust_change_to_fee {
+                return Err(anyhow!("{}", Self::dust_change_refused(dust_change)));
+            }
+            Ok(Some(dust_change))
+        };
+
+        if let Some(mut inputs) = psbt_inputs {
+            for outpoint in coin_inputs {
+                if !inputs.contains(&outpoint) {
+                    inputs.push(outpoint);
+                }
+            }
+            // Left unsigned since signing would commit to the final set of
+            // inputs and outputs before it's combined with other psbts
+            let (psbt, tags) = builder.inputs(inputs).build_psbt(
+                tx.dust,
+                median_time,
+                wallet,
+                coin_selection,
+            )?;
+            return Ok(WalletResponse {
+                result: vec![TxResponse {
+                    txid: psbt.unsigned_tx.compute_txid(),
+                    tags,
+                    error: None,
+                    raw: Some(psbt.to_string()),
+                    warnings: vec![],
+                    change: vec![],
+                    dust_change: psbt_dust_change()?,
+                    fee: psbt.fee().ok(),
+                    vsize: None,
+                    fee_bump: None,
+                }],
+                confirmation: None,
+            });
+        }
+
+        if wallet.is_multisig() {
+            // Only partially signed, co-signers must add their signatures before broadcasting
+            let (psbt, tags) = builder.build_psbt(tx.dust, median_time, wallet, coin_selection)?;
+            let dust_change = psbt_dust_change()?;
+            let warnings = match tx.avoid_address_reuse {
+                true => Self::address_linkage_warnings(wallet, &psbt.unsigned_tx),
+                false => vec![],
+            };
+            let change = match preview {
+                true => Self::change_outputs(wallet, &psbt.unsigned_tx),
+                false => vec![],
+            };
+            let psbt = wallet.sign_partial(psbt)?;
+            return Ok(WalletResponse {
+                result: vec![TxResponse {
+                    txid: psbt.unsigned_tx.compute_txid(),
+                    tags,
+                    error: None,
+                    raw: Some(psbt.to_string()),
+                    warnings,
+                    change,
+                    dust_change,
+                    fee: psbt.fee().ok(),
+                    vsize: None,
+                    fee_bump: None,
+                }],
+                confirmation: None,
+            });
+        }
+
+        if preview && !replaced_opens.is_empty() {
+            return Err(anyhow!(
+                "cannot preview a batch that fee bumps a pending open from this wallet"
+            ));
+        }
+        let mut result_set = Vec::new();
+        for txid in replaced_opens {
+            result_set.extend(Self::handle_fee_bump(
+                source,
+                store,
+                wallet,
+                txid,
+                tx.skip_tx_check,
+                fee_rate,
+            )?);
+        }
+
+        let mut checker = TxChecker::new(store);
+
+        if !tx.skip_tx_check {
+            let mut unconfirmed: Vec<_> = wallet
+                .spaces
+                .transactions()
+                .filter(|x|
+                    !x.chain_position.is_confirmed()).collect();
+            unconfirmed.sort();
+            // no tx checks for unconfirmed as they're already broadcasted,
+            // but we need to build on their state still
+            for un in unconfirmed {
+                checker.apply_tx(tip_height + 1, &un.tx_node.tx)?;
+            }
+        }
+
+        let mut tx_iter = builder.build_iter(tx.dust, median_time, wallet, coin_selection)?;
+        let mut conflict_retries = 0;
+        let mut streamed = 0;
+
+        while let Some(tx_result) = tx_iter.next() {
+            // Earlier results are final once the next transaction is built
+            Self::stream_responses(stream, &mut result_set, &mut streamed);
+            let tagged = tx_result?;
+
+            let is_bid = tagged.tags.iter().any(|tag| *tag == TransactionTag::Bid);
+            let spends_batch_tx = tagged.tx.input.iter().any(|input| {
+                result_set
+                    .iter()
+                    .any(|prev| prev.txid == input.previous_output.txid)
+            });
+            let warnings = match tx.avoid_address_reuse {
+                true => Self::address_linkage_warnings(tx_iter.wallet, &tagged.tx),
+                false => vec![],
+            };
+            let dust_change = (tagged.dust_change > Amount::ZERO).then_some(tagged.dust_change);
+            result_set.push(TxResponse {
+                txid: tagged.tx.compute_txid(),
+                tags: tagged.tags,
+                error: None,
+                raw: None,
+                warnings,
+                change: vec![],
+                dust_change,
+                fee: tx_iter.wallet.spaces.calculate_fee(&tagged.tx).ok(),
+                vsize: Some(tagged.tx.vsize() as u64),
+                fee_bump: None,
+            });
+            if let (Some(dust_change), false) = (dust_change, tx.donate_dust_change_to_fee) {
+                let mut error_data = BTreeMap::new();
+                error_data.insert(
+                    "message".to_string(),
+                    Self::dust_change_refused(dust_change),
+                );
+                result_set.last_mut().unwrap().error = Some(error_data);
+                break;
+            }
+
+            // Checker state before this transaction in case it has to be rebuilt
+            let checkpoint = (checker.spaces.clone(), checker.spaceouts.clone());
+            if !tx.skip_tx_check {
+                checker.check_apply_tx(tip_height + 1, &tagged.tx)?;
+            }
+
+            let raw = bitcoin::consensus::encode::serialize_hex(&tagged.tx);
+            if !tx.force {
+                let violations = check_standardness(&tagged.tx);
+                if !violations.is_empty() {
+                    let mut error_data = BTreeMap::new();
+                    error_data.insert(
+                        "message".to_string(),
+                        "transaction is non-standard and would be rejected by the node"
+                            .to_string(),
+                    );
+                    error_data.insert(
+                        "violations".to_string(),
+                        serde_json::to_string(&violations)?,
+                    );
+                    let last = result_set.last_mut().unwrap();
+                    last.raw = Some(raw);
+                    last.error = Some(error_data);
+                    break;
+                }
+            }
+
+            if is_bid {
+                if let Err(e) = Self::check_competing_bids(
+                    source,
+                    tx_iter.wallet,
+                    &tagged.tx,
+                    &tx.outbid_limits,
+                    fee_rate,
+                ) {
+                    let mut error_data = BTreeMap::new();
+                    error_data.insert("message".to_string(), e.to_string());
+                    let last = result_set.last_mut().unwrap();
+                    last.raw = Some(raw);
+                    last.error = Some(error_data);
+                    break;
+                }
+            }
+
+            if preview {
+                let last = result_set.last_mut().unwrap();
+                last.raw = Some(raw);
+                last.change = Self::change_outputs(tx_iter.wallet, &tagged.tx);
+                tx_iter.wallet.insert_tx(
+                    tagged.tx,
+                    ConfirmationTime::Unconfirmed { last_seen: 0 },
+                )?;
+                continue;
+            }
+
+            let inputs: Vec<_> = tagged
+                .tx
+                .input
+                .iter()
+                .map(|input| input.previous_output)
+                .collect();
+            let error = Self::broadcast_and_insert(
+                source,
+                tx_iter.wallet,
+                tagged.tx,
+                &result_set.last().unwrap().tags,
+                is_bid,
+                spends_batch_tx,
+            )?;
+            if let Some(error_data) = error {
+                // A coin may have been spent concurrently by another device sharing these keys
+                let conflict = error_data
+                    .get("message")
+                    .is_some_and(|message| message.contains(MISSING_INPUTS_ERROR));
+                if conflict && !spends_batch_tx && conflict_retries < MAX_CONFLICT_RETRIES {
+                    let spent = Self::spent_inputs(source, &inputs)?;
+                    if !spent.is_empty() && tx_iter.retry_excluding(&spent) {
+                        conflict_retries += 1;
+                        warn!(
+                            "Rebuilding transaction without concurrently spent coins: {:?}",
+                            spent
+                        );
+                        (checker.spaces, checker.spaceouts) = checkpoint;
+                        result_set.pop();
+                        continue;
                     }
+                }
 
-                    if !tx.force {
-                        let claim_height = utxo.spaceout.space.as_ref().unwrap().claim_height();
-                        let tip_height = wallet.spaces.local_chain().tip().height();
+                let last = result_set.last_mut().unwrap();
+                last.raw = Some(raw);
+                last.error = Some(error_data);
+                break;
+            }
+        }
+        Self::stream_responses(stream, &mut result_set, &mut streamed);
 
-                        if claim_height.is_none() {
-                            return Err(anyhow!(
-                                "register '{}': space may be in pre-auctions or already registered",
-                                params.name
-                            ));
-                        }
+        // Keep labels from payment URIs as notes on the transactions carrying the sends
+        if !labels.is_empty() && !preview {
+            let note = labels.join(", ");
+            for sent in result_set.iter().filter(|res| {
+                res.error.is_none() && res.tags.contains(&TransactionTag::Transfers)
+            }) {
+                // The transaction is already broadcast, losing its note isn't worth failing over
+                if let Err(e) = Self::set_tx_note(wallet, sent.txid, note.clone()) {
+                    warn!("Could not save note for {}: {}", sent.txid, e);
+                }
+            }
+        }
+
+        Ok(WalletResponse {
+            result: result_set.split_off(streamed),
+            confirmation: None,
+        })
+    }
+
+    /// Sends results not yet streamed, keeping only what the rest of the batch
+    /// needs to reference them so memory stays bounded for large batches.
+    fn stream_responses(
+        stream: Option<&mpsc::Sender<TxResponse>>,
+        result_set: &mut [TxResponse],
+        streamed: &mut usize,
+    ) {
+        let stream = match stream {
+            None => return,
+            Some(stream) => stream,
+        };
+        for response in result_set[*streamed..].iter_mut() {
+            let stripped = TxResponse {
+                txid: response.txid,
+                tags: response.tags.clone(),
+                error: response.error.clone(),
+                raw: None,
+                warnings: vec![],
+                change: vec![],
+                dust_change: response.dust_change,
+                fee: response.fee,
+                vsize: response.vsize,
+                fee_bump: None,
+            };
+            // The batch continues even if the receiver went away
+            _ = stream.blocking_send(mem::replace(response, stripped));
+        }
+        *streamed = result_set.len();
+    }
+
+    fn dust_change_refused(dust_change: Amount) -> String {
+        format!(
+            "change of {} sat is below the dust limit and would be added to the fee, \
+             adjust the amounts or allow donate_dust_change_to_fee",
+            dust_change.to_sat()
+        )
+    }
+
+    /// Describes how spending a transaction's inputs together ties wallet
+    /// addresses to each other on-chain. Addresses already spent together by
+    /// an earlier wallet transaction count as linked.
+    fn address_linkage_warnings(wallet: &SpacesWallet, tx: &Transaction) -> Vec<String> {
+        let network = wallet.spaces.network();
+        let graph = wallet.spaces.tx_graph();
+        let input_scripts = |tx: &Transaction| -> Vec<bitcoin::ScriptBuf> {
+            tx.input
+                .iter()
+                .filter_map(|input| graph.get_txout(input.previous_output))
+                .filter(|txout| wallet.spaces.is_mine(&txout.script_pubkey))
+                .map(|txout| txout.script_pubkey.clone())
+                .collect()
+        };
+        let display = |script: &bitcoin::ScriptBuf| match Address::from_script(script, network) {
+            Ok(address) => address.to_string(),
+            Err(_) => script.to_hex_string(),
+        };
+
+        let mut clusters: BTreeMap<bitcoin::ScriptBuf, usize> = BTreeMap::new();
+        for (id, ctx) in wallet.spaces.transactions().enumerate() {
+            let scripts = input_scripts(ctx.tx_node.tx.as_ref());
+            let merged: BTreeSet<usize> = scripts
+                .iter()
+                .filter_map(|script| clusters.get(script).copied())
+                .collect();
+            for cluster in clusters.values_mut() {
+                if merged.contains(cluster) {
+                    *cluster = id;
+                }
+            }
+            for script in scripts {
+                clusters.insert(script, id);
+            }
+        }
+
+        let mut spent: BTreeMap<bitcoin::ScriptBuf, usize> = BTreeMap::new();
+        for script in input_scripts(tx) {
+            *spent.entry(script).or_default() += 1;
+        }
+
+        let mut warnings = Vec::new();
+        for (script, count) in spent.iter().filter(|(_, count)| **count > 1) {
+            warnings.push(format!(
+                "spends {} outputs of reused address {} together",
+                count,
+                display(script)
+            ));
+        }
+        let linked: BTreeSet<_> = spent
+            .keys()
+            .map(|script| clusters.get(script).copied().ok_or(script))
+            .collect();
+        if linked.len() > 1 {
+            let addresses: Vec<_> = spent.keys().map(display).collect();
+            warnings.push(format!(
+                "links {} previously unlinked addresses: {}",
+                linked.len(),
+                addresses.join(", ")
+            ));
+        }
+        warnings
+    }
 
-                        let claim_height = claim_height.unwrap();
-                        if claim_height > tip_height {
-                            return Err(anyhow!(
-                                "register '{}': cannot register until claim height {}",
-                                params.name,
-                                claim_height
-                            ));
-                        }
-                    }
+    /// Broadcasts a transaction and inserts it into the wallet once accepted.
+    /// Returns the error details if the node rejected it.
+    fn broadcast_and_insert(
+        source: &BitcoinBlockSource,
+        wallet: &mut SpacesWallet,
+        tx: Transaction,
+        tags: &[TransactionTag],
+        is_bid: bool,
+        spends_batch_tx: bool,
+    ) -> anyhow::Result<Option<BTreeMap<String, String>>> {
+        Self::log_broadcast(source, wallet, &tx, tags)?;
+        let mut result = source.rpc.broadcast_tx(&source.client, &tx);
+        if spends_batch_tx {
+            let mut attempts = 0;
+            while attempts < 3
+                && matches!(&result, Err(BitcoinRpcError::Rpc(rpc))
+                    if rpc.message.contains(MISSING_INPUTS_ERROR))
+            {
+                // The parent from this batch may not have propagated yet
+                std::thread::sleep(Duration::from_millis(500));
+                result = source.rpc.broadcast_tx(&source.client, &tx);
+                attempts += 1;
+            }
+        }
 
-                    let address = match params.to {
-                        None => wallet.next_unused_space_address(),
-                        Some(address) => match SpaceAddress::from_str(&address) {
-                            Ok(addr) => addr,
-                            Err(_) => {
-                                return Err(anyhow!(
-                                    "transfer '{}': recipient must be a valid space address",
-                                    params.name
-                                ));
-                            }
-                        },
-                    };
+        let e = match result {
+            Ok(confirmation) => {
+                // Externally built batches may carry transactions of other wallets
+                if Self::touches_wallet(wallet, &tx) {
+                    wallet.insert_tx(tx, confirmation)?;
+                    wallet.commit()?;
+                }
+                return Ok(None);
+            }
+            Err(e) => e,
+        };
+        Self::log_broadcast_rejected(source, wallet, tx.compute_txid(), &e)?;
 
-                    builder = builder.add_register(utxo, Some(address));
+        let mut error_data = BTreeMap::new();
+        if let BitcoinRpcError::Rpc(rpc) = e {
+            if rpc.message.contains(MISSING_INPUTS_ERROR) {
+                error_data.insert(
+                    "hint".to_string(),
+                    "some inputs are missing or already spent: a parent transaction \
+                    may not have reached the mempool yet, or a coin was spent elsewhere. \
+                    Wait for the wallet to sync and try again"
+                        .to_string(),
+                );
+            }
+            if is_bid {
+                if rpc.message.contains("replacement-adds-unconfirmed") {
+                    error_data.insert(
+                        "hint".to_string(),
+                        "a competing bid in mempool but wallet must use confirmed bidouts and funding \
+                        outputs to replace it. Try --confirmed-only"
+                            .to_string(),
+                    );
                 }
-                RpcWalletRequest::Execute(params) => {
-                    let mut spaces = Vec::new();
-                    for space in params.context.iter() {
-                        let name = SLabel::from_str(&space)?;
-                        let spacehash = SpaceKey::from(Sha256::hash(name.as_ref()));
-                        let spaceout = store.get_space_info(&spacehash)?;
-                        if spaceout.is_none() {
-                            return Err(anyhow!("execute on '{}': space does not exist", space));
-                        }
-                        let spaceout = spaceout.unwrap();
-                        if !wallet.spaces.is_mine(&spaceout.spaceout.script_pubkey) {
-                            return Err(anyhow!(
-                                "execute on '{}': you don't own this space",
-                                space
-                            ));
-                        }
-                        let address = wallet.next_unused_space_address();
-                        spaces.push(SpaceTransfer {
-                            space: spaceout,
-                            recipient: address.0,
-                        });
-                    }
 
-                    let script = SpaceScript::nop_script(params.space_script);
-                    builder = builder.add_execute(spaces, script);
+                if let Some(fee_rate) = fee_rate_from_message(&rpc.message) {
+                    error_data.insert(
+                        "hint".to_string(),
+                        format!(
+                            "a competing bid in the mempool; replace \
+                                      with a feerate > {} sat/vB.",
+                            fee_rate.to_sat_per_vb_ceil()
+                        ),
+                    );
                 }
             }
+
+            error_data.insert("rpc_code".to_string(), rpc.code.to_string());
+            error_data.insert("message".to_string(), rpc.message);
+        } else {
+            error_data.insert("message".to_string(), format!("{:?}", e));
         }
+        Ok(Some(error_data))
+    }
 
-        let median_time = source.get_median_time()?;
-        let coin_selection = Self::get_spaces_coin_selection(wallet, store, bid_replacement)?;
-        let mut checker = TxChecker::new(store);
+    /// Returns the given outpoints that are spent or missing according to the node
+    fn spent_inputs(
+        source: &BitcoinBlockSource,
+        inputs: &[OutPoint],
+    ) -> anyhow::Result<Vec<OutPoint>> {
+        let mut spent = Vec::new();
+        for outpoint in inputs {
+            let request = source.rpc.get_tx_out(outpoint, true);
+            let txout: Option<serde_json::Value> =
+                source.rpc.send_json_blocking(&source.client, &request)?;
+            if txout.is_none() {
+                spent.push(*outpoint);
+            }
+        }
+        Ok(spent)
+    }
 
-        if !tx.skip_tx_check {
-            let mut unconfirmed: Vec<_> = wallet
-                .spaces
-                .transactions()
-                .filter(|x|
-                    !x.chain_position.is_confirmed()).collect();
-            unconfirmed.sort();
-            // no tx checks for unconfirmed as they're already broadcasted,
-            // but we need to build on their state still
-            for un in unconfirmed {
-                checker.apply_tx(tip_height + 1, &un.tx_node.tx)?;
+    /// Broadcasts a transaction already recorded with [Self::log_broadcast],
+    /// recording it as rejected if the node refuses it
+    fn broadcast_logged(
+        source: &BitcoinBlockSource,
+        wallet: &SpacesWallet,
+        tx: &Transaction,
+    ) -> anyhow::Result<ConfirmationTime> {
+        match source.rpc.broadcast_tx(&source.client, tx) {
+            Ok(confirmation) => Ok(confirmation),
+            Err(e) => {
+                Self::log_broadcast_rejected(source, wallet, tx.compute_txid(), &e)?;
+                Err(e.into())
             }
         }
+    }
 
-        let mut tx_iter = builder.build_iter(tx.dust, median_time, wallet, coin_selection)?;
-        let mut result_set = Vec::new();
+    /// Records a transaction in the broadcast log if one is configured. Written
+    /// and synced before broadcasting so a crash can't lose an in-flight broadcast.
+    fn log_broadcast(
+        source: &BitcoinBlockSource,
+        wallet: &SpacesWallet,
+        tx: &Transaction,
+        tags: &[TransactionTag],
+    ) -> anyhow::Result<()> {
+        let entry = json!({
+            "event": "broadcast",
+            "wallet": wallet.name(),
+            "txid": tx.compute_txid(),
+            "tags": tags,
+            "fee": wallet.spaces.calculate_fee(tx).ok(),
+            "raw": bitcoin::consensus::encode::serialize_hex(tx),
+        });
+        Self::append_broadcast_log(source, entry)
+    }
 
-        while let Some(tx_result) = tx_iter.next() {
-            let tagged = tx_result?;
+    fn log_broadcast_rejected(
+        source: &BitcoinBlockSource,
+        wallet: &SpacesWallet,
+        txid: Txid,
+        error: &BitcoinRpcError,
+    ) -> anyhow::Result<()> {
+        let entry = json!({
+            "event": "rejected",
+            "wallet": wallet.name(),
+            "txid": txid,
+            "error": error.to_string(),
+        });
+        Self::append_broadcast_log(source, entry)
+    }
 
-            let is_bid = tagged.tags.iter().any(|tag| *tag == TransactionTag::Bid);
-            result_set.push(TxResponse {
-                txid: tagged.tx.compute_txid(),
-                tags: tagged.tags,
-                error: None,
-                raw: None,
-            });
+    fn append_broadcast_log(
+        source: &BitcoinBlockSource,
+        mut entry: serde_json::Value,
+    ) -> anyhow::Result<()> {
+        let path = match source.rpc.broadcast_log() {
+            None => return Ok(()),
+            Some(path) => path,
+        };
+        let time = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs();
+        entry["time"] = json!(time);
 
-            if !tx.skip_tx_check {
-                checker.check_apply_tx(tip_height + 1, &tagged.tx)?;
-            }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| anyhow!("could not open broadcast log {}: {}", path.display(), e))?;
+        let mut line = serde_json::to_vec(&entry)?;
+        line.push(b'\n');
+        std::io::Write::write_all(&mut file, &line)?;
+        file.sync_data()?;
+        Ok(())
+    }
 
-            let raw = bitcoin::consensus::encode::serialize_hex(&tagged.tx);
-            let result = source.rpc.broadcast_tx(&source.client, &tagged.tx);
-            match result {
-                Ok(confirmation) => {
-                    tx_iter.wallet.insert_tx(tagged.tx, confirmation)?;
-                    tx_iter.wallet.commit()?;
-                }
-                Err(e) => {
-                    result_set.last_mut().unwrap().raw = Some(raw);
+    /// Whether the transaction spends or pays to any of the wallet's scripts
+    fn touches_wallet(wallet: &SpacesWallet, tx: &Transaction) -> bool {
+        let spends = tx.input.iter().any(|input| {
+            wallet
+                .spaces
+                .tx_graph()
+                .get_txout(input.previous_output)
+                .is_some_and(|txout| wallet.spaces.is_mine(txout.script_pubkey.as_script()))
+        });
+        spends
+            || tx
+                .output
+                .iter()
+                .any(|out| wallet.spaces.is_mine(out.script_pubkey.as_script()))
+    }
 
-                    let mut error_data = BTreeMap::new();
-                    if let BitcoinRpcError::Rpc(rpc) = e {
-                        if is_bid {
-                            if rpc.message.contains("replacement-adds-unconfirmed") {
-                                error_data.insert(
-                                    "hint".to_string(),
-                                    "a competing bid in mempool but wallet must use confirmed bidouts and funding \
-                                    outputs to replace it. Try --confirmed-only"
-                                        .to_string(),
-                                );
-                            }
+    /// Broadcasts already signed transactions in order, stopping at the first failure
+    fn broadcast_batch(
+        source: &BitcoinBlockSource,
+        wallet: &mut SpacesWallet,
+        raw_txs: Vec<String>,
+    ) -> anyhow::Result<WalletResponse> {
+        let mut txs = Vec::with_capacity(raw_txs.len());
+        for (n, raw_tx) in raw_txs.iter().enumerate() {
+            let tx: Transaction = bitcoin::consensus::encode::deserialize_hex(raw_tx)
+                .map_err(|e| anyhow!("could not decode transaction #{}: {}", n, e))?;
+            txs.push(tx);
+        }
 
-                            if let Some(fee_rate) = fee_rate_from_message(&rpc.message) {
-                                error_data.insert(
-                                    "hint".to_string(),
-                                    format!(
-                                        "a competing bid in the mempool; replace \
-                                                  with a feerate > {} sat/vB.",
-                                        fee_rate.to_sat_per_vb_ceil()
-                                    ),
-                                );
-                            }
-                        }
+        let mut result_set: Vec<TxResponse> = Vec::with_capacity(txs.len());
+        for (tx, raw) in txs.into_iter().zip(raw_txs) {
+            let spends_batch_tx = tx.input.iter().any(|input| {
+                result_set
+                    .iter()
+                    .any(|prev| prev.txid == input.previous_output.txid)
+            });
+            result_set.push(TxResponse {
+                txid: tx.compute_txid(),
+                tags: vec![],
+                error: None,
+                raw: None,
+                warnings: vec![],
+                change: vec![],
+                dust_change: None,
+                fee: wallet.spaces.calculate_fee(&tx).ok(),
+                vsize: Some(tx.vsize() as u64),
+                fee_bump: None,
+            });
 
-                        error_data.insert("rpc_code".to_string(), rpc.code.to_string());
-                        error_data.insert("message".to_string(), rpc.message);
-                        result_set.last_mut().unwrap().error = Some(error_data);
-                    } else {
-                        error_data.insert("message".to_string(), format!("{:?}", e));
-                        result_set.last_mut().unwrap().error = Some(error_data);
-                    }
-                    break;
-                }
+            let error =
+                Self::broadcast_and_insert(source, wallet, tx, &[], false, spends_batch_tx)?;
+            if let Some(error_data) = error {
+                let last = result_set.last_mut().unwrap();
+                last.raw = Some(raw);
+                last.error = Some(error_data);
+                break;
             }
         }
 
-        Ok(WalletResponse { result: result_set })
+        Ok(WalletResponse {
+            result: result_set,
+            confirmation: None,
+        })
     }
 
     pub async fn service(
         network: ExtendedNetwork,
+        fee_estimation: FeeEstimation,
         rpc: BitcoinRpc,
+        rpc_pool: ClientPool,
         store: LiveSnapshot,
         mut channel: Receiver<LoadedWallet>,
         shutdown: broadcast::Sender<()>,
         num_workers: usize,
+        catch_up_threshold: u32,
+        tip_refetch_depth: u32,
+        tip_preview_depth: u32,
+        dropped_tx_grace_period: Duration,
     ) -> anyhow::Result<()> {
         let mut shutdown_signal = shutdown.subscribe();
         let mut wallet_results = FuturesUnordered::new();
@@ -920,15 +5214,20 @@ impl RpcWallet {
                         let (tx, rx) = oneshot::channel();
 
                         std::thread::spawn(move || {
-                            let source = BitcoinBlockSource::new(rpc);
+                            let source = BitcoinBlockSource::with_pool(rpc, rpc_pool);
                             _ = tx.send(Self::wallet_sync(
                                 network,
+                                fee_estimation,
                                 source,
                                 wallet_chain,
                                 loaded.wallet,
                                 loaded.rx,
                                 wallet_shutdown,
-                                num_workers
+                                num_workers,
+                                catch_up_threshold,
+                                tip_refetch_depth,
+                                tip_preview_depth,
+                                dropped_tx_grace_period,
                             ));
                         });
                         wallet_results.push(named_future(wallet_name, rx));
@@ -952,55 +5251,295 @@ impl RpcWallet {
 
     pub async fn send_get_info(&self) -> anyhow::Result<WalletInfo> {
         let (resp, resp_rx) = oneshot::channel();
-        self.sender.send(WalletCommand::GetInfo { resp }).await?;
+        self.sender.send(WalletCommand::GetInfo { resp }).await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_get_wallet_metadata(&self) -> anyhow::Result<WalletMetadata> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::GetWalletMetadata { resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_batch_tx(
+        &self,
+        request: RpcWalletTxBuilder,
+    ) -> anyhow::Result<WalletResponse> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::BatchTx { request, resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    /// Like [Self::send_batch_tx] but each transaction's result is sent over the
+    /// returned receiver as soon as it's built and broadcast. The final response
+    /// only carries what wasn't streamed, such as a required confirmation.
+    pub async fn send_batch_tx_stream(
+        &self,
+        request: RpcWalletTxBuilder,
+    ) -> anyhow::Result<(
+        mpsc::Receiver<TxResponse>,
+        oneshot::Receiver<anyhow::Result<WalletResponse>>,
+    )> {
+        let (stream, stream_rx) = mpsc::channel(BATCH_STREAM_BUFFER);
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::BatchTxStream {
+                request,
+                stream,
+                resp,
+            })
+            .await?;
+        Ok((stream_rx, resp_rx))
+    }
+
+    pub async fn send_build_psbt(
+        &self,
+        request: RpcWalletRequest,
+        inputs: Vec<OutPoint>,
+        fee_rate: Option<FeeRate>,
+    ) -> anyhow::Result<String> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::BuildPsbt {
+                request,
+                inputs,
+                fee_rate,
+                resp,
+            })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_preview_batch(
+        &self,
+        request: RpcWalletTxBuilder,
+    ) -> anyhow::Result<WalletResponse> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::PreviewBatch { request, resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_broadcast_batch(
+        &self,
+        raw_txs: Vec<String>,
+    ) -> anyhow::Result<WalletResponse> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::BroadcastBatch { raw_txs, resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_derive_address(
+        &self,
+        internal: bool,
+        index: u32,
+    ) -> anyhow::Result<DerivedAddress> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::DeriveAddress {
+                internal,
+                index,
+                resp,
+            })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_get_new_address(&self, kind: AddressKind) -> anyhow::Result<NewAddress> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::GetNewAddress { kind, resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_fee_bump(
+        &self,
+        txid: Txid,
+        fee_rate: FeeRate,
+        skip_tx_check: bool,
+        dry_run: bool,
+    ) -> anyhow::Result<Vec<TxResponse>> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::BumpFee {
+                txid,
+                fee_rate,
+                skip_tx_check,
+                dry_run,
+                resp,
+            })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_fee_bump_to_target(
+        &self,
+        txid: Txid,
+        conf_target: u16,
+        dry_run: bool,
+    ) -> anyhow::Result<TargetFeeBump> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::BumpFeeToTarget {
+                txid,
+                conf_target,
+                dry_run,
+                resp,
+            })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_list_transactions(
+        &self,
+        count: usize,
+        skip: usize,
+    ) -> anyhow::Result<Vec<TxInfo>> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::ListTransactions { count, skip, resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_list_fee_rates(&self, count: usize) -> anyhow::Result<Vec<TxFeeRate>> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::ListFeeRates { count, resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_list_checkpoints(&self, count: usize) -> anyhow::Result<Vec<ChainAnchor>> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::ListCheckpoints { count, resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_reconcile(&self) -> anyhow::Result<ReconcileReport> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender.send(WalletCommand::Reconcile { resp }).await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_abandon_all_pending(&self) -> anyhow::Result<AbandonReport> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::AbandonAllPending { resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_abandon_open(
+        &self,
+        name: String,
+        reclaim_fee_rate: Option<FeeRate>,
+    ) -> anyhow::Result<AbandonOpenReport> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::AbandonOpen {
+                name,
+                reclaim_fee_rate,
+                resp,
+            })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_bump_all_stuck(
+        &self,
+        min_age_blocks: u32,
+        target_fee_rate: FeeRate,
+    ) -> anyhow::Result<Vec<StuckTxReport>> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::BumpAllStuck {
+                min_age_blocks,
+                target_fee_rate,
+                resp,
+            })
+            .await?;
         resp_rx.await?
     }
 
-    pub async fn send_batch_tx(
+    pub async fn send_list_bump_candidates(
         &self,
-        request: RpcWalletTxBuilder,
-    ) -> anyhow::Result<WalletResponse> {
+        min_age_blocks: u32,
+        conf_target: Option<u16>,
+    ) -> anyhow::Result<Vec<BumpCandidate>> {
         let (resp, resp_rx) = oneshot::channel();
         self.sender
-            .send(WalletCommand::BatchTx { request, resp })
+            .send(WalletCommand::ListBumpCandidates {
+                min_age_blocks,
+                conf_target,
+                resp,
+            })
             .await?;
         resp_rx.await?
     }
 
-    pub async fn send_get_new_address(&self, kind: AddressKind) -> anyhow::Result<String> {
+    pub async fn send_list_dust(&self, fee_rate: FeeRate) -> anyhow::Result<DustReport> {
         let (resp, resp_rx) = oneshot::channel();
         self.sender
-            .send(WalletCommand::GetNewAddress { kind, resp })
+            .send(WalletCommand::ListDust { fee_rate, resp })
             .await?;
         resp_rx.await?
     }
 
-    pub async fn send_fee_bump(
+    pub async fn send_estimate_selection(
         &self,
-        txid: Txid,
+        amount: Amount,
         fee_rate: FeeRate,
-        skip_tx_check: bool,
-    ) -> anyhow::Result<Vec<TxResponse>> {
+    ) -> anyhow::Result<SelectionEstimate> {
         let (resp, resp_rx) = oneshot::channel();
         self.sender
-            .send(WalletCommand::BumpFee {
-                txid,
+            .send(WalletCommand::EstimateSelection {
+                amount,
                 fee_rate,
-                skip_tx_check,
                 resp,
             })
             .await?;
         resp_rx.await?
     }
 
-    pub async fn send_list_transactions(
+    pub async fn send_get_max_sendable(
         &self,
-        count: usize,
+        fee_rate: FeeRate,
+        to: String,
+    ) -> anyhow::Result<MaxSendable> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::GetMaxSendable { fee_rate, to, resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_export_history(
+        &self,
+        format: ExportFormat,
+        as_of: Option<u32>,
         skip: usize,
-    ) -> anyhow::Result<Vec<TxInfo>> {
+        count: usize,
+    ) -> anyhow::Result<String> {
         let (resp, resp_rx) = oneshot::channel();
         self.sender
-            .send(WalletCommand::ListTransactions { count, skip, resp })
+            .send(WalletCommand::ExportHistory {
+                format,
+                as_of,
+                skip,
+                count,
+                resp,
+            })
             .await?;
         resp_rx.await?
     }
@@ -1021,12 +5560,92 @@ impl RpcWallet {
         resp_rx.await?
     }
 
+    pub async fn send_cancel_bid(
+        &self,
+        txid: Txid,
+        fee_rate: FeeRate,
+    ) -> anyhow::Result<TxResponse> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::CancelBid {
+                txid,
+                fee_rate,
+                resp,
+            })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_is_replaceable(&self, txid: Txid) -> anyhow::Result<ReplaceableInfo> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::IsReplaceable { txid, resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_get_claim_status(&self, space: String) -> anyhow::Result<ClaimStatus> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::GetClaimStatus { space, resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_get_space_script(&self, space: String) -> anyhow::Result<SpaceScriptInfo> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::GetSpaceScript { space, resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    #[cfg(feature = "testing")]
+    pub async fn send_apply_test_block(&self, block_hex: String, height: u32) -> anyhow::Result<()> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::ApplyTestBlock {
+                block_hex,
+                height,
+                resp,
+            })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_encrypt(&self, cipher: WalletCipher) -> anyhow::Result<()> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::Encrypt { cipher, resp })
+            .await?;
+        resp_rx.await?
+    }
+
     pub async fn send_list_spaces(&self) -> anyhow::Result<Vec<WalletOutput>> {
         let (resp, resp_rx) = oneshot::channel();
         self.sender.send(WalletCommand::ListSpaces { resp }).await?;
         resp_rx.await?
     }
 
+    pub async fn send_list_spaces_by_state(
+        &self,
+        state: Option<SpaceStateFilter>,
+    ) -> anyhow::Result<SpacesByState> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::ListSpacesByState { state, resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_get_spaces_value_locked(&self) -> anyhow::Result<SpacesValueLocked> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::GetSpacesValueLocked { resp })
+            .await?;
+        resp_rx.await?
+    }
+
     pub async fn send_list_bidouts(&self) -> anyhow::Result<Vec<DoubleUtxo>> {
         let (resp, resp_rx) = oneshot::channel();
         self.sender
@@ -1043,17 +5662,353 @@ impl RpcWallet {
         resp_rx.await?
     }
 
+    pub async fn send_list_output_groups(&self) -> anyhow::Result<OutputGroups> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::ListOutputGroups { resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_estimate_vsize(
+        &self,
+        request: RpcWalletRequest,
+    ) -> anyhow::Result<VsizeEstimate> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::EstimateVsize { request, resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_check_operation(
+        &self,
+        request: RpcWalletRequest,
+    ) -> anyhow::Result<OperationCheck> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::CheckOperation { request, resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_get_space_info(&self, space: String) -> anyhow::Result<WalletSpaceInfo> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::GetSpaceInfo { space, resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_sign_message(
+        &self,
+        space: String,
+        message: String,
+    ) -> anyhow::Result<SignedMessage> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::SignMessage {
+                space,
+                message,
+                resp,
+            })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_get_snapshot(&self) -> anyhow::Result<WalletSnapshot> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender.send(WalletCommand::GetSnapshot { resp }).await?;
+        resp_rx.await?
+    }
+
     pub async fn send_get_balance(&self) -> anyhow::Result<Balance> {
         let (resp, resp_rx) = oneshot::channel();
         self.sender.send(WalletCommand::GetBalance { resp }).await?;
         resp_rx.await?
     }
 
+    pub async fn send_set_fee_policy(&self, policy: FeePolicy) -> anyhow::Result<()> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::SetFeePolicy { policy, resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_get_fee_policy(&self) -> anyhow::Result<FeePolicy> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::GetFeePolicy { resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_set_confirm_policy(&self, policy: ConfirmPolicy) -> anyhow::Result<()> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::SetConfirmPolicy { policy, resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_get_confirm_policy(&self) -> anyhow::Result<ConfirmPolicy> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::GetConfirmPolicy { resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_set_tx_note(&self, txid: Txid, note: String) -> anyhow::Result<()> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::SetTxNote { txid, note, resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_get_tx_note(&self, txid: Txid) -> anyhow::Result<Option<String>> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::GetTxNote { txid, resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_get_sync_progress(&self) -> anyhow::Result<WalletSyncProgress> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::GetSyncProgress { resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    pub async fn send_has_tx(&self, txid: Txid) -> anyhow::Result<bool> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::HasTx { txid, resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    /// Subscribe to balance updates, a new balance is published whenever
+    /// it changes after applying a block or handling a wallet command.
+    pub async fn subscribe_balance(&self) -> anyhow::Result<broadcast::Receiver<Balance>> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::SubscribeBalance { resp })
+            .await?;
+        resp_rx.await?
+    }
+
+    /// Subscribe to sync progress, published every few blocks while the wallet
+    /// is syncing towards the node's tip.
+    pub async fn subscribe_sync_progress(
+        &self,
+    ) -> anyhow::Result<broadcast::Receiver<SyncProgress>> {
+        let (resp, resp_rx) = oneshot::channel();
+        self.sender
+            .send(WalletCommand::SubscribeSyncProgress { resp })
+            .await?;
+        resp_rx.await?
+    }
+
     pub async fn unload_wallet(&self) {
         _ = self.sender.send(WalletCommand::UnloadWallet);
     }
 }
 
+/// Number of confirmations of a transaction, 0 if unconfirmed
+fn tx_depth(tip_height: u32, confirmation: ConfirmationTime) -> u32 {
+    match confirmation {
+        ConfirmationTime::Confirmed { height, .. } => tip_height.saturating_sub(height) + 1,
+        ConfirmationTime::Unconfirmed { .. } => 0,
+    }
+}
+
+const MAX_STANDARD_TX_WEIGHT: u64 = 400_000;
+const MAX_STANDARD_TX_SIGOPS_COST: usize = 16_000;
+const WITNESS_SCALE_FACTOR: usize = 4;
+const MAX_OP_RETURN_RELAY: usize = 83;
+
+/// Checks a transaction against bitcoind's default relay policy so that
+/// common rejections can be reported with the specific output at fault
+pub fn check_standardness(tx: &Transaction) -> Vec<StandardnessViolation> {
+    let mut violations = Vec::new();
+    let mut violation = |rule: &str, output: Option<usize>, message: String| {
+        violations.push(StandardnessViolation {
+            rule: rule.to_string(),
+            output,
+            message,
+        })
+    };
+
+    let version = tx.version.0;
+    if !(1..=3).contains(&version) {
+        violation(
+            "version",
+            None,
+            format!("transaction version {} is non-standard", version),
+        );
+    }
+
+    let weight = tx.weight().to_wu();
+    if weight > MAX_STANDARD_TX_WEIGHT {
+        violation(
+            "tx-size",
+            None,
+            format!(
+                "transaction weight {} exceeds the standard limit of {}",
+                weight, MAX_STANDARD_TX_WEIGHT
+            ),
+        );
+    }
+
+    let mut data_carriers = 0;
+    for (n, output) in tx.output.iter().enumerate() {
+        let script = &output.script_pubkey;
+        if script.is_op_return() {
+            data_carriers += 1;
+            if script.len() > MAX_OP_RETURN_RELAY {
+                violation(
+                    "datacarrier",
+                    Some(n),
+                    format!(
+                        "OP_RETURN output is {} bytes, more than the standard {} bytes",
+                        script.len(),
+                        MAX_OP_RETURN_RELAY
+                    ),
+                );
+            }
+            continue;
+        }
+
+        if !(script.is_p2pkh()
+            || script.is_p2sh()
+            || script.is_p2pk()
+            || script.is_witness_program())
+        {
+            violation(
+                "scriptpubkey",
+                Some(n),
+                "output script is not a standard script type".to_string(),
+            );
+            continue;
+        }
+
+        let dust_limit = script.minimal_non_dust();
+        if output.value < dust_limit {
+            violation(
+                "dust",
+                Some(n),
+                format!(
+                    "output value {} sat is below the dust limit of {} sat",
+                    output.value.to_sat(),
+                    dust_limit.to_sat()
+                ),
+            );
+        }
+    }
+    if data_carriers > 1 {
+        violation(
+            "multi-op-return",
+            None,
+            format!(
+                "transaction has {} OP_RETURN outputs, only one is standard",
+                data_carriers
+            ),
+        );
+    }
+
+    let legacy_sigops: usize = tx
+        .input
+        .iter()
+        .map(|input| input.script_sig.count_sigops_legacy())
+        .chain(
+            tx.output
+                .iter()
+                .map(|output| output.script_pubkey.count_sigops_legacy()),
+        )
+        .sum();
+    let sigops_cost = legacy_sigops * WITNESS_SCALE_FACTOR;
+    if sigops_cost > MAX_STANDARD_TX_SIGOPS_COST {
+        violation(
+            "bad-txns-too-many-sigops",
+            None,
+            format!(
+                "transaction sigops cost {} exceeds the standard limit of {}",
+                sigops_cost, MAX_STANDARD_TX_SIGOPS_COST
+            ),
+        );
+    }
+
+    violations
+}
+
+/// Outpoint, sequence, empty script sig and a schnorr signature witness
+const TAPROOT_KEY_SPEND_INPUT_WEIGHT: u64 = (32 + 4 + 4 + 1) * 4 + 66;
+
+const MISSING_INPUTS_ERROR: &str = "bad-txns-inputs-missingorspent";
+
+/// Pending updates of a space followed in the mempool when resolving its address
+const MAX_PENDING_SPACE_UPDATES: usize = 25;
+
+/// How close the unused address gap may get to the lookahead before new addresses warn
+const GAP_LIMIT_WARNING_MARGIN: u32 = 5;
+
+/// How many times a batch transaction is rebuilt after its coins were spent elsewhere
+const MAX_CONFLICT_RETRIES: usize = 3;
+
+/// Streamed batch results buffered before the wallet waits for the receiver
+const BATCH_STREAM_BUFFER: usize = 16;
+
+/// How often a wallet waiting to sync checks if the node left initial block download
+const IBD_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Files in the wallet's data directory, encrypted along with its store
+const TX_NOTES_FILE: &str = "tx_notes.json";
+const FEE_POLICY_FILE: &str = "fee_policy.json";
+const CONFIRM_POLICY_FILE: &str = "confirm_policy.json";
+
+/// How often unconfirmed wallet transactions are checked against the node's mempool
+const RECONCILE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Default seconds an unconfirmed transaction must be missing from the mempool before it's removed
+pub const DEFAULT_DROPPED_TX_GRACE_PERIOD: u64 = 60 * 60;
+
+/// Default number of requests all wallets together may have in flight to the bitcoin node
+pub const DEFAULT_WALLET_RPC_CONCURRENCY: usize = 8;
+
+/// Fee rates above this are almost certainly a units mistake (e.g. sat/kvB passed as sat/vB)
+const MAX_SANE_FEE_RATE: FeeRate = FeeRate::from_sat_per_vb_unchecked(5000);
+
+fn check_fee_rate(fee_rate: FeeRate) -> anyhow::Result<()> {
+    if fee_rate < FeeRate::BROADCAST_MIN {
+        return Err(anyhow!(
+            "fee rate {} sat/kwu is below the minimum relay fee of {} sat/vB; \
+            fee rates must be in sat/vB (was it given in BTC/kvB?)",
+            fee_rate.to_sat_per_kwu(),
+            FeeRate::BROADCAST_MIN.to_sat_per_vb_ceil()
+        ));
+    }
+    if fee_rate > MAX_SANE_FEE_RATE {
+        return Err(anyhow!(
+            "fee rate {} sat/vB is above the sanity limit of {} sat/vB; \
+            fee rates must be in sat/vB (was it given in sat/kvB?)",
+            fee_rate.to_sat_per_vb_ceil(),
+            MAX_SANE_FEE_RATE.to_sat_per_vb_ceil()
+        ));
+    }
+    Ok(())
+}
+
+/// Lowest fee rate a replacement may pay, since BIP-125 requires it to exceed
+/// the fee rate of the replaced transaction by the incremental relay fee
+fn min_replacement_fee_rate(current: FeeRate) -> FeeRate {
+    FeeRate::from_sat_per_kwu(current.to_sat_per_kwu() + FeeRate::BROADCAST_MIN.to_sat_per_kwu())
+}
+
 // Extracts fee rate from example rpc message: "insufficient fee, rejecting replacement
 // 96bb0d5fa00a35e888ff8afb5b41903955b8f34b5b2de01d874ae579a4d1eba0;
 // new feerate 0.01000000 BTC/kvB <= old feerate 0.01000000 BTC/kvB"
@@ -1070,9 +6025,45 @@ fn fee_rate_from_message(message: &str) -> Option<FeeRate> {
     let old_fee_str = fee_rates.get(1)?;
 
     let fee_value = old_fee_str.split_whitespace().nth(2)?.parse::<f64>().ok()?;
+    fee_rate_from_btc_per_kvb(fee_value)
+}
+
+fn fee_rate_from_btc_per_kvb(btc_per_kvb: f64) -> Option<FeeRate> {
+    if !btc_per_kvb.is_finite() || btc_per_kvb < 0.0 {
+        return None;
+    }
+    // Rounded in sat/kwu since values like 0.00007 aren't exact as floats and
+    // truncating to whole sat/vB would drop fractional rates entirely
+    let sat_per_kwu = (btc_per_kvb * 25_000_000.0).round();
+    if sat_per_kwu > u64::MAX as f64 {
+        return None;
+    }
+    Some(FeeRate::from_sat_per_kwu(sat_per_kwu as u64))
+}
 
-    let fee_rate_sat_vb = (fee_value * 100_000.0) as u64;
-    FeeRate::from_sat_per_vb(fee_rate_sat_vb)
+/// Orders transactions so each one comes after any of the given ones it spends,
+/// keeping the original order otherwise
+fn parents_first(mut remaining: Vec<Transaction>) -> Vec<Transaction> {
+    let mut unordered: HashSet<Txid> = remaining.iter().map(|tx| tx.compute_txid()).collect();
+    let mut ordered = Vec::with_capacity(remaining.len());
+    while !remaining.is_empty() {
+        let (ready, blocked): (Vec<_>, Vec<_>) = remaining.into_iter().partition(|tx| {
+            !tx.input
+                .iter()
+                .any(|input| unordered.contains(&input.previous_output.txid))
+        });
+        if ready.is_empty() {
+            // Can't happen for valid transactions, keep whatever is left as is
+            ordered.extend(blocked);
+            break;
+        }
+        for tx in ready.iter() {
+            unordered.remove(&tx.compute_txid());
+        }
+        ordered.extend(ready);
+        remaining = blocked;
+    }
+    ordered
 }
 
 async fn named_future<T>(
@@ -1081,3 +6072,69 @@ async fn named_future<T>(
 ) -> (String, Result<T, tokio::sync::oneshot::error::RecvError>) {
     (name, rx.await)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fee_rate_from_btc_per_kvb() {
+        assert_eq!(
+            fee_rate_from_btc_per_kvb(0.00007),
+            FeeRate::from_sat_per_vb(7),
+            "must not truncate rates that aren't exact as floats"
+        );
+        assert_eq!(
+            fee_rate_from_btc_per_kvb(0.000015),
+            Some(FeeRate::from_sat_per_kwu(375)),
+            "must keep fractional sat/vB rates"
+        );
+        assert_eq!(fee_rate_from_btc_per_kvb(0.0), Some(FeeRate::ZERO));
+        assert_eq!(fee_rate_from_btc_per_kvb(-0.0001), None);
+        assert_eq!(fee_rate_from_btc_per_kvb(f64::NAN), None);
+    }
+
+    #[test]
+    fn test_parents_first() {
+        let spend = |previous_output: OutPoint| Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![bitcoin::TxIn {
+                previous_output,
+                ..Default::default()
+            }],
+            output: vec![bitcoin::TxOut {
+                value: Amount::from_sat(1000),
+                script_pubkey: ScriptBuf::new(),
+            }],
+        };
+        let parent = spend(OutPoint::new(Txid::all_zeros(), 0));
+        let child = spend(OutPoint::new(parent.compute_txid(), 0));
+        let grandchild = spend(OutPoint::new(child.compute_txid(), 0));
+        let unrelated = spend(OutPoint::new(Txid::all_zeros(), 1));
+
+        let ordered = parents_first(vec![
+            grandchild.clone(),
+            child.clone(),
+            unrelated.clone(),
+            parent.clone(),
+        ]);
+        assert_eq!(
+            ordered,
+            vec![unrelated, parent, child, grandchild],
+            "parents must come before their children"
+        );
+    }
+
+    #[test]
+    fn test_fee_rate_from_message() {
+        let message = "insufficient fee, rejecting replacement \
+            96bb0d5fa00a35e888ff8afb5b41903955b8f34b5b2de01d874ae579a4d1eba0; \
+            new feerate 0.00007000 BTC/kvB <= old feerate 0.00007000 BTC/kvB";
+        assert_eq!(fee_rate_from_message(message), FeeRate::from_sat_per_vb(7));
+        assert_eq!(
+            fee_rate_from_message("bad-txns-inputs-missingorspent"),
+            None
+        );
+    }
+}
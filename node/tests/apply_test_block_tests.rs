@@ -0,0 +1,115 @@
+//! Run with `cargo test --features testing`
+#![cfg(feature = "testing")]
+
+use std::str::FromStr;
+
+use protocol::bitcoin::{
+    absolute::LockTime,
+    block::{Header, Version},
+    consensus::encode::serialize_hex,
+    hashes::Hash,
+    transaction, Address, Amount, Block, CompactTarget, OutPoint, Transaction, TxIn, TxMerkleNode,
+    TxOut, Txid,
+};
+use spaced::{
+    rpc::{RpcClient, TestingRpcClient},
+    wallets::AddressKind,
+};
+use testutil::TestRig;
+
+const WALLET: &str = "wallet_test_block";
+
+#[tokio::test]
+async fn it_should_apply_test_blocks_to_wallets() -> anyhow::Result<()> {
+    let rig = TestRig::new().await?;
+    rig.mine_blocks(10, None).await?;
+    rig.wait_until_synced().await?;
+    rig.spaced.client.wallet_create(WALLET).await?;
+    rig.wait_until_wallet_synced(WALLET).await?;
+
+    let before = rig.spaced.client.wallet_get_balance(WALLET, None).await?;
+    let tip = rig.spaced.client.wallet_get_info(WALLET).await?.tip;
+    let prev = rig.get_block_hash(tip as u64).await?;
+
+    let address = rig
+        .spaced
+        .client
+        .wallet_get_new_address(WALLET, AddressKind::Coin, None)
+        .await?;
+    let script_pubkey = Address::from_str(&address)?
+        .assume_checked()
+        .script_pubkey();
+
+    // A coin and an output small enough to be holding a space
+    let tx = Transaction {
+        version: transaction::Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint {
+                txid: Txid::all_zeros(),
+                vout: 0,
+            },
+            ..Default::default()
+        }],
+        output: vec![
+            TxOut {
+                value: Amount::ONE_BTC,
+                script_pubkey: script_pubkey.clone(),
+            },
+            TxOut {
+                value: Amount::from_sat(666),
+                script_pubkey,
+            },
+        ],
+    };
+    let txid = tx.compute_txid();
+    let block = Block {
+        header: Header {
+            version: Version::TWO,
+            prev_blockhash: prev,
+            merkle_root: TxMerkleNode::all_zeros(),
+            time: 0,
+            bits: CompactTarget::from_consensus(0x207fffff),
+            nonce: 0,
+        },
+        txdata: vec![tx],
+    };
+
+    assert!(
+        rig.spaced
+            .client
+            .wallet_apply_test_block(WALLET, serialize_hex(&block), tip + 2)
+            .await
+            .is_err(),
+        "blocks must connect to the wallet tip"
+    );
+    rig.spaced
+        .client
+        .wallet_apply_test_block(WALLET, serialize_hex(&block), tip + 1)
+        .await?;
+
+    assert_eq!(
+        rig.spaced.client.wallet_get_info(WALLET).await?.tip,
+        tip + 1
+    );
+    let after = rig.spaced.client.wallet_get_balance(WALLET, None).await?;
+    assert_eq!(
+        after.balance,
+        before.balance + Amount::ONE_BTC,
+        "only the coin is spendable"
+    );
+    assert_eq!(
+        after.details.dust,
+        before.details.dust + Amount::from_sat(666),
+        "small outputs are held back as they may carry spaces"
+    );
+
+    let spaces = rig.spaced.client.wallet_list_spaces(WALLET, None).await?;
+    assert!(
+        spaces
+            .iter()
+            .all(|space| space.output.outpoint.txid != txid),
+        "outputs without a space in the chain state are not spaces"
+    );
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use protocol::bitcoin::Amount;
+use spaced::wallets::{parse_bitcoin_uri, payment_uri, AddressKind, BitcoinUri};
+
+const ADDRESS: &str = "bcrt1qjh6yl2ajfpzpmz8nyyv8ysgn0c5rf0mcxxsa2r";
+
+#[test]
+fn it_should_parse_amounts() -> anyhow::Result<()> {
+    let uri = parse_bitcoin_uri(&format!("bitcoin:{}?amount=0.0005", ADDRESS))?;
+    assert_eq!(
+        uri,
+        Some(BitcoinUri {
+            address: ADDRESS.to_string(),
+            amount: Some(Amount::from_sat(50_000)),
+            label: None,
+        })
+    );
+
+    let uri = parse_bitcoin_uri(&format!("BITCOIN:{}?amount=1", ADDRESS))?.unwrap();
+    assert_eq!(uri.amount, Some(Amount::ONE_BTC));
+
+    assert!(parse_bitcoin_uri(&format!("bitcoin:{}?amount=abc", ADDRESS)).is_err());
+    assert!(
+        parse_bitcoin_uri(&format!("bitcoin:{}?amount=0.000000001", ADDRESS)).is_err(),
+        "amounts can't be more precise than a sat"
+    );
+    Ok(())
+}
+
+#[test]
+fn it_should_decode_labels() -> anyhow::Result<()> {
+    let uri = parse_bitcoin_uri(&format!(
+        "bitcoin:{}?label=Coffee+for%20Bob%21&message=ignored",
+        ADDRESS
+    ))?
+    .unwrap();
+    assert_eq!(uri.label.as_deref(), Some("Coffee for Bob!"));
+
+    let uri = parse_bitcoin_uri(&format!("bitcoin:{}?label=caf%C3%A9", ADDRESS))?.unwrap();
+    assert_eq!(uri.label.as_deref(), Some("café"));
+
+    assert!(parse_bitcoin_uri(&format!("bitcoin:{}?label=100%", ADDRESS)).is_err());
+    assert!(parse_bitcoin_uri(&format!("bitcoin:{}?label=%zz", ADDRESS)).is_err());
+    assert!(
+        parse_bitcoin_uri(&format!("bitcoin:{}?label=%FF", ADDRESS)).is_err(),
+        "labels must decode to utf-8"
+    );
+    Ok(())
+}
+
+#[test]
+fn it_should_round_trip_payment_uris() -> anyhow::Result<()> {
+    let uri = payment_uri(
+        AddressKind::Coin,
+        ADDRESS,
+        Some(Amount::from_sat(1234)),
+        Some("rent & utilities"),
+    );
+    let parsed = parse_bitcoin_uri(&uri)?.unwrap();
+    assert_eq!(parsed.amount, Some(Amount::from_sat(1234)));
+    assert_eq!(parsed.label.as_deref(), Some("rent & utilities"));
+    Ok(())
+}
+
+#[test]
+fn it_should_reject_required_parameters() {
+    let err = parse_bitcoin_uri(&format!("bitcoin:{}?req-somethingnew=1", ADDRESS))
+        .expect_err("unknown required parameters must be rejected");
+    assert!(err.to_string().contains("req-somethingnew"));
+
+    assert!(
+        parse_bitcoin_uri(&format!("bitcoin:{}?somethingnew=1", ADDRESS)).is_ok(),
+        "unknown optional parameters are ignored"
+    );
+}
+
+#[test]
+fn it_should_ignore_other_schemes() -> anyhow::Result<()> {
+    assert_eq!(parse_bitcoin_uri(ADDRESS)?, None);
+    assert_eq!(parse_bitcoin_uri(&format!("spaces:{}", ADDRESS))?, None);
+    assert_eq!(parse_bitcoin_uri("@example")?, None);
+    Ok(())
+}
+
+#[test]
+fn it_should_reject_empty_addresses() {
+    assert!(parse_bitcoin_uri("bitcoin:").is_err());
+    assert!(parse_bitcoin_uri("bitcoin:?amount=1").is_err());
+}
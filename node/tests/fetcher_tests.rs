@@ -1,12 +1,33 @@
 use std::{
-    sync::mpsc::TryRecvError,
+    collections::HashMap,
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::TryRecvError,
+        Arc,
+    },
     time::{Duration, Instant},
 };
 
 use anyhow::Result;
-use protocol::{bitcoin::BlockHash, constants::ChainAnchor};
-use spaced::source::{BitcoinBlockSource, BitcoinRpc, BitcoinRpcAuth, BlockEvent, BlockFetcher};
-use testutil::TestRig;
+use assert_cmd::Command;
+use base64::{prelude::BASE64_STANDARD, Engine};
+use protocol::{
+    bitcoin::{
+        block::{Header, Version},
+        consensus::encode::serialize_hex,
+        hashes::Hash,
+        Block, BlockHash, CompactTarget, TxMerkleNode,
+    },
+    constants::ChainAnchor,
+};
+use serde_json::{json, Value};
+use spaced::source::{
+    BitcoinBlockSource, BitcoinRpc, BitcoinRpcAuth, BlockEvent, BlockFetchError, BlockFetcher,
+    RetryPolicy,
+};
+use testutil::{bitcoind::tempfile::tempdir, TestRig};
 
 async fn setup(blocks: u64) -> Result<(TestRig, u64, BlockHash)> {
     let rig = TestRig::new().await?;
@@ -25,6 +46,7 @@ fn test_block_fetching_from_bitcoin_rpc() -> Result<()> {
     let fetcher_rpc = BitcoinBlockSource::new(BitcoinRpc::new(
         &rig.bitcoind.rpc_url(),
         BitcoinRpcAuth::UserPass("user".to_string(), "password".to_string()),
+        RetryPolicy::default(),
     ));
     let (fetcher, receiver) = BlockFetcher::new(fetcher_rpc.clone(), 8);
     fetcher.start(ChainAnchor { hash, height: 0 });
@@ -43,6 +65,7 @@ fn test_block_fetching_from_bitcoin_rpc() -> Result<()> {
                     break;
                 }
             }
+            Ok(BlockEvent::TipPreview(..)) | Ok(BlockEvent::Progress { .. }) => {}
             Ok(BlockEvent::Error(e)) => panic!("Unexpected error: {}", e),
             Err(TryRecvError::Empty) => {
                 std::thread::sleep(Duration::from_millis(10));
@@ -52,5 +75,438 @@ fn test_block_fetching_from_bitcoin_rpc() -> Result<()> {
     }
 
     assert_eq!(height, GENERATED_BLOCKS, "Not all blocks were received");
+
+    // Progress is always reported for the last block of a run
+    loop {
+        match receiver.recv_timeout(timeout.saturating_sub(start_time.elapsed())) {
+            Ok(BlockEvent::Progress { height, tip }) => {
+                assert_eq!(
+                    tip, GENERATED_BLOCKS as u32,
+                    "progress must be towards the tip"
+                );
+                if height == GENERATED_BLOCKS as u32 {
+                    break;
+                }
+            }
+            Ok(BlockEvent::Error(e)) => panic!("Unexpected error: {}", e),
+            Ok(_) => {}
+            Err(_) => panic!("No progress reported for the last block"),
+        }
+    }
+    Ok(())
+}
+
+/// An http request read by a mock node
+struct HttpRequest {
+    head: String,
+    body: Vec<u8>,
+}
+
+/// Reads a full http request (headers and body) from the stream
+fn read_http_request(stream: &mut TcpStream) -> Result<HttpRequest> {
+    let mut data = Vec::new();
+    let mut buf = [0u8; 1024];
+    loop {
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            return Ok(HttpRequest {
+                head: String::from_utf8_lossy(&data).to_string(),
+                body: vec![],
+            });
+        }
+        data.extend_from_slice(&buf[..n]);
+
+        let text = String::from_utf8_lossy(&data);
+        if let Some(header_end) = text.find("\r\n\r\n") {
+            let content_length = text[..header_end]
+                .lines()
+                .find_map(|line| {
+                    let (name, value) = line.split_once(':')?;
+                    if name.eq_ignore_ascii_case("content-length") {
+                        value.trim().parse::<usize>().ok()
+                    } else {
+                        None
+                    }
+                })
+                .unwrap_or(0);
+            if data.len() >= header_end + 4 + content_length {
+                return Ok(HttpRequest {
+                    head: text[..header_end].to_string(),
+                    body: data[header_end + 4..header_end + 4 + content_length].to_vec(),
+                });
+            }
+        }
+    }
+}
+
+/// Starts a mock node answering every request on its own connection with the
+/// status and body returned by `respond`, returning the node's url
+fn mock_node<F>(respond: F) -> Result<String>
+where
+    F: Fn(&HttpRequest) -> (u16, String) + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let url = format!("http://{}", listener.local_addr()?);
+    let respond = Arc::new(respond);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else {
+                return;
+            };
+            let respond = respond.clone();
+            std::thread::spawn(move || -> Result<()> {
+                let request = read_http_request(&mut stream)?;
+                let (status, body) = respond(&request);
+                write!(
+                    stream,
+                    "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    if status == 200 { "OK" } else { "Error" },
+                    body.len(),
+                    body
+                )?;
+                stream.flush()?;
+                Ok(())
+            });
+        }
+    });
+    Ok(url)
+}
+
+/// Answers a json-rpc request or batch laid out the way bitcoind writes it,
+/// with `result` giving the result of each call from its method and params
+fn rpc_reply(body: &[u8], result: impl Fn(&str, &Value) -> Value) -> String {
+    let reply = |call: &Value| {
+        format!(
+            "{{\"result\":{},\"error\":null,\"id\":{}}}",
+            result(call["method"].as_str().unwrap_or_default(), &call["params"]),
+            call["id"]
+        )
+    };
+    match serde_json::from_slice(body).expect("json-rpc request") {
+        Value::Array(calls) => {
+            let replies: Vec<_> = calls.iter().map(reply).collect();
+            format!("[{}]\n", replies.join(","))
+        }
+        call => format!("{}\n", reply(&call)),
+    }
+}
+
+fn mock_block(prev_blockhash: BlockHash, nonce: u32) -> Block {
+    Block {
+        header: Header {
+            version: Version::ONE,
+            prev_blockhash,
+            merkle_root: TxMerkleNode::all_zeros(),
+            time: 0,
+            bits: CompactTarget::from_consensus(0x207fffff),
+            nonce,
+        },
+        txdata: vec![],
+    }
+}
+
+/// A mock node whose two blocks at the tip are replaced between the fetcher
+/// looking up the tip's hash and emitting it. Returns the node's url and the
+/// chain it ends up with.
+fn reorging_node(tip: u32) -> Result<(String, Vec<BlockHash>)> {
+    let mut blocks = HashMap::new();
+    let mut chain = vec![];
+    let mut prev = BlockHash::all_zeros();
+    for height in 0..=tip {
+        let block = mock_block(prev, height);
+        prev = block.block_hash();
+        chain.push(prev);
+        blocks.insert(prev, block);
+    }
+    // The orphaned tip, handed out the first time its height is looked up
+    let mut prev = chain[tip as usize - 2];
+    let mut orphaned = vec![];
+    for height in tip - 1..=tip {
+        let block = mock_block(prev, 1000 + height);
+        prev = block.block_hash();
+        orphaned.push(prev);
+        blocks.insert(prev, block);
+    }
+
+    let best = chain.clone();
+    let tip_lookups = AtomicUsize::new(0);
+    let url = mock_node(move |request| {
+        let body = rpc_reply(&request.body, |method, params| match method {
+            "getblockchaininfo" => json!({
+                "blocks": tip,
+                "bestblockhash": best[tip as usize],
+                "initialblockdownload": false,
+                "mediantime": 0,
+            }),
+            "getblockhash" => {
+                let height = params[0].as_u64().expect("height") as u32;
+                if height == tip && tip_lookups.fetch_add(1, Ordering::SeqCst) == 0 {
+                    return json!(orphaned[1]);
+                }
+                json!(best[height as usize])
+            }
+            "getblock" => {
+                let hash: BlockHash = serde_json::from_value(params[0].clone()).expect("hash");
+                json!(serialize_hex(&blocks[&hash]))
+            }
+            method => panic!("unexpected rpc call {}", method),
+        });
+        (200, body)
+    })?;
+    Ok((url, chain))
+}
+
+#[test]
+fn test_block_fetcher_refetches_an_orphaned_tip() -> Result<()> {
+    const TIP: u32 = 6;
+    let (url, chain) = reorging_node(TIP)?;
+    let source = BitcoinBlockSource::new(BitcoinRpc::new(
+        &url,
+        BitcoinRpcAuth::None,
+        RetryPolicy::default(),
+    ));
+    let (fetcher, receiver) = BlockFetcher::new(source, 2);
+    fetcher.start(ChainAnchor {
+        hash: chain[0],
+        height: 0,
+    });
+
+    let mut next_height = 1;
+    while next_height <= TIP {
+        match receiver.recv_timeout(Duration::from_secs(5)) {
+            Ok(BlockEvent::Block(id, block)) => {
+                assert_eq!(id.height, next_height, "blocks must be emitted in order");
+                assert_eq!(
+                    id.hash, chain[id.height as usize],
+                    "blocks must be on the best chain"
+                );
+                assert_eq!(block.block_hash(), id.hash);
+                next_height += 1;
+            }
+            Ok(BlockEvent::Error(e)) => panic!("Unexpected error: {}", e),
+            Ok(_) => {}
+            Err(_) => panic!("Test timed out"),
+        }
+    }
+    fetcher.stop();
+
+    // Without re-fetching the consumer has to roll back
+    let (url, chain) = reorging_node(TIP)?;
+    let source = BitcoinBlockSource::new(BitcoinRpc::new(
+        &url,
+        BitcoinRpcAuth::None,
+        RetryPolicy::default(),
+    ));
+    let (mut fetcher, receiver) = BlockFetcher::new(source, 2);
+    fetcher.set_tip_refetch_depth(0);
+    fetcher.start(ChainAnchor {
+        hash: chain[0],
+        height: 0,
+    });
+    loop {
+        match receiver.recv_timeout(Duration::from_secs(5)) {
+            Ok(BlockEvent::Block(id, _)) => {
+                assert!(id.height < TIP, "the orphaned tip must not be emitted")
+            }
+            Ok(BlockEvent::Error(BlockFetchError::BlockMismatch)) => break,
+            Ok(BlockEvent::Error(e)) => panic!("Unexpected error: {}", e),
+            Ok(_) => {}
+            Err(_) => panic!("Test timed out"),
+        }
+    }
+    fetcher.stop();
+    Ok(())
+}
+
+#[test]
+fn test_rpc_reloads_a_rotated_cookie_once() -> Result<()> {
+    let dir = tempdir()?;
+    let cookie = dir.path().join(".cookie");
+    std::fs::write(&cookie, "__cookie__:first\n")?;
+
+    let accepted = format!("Basic {}", BASE64_STANDARD.encode("__cookie__:second"));
+    let requests = Arc::new(AtomicUsize::new(0));
+    let url = mock_node({
+        let requests = requests.clone();
+        move |request| {
+            requests.fetch_add(1, Ordering::SeqCst);
+            let authorized = request.head.lines().any(|line| {
+                line.split_once(':').is_some_and(|(name, value)| {
+                    name.eq_ignore_ascii_case("authorization") && value.trim() == accepted
+                })
+            });
+            match authorized {
+                true => (200, rpc_reply(&request.body, |_, _| json!(42))),
+                false => (401, String::new()),
+            }
+        }
+    })?;
+
+    let rpc = BitcoinRpc::new(
+        &url,
+        BitcoinRpcAuth::Cookie(cookie.clone()),
+        RetryPolicy::default(),
+    );
+    let client = reqwest::blocking::Client::new();
+
+    // The node restarted with a new cookie since the client read it
+    std::fs::write(&cookie, "__cookie__:second\n")?;
+    let count: u64 = rpc.send_json_blocking(&client, &rpc.get_block_count())?;
+    assert_eq!(count, 42, "request should succeed with the new cookie");
+    assert_eq!(
+        requests.load(Ordering::SeqCst),
+        2,
+        "should retry once after reloading"
+    );
+
+    // A reloaded cookie that's still rejected isn't retried again
+    std::fs::write(&cookie, "__cookie__:third\n")?;
+    let result: std::result::Result<u64, _> =
+        rpc.send_json_blocking(&client, &rpc.get_block_count());
+    assert!(
+        result.is_err(),
+        "request should fail with a rejected cookie"
+    );
+    assert_eq!(requests.load(Ordering::SeqCst), 4, "should retry only once");
+
+    // Nor is one that didn't change
+    let result: std::result::Result<u64, _> =
+        rpc.send_json_blocking(&client, &rpc.get_block_count());
+    assert!(
+        result.is_err(),
+        "request should fail with a rejected cookie"
+    );
+    assert_eq!(
+        requests.load(Ordering::SeqCst),
+        5,
+        "should not retry with the same cookie"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_missing_cookie_fails_at_startup() -> Result<()> {
+    let dir = tempdir()?;
+    Command::cargo_bin("spaced")?
+        .args(["--chain", "regtest", "--bitcoin-rpc-cookie"])
+        .arg(dir.path().join("missing.cookie"))
+        .arg("--data-dir")
+        .arg(dir.path())
+        .timeout(Duration::from_secs(30))
+        .assert()
+        .failure();
+    Ok(())
+}
+
+#[test]
+fn test_rpc_retries_on_connection_reset() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let url = format!("http://{}", listener.local_addr()?);
+
+    let server = std::thread::spawn(move || -> Result<()> {
+        // Drop the first connection right away
+        let (stream, _) = listener.accept()?;
+        drop(stream);
+
+        // Drop the second connection mid-request after reading it
+        let (mut stream, _) = listener.accept()?;
+        read_http_request(&mut stream)?;
+        drop(stream);
+
+        // Answer the third one
+        let (mut stream, _) = listener.accept()?;
+        read_http_request(&mut stream)?;
+        let body = r#"{"result":42,"error":null,"id":"0"}"#;
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )?;
+        stream.flush()?;
+        Ok(())
+    });
+
+    let rpc = BitcoinRpc::new(
+        &url,
+        BitcoinRpcAuth::UserPass("user".to_string(), "password".to_string()),
+        RetryPolicy::default(),
+    );
+    let client = reqwest::blocking::Client::new();
+    let count: u64 = rpc.send_json_blocking(&client, &rpc.get_block_count())?;
+
+    assert_eq!(count, 42, "request should succeed after retrying dropped connections");
+    server.join().expect("server thread")?;
+    Ok(())
+}
+
+#[test]
+fn test_block_fetcher_resumes_after_dying() -> Result<()> {
+    const GENERATED_BLOCKS: u32 = 20;
+
+    let (rig, _, hash) =
+        tokio::runtime::Runtime::new()?.block_on(setup(GENERATED_BLOCKS as u64))?;
+    let fetcher_rpc = BitcoinBlockSource::new(BitcoinRpc::new(
+        &rig.bitcoind.rpc_url(),
+        BitcoinRpcAuth::UserPass("user".to_string(), "password".to_string()),
+        RetryPolicy::default(),
+    ));
+    let (mut fetcher, receiver) = BlockFetcher::with_capacity(fetcher_rpc, 8, 1);
+    fetcher.start(ChainAnchor { hash, height: 0 });
+
+    let timeout = Duration::from_secs(5);
+    let start_time = Instant::now();
+    let mut received = 0;
+    while received < 2 {
+        assert!(
+            start_time.elapsed() < timeout,
+            "Test timed out after {:?}",
+            timeout
+        );
+        match receiver.recv_timeout(Duration::from_millis(10)) {
+            Ok(BlockEvent::Block(..)) => received += 1,
+            Ok(BlockEvent::Error(e)) => panic!("Unexpected error: {}", e),
+            _ => {}
+        }
+    }
+    assert!(
+        !fetcher.is_dead(),
+        "fetcher should be alive while its receiver is"
+    );
+
+    // The fetcher dies on its next send once nobody is listening
+    drop(receiver);
+    while !fetcher.is_dead() {
+        assert!(start_time.elapsed() < timeout, "fetcher did not die");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    let resume_from = fetcher.last_emitted().expect("emitted blocks");
+    assert!(
+        resume_from.height < GENERATED_BLOCKS,
+        "fetcher should die before the tip"
+    );
+    let receiver = fetcher.reconnect();
+    assert!(!fetcher.is_dead(), "reconnected fetcher should be running");
+
+    let mut next_height = resume_from.height + 1;
+    while next_height <= GENERATED_BLOCKS {
+        assert!(
+            start_time.elapsed() < timeout,
+            "Test timed out after {:?}",
+            timeout
+        );
+        match receiver.recv_timeout(Duration::from_millis(10)) {
+            Ok(BlockEvent::Block(id, _)) => {
+                assert_eq!(id.height, next_height, "blocks should resume in order");
+                next_height += 1;
+            }
+            Ok(BlockEvent::Error(e)) => panic!("Unexpected error: {}", e),
+            _ => {}
+        }
+    }
+
+    fetcher.stop();
     Ok(())
 }
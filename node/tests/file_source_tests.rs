@@ -0,0 +1,134 @@
+use std::{fs, path::PathBuf};
+
+use protocol::bitcoin::{
+    block::{Header, Version},
+    consensus,
+    constants::genesis_block,
+    hashes::Hash,
+    Block, BlockHash, CompactTarget, Network, TxMerkleNode,
+};
+use spaced::{node::BlockSource, source::FileBlockSource};
+
+const EASY_BITS: u32 = 0x207fffff;
+const HARD_BITS: u32 = 0x1d00ffff;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "spaces-block-files-{}-{}",
+        name,
+        std::process::id()
+    ));
+    _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn child(prev: &Block, bits: u32) -> Block {
+    Block {
+        header: Header {
+            version: Version::TWO,
+            prev_blockhash: prev.block_hash(),
+            merkle_root: TxMerkleNode::all_zeros(),
+            time: prev.header.time + 600,
+            bits: CompactTarget::from_consensus(bits),
+            nonce: 0,
+        },
+        txdata: vec![],
+    }
+}
+
+/// Writes blocks to `blk00000.dat` the way bitcoind lays them out, followed by
+/// pre-allocated zeroed space, obfuscated with `key` if one is given
+fn write_blocks(dir: &PathBuf, blocks: &[&Block], key: Option<[u8; 8]>) {
+    let mut data = Vec::new();
+    for block in blocks {
+        let raw = consensus::serialize(*block);
+        data.extend_from_slice(&Network::Regtest.magic().to_bytes());
+        data.extend_from_slice(&(raw.len() as u32).to_le_bytes());
+        data.extend_from_slice(&raw);
+    }
+    data.extend_from_slice(&[0u8; 256]);
+
+    if let Some(key) = key {
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte ^= key[i % 8];
+        }
+        fs::write(dir.join("xor.dat"), key).unwrap();
+    }
+    fs::write(dir.join("blk00000.dat"), data).unwrap();
+}
+
+fn best_hash(files: &FileBlockSource) -> BlockHash {
+    files.get_best_chain().unwrap().hash
+}
+
+#[test]
+fn it_should_read_blocks_from_block_files() -> anyhow::Result<()> {
+    let dir = temp_dir("plain");
+    let genesis = genesis_block(Network::Regtest);
+    let first = child(&genesis, EASY_BITS);
+    let second = child(&first, EASY_BITS);
+    write_blocks(&dir, &[&genesis, &first, &second], None);
+
+    let files = FileBlockSource::new(dir.clone(), Network::Regtest.magic())?;
+    assert_eq!(files.get_block_count()?, 2);
+    assert_eq!(best_hash(&files), second.block_hash());
+    assert_eq!(files.get_block_hash(1)?, first.block_hash());
+    assert_eq!(files.get_block(&genesis.block_hash())?, genesis);
+    assert_eq!(files.get_block(&second.block_hash())?, second);
+
+    fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn it_should_undo_xor_obfuscation() -> anyhow::Result<()> {
+    let dir = temp_dir("xor");
+    let genesis = genesis_block(Network::Regtest);
+    let first = child(&genesis, EASY_BITS);
+    write_blocks(&dir, &[&genesis, &first], Some([1, 2, 3, 4, 5, 6, 7, 8]));
+
+    let files = FileBlockSource::new(dir.clone(), Network::Regtest.magic())?;
+    assert_eq!(best_hash(&files), first.block_hash());
+    assert_eq!(files.get_block(&first.block_hash())?, first);
+
+    fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn it_should_select_the_chain_with_most_work() -> anyhow::Result<()> {
+    let dir = temp_dir("work");
+    let genesis = genesis_block(Network::Regtest);
+    let first = child(&genesis, EASY_BITS);
+    let second = child(&first, EASY_BITS);
+    let third = child(&second, EASY_BITS);
+    // A shorter fork that still has more cumulative work
+    let fork = child(&genesis, HARD_BITS);
+    write_blocks(&dir, &[&genesis, &first, &fork, &second, &third], None);
+
+    let files = FileBlockSource::new(dir.clone(), Network::Regtest.magic())?;
+    assert_eq!(files.get_block_count()?, 1);
+    assert_eq!(best_hash(&files), fork.block_hash());
+
+    fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[test]
+fn it_should_not_find_a_chain_without_genesis() -> anyhow::Result<()> {
+    let dir = temp_dir("pruned");
+    let genesis = genesis_block(Network::Regtest);
+    let first = child(&genesis, EASY_BITS);
+    let second = child(&first, EASY_BITS);
+    write_blocks(&dir, &[&first, &second], None);
+
+    let files = FileBlockSource::new(dir.clone(), Network::Regtest.magic())?;
+    assert!(
+        files.get_best_chain().is_err(),
+        "pruned files have no usable chain"
+    );
+
+    fs::remove_dir_all(dir)?;
+    Ok(())
+}
@@ -1,12 +1,14 @@
 use std::path::{PathBuf};
 use std::str::FromStr;
-use protocol::bitcoin::{Amount, FeeRate};
-use protocol::constants::RENEWAL_INTERVAL;
+use protocol::bitcoin::{Address, Amount, FeeRate, Network, OutPoint};
+use protocol::constants::{RENEWAL_INTERVAL, ROLLOUT_BLOCK_INTERVAL};
 use protocol::{Covenant};
 use protocol::script::SpaceScript;
-use spaced::rpc::{BidParams, ExecuteParams, OpenParams, RegisterParams, RpcClient, RpcWalletRequest, RpcWalletTxBuilder, TransferSpacesParams};
-use spaced::wallets::{AddressKind, WalletResponse};
+use spaced::rpc::{BidParams, ExecuteParams, OpenParams, OwnershipStep, RegisterParams, RpcClient, RpcWalletRequest, RpcWalletTxBuilder, SendCoinsParams, TransferSpacesParams};
+use spaced::wallets::{AddressKind, ConfirmPolicy, ExportFormat, HistoryEntry, HistoryEntryKind, OutbidLimits, SpaceStateFilter, SpacesByState, WalletResponse};
 use testutil::{TestRig};
+use wallet::address::SpaceAddress;
+use wallet::builder::CoinSelectionStrategy;
 use wallet::export::WalletExport;
 
 const ALICE: &str = "wallet_99";
@@ -57,9 +59,9 @@ async fn it_should_allow_outbidding(rig: &TestRig) -> anyhow::Result<()> {
     rig.wait_until_synced().await?;
     rig.wait_until_wallet_synced(BOB).await?;
     rig.wait_until_wallet_synced(ALICE).await?;
-    let bobs_spaces = rig.spaced.client.wallet_list_spaces(BOB).await?;
-    let alices_spaces = rig.spaced.client.wallet_list_spaces(ALICE).await?;
-    let alices_balance = rig.spaced.client.wallet_get_balance(ALICE).await?;
+    let bobs_spaces = rig.spaced.client.wallet_list_spaces(BOB, None).await?;
+    let alices_spaces = rig.spaced.client.wallet_list_spaces(ALICE, None).await?;
+    let alices_balance = rig.spaced.client.wallet_get_balance(ALICE, None).await?;
 
     let result = wallet_do(
         rig, BOB,
@@ -76,9 +78,9 @@ async fn it_should_allow_outbidding(rig: &TestRig) -> anyhow::Result<()> {
     rig.wait_until_synced().await?;
     rig.wait_until_wallet_synced(BOB).await?;
     rig.wait_until_wallet_synced(ALICE).await?;
-    let bob_spaces_updated = rig.spaced.client.wallet_list_spaces(BOB).await?;
-    let alice_spaces_updated = rig.spaced.client.wallet_list_spaces(ALICE).await?;
-    let alices_balance_updated = rig.spaced.client.wallet_get_balance(ALICE).await?;
+    let bob_spaces_updated = rig.spaced.client.wallet_list_spaces(BOB, None).await?;
+    let alice_spaces_updated = rig.spaced.client.wallet_list_spaces(ALICE, None).await?;
+    let alices_balance_updated = rig.spaced.client.wallet_get_balance(ALICE, None).await?;
 
     assert_eq!(alices_spaces.len() - 1, alice_spaces_updated.len(), "alice must have one less space");
     assert_eq!(bobs_spaces.len() + 1, bob_spaces_updated.len(), "bob must have a new space");
@@ -101,14 +103,100 @@ async fn it_should_allow_outbidding(rig: &TestRig) -> anyhow::Result<()> {
     Ok(())
 }
 
+async fn it_should_prove_ownership_back_to_the_open(rig: &TestRig) -> anyhow::Result<()> {
+    let space = rig.spaced.client.get_space(TEST_SPACE).await?.expect("space must exist");
+    let proof = rig.spaced.client.get_ownership_proof(TEST_SPACE).await?.expect("space must have a proof");
+    assert!(proof.complete, "the open must be within the bid search");
+    assert_eq!(proof.outpoint, space.outpoint());
+    let steps: Vec<_> = proof.chain.iter().map(|link| link.step).collect();
+    assert_eq!(steps, vec![OwnershipStep::Open, OwnershipStep::Bid], "must prove the open and bob's outbid");
+    assert_eq!(proof.chain[1].spent, Some(proof.chain[0].output), "the outbid must spend the open's bid output");
+    assert_eq!(proof.chain[1].output, space.outpoint(), "the chain must end at the current output");
+
+    // Without the block index the proof can't be followed, but it must not fail
+    let unindexed = rig.spawn_spaced(vec![]).await?;
+    let proof = unindexed.client.get_ownership_proof(TEST_SPACE).await?.expect("space must have a proof");
+    assert!(!proof.complete, "proof must be incomplete without the block index");
+    assert!(proof.chain.is_empty(), "no links can be proven without the block index");
+    assert_eq!(proof.outpoint, space.outpoint());
+    Ok(())
+}
+
+async fn export_history(rig: &TestRig, wallet: &str, format: ExportFormat, as_of: u32, page_size: usize) -> anyhow::Result<String> {
+    let mut out = String::new();
+    let mut skip = 0;
+    loop {
+        let page = rig.spaced.client.wallet_export_history(wallet, format, Some(as_of), skip, page_size).await?;
+        // Pages as of a height never skip transactions, so only the end is empty
+        if page.is_empty() {
+            return Ok(out);
+        }
+        let entries = page.lines().filter(|line| !line.starts_with("timestamp,")).count();
+        assert!(entries <= page_size, "pages must not exceed the page size");
+        out.push_str(&page);
+        skip += page_size;
+    }
+}
+
+async fn it_should_export_history(rig: &TestRig) -> anyhow::Result<()> {
+    rig.wait_until_wallet_synced(ALICE).await?;
+    rig.wait_until_wallet_synced(BOB).await?;
+
+    let as_of = rig.spaced.client.wallet_get_info(BOB).await?.tip;
+    let full = rig.spaced.client.wallet_export_history(BOB, ExportFormat::Json, Some(as_of), 0, usize::MAX).await?;
+    let paged = export_history(rig, BOB, ExportFormat::Json, as_of, 3).await?;
+    assert_eq!(paged, full, "pages must add up to the full history");
+
+    // Transactions arriving mid-export must not shift later pages
+    let first = rig.spaced.client.wallet_export_history(BOB, ExportFormat::Json, Some(as_of), 0, 3).await?;
+    wallet_do(rig, BOB, vec![
+        RpcWalletRequest::SendCoins(SendCoinsParams {
+            amount: Some(Amount::from_sat(10_000)),
+            to: rig.spaced.client.wallet_get_new_address(BOB, AddressKind::Coin, None).await?,
+            from: vec![],
+        }),
+    ], false).await?;
+    let first_after = rig.spaced.client.wallet_export_history(BOB, ExportFormat::Json, Some(as_of), 0, 3).await?;
+    assert_eq!(first_after, first, "first page must be unchanged");
+    let paged = export_history(rig, BOB, ExportFormat::Json, as_of, 3).await?;
+    assert_eq!(paged, full, "export as of a height must ignore new transactions");
+    let unconfirmed = rig.spaced.client.wallet_export_history(BOB, ExportFormat::Json, None, 0, usize::MAX).await?;
+    assert_eq!(unconfirmed.lines().count(), full.lines().count() + 1, "export without a height must include unconfirmed transactions");
+    rig.mine_blocks(1, None).await?;
+    rig.wait_until_synced().await?;
+    rig.wait_until_wallet_synced(BOB).await?;
+
+    let entries: Vec<HistoryEntry> = full.lines()
+        .map(serde_json::from_str)
+        .collect::<Result<_, _>>()?;
+    assert!(entries.iter().any(|e|
+        e.kind == HistoryEntryKind::Bid && e.space.as_deref() == Some(TEST_SPACE)
+    ), "bob's bid must be reported with the space");
+
+    let alice = rig.spaced.client.wallet_export_history(ALICE, ExportFormat::Json, None, 0, usize::MAX).await?;
+    let alice: Vec<HistoryEntry> = alice.lines()
+        .map(serde_json::from_str)
+        .collect::<Result<_, _>>()?;
+    assert!(alice.iter().any(|e|
+        e.kind == HistoryEntryKind::Open && e.space.as_deref() == Some(TEST_SPACE)
+    ), "alice's open must be reported with the space");
+
+    let csv = rig.spaced.client.wallet_export_history(BOB, ExportFormat::Csv, Some(as_of), 0, usize::MAX).await?;
+    let paged = export_history(rig, BOB, ExportFormat::Csv, as_of, 3).await?;
+    assert_eq!(paged, csv, "csv pages must add up to the full history");
+    assert_eq!(csv.matches("timestamp,txid,type").count(), 1, "header must only be in the first page");
+    assert_eq!(csv.lines().count(), entries.len() + 1);
+    Ok(())
+}
+
 /// Eve makes an invalid bid with a burn increment of 0 only refunding Bob's money
 async fn it_should_only_accept_forced_zero_value_bid_increments_and_revoke(rig: &TestRig) -> anyhow::Result<()> {
     // Bob outbids alice
     rig.wait_until_wallet_synced(BOB).await?;
     rig.wait_until_wallet_synced(EVE).await?;
-    let eve_spaces = rig.spaced.client.wallet_list_spaces(EVE).await?;
-    let bob_spaces = rig.spaced.client.wallet_list_spaces(BOB).await?;
-    let bob_balance = rig.spaced.client.wallet_get_balance(BOB).await?;
+    let eve_spaces = rig.spaced.client.wallet_list_spaces(EVE, None).await?;
+    let bob_spaces = rig.spaced.client.wallet_list_spaces(BOB, None).await?;
+    let bob_balance = rig.spaced.client.wallet_get_balance(BOB, None).await?;
 
     let fullspaceout = rig.spaced.client.get_space(TEST_SPACE).await?.expect("exists");
     let space = fullspaceout.spaceout.space.expect("a space");
@@ -139,10 +227,21 @@ async fn it_should_only_accept_forced_zero_value_bid_increments_and_revoke(rig:
                 }),
             ],
             fee_rate: Some(FeeRate::from_sat_per_vb(1).expect("fee")),
+            fee: None,
+            conf_target: None,
+            estimate_mode: None,
             dust: None,
             force: true,
             confirmed_only: false,
             skip_tx_check: false,
+            coin_selection: CoinSelectionStrategy::default(),
+            avoid_address_reuse: false,
+            donate_dust_change_to_fee: true,
+            outbid_limits: OutbidLimits::default(),
+            resolve_pending: false,
+            expected_tip: None,
+            confirm_token: None,
+            broadcast: true,
         },
     ).await.is_err(), "should require skip tx check");
 
@@ -158,10 +257,21 @@ async fn it_should_only_accept_forced_zero_value_bid_increments_and_revoke(rig:
                 }),
             ],
             fee_rate: Some(FeeRate::from_sat_per_vb(1).expect("fee")),
+            fee: None,
+            conf_target: None,
+            estimate_mode: None,
             dust: None,
             force: true,
             confirmed_only: false,
             skip_tx_check: true,
+            coin_selection: CoinSelectionStrategy::default(),
+            avoid_address_reuse: false,
+            donate_dust_change_to_fee: true,
+            outbid_limits: OutbidLimits::default(),
+            resolve_pending: false,
+            expected_tip: None,
+            confirm_token: None,
+            broadcast: true,
         },
     ).await?;
 
@@ -171,9 +281,9 @@ async fn it_should_only_accept_forced_zero_value_bid_increments_and_revoke(rig:
     rig.wait_until_synced().await?;
     rig.wait_until_wallet_synced(BOB).await?;
     rig.wait_until_wallet_synced(ALICE).await?;
-    let bob_spaces_updated = rig.spaced.client.wallet_list_spaces(BOB).await?;
-    let bob_balance_updated = rig.spaced.client.wallet_get_balance(BOB).await?;
-    let eve_spaces_updated = rig.spaced.client.wallet_list_spaces(EVE).await?;
+    let bob_spaces_updated = rig.spaced.client.wallet_list_spaces(BOB, None).await?;
+    let bob_balance_updated = rig.spaced.client.wallet_get_balance(BOB, None).await?;
+    let eve_spaces_updated = rig.spaced.client.wallet_list_spaces(EVE, None).await?;
 
     assert_eq!(bob_spaces.len() - 1, bob_spaces_updated.len(), "bob must have one less space");
     assert_eq!(bob_balance_updated.balance, bob_balance.balance +
@@ -199,7 +309,7 @@ async fn it_should_allow_claim_on_or_after_claim_height(rig: &TestRig) -> anyhow
     assert_eq!(claim_height, rig.get_block_count().await?, "heights must match");
 
     rig.wait_until_wallet_synced(wallet).await?;
-    let all_spaces = rig.spaced.client.wallet_list_spaces(wallet).await?;
+    let all_spaces = rig.spaced.client.wallet_list_spaces(wallet, None).await?;
 
     let result = wallet_do(rig, wallet, vec![
         RpcWalletRequest::Register(RegisterParams {
@@ -213,7 +323,7 @@ async fn it_should_allow_claim_on_or_after_claim_height(rig: &TestRig) -> anyhow
 
     rig.wait_until_synced().await?;
     rig.wait_until_wallet_synced(wallet).await?;
-    let all_spaces_2 = rig.spaced.client.wallet_list_spaces(wallet).await?;
+    let all_spaces_2 = rig.spaced.client.wallet_list_spaces(wallet, None).await?;
 
     assert_eq!(all_spaces.len(), all_spaces_2.len(), "must be equal");
 
@@ -231,7 +341,7 @@ async fn it_should_allow_claim_on_or_after_claim_height(rig: &TestRig) -> anyhow
 async fn it_should_allow_batch_transfers_refreshing_expire_height(rig: &TestRig) -> anyhow::Result<()> {
     rig.wait_until_wallet_synced(ALICE).await?;
     rig.wait_until_synced().await?;
-    let all_spaces = rig.spaced.client.wallet_list_spaces(ALICE).await?;
+    let all_spaces = rig.spaced.client.wallet_list_spaces(ALICE, None).await?;
     let registered_spaces: Vec<_> = all_spaces.iter().filter_map(|s| {
         let space = s.space.as_ref().expect("space");
         match space.covenant {
@@ -240,7 +350,7 @@ async fn it_should_allow_batch_transfers_refreshing_expire_height(rig: &TestRig)
         }
     }).collect();
 
-    let space_address = rig.spaced.client.wallet_get_new_address(ALICE, AddressKind::Space).await?;
+    let space_address = rig.spaced.client.wallet_get_new_address(ALICE, AddressKind::Space, None).await?;
 
     let result = wallet_do(rig, ALICE, vec![
         RpcWalletRequest::Transfer(TransferSpacesParams {
@@ -256,7 +366,7 @@ async fn it_should_allow_batch_transfers_refreshing_expire_height(rig: &TestRig)
 
     rig.wait_until_synced().await?;
     rig.wait_until_wallet_synced(ALICE).await?;
-    let all_spaces_2 = rig.spaced.client.wallet_list_spaces(ALICE).await?;
+    let all_spaces_2 = rig.spaced.client.wallet_list_spaces(ALICE, None).await?;
 
     assert_eq!(all_spaces.len(), all_spaces_2.len(), "must be equal");
 
@@ -280,7 +390,7 @@ async fn it_should_allow_batch_transfers_refreshing_expire_height(rig: &TestRig)
 async fn it_should_allow_applying_script_in_batch(rig: &TestRig) -> anyhow::Result<()> {
     rig.wait_until_wallet_synced(ALICE).await?;
     rig.wait_until_synced().await?;
-    let all_spaces = rig.spaced.client.wallet_list_spaces(ALICE).await?;
+    let all_spaces = rig.spaced.client.wallet_list_spaces(ALICE, None).await?;
     let registered_spaces: Vec<_> = all_spaces.iter().filter_map(|s| {
         let space = s.space.as_ref().expect("space");
         match space.covenant {
@@ -293,6 +403,7 @@ async fn it_should_allow_applying_script_in_batch(rig: &TestRig) -> anyhow::Resu
         RpcWalletRequest::Execute(ExecuteParams {
             context: registered_spaces.clone(),
             space_script: SpaceScript::create_set_fallback(&[0xDE, 0xAD, 0xBE, 0xEF]),
+            inputs: vec![],
         }),
     ], false).await.expect("send request");
 
@@ -303,7 +414,7 @@ async fn it_should_allow_applying_script_in_batch(rig: &TestRig) -> anyhow::Resu
 
     rig.wait_until_synced().await?;
     rig.wait_until_wallet_synced(ALICE).await?;
-    let all_spaces_2 = rig.spaced.client.wallet_list_spaces(ALICE).await?;
+    let all_spaces_2 = rig.spaced.client.wallet_list_spaces(ALICE, None).await?;
 
     assert_eq!(all_spaces.len(), all_spaces_2.len(), "must be equal");
 
@@ -326,7 +437,219 @@ async fn it_should_allow_applying_script_in_batch(rig: &TestRig) -> anyhow::Resu
     Ok(())
 }
 
+async fn it_should_transfer_spaces_to_one_address_in_distinct_outputs(rig: &TestRig) -> anyhow::Result<()> {
+    rig.wait_until_wallet_synced(ALICE).await?;
+    rig.wait_until_synced().await?;
+    let all_spaces = rig.spaced.client.wallet_list_spaces(ALICE, None).await?;
+    let spaces: Vec<_> = all_spaces.iter().filter_map(|s| {
+        let space = s.space.as_ref().expect("space");
+        match space.covenant {
+            Covenant::Transfer { .. } => Some(space.name.to_string()),
+            _ => None,
+        }
+    }).take(3).collect();
+    assert_eq!(spaces.len(), 3, "need three registered spaces");
+
+    let space_address = rig.spaced.client.wallet_get_new_address(ALICE, AddressKind::Space, None).await?;
+    let script_pubkey = SpaceAddress::from_str(&space_address)?.script_pubkey();
+
+    let result = wallet_do(rig, ALICE, vec![
+        RpcWalletRequest::Transfer(TransferSpacesParams {
+            spaces: spaces.clone(),
+            to: space_address,
+        }),
+    ], false).await.expect("send request");
+    assert!(result.result.iter().all(|tx| tx.error.is_none()), "transfer must succeed");
+
+    rig.mine_blocks(1, None).await?;
+    rig.wait_until_synced().await?;
+
+    let mut outpoints = Vec::new();
+    for space in spaces.iter() {
+        let out = rig.spaced.client.get_space(space).await?.expect("space must exist");
+        assert_eq!(out.spaceout.script_pubkey, script_pubkey, "{} must be sent to the recipient", space);
+        assert_eq!(out.spaceout.space.as_ref().expect("space").name.to_string(), *space, "output must hold the right space");
+        outpoints.push(out.outpoint());
+    }
+    outpoints.sort();
+    outpoints.dedup();
+    assert_eq!(outpoints.len(), spaces.len(), "each space must land in its own output");
+
+    let script_hex = hex::encode(script_pubkey.as_bytes());
+    let all = rig.spaced.client.get_spaces_by_script(&script_hex, None, None).await?;
+    assert!(all.next_cursor.is_none(), "all spaces must fit in a single page");
+    let mut found: Vec<_> = all.spaces.iter().map(|s| s.spaceout.space.as_ref().expect("space").name.to_string()).collect();
+    found.sort();
+    let mut expected = spaces.clone();
+    expected.sort();
+    assert_eq!(found, expected, "must find the transferred spaces by script");
+
+    let mut paged = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = rig.spaced.client.get_spaces_by_script(&script_hex, cursor, Some(2)).await?;
+        assert!(page.spaces.len() <= 2, "must examine at most the limit");
+        paged.extend(page.spaces.iter().map(|s| s.spaceout.space.as_ref().expect("space").name.to_string()));
+        cursor = page.next_cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+    paged.sort();
+    assert_eq!(paged, expected, "paging must find each space once");
+
+    Ok(())
+}
+
+async fn it_should_reject_space_addresses_for_other_networks(rig: &TestRig) -> anyhow::Result<()> {
+    rig.wait_until_wallet_synced(ALICE).await?;
+    let all_spaces = rig.spaced.client.wallet_list_spaces(ALICE, None).await?;
+    let registered_space = all_spaces.iter().find_map(|s| {
+        let space = s.space.as_ref().expect("space");
+        match space.covenant {
+            Covenant::Transfer { .. } => Some(space.name.to_string()),
+            _ => None,
+        }
+    }).expect("a registered space");
 
+    let space_address = rig.spaced.client.wallet_get_new_address(ALICE, AddressKind::Space, None).await?;
+    let program = SpaceAddress::from_str(&space_address)?.0.witness_program().expect("p2tr");
+
+    for network in [Network::Bitcoin, Network::Testnet] {
+        let foreign = SpaceAddress(Address::from_witness_program(program, network)).to_string();
+
+        let error = wallet_do(rig, ALICE, vec![
+            RpcWalletRequest::Transfer(TransferSpacesParams {
+                spaces: vec![registered_space.clone()],
+                to: foreign.clone(),
+            }),
+        ], false).await.expect_err("transfer to a foreign network space address must be rejected");
+        assert!(error.to_string().contains("not valid for network"), "expected a network mismatch error, got: {}", error);
+
+        let error = wallet_do(rig, ALICE, vec![
+            RpcWalletRequest::SendCoins(SendCoinsParams {
+                amount: Some(Amount::from_sat(10_000)),
+                to: foreign,
+                from: vec![],
+            }),
+        ], false).await.expect_err("sending coins to a foreign network space address must be rejected");
+        assert!(error.to_string().contains("not valid for network"), "expected a network mismatch error, got: {}", error);
+    }
+    Ok(())
+}
+
+async fn it_should_reject_duplicate_execute_context(rig: &TestRig) -> anyhow::Result<()> {
+    rig.wait_until_wallet_synced(ALICE).await?;
+    let all_spaces = rig.spaced.client.wallet_list_spaces(ALICE, None).await?;
+    let registered_space = all_spaces.iter().find_map(|s| {
+        let space = s.space.as_ref().expect("space");
+        match space.covenant {
+            Covenant::Transfer { .. } => Some(space.name.to_string()),
+            _ => None,
+        }
+    }).expect("a registered space");
+
+    let result = wallet_do(rig, ALICE, vec![
+        RpcWalletRequest::Execute(ExecuteParams {
+            context: vec![registered_space.clone(), registered_space],
+            space_script: SpaceScript::create_set_fallback(&[0xDE, 0xAD, 0xBE, 0xEF]),
+            inputs: vec![],
+        }),
+    ], false).await;
+
+    let error = result.expect_err("duplicate context must be rejected");
+    assert!(error.to_string().contains("more than once"), "expected a duplicate space error, got: {}", error);
+    Ok(())
+}
+
+async fn it_should_reject_requests_for_the_reasons_checked(rig: &TestRig) -> anyhow::Result<()> {
+    rig.wait_until_wallet_synced(BOB).await?;
+    let invalid = vec![
+        RpcWalletRequest::Open(OpenParams { name: TEST_SPACE.to_string(), amount: 1000 }),
+        RpcWalletRequest::Open(OpenParams { name: "not a name".to_string(), amount: 1000 }),
+        RpcWalletRequest::Bid(BidParams { name: "@doesnotexist".to_string(), amount: 1000 }),
+        RpcWalletRequest::Bid(BidParams { name: TEST_SPACE.to_string(), amount: 1 }),
+        RpcWalletRequest::Transfer(TransferSpacesParams {
+            spaces: vec!["@doesnotexist".to_string()],
+            to: rig.spaced.client.wallet_get_new_address(BOB, AddressKind::Space, None).await?,
+        }),
+        RpcWalletRequest::SendCoins(SendCoinsParams {
+            amount: Some(Amount::from_sat(1000)),
+            to: rig.spaced.client.wallet_get_new_address(BOB, AddressKind::Coin, None).await?,
+            from: vec![OutPoint::null()],
+        }),
+    ];
+
+    for request in invalid {
+        let check = rig.spaced.client.wallet_check_operation(BOB, request.clone()).await?;
+        assert!(!check.allowed, "check must reject the request");
+        let reason = check.reasons.first().expect("a reason");
+        let error = wallet_do(rig, BOB, vec![request], false).await
+            .expect_err("request must be rejected");
+        assert!(error.to_string().contains(reason.as_str()), "expected `{}` for the same reason as the check: `{}`", error, reason);
+    }
+    Ok(())
+}
+
+async fn it_should_spend_only_the_listed_coins(rig: &TestRig) -> anyhow::Result<()> {
+    rig.wait_until_synced().await?;
+    rig.wait_until_wallet_synced(ALICE).await?;
+    rig.wait_until_wallet_synced(BOB).await?;
+    let bob_address = rig.spaced.client.wallet_get_new_address(BOB, AddressKind::Coin, None).await?;
+    let send_from = |from: Vec<OutPoint>, amount: Amount| RpcWalletRequest::SendCoins(SendCoinsParams {
+        amount: Some(amount),
+        to: bob_address.clone(),
+        from,
+    });
+
+    let unspent = rig.spaced.client.wallet_list_unspent(ALICE, None).await?;
+    let coin = unspent.iter()
+        .filter(|out| out.space.is_none() && !out.is_spaceout && out.output.confirmation_time.is_confirmed())
+        .max_by_key(|out| out.output.txout.value)
+        .expect("a confirmed coin").output.clone();
+    let response = wallet_do(rig, ALICE, vec![send_from(vec![coin.outpoint], coin.txout.value / 2)], false).await?;
+    assert!(response.result.iter().all(|tx| tx.error.is_none()), "send must succeed");
+
+    let remaining = rig.spaced.client.wallet_list_unspent(ALICE, None).await?;
+    let remaining: Vec<_> = remaining.iter().map(|out| out.output.outpoint).collect();
+    assert!(!remaining.contains(&coin.outpoint), "the listed coin must be spent");
+    for out in unspent.iter().filter(|out| out.output.outpoint != coin.outpoint) {
+        assert!(remaining.contains(&out.output.outpoint), "{} must not be spent", out.output.outpoint);
+    }
+
+    let unconfirmed = rig.spaced.client.wallet_list_unspent(ALICE, None).await?.into_iter()
+        .find(|out| !out.output.confirmation_time.is_confirmed())
+        .expect("unconfirmed change");
+    let foreign = rig.spaced.client.wallet_list_unspent(BOB, None).await?.into_iter()
+        .find(|out| out.space.is_none() && !out.is_spaceout)
+        .expect("a coin of bob's");
+    let space_out = rig.spaced.client.wallet_list_spaces(ALICE, None).await?.into_iter()
+        .find(|out| out.space.is_some())
+        .expect("a space of alice's");
+    let cases = [
+        (unconfirmed.output.outpoint, "is not confirmed"),
+        (foreign.output.outpoint, "is not an unspent output of this wallet"),
+        (space_out.output.outpoint, "carries a space"),
+    ];
+    for (outpoint, reason) in cases {
+        let error = wallet_do(rig, ALICE, vec![send_from(vec![outpoint], Amount::from_sat(1000))], false).await
+            .expect_err("coin must be rejected");
+        assert!(error.to_string().contains(reason), "expected `{}`, got: {}", reason, error);
+    }
+
+    let other_coin = unspent.iter()
+        .find(|out| out.output.outpoint != coin.outpoint && out.space.is_none() && !out.is_spaceout && out.output.confirmation_time.is_confirmed())
+        .expect("another confirmed coin").output.outpoint;
+    let error = wallet_do(rig, ALICE, vec![
+        send_from(vec![other_coin], Amount::from_sat(1000)),
+        RpcWalletRequest::Open(OpenParams { name: "@listedcoins".to_string(), amount: 1000 }),
+    ], false).await.expect_err("listed coins can't fund other requests");
+    assert!(error.to_string().contains("can only be listed"), "expected a mixed batch error, got: {}", error);
+
+    rig.mine_blocks(1, None).await?;
+    rig.wait_until_synced().await?;
+    Ok(())
+}
 
 // Alice places an unconfirmed bid on @test2.
 // Bob attempts to replace it but fails due to a lack of confirmed bid & funding utxos.
@@ -339,10 +662,21 @@ async fn it_should_replace_mempool_bids(rig: &TestRig) -> anyhow::Result<()> {
             bidouts: Some(2),
             requests: vec![],
             fee_rate: Some(FeeRate::from_sat_per_vb(2).expect("fee")),
+            fee: None,
+            conf_target: None,
+            estimate_mode: None,
             dust: None,
             force: false,
             confirmed_only: false,
             skip_tx_check: false,
+            coin_selection: CoinSelectionStrategy::default(),
+            avoid_address_reuse: false,
+            donate_dust_change_to_fee: true,
+            outbid_limits: OutbidLimits::default(),
+            resolve_pending: false,
+            expected_tip: None,
+            confirm_token: None,
+            broadcast: true,
         },
     ).await?;
     rig.mine_blocks(1, None).await?;
@@ -382,10 +716,21 @@ async fn it_should_replace_mempool_bids(rig: &TestRig) -> anyhow::Result<()> {
                     amount: 1000,
                 })],
             fee_rate: Some(FeeRate::from_sat_per_vb(2).expect("fee")),
+            fee: None,
+            conf_target: None,
+            estimate_mode: None,
             dust: None,
             force: false,
             confirmed_only: false,
             skip_tx_check: false,
+            coin_selection: CoinSelectionStrategy::default(),
+            avoid_address_reuse: false,
+            donate_dust_change_to_fee: true,
+            outbid_limits: OutbidLimits::default(),
+            resolve_pending: false,
+            expected_tip: None,
+            confirm_token: None,
+            broadcast: true,
         },
     ).await?;
 
@@ -406,10 +751,21 @@ async fn it_should_replace_mempool_bids(rig: &TestRig) -> anyhow::Result<()> {
                     amount: 1000,
                 })],
             fee_rate: Some(FeeRate::from_sat_per_vb(2).expect("fee")),
+            fee: None,
+            conf_target: None,
+            estimate_mode: None,
             dust: None,
             force: false,
             confirmed_only: false,
             skip_tx_check: false,
+            coin_selection: CoinSelectionStrategy::default(),
+            avoid_address_reuse: false,
+            donate_dust_change_to_fee: true,
+            outbid_limits: OutbidLimits::default(),
+            resolve_pending: false,
+            expected_tip: None,
+            confirm_token: None,
+            broadcast: true,
         },
     ).await?;
 
@@ -426,7 +782,7 @@ async fn it_should_replace_mempool_bids(rig: &TestRig) -> anyhow::Result<()> {
     rig.wait_until_wallet_synced(ALICE).await?;
     let txs = rig.spaced.client.wallet_list_transactions(
         ALICE,
-        1000, 0
+        1000, 0, None
     ).await?;
     let unconfirmed : Vec<_> = txs.iter().filter(|tx| !tx.confirmed).collect();
     assert_eq!(unconfirmed.len(), 0, "there should be no stuck unconfirmed transactions");
@@ -442,10 +798,21 @@ async fn it_should_maintain_locktime_when_fee_bumping(rig: &TestRig) -> anyhow::
             bidouts: Some(2),
             requests: vec![],
             fee_rate: Some(FeeRate::from_sat_per_vb(1).expect("fee")),
+            fee: None,
+            conf_target: None,
+            estimate_mode: None,
             dust: None,
             force: false,
             confirmed_only: false,
-            skip_tx_check: false
+            skip_tx_check: false,
+            coin_selection: CoinSelectionStrategy::default(),
+            avoid_address_reuse: false,
+            donate_dust_change_to_fee: true,
+            outbid_limits: OutbidLimits::default(),
+            resolve_pending: false,
+            expected_tip: None,
+            confirm_token: None,
+            broadcast: true,
         },
     ).await?;
 
@@ -458,8 +825,20 @@ async fn it_should_maintain_locktime_when_fee_bumping(rig: &TestRig) -> anyhow::
 
     let tx = rig.get_raw_transaction(&txid).await?;
 
+    let dry_run = rig.spaced.client.wallet_bump_fee(
+        ALICE, txid, FeeRate::from_sat_per_vb(4).expect("fee"), false, Some(true)
+    ).await?;
+    assert_eq!(dry_run.len(), 1, "should only be 1 tx");
+    let cost = dry_run[0].fee_bump.as_ref().expect("dry run should report its cost");
+    assert!(cost.additional_fee > Amount::ZERO, "replacement must pay more");
+    assert!(dry_run[0].raw.is_some(), "dry run should return the raw tx");
+    assert!(
+        rig.get_raw_transaction(&dry_run[0].txid).await.is_err(),
+        "dry run must not broadcast"
+    );
+
     let bump = rig.spaced.client.wallet_bump_fee(
-        ALICE, txid, FeeRate::from_sat_per_vb(4).expect("fee"), false
+        ALICE, txid, FeeRate::from_sat_per_vb(4).expect("fee"), false, None
     ).await?;
     assert_eq!(bump.len(), 1, "should only be 1 tx");
     assert!(bump[0].error.is_none(), "should be no errors");
@@ -470,6 +849,253 @@ async fn it_should_maintain_locktime_when_fee_bumping(rig: &TestRig) -> anyhow::
     Ok(())
 }
 
+async fn it_should_abandon_an_open_in_the_mempool(rig: &TestRig) -> anyhow::Result<()> {
+    const ABANDONED_SPACE: &str = "@abandoned";
+    rig.wait_until_wallet_synced(ALICE).await?;
+    let balance = rig.spaced.client.wallet_get_balance(ALICE, None).await?;
+
+    let response = wallet_do(rig, ALICE, vec![
+        RpcWalletRequest::Open(OpenParams {
+            name: ABANDONED_SPACE.to_string(),
+            amount: 1000,
+        }),
+    ], false).await?;
+    for tx_res in &response.result {
+        assert!(tx_res.error.is_none(), "expect no errors for simple open");
+    }
+
+    let report = rig.spaced.client.wallet_abandon_open(
+        ALICE, ABANDONED_SPACE.to_string(), Some(FeeRate::from_sat_per_vb(1).expect("fee"))
+    ).await?;
+    assert!(response.result.iter().any(|res| res.txid == report.txid), "must abandon the open");
+    assert!(report.abandoned.contains(&report.txid));
+    assert!(rig.get_raw_transaction(&report.reclaim).await.is_ok(), "reclaim must be in the mempool");
+    assert!(rig.get_raw_transaction(&report.txid).await.is_err(), "open must be replaced");
+
+    rig.mine_blocks(1, None).await?;
+    rig.wait_until_synced().await?;
+    rig.wait_until_wallet_synced(ALICE).await?;
+    assert!(rig.spaced.client.get_space(ABANDONED_SPACE).await?.is_none(), "space must not be opened");
+
+    let updated = rig.spaced.client.wallet_get_balance(ALICE, None).await?;
+    assert!(updated.balance + Amount::from_sat(10_000) > balance.balance, "only fees must be lost");
+    Ok(())
+}
+
+/// A high value send is held back until it's resubmitted with the token for that exact request
+async fn it_should_require_a_token_for_high_value_sends(rig: &TestRig) -> anyhow::Result<()> {
+    rig.wait_until_wallet_synced(ALICE).await?;
+    let to = rig.spaced.client.wallet_get_new_address(BOB, AddressKind::Coin, None).await?;
+    rig.spaced.client.wallet_set_confirm_policy(ALICE, ConfirmPolicy {
+        confirm_high_value: Some(Amount::from_sat(10_000)),
+    }).await?;
+
+    let low = rig.spaced.client.wallet_send_request(ALICE,
+        send_coins_request(to.clone(), Amount::from_sat(5_000), None, false)
+    ).await?;
+    assert!(low.confirmation.is_none(), "sends below the threshold must not need a token");
+
+    let response = rig.spaced.client.wallet_send_request(ALICE,
+        send_coins_request(to.clone(), Amount::from_sat(50_000), None, true)
+    ).await?;
+    assert!(response.result.is_empty(), "nothing must be sent without a token");
+    let confirmation = response.confirmation.expect("a confirmation must be required");
+    assert_eq!(confirmation.amount, Amount::from_sat(50_000));
+    assert_eq!(confirmation.threshold, Amount::from_sat(10_000));
+
+    let error = rig.spaced.client.wallet_send_request(ALICE,
+        send_coins_request(to.clone(), Amount::from_sat(50_000), Some("0000000000000000".to_string()), true)
+    ).await.expect_err("a wrong token must be rejected");
+    assert!(error.to_string().contains("does not match"), "expected a token mismatch, got: {}", error);
+
+    let error = rig.spaced.client.wallet_send_request(ALICE,
+        send_coins_request(to.clone(), Amount::from_sat(60_000), Some(confirmation.token.clone()), true)
+    ).await.expect_err("a token for another request must be rejected");
+    assert!(error.to_string().contains("does not match"), "expected a token mismatch, got: {}", error);
+
+    // Tokens are tied to the wallet tip
+    rig.mine_blocks(1, None).await?;
+    rig.wait_until_synced().await?;
+    rig.wait_until_wallet_synced(ALICE).await?;
+    let error = rig.spaced.client.wallet_send_request(ALICE,
+        send_coins_request(to.clone(), Amount::from_sat(50_000), Some(confirmation.token), true)
+    ).await.expect_err("a stale token must be rejected");
+    assert!(error.to_string().contains("does not match"), "expected a token mismatch, got: {}", error);
+
+    let token = rig.spaced.client.wallet_send_request(ALICE,
+        send_coins_request(to.clone(), Amount::from_sat(50_000), None, true)
+    ).await?.confirmation.expect("a confirmation must be required").token;
+    let response = rig.spaced.client.wallet_send_request(ALICE,
+        send_coins_request(to, Amount::from_sat(50_000), Some(token), true)
+    ).await?;
+    assert!(response.confirmation.is_none(), "the token must be accepted");
+    assert!(!response.result.is_empty(), "the send must go through");
+    for tx_res in &response.result {
+        assert!(tx_res.error.is_none(), "expect no errors for the confirmed send");
+    }
+
+    rig.spaced.client.wallet_set_confirm_policy(ALICE, ConfirmPolicy::default()).await?;
+    rig.mine_blocks(1, None).await?;
+    rig.wait_until_synced().await?;
+    Ok(())
+}
+
+async fn it_should_build_identical_transactions_in_a_dry_run(rig: &TestRig) -> anyhow::Result<()> {
+    rig.wait_until_wallet_synced(ALICE).await?;
+    let to = rig.spaced.client.wallet_get_new_address(BOB, AddressKind::Coin, None).await?;
+    let request = |confirm_token: Option<String>, broadcast: bool| {
+        send_coins_request(to.clone(), Amount::from_sat(50_000), confirm_token, broadcast)
+    };
+
+    rig.spaced.client.wallet_set_confirm_policy(ALICE, ConfirmPolicy {
+        confirm_high_value: Some(Amount::from_sat(10_000)),
+    }).await?;
+    let balance = rig.spaced.client.wallet_get_balance(ALICE, None).await?;
+    let unspent = rig.spaced.client.wallet_list_unspent(ALICE, None).await?.len();
+    let txs: Vec<_> = rig.spaced.client.wallet_list_transactions(ALICE, 100, 0, None).await?
+        .into_iter().map(|tx| tx.txid).collect();
+
+    let dry_run = rig.spaced.client.wallet_send_request(ALICE, request(None, false)).await?;
+    assert!(dry_run.result.is_empty(), "a dry run must not build high value transactions without a token");
+    let token = dry_run.confirmation.expect("a dry run must require confirmation").token;
+
+    let dry_run = rig.spaced.client.wallet_send_request(ALICE, request(Some(token.clone()), false)).await?;
+    assert!(!dry_run.result.is_empty(), "a confirmed dry run must build the transactions");
+    for tx_res in &dry_run.result {
+        assert!(tx_res.error.is_none(), "expect no errors for a dry run");
+        assert!(rig.get_raw_transaction(&tx_res.txid).await.is_err(), "a dry run must not broadcast");
+    }
+    assert_eq!(rig.spaced.client.wallet_get_balance(ALICE, None).await?.balance, balance.balance, "a dry run must not change the balance");
+    assert_eq!(rig.spaced.client.wallet_list_unspent(ALICE, None).await?.len(), unspent, "a dry run must not spend coins");
+    let updated: Vec<_> = rig.spaced.client.wallet_list_transactions(ALICE, 100, 0, None).await?
+        .into_iter().map(|tx| tx.txid).collect();
+    assert_eq!(updated, txs, "a dry run must not add transactions");
+
+    let response = rig.spaced.client.wallet_send_request(ALICE, request(Some(token), true)).await?;
+    let txids: Vec<_> = response.result.iter().map(|res| res.txid).collect();
+    let dry_run_txids: Vec<_> = dry_run.result.iter().map(|res| res.txid).collect();
+    assert_eq!(txids, dry_run_txids, "a dry run must build the same transactions");
+    for tx_res in &response.result {
+        assert!(tx_res.error.is_none(), "expect no errors for the confirmed request");
+        assert!(rig.get_raw_transaction(&tx_res.txid).await.is_ok(), "must be broadcast");
+    }
+
+    rig.spaced.client.wallet_set_confirm_policy(ALICE, ConfirmPolicy::default()).await?;
+    rig.mine_blocks(1, None).await?;
+    rig.wait_until_synced().await?;
+    Ok(())
+}
+
+async fn it_should_export_received_transfers_as_transfers(rig: &TestRig) -> anyhow::Result<()> {
+    rig.wait_until_wallet_synced(ALICE).await?;
+    rig.wait_until_wallet_synced(BOB).await?;
+    let all_spaces = rig.spaced.client.wallet_list_spaces(ALICE, None).await?;
+    let registered_space = all_spaces.iter().find_map(|s| {
+        let space = s.space.as_ref().expect("space");
+        match space.covenant {
+            Covenant::Transfer { .. } => Some(space.name.to_string()),
+            _ => None,
+        }
+    }).expect("a registered space");
+
+    let bob_address = rig.spaced.client.wallet_get_new_address(BOB, AddressKind::Space, None).await?;
+    let response = wallet_do(rig, ALICE, vec![
+        RpcWalletRequest::Transfer(TransferSpacesParams {
+            spaces: vec![registered_space.clone()],
+            to: bob_address,
+        }),
+    ], false).await?;
+    let txid = response.result.last().expect("a transfer").txid;
+    rig.mine_blocks(1, None).await?;
+    rig.wait_until_synced().await?;
+    rig.wait_until_wallet_synced(ALICE).await?;
+    rig.wait_until_wallet_synced(BOB).await?;
+
+    for wallet in [ALICE, BOB] {
+        let history = rig.spaced.client.wallet_export_history(wallet, ExportFormat::Json, None, 0, usize::MAX).await?;
+        let entry = history.lines()
+            .map(serde_json::from_str::<HistoryEntry>)
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .find(|e| e.txid == txid)
+            .expect("transfer must be in the history");
+        assert_eq!(entry.kind, HistoryEntryKind::Transfer, "{} must report a transfer", wallet);
+        assert_eq!(entry.space.as_deref(), Some(registered_space.as_str()));
+    }
+    Ok(())
+}
+
+/// Stages `space` is listed under in `wallet`, checking each filter lists the same
+/// spaces as the matching group of the unfiltered listing
+async fn listed_stages(rig: &TestRig, wallet: &str, space: &str) -> anyhow::Result<Vec<SpaceStateFilter>> {
+    let groups = |listing: SpacesByState| -> Vec<Vec<String>> {
+        [listing.pre_auction, listing.in_auction, listing.claimable, listing.registered].iter().map(|outputs| {
+            outputs.iter().map(|out| out.space.as_ref().expect("space").name.to_string()).collect()
+        }).collect()
+    };
+    let all = groups(rig.spaced.client.wallet_list_spaces_by_state(wallet, None).await?);
+    let stages = [SpaceStateFilter::PreAuction, SpaceStateFilter::InAuction, SpaceStateFilter::Claimable, SpaceStateFilter::Registered];
+
+    let mut listed = Vec::new();
+    for (n, stage) in stages.into_iter().enumerate() {
+        let filtered = groups(rig.spaced.client.wallet_list_spaces_by_state(wallet, Some(stage)).await?);
+        for (m, group) in filtered.iter().enumerate() {
+            match m == n {
+                true => assert_eq!(group, &all[n], "filtering by {:?} must list the same spaces", stage),
+                false => assert!(group.is_empty(), "filtering by {:?} must only fill its group", stage),
+            }
+        }
+        if all[n].iter().any(|name| name == space) {
+            listed.push(stage);
+        }
+    }
+    Ok(listed)
+}
+
+/// alice opens a space and takes it through each stage up to registration
+async fn it_should_list_spaces_by_stage(rig: &TestRig) -> anyhow::Result<()> {
+    let space = "@stages";
+    rig.wait_until_synced().await?;
+    rig.wait_until_wallet_synced(ALICE).await?;
+    let amount = rig.spaced.client.estimate_bid(0).await?.max(TEST_INITIAL_BID) + 1;
+    let response = wallet_do(rig, ALICE, vec![
+        RpcWalletRequest::Open(OpenParams { name: space.to_string(), amount }),
+    ], false).await?;
+    assert!(response.result.iter().all(|tx| tx.error.is_none()), "open must succeed");
+    rig.mine_blocks(1, None).await?;
+    rig.wait_until_synced().await?;
+    rig.wait_until_wallet_synced(ALICE).await?;
+    assert_eq!(listed_stages(rig, ALICE, space).await?, vec![SpaceStateFilter::PreAuction]);
+
+    let rollout = rig.spaced.client.get_rollout(0).await?;
+    assert!(rollout.iter().any(|entry| entry.space == space), "space must be in the next rollout");
+    // Rollouts happen in the block after each interval
+    let height = rig.get_block_count().await? as u32;
+    let to_rollout = ROLLOUT_BLOCK_INTERVAL - (height - 1) % ROLLOUT_BLOCK_INTERVAL;
+    rig.mine_blocks(to_rollout as _, None).await?;
+    rig.wait_until_synced().await?;
+    rig.wait_until_wallet_synced(ALICE).await?;
+    assert_eq!(listed_stages(rig, ALICE, space).await?, vec![SpaceStateFilter::InAuction]);
+
+    let claim_height = rig.spaced.client.get_space(space).await?.expect("space must exist")
+        .spaceout.space.expect("space").claim_height().expect("an auction must have a claim height");
+    let height = rig.get_block_count().await? as u32;
+    rig.mine_blocks((claim_height - height) as _, None).await?;
+    rig.wait_until_synced().await?;
+    rig.wait_until_wallet_synced(ALICE).await?;
+    assert_eq!(listed_stages(rig, ALICE, space).await?, vec![SpaceStateFilter::Claimable]);
+
+    let response = wallet_do(rig, ALICE, vec![
+        RpcWalletRequest::Register(RegisterParams { name: space.to_string(), to: None }),
+    ], false).await?;
+    assert!(response.result.iter().all(|tx| tx.error.is_none()), "register must succeed");
+    rig.mine_blocks(1, None).await?;
+    rig.wait_until_synced().await?;
+    rig.wait_until_wallet_synced(ALICE).await?;
+    assert_eq!(listed_stages(rig, ALICE, space).await?, vec![SpaceStateFilter::Registered]);
+    Ok(())
+}
+
 #[tokio::test]
 async fn run_auction_tests() -> anyhow::Result<()> {
     let rig = TestRig::new_with_regtest_preset().await?;
@@ -485,12 +1111,24 @@ async fn run_auction_tests() -> anyhow::Result<()> {
 
     it_should_open_a_space_for_auction(&rig).await?;
     it_should_allow_outbidding(&rig).await?;
+    it_should_prove_ownership_back_to_the_open(&rig).await?;
+    it_should_export_history(&rig).await?;
     it_should_only_accept_forced_zero_value_bid_increments_and_revoke(&rig).await?;
     it_should_allow_claim_on_or_after_claim_height(&rig).await?;
     it_should_allow_batch_transfers_refreshing_expire_height(&rig).await?;
     it_should_allow_applying_script_in_batch(&rig).await?;
+    it_should_transfer_spaces_to_one_address_in_distinct_outputs(&rig).await?;
+    it_should_reject_space_addresses_for_other_networks(&rig).await?;
+    it_should_reject_duplicate_execute_context(&rig).await?;
+    it_should_reject_requests_for_the_reasons_checked(&rig).await?;
+    it_should_spend_only_the_listed_coins(&rig).await?;
     it_should_replace_mempool_bids(&rig).await?;
     it_should_maintain_locktime_when_fee_bumping(&rig).await?;
+    it_should_abandon_an_open_in_the_mempool(&rig).await?;
+    it_should_require_a_token_for_high_value_sends(&rig).await?;
+    it_should_build_identical_transactions_in_a_dry_run(&rig).await?;
+    it_should_export_received_transfers_as_transfers(&rig).await?;
+    it_should_list_spaces_by_stage(&rig).await?;
 
     Ok(())
 }
@@ -502,15 +1140,53 @@ async fn wallet_do(rig: &TestRig, wallet: &str, requests: Vec<RpcWalletRequest>,
             bidouts: None,
             requests,
             fee_rate: Some(FeeRate::from_sat_per_vb(1).expect("fee")),
+            fee: None,
+            conf_target: None,
+            estimate_mode: None,
             dust: None,
             force,
             confirmed_only: false,
             skip_tx_check: false,
+            coin_selection: CoinSelectionStrategy::default(),
+            avoid_address_reuse: false,
+            donate_dust_change_to_fee: true,
+            outbid_limits: OutbidLimits::default(),
+            resolve_pending: false,
+            expected_tip: None,
+            confirm_token: None,
+            broadcast: true,
         },
     ).await?;
     Ok(res)
 }
 
+fn send_coins_request(to: String, amount: Amount, confirm_token: Option<String>, broadcast: bool) -> RpcWalletTxBuilder {
+    RpcWalletTxBuilder {
+        bidouts: None,
+        requests: vec![RpcWalletRequest::SendCoins(SendCoinsParams {
+            amount: Some(amount),
+            to,
+            from: vec![],
+        })],
+        fee_rate: Some(FeeRate::from_sat_per_vb(1).expect("fee")),
+        fee: None,
+        conf_target: None,
+        estimate_mode: None,
+        dust: None,
+        force: false,
+        confirmed_only: false,
+        skip_tx_check: false,
+        coin_selection: CoinSelectionStrategy::default(),
+        avoid_address_reuse: false,
+        donate_dust_change_to_fee: true,
+        outbid_limits: OutbidLimits::default(),
+        resolve_pending: false,
+        expected_tip: None,
+        confirm_token,
+        broadcast,
+    }
+}
+
 pub async fn load_wallet(rig: &TestRig, wallets_dir: PathBuf, name: &str) -> anyhow::Result<()> {
     let wallet_path = wallets_dir.join(format!("{name}.json"));
     let json = std::fs::read_to_string(wallet_path)?;
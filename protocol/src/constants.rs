@@ -16,6 +16,10 @@ pub struct ChainAnchor {
     pub height: u32,
 }
 
+/// Version of the consensus rules implemented by this crate.
+/// Bumped whenever a change affects how transactions are validated.
+pub const RULES_VERSION: u32 = 0;
+
 pub const RESERVED_SPACES: [&'static [u8]; 3] = [b"\x07example", b"\x04test", b"\x05local"];
 
 /// The number of blocks between each rollout of new spaces for auction.
@@ -17,7 +17,7 @@ use crate::{
 const COMPRESSED_PSBT_SIZE: usize = 65;
 
 pub struct BidPsbt {
-    pub(crate) outpoint: OutPoint,
+    pub outpoint: OutPoint,
     pub(crate) signature: Signature,
     pub(crate) burn_amount: Amount,
 }
@@ -124,7 +124,7 @@ impl TxContext {
     }
 
     /// Carried PSBT must be the first output in a transaction
-    fn get_bid_psbt(tx: &Transaction) -> Option<BidPsbt> {
+    pub fn get_bid_psbt(tx: &Transaction) -> Option<BidPsbt> {
         if tx.input.is_empty() || tx.output.is_empty() || !tx.output[0].script_pubkey.is_op_return()
         {
             return None;
@@ -115,6 +115,34 @@ impl TestRig {
         })
     }
 
+    /// Starts another spaced on this rig's bitcoind with the given extra
+    /// arguments and waits until it's synced
+    pub async fn spawn_spaced(&self, args: Vec<&str>) -> Result<SpaceD> {
+        let rpc_url = self.bitcoind.rpc_url();
+        let mut spaced_args = vec![
+            "--chain",
+            "regtest",
+            "--bitcoin-rpc-url",
+            &rpc_url,
+            "--bitcoin-rpc-user",
+            "user",
+            "--bitcoin-rpc-password",
+            "password",
+        ];
+        spaced_args.extend(args);
+        let spaced = SpaceD::new(spaced::Conf {
+            args: spaced_args,
+            view_stdout: false,
+        })
+        .await?;
+
+        let count = self.get_block_count().await? as u32;
+        while spaced.client.get_server_info().await?.tip.height < count {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        Ok(spaced)
+    }
+
     /// Waits until spaced tip == bitcoind tip
     pub async fn wait_until_synced(&self) -> anyhow::Result<()> {
         loop {
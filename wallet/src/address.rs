@@ -18,6 +18,16 @@ impl SpaceAddress {
     pub fn script_pubkey(&self) -> ScriptBuf {
         self.0.script_pubkey()
     }
+
+    /// Whether the address is encoded for the given network
+    pub fn is_valid_for_network(&self, network: Network) -> bool {
+        let expected = match network {
+            Network::Bitcoin => "bc",
+            Network::Regtest => "bcrt",
+            _ => "tb",
+        };
+        find_bech32_prefix(&self.0.to_string()) == expected
+    }
 }
 
 impl fmt::Display for SpaceAddress {
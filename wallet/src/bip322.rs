@@ -0,0 +1,216 @@
+//! BIP-322 generic signed messages using the "simple" signature format.
+//!
+//! A message is signed by spending a virtual `to_spend` transaction paying to the
+//! signing script with a virtual `to_sign` transaction. The signature is the
+//! consensus encoded witness of that spend.
+
+use bitcoin::{
+    absolute::LockTime,
+    base64::{engine::general_purpose::STANDARD, Engine},
+    consensus,
+    hashes::{sha256, Hash, HashEngine},
+    opcodes::{all::OP_RETURN, OP_0},
+    script::{Builder, PushBytesBuf},
+    transaction, Amount, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness,
+};
+
+const TAG: &[u8] = b"BIP0322-signed-message";
+
+/// Tagged hash of the message committed to in `to_spend`
+pub fn message_hash(message: &[u8]) -> sha256::Hash {
+    let tag = sha256::Hash::hash(TAG);
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_ref());
+    engine.input(tag.as_ref());
+    engine.input(message);
+    sha256::Hash::from_engine(engine)
+}
+
+/// The virtual transaction paying to `script_pubkey` whose output is spent to sign
+pub fn to_spend(script_pubkey: &ScriptBuf, message: &[u8]) -> Transaction {
+    let hash = PushBytesBuf::from(message_hash(message).to_byte_array());
+    Transaction {
+        version: transaction::Version(0),
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint {
+                txid: Txid::all_zeros(),
+                vout: 0xFFFFFFFF,
+            },
+            script_sig: Builder::new()
+                .push_opcode(OP_0)
+                .push_slice(hash)
+                .into_script(),
+            sequence: Sequence::ZERO,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: Amount::ZERO,
+            script_pubkey: script_pubkey.clone(),
+        }],
+    }
+}
+
+/// The unsigned virtual transaction spending the output of `to_spend`
+pub fn to_sign(to_spend: &Transaction) -> Transaction {
+    Transaction {
+        version: transaction::Version(0),
+        lock_time: LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output: OutPoint {
+                txid: to_spend.compute_txid(),
+                vout: 0,
+            },
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::ZERO,
+            witness: Witness::new(),
+        }],
+        output: vec![TxOut {
+            value: Amount::ZERO,
+            script_pubkey: Builder::new().push_opcode(OP_RETURN).into_script(),
+        }],
+    }
+}
+
+/// Encodes the witness of a signed `to_sign` as a simple signature
+pub fn encode_simple(witness: &Witness) -> String {
+    STANDARD.encode(consensus::serialize(witness))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, str::FromStr};
+
+    use bdk_wallet::{chain::ConfirmationTime, KeychainKind};
+    use bitcoin::{
+        bip32::Xpriv,
+        secp256k1::{Message, Secp256k1},
+        sighash::{Prevouts, SighashCache},
+        taproot, Address, Network, TapSighashType, XOnlyPublicKey,
+    };
+
+    use super::*;
+    use crate::{SpacesWallet, WalletConfig, WalletDescriptors};
+
+    // Test vectors from BIP-322
+    const ADDRESS: &str = "bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l";
+
+    fn script_pubkey() -> ScriptBuf {
+        Address::from_str(ADDRESS)
+            .unwrap()
+            .assume_checked()
+            .script_pubkey()
+    }
+
+    #[test]
+    fn test_message_hash() {
+        assert_eq!(
+            message_hash(b"").to_string(),
+            "c90c269c4f8fcbe6880f72a721ddfbf1914268a794cbb21cfafee13770ae19f1"
+        );
+        assert_eq!(
+            message_hash(b"Hello World").to_string(),
+            "f0eb03b1a75ac6d9847f55c624a99169b5dccba2a31f5b23bea77ba270de0a7a"
+        );
+    }
+
+    #[test]
+    fn test_virtual_transactions() {
+        let spend = to_spend(&script_pubkey(), b"");
+        assert_eq!(
+            spend.compute_txid().to_string(),
+            "c5680aa69bb8d860bf82d4e9cd3504b55dde018de765a91bb566283c545a99a7"
+        );
+        assert_eq!(
+            to_sign(&spend).compute_txid().to_string(),
+            "1e9654e951a5ba44c8604c4de6c67fd78a27e81dcadcfe1edf638ba3aaebaed6"
+        );
+
+        let spend = to_spend(&script_pubkey(), b"Hello World");
+        assert_eq!(
+            spend.compute_txid().to_string(),
+            "b79d196740ad5217771c1098fc4a4b51e0535c32236c71f1ea4d61a2d603352b"
+        );
+        assert_eq!(
+            to_sign(&spend).compute_txid().to_string(),
+            "88737ae86f2077145f93cc4b153ae9a1cb8d56afa511988c149c5c8c9d93bddf"
+        );
+    }
+
+    #[test]
+    fn test_sign_message_round_trip() {
+        let data_dir = std::env::temp_dir().join(format!("spaces-bip322-{}", std::process::id()));
+        _ = fs::remove_dir_all(&data_dir);
+
+        let xpriv = Xpriv::new_master(Network::Regtest, &[7u8; 32]).unwrap();
+        let mut wallet = SpacesWallet::new(WalletConfig {
+            name: "bip322".to_string(),
+            data_dir: data_dir.clone(),
+            start_block: 0,
+            network: Network::Regtest,
+            genesis_hash: None,
+            space_descriptors: WalletDescriptors {
+                external: format!("tr({}/86'/1'/0'/0/*)", xpriv),
+                internal: format!("tr({}/86'/1'/0'/1/*)", xpriv),
+            },
+            cipher: None,
+        })
+        .unwrap();
+
+        // Fund the wallet with an output it can sign for
+        let script_pubkey = wallet
+            .spaces
+            .reveal_next_address(KeychainKind::External)
+            .address
+            .script_pubkey();
+        let funding = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn::default()],
+            output: vec![TxOut {
+                value: Amount::from_sat(10_000),
+                script_pubkey: script_pubkey.clone(),
+            }],
+        };
+        let outpoint = OutPoint {
+            txid: funding.compute_txid(),
+            vout: 0,
+        };
+        wallet
+            .insert_tx(funding, ConfirmationTime::Unconfirmed { last_seen: 0 })
+            .unwrap();
+
+        let signature = wallet.sign_message(outpoint, b"Hello World").unwrap();
+        let witness: Witness =
+            consensus::deserialize(&STANDARD.decode(signature).unwrap()).unwrap();
+        assert_eq!(witness.len(), 1, "a key path spend has a single signature");
+
+        // The signature must be valid for the virtual spend of the output's script
+        let spend = to_spend(&script_pubkey, b"Hello World");
+        let sign = to_sign(&spend);
+        let sighash = SighashCache::new(&sign)
+            .taproot_key_spend_signature_hash(
+                0,
+                &Prevouts::All(&spend.output),
+                TapSighashType::Default,
+            )
+            .unwrap();
+        let signature = taproot::Signature::from_slice(&witness[0]).unwrap();
+        let key = XOnlyPublicKey::from_slice(&script_pubkey.as_bytes()[2..]).unwrap();
+        Secp256k1::verification_only()
+            .verify_schnorr(
+                &signature.signature,
+                &Message::from_digest(sighash.to_byte_array()),
+                &key,
+            )
+            .expect("valid signature");
+
+        assert!(
+            wallet
+                .sign_message(OutPoint::null(), b"Hello World")
+                .is_err(),
+            "outputs not owned by the wallet can't sign"
+        );
+        fs::remove_dir_all(data_dir).unwrap();
+    }
+}
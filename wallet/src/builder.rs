@@ -4,6 +4,10 @@ use std::{
     default::Default,
     ops::{Add, Mul},
     str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
 };
 
 use anyhow::{anyhow, Context};
@@ -11,6 +15,7 @@ use bdk_wallet::{
     wallet::{
         coin_selection::{
             CoinSelectionAlgorithm, CoinSelectionResult, DefaultCoinSelectionAlgorithm, Error,
+            Excess, LargestFirstCoinSelection, OldestFirstCoinSelection,
         },
         error::CreateTxError,
         tx_builder::TxOrdering,
@@ -48,6 +53,10 @@ pub struct Builder {
     /// e.g. opens for name that already exist ... etc.
     /// enable only for testing purposes!
     force: bool,
+
+    /// Coins to fund a psbt, or the transaction carrying transfers, with
+    /// instead of using coin selection
+    inputs: Vec<OutPoint>,
 }
 
 pub struct BuilderIterator<'a> {
@@ -58,6 +67,8 @@ pub struct BuilderIterator<'a> {
     force: bool,
     median_time: u64,
     coin_selection: SpacesAwareCoinSelection,
+    /// The last operation built and the stack size before it ran
+    last: Option<(StackOp, usize)>,
 }
 
 pub enum BuilderStack {
@@ -81,6 +92,7 @@ pub enum StackRequest {
     Execute(ExecuteRequest),
 }
 
+#[derive(Clone)]
 pub enum StackOp {
     Prepare(CreateParams),
     Open(OpenParams),
@@ -88,19 +100,23 @@ pub enum StackOp {
     Execute(ExecuteParams),
 }
 
+#[derive(Clone)]
 pub struct SpaceScriptRevealParams {
     signing: SpaceScriptSigningInfo,
     commitment: FullTxOut,
 }
 
+#[derive(Clone)]
 pub struct OpenParams {
     reveals: Vec<SpaceScriptRevealParams>,
     amount: Amount,
 }
 
+#[derive(Clone)]
 pub struct ExecuteParams {
     reveal: SpaceScriptRevealParams,
     context: Vec<SpaceTransfer>,
+    inputs: Vec<OutPoint>,
 }
 
 #[derive(Debug, Clone)]
@@ -131,13 +147,17 @@ pub struct CoinTransfer {
 pub struct ExecuteRequest {
     pub context: Vec<SpaceTransfer>,
     pub script: script::Builder,
+    /// Coins to fund the execute transaction with instead of using coin selection
+    pub inputs: Vec<OutPoint>,
 }
 
+#[derive(Clone)]
 pub struct CreateParams {
     opens: Vec<OpenRequest>,
     executes: Vec<ExecuteRequest>,
     transfers: Vec<TransferRequest>,
     auction_outputs: Option<u8>,
+    inputs: Vec<OutPoint>,
 }
 
 #[derive(Clone, Debug)]
@@ -339,7 +359,46 @@ impl Builder {
         coin_transfers: Vec<CoinTransfer>,
         fee_rate: FeeRate,
         dust: Option<Amount>,
+        inputs: &[OutPoint],
     ) -> anyhow::Result<(Transaction, Vec<FullTxOut>)> {
+        let (commit_psbt, tap_outputs) = Self::prepare_all_psbt(
+            coin_selection,
+            median_time,
+            w,
+            auction_outputs,
+            reveals,
+            space_transfers,
+            coin_transfers,
+            fee_rate,
+            dust,
+            inputs,
+        )?;
+
+        let tx = w.sign(commit_psbt, None)?;
+        let txid = tx.compute_txid();
+        let commitments = tap_outputs
+            .into_iter()
+            .map(|vout| FullTxOut {
+                outpoint: OutPoint { txid, vout },
+                txout: tx.output[vout as usize].clone(),
+            })
+            .collect();
+
+        Ok((tx, commitments))
+    }
+
+    fn prepare_all_psbt(
+        coin_selection: SpacesAwareCoinSelection,
+        median_time: u64,
+        w: &mut SpacesWallet,
+        auction_outputs: Option<u8>,
+        reveals: Option<&Vec<SpaceScriptSigningInfo>>,
+        space_transfers: Vec<SpaceTransfer>,
+        coin_transfers: Vec<CoinTransfer>,
+        fee_rate: FeeRate,
+        dust: Option<Amount>,
+        inputs: &[OutPoint],
+    ) -> anyhow::Result<(Psbt, Vec<u32>)> {
         let coin_selection_confirmed_only = coin_selection.confirmed_only;
         let mut vout: u32 = 0;
         let mut tap_outputs = Vec::new();
@@ -419,6 +478,10 @@ impl Builder {
                 }
             }
 
+            if !inputs.is_empty() {
+                builder.add_utxos(inputs)?.manually_selected_only();
+            }
+
             builder.enable_rbf().fee_rate(fee_rate);
             let r = builder.finish().map_err(|e| match e {
                 CreateTxError::CoinSelection(e) if coin_selection_confirmed_only => {
@@ -431,29 +494,22 @@ impl Builder {
             r
         };
 
-        let tx = w.sign(commit_psbt, None)?;
-        let txid = tx.compute_txid();
-        let commitments = tap_outputs
-            .into_iter()
-            .map(|vout| FullTxOut {
-                outpoint: OutPoint { txid, vout },
-                txout: tx.output[vout as usize].clone(),
-            })
-            .collect();
-
-        Ok((tx, commitments))
+        Ok((commit_psbt, tap_outputs))
     }
 }
 
 pub struct TaggedTransaction {
     pub tx: Transaction,
     pub tags: Vec<TransactionTag>,
+    /// Change below the dust limit that was added to the fee
+    pub dust_change: Amount,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
 #[serde(rename_all = "kebab-case")]
 pub enum TransactionTag {
     FeeBump,
+    CancelBid,
     Bidouts,
     Commitment,
     Transfers,
@@ -463,14 +519,34 @@ pub enum TransactionTag {
     ForceSpendTestOnly,
 }
 
-impl Iterator for BuilderIterator<'_> {
-    type Item = anyhow::Result<TaggedTransaction>;
+impl BuilderIterator<'_> {
+    /// Builds the last transaction again with a fresh coin selection that
+    /// excludes the given outpoints, e.g. coins that were spent elsewhere.
+    /// Returns false if nothing was built yet.
+    pub fn retry_excluding(&mut self, outpoints: &[OutPoint]) -> bool {
+        let (op, len) = match self.last.take() {
+            None => return false,
+            Some(last) => last,
+        };
+        // Drop any follow-up operations the last one queued
+        self.stack.truncate(len);
+        self.stack.push(op);
+        self.coin_selection
+            .exclude_outputs
+            .extend(outpoints.iter().map(|outpoint| SelectionOutput {
+                outpoint: *outpoint,
+                is_space: false,
+                is_spaceout: false,
+            }));
+        true
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
+    fn build_next(&mut self) -> Option<anyhow::Result<TaggedTransaction>> {
         let op = match self.stack.pop() {
             None => return None,
             Some(req) => req,
         };
+        self.last = Some((op.clone(), self.stack.len()));
 
         match op {
             StackOp::Prepare(params) => {
@@ -506,7 +582,7 @@ impl Iterator for BuilderIterator<'_> {
                         return Some(Err(signing_info.unwrap_err()));
                     }
                     reveals.push(signing_info.unwrap());
-                    contexts.push(execute.context);
+                    contexts.push((execute.context, execute.inputs));
                 }
 
                 let prep = Builder::prepare_all(
@@ -533,6 +609,7 @@ impl Iterator for BuilderIterator<'_> {
                         .collect(),
                     self.fee_rate,
                     self.dust,
+                    &params.inputs,
                 );
                 if prep.is_err() {
                     return Some(Err(prep.unwrap_err()));
@@ -566,7 +643,7 @@ impl Iterator for BuilderIterator<'_> {
                     }))
                 }
 
-                for ((signing, commitment), context) in
+                for ((signing, commitment), (context, inputs)) in
                     reveals_iter.zip(commitments_iter).zip(contexts)
                 {
                     self.stack.push(StackOp::Execute(ExecuteParams {
@@ -575,10 +652,15 @@ impl Iterator for BuilderIterator<'_> {
                             commitment,
                         },
                         context,
+                        inputs,
                     }))
                 }
 
-                Some(Ok(TaggedTransaction { tx, tags }))
+                Some(Ok(TaggedTransaction {
+                    tx,
+                    tags,
+                    dust_change: Amount::ZERO,
+                }))
             }
             StackOp::Open(params) => {
                 let tx = Builder::open_tx(
@@ -591,6 +673,7 @@ impl Iterator for BuilderIterator<'_> {
                 Some(tx.map(|tx| TaggedTransaction {
                     tx,
                     tags: vec![TransactionTag::Open],
+                    dust_change: Amount::ZERO,
                 }))
             }
             StackOp::Execute(params) => {
@@ -604,6 +687,7 @@ impl Iterator for BuilderIterator<'_> {
                 Some(tx.map(|tx| TaggedTransaction {
                     tx,
                     tags: vec![TransactionTag::Script],
+                    dust_change: Amount::ZERO,
                 }))
             }
             StackOp::Bid(bid) => {
@@ -618,12 +702,27 @@ impl Iterator for BuilderIterator<'_> {
                 Some(tx.map(|tx| TaggedTransaction {
                     tx,
                     tags: vec![TransactionTag::Bid],
+                    dust_change: Amount::ZERO,
                 }))
             }
         }
     }
 }
 
+impl Iterator for BuilderIterator<'_> {
+    type Item = anyhow::Result<TaggedTransaction>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.coin_selection.dust_change.store(0, Ordering::Relaxed);
+        let result = self.build_next()?;
+        let dust_change = Amount::from_sat(self.coin_selection.dust_change.load(Ordering::Relaxed));
+        Some(result.map(|mut tagged| {
+            tagged.dust_change = dust_change;
+            tagged
+        }))
+    }
+}
+
 impl Builder {
     pub fn new() -> Self {
         Builder {
@@ -631,6 +730,7 @@ impl Builder {
             fee_rate: None,
             bidouts: None,
             force: false,
+            inputs: Vec::new(),
         }
     }
 
@@ -644,6 +744,11 @@ impl Builder {
         self
     }
 
+    pub fn inputs(mut self, inputs: Vec<OutPoint>) -> Self {
+        self.inputs = inputs;
+        self
+    }
+
     pub fn bidouts(mut self, num: u8) -> Self {
         self.bidouts = Some(num);
         self
@@ -682,6 +787,23 @@ impl Builder {
         self.requests.push(StackRequest::Execute(ExecuteRequest {
             context: spaces,
             script: space_script,
+            inputs: Vec::new(),
+        }));
+        self
+    }
+
+    /// Same as [Builder::add_execute] but funds the execute transaction
+    /// exclusively from the given wallet coins
+    pub fn add_execute_with_inputs(
+        mut self,
+        spaces: Vec<SpaceTransfer>,
+        space_script: script::Builder,
+        inputs: Vec<OutPoint>,
+    ) -> Self {
+        self.requests.push(StackRequest::Execute(ExecuteRequest {
+            context: spaces,
+            script: space_script,
+            inputs,
         }));
         self
     }
@@ -786,6 +908,7 @@ impl Builder {
                 executes,
                 transfers,
                 auction_outputs,
+                inputs: self.inputs,
             }));
         }
 
@@ -797,9 +920,92 @@ impl Builder {
             force: self.force,
             median_time,
             coin_selection,
+            last: None,
         })
     }
 
+    /// Builds an unsigned psbt for requests that fit into a single transaction
+    /// i.e. transfers, registers and bidouts, or a bid on its own. Opens and
+    /// script executions need a chain of signed transactions and are not supported.
+    pub fn build_psbt(
+        self,
+        dust: Option<Amount>,
+        median_time: u64,
+        wallet: &mut SpacesWallet,
+        coin_selection: SpacesAwareCoinSelection,
+    ) -> anyhow::Result<(Psbt, Vec<TransactionTag>)> {
+        let fee_rate = self
+            .fee_rate
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("fee_rate is required"))?
+            .clone();
+
+        if let [StackRequest::Bid(bid)] = self.requests.as_slice() {
+            if self.bidouts.is_none() {
+                let psbt = Self::bid_psbt(
+                    coin_selection,
+                    wallet,
+                    bid.space.clone(),
+                    bid.amount,
+                    fee_rate,
+                    self.force,
+                    &self.inputs,
+                )?;
+                return Ok((psbt, vec![TransactionTag::Bid]));
+            }
+        }
+
+        let mut space_transfers = Vec::new();
+        let mut coin_transfers = Vec::new();
+        for req in self.requests {
+            match req {
+                StackRequest::Register(params) => {
+                    let to = match params.to {
+                        None => wallet.next_unused_space_address(),
+                        Some(address) => address,
+                    };
+                    space_transfers.push(SpaceTransfer {
+                        space: params.space,
+                        recipient: to.0,
+                    })
+                }
+                StackRequest::Transfer(TransferRequest::Space(transfer)) => {
+                    space_transfers.push(transfer)
+                }
+                StackRequest::Transfer(TransferRequest::Coin(transfer)) => {
+                    coin_transfers.push(transfer)
+                }
+                StackRequest::Open(_) | StackRequest::Bid(_) | StackRequest::Execute(_) => {
+                    return Err(anyhow!(
+                        "only transfers, registers, bidouts or a single bid can be built as a psbt"
+                    ))
+                }
+            }
+        }
+
+        let mut tags = Vec::new();
+        if !space_transfers.is_empty() || !coin_transfers.is_empty() {
+            tags.push(TransactionTag::Transfers);
+        }
+        if self.bidouts.is_some() {
+            tags.push(TransactionTag::Bidouts);
+        }
+
+        let (psbt, _) = Self::prepare_all_psbt(
+            coin_selection,
+            median_time,
+            wallet,
+            self.bidouts,
+            None,
+            space_transfers,
+            coin_transfers,
+            fee_rate,
+            dust,
+            &self.inputs,
+        )?;
+        Ok((psbt, tags))
+    }
+
     fn bid_tx(
         coin_selection: SpacesAwareCoinSelection,
         w: &mut SpacesWallet,
@@ -809,7 +1015,20 @@ impl Builder {
         force: bool,
     ) -> anyhow::Result<Transaction> {
         w.watch_bid_spend(prev.outpoint());
+        let bid_psbt = Self::bid_psbt(coin_selection, w, prev, bid, fee_rate, force, &[])?;
+        let signed = w.sign(bid_psbt, None)?;
+        Ok(signed)
+    }
 
+    fn bid_psbt(
+        coin_selection: SpacesAwareCoinSelection,
+        w: &mut SpacesWallet,
+        prev: FullSpaceOut,
+        bid: Amount,
+        fee_rate: FeeRate,
+        force: bool,
+        inputs: &[OutPoint],
+    ) -> anyhow::Result<Psbt> {
         let (offer, placeholder) = w.new_bid_psbt(bid, &coin_selection)?;
         let bid_psbt = {
             let mut builder = w.spaces.build_tx().coin_selection(coin_selection);
@@ -826,11 +1045,12 @@ impl Builder {
                 )?
                 .add_refund(&prev)?
                 .fee_rate(fee_rate);
+            if !inputs.is_empty() {
+                builder.add_utxos(inputs)?.manually_selected_only();
+            }
             builder.finish()?
         };
-
-        let signed = w.sign(bid_psbt, None)?;
-        Ok(signed)
+        Ok(bid_psbt)
     }
 
     fn open_tx(
@@ -894,6 +1114,10 @@ impl Builder {
                 builder.add_transfer(TransferRequest::Space(transfer))?;
             }
 
+            if !params.inputs.is_empty() {
+                builder.add_utxos(&params.inputs)?.manually_selected_only();
+            }
+
             builder
                 // add reveal last to not disrupt space inputs order
                 .add_reveal(params.reveal.commitment, params.reveal.signing)?
@@ -923,19 +1147,56 @@ pub struct SelectionOutput {
     pub is_spaceout: bool,
 }
 
+/// Algorithm used to select among the wallet's own coins
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CoinSelectionStrategy {
+    /// Minimizes change and fees (falls back to a random draw)
+    #[default]
+    BranchAndBound,
+    /// Spends the largest coins first
+    LargestFirst,
+    /// Spends the oldest coins first
+    OldestFirst,
+}
+
+impl FromStr for CoinSelectionStrategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "branch_and_bound" | "bnb" => Ok(Self::BranchAndBound),
+            "largest_first" => Ok(Self::LargestFirst),
+            "oldest_first" => Ok(Self::OldestFirst),
+            _ => Err(anyhow!(
+                "unknown coin selection `{}`, expected one of: branch_and_bound, largest_first, oldest_first",
+                s
+            )),
+        }
+    }
+}
+
 /// A coin selection algorithm that :
 /// 1. Guarantees required utxos are ordered first appending
 /// any funding/change outputs to the end of the selected utxos.
 /// 2. Excludes all dust outputs to avoid accidentally spending space utxos
 /// 3. Enables adding additional output exclusions
+/// 4. Optionally avoids combining outputs of the same address
 #[derive(Debug, Clone)]
 pub struct SpacesAwareCoinSelection {
     pub default_algorithm: DefaultCoinSelectionAlgorithm,
+    pub strategy: CoinSelectionStrategy,
     // Exclude outputs
     pub exclude_outputs: Vec<SelectionOutput>,
     // Whether to use confirmed only outputs
     // to fund the transaction
     pub confirmed_only: bool,
+    // Whether to avoid spending outputs of the same
+    // address together unless needed to meet the target
+    pub avoid_address_reuse: bool,
+    // Change below the dust limit that the last selection
+    // added to the fee instead of creating a change output
+    pub dust_change: Arc<AtomicU64>,
 }
 
 impl SpacesAwareCoinSelection {
@@ -945,9 +1206,71 @@ impl SpacesAwareCoinSelection {
     pub fn new(excluded: Vec<SelectionOutput>, confirmed_only: bool) -> Self {
         Self {
             default_algorithm: DefaultCoinSelectionAlgorithm::default(),
+            strategy: CoinSelectionStrategy::default(),
             exclude_outputs: excluded,
             confirmed_only,
+            avoid_address_reuse: false,
+            dust_change: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    fn select(
+        &self,
+        required_utxos: Vec<WeightedUtxo>,
+        optional_utxos: Vec<WeightedUtxo>,
+        fee_rate: FeeRate,
+        target_amount: u64,
+        drain_script: &Script,
+    ) -> Result<CoinSelectionResult, Error> {
+        match self.strategy {
+            CoinSelectionStrategy::BranchAndBound => self.default_algorithm.coin_select(
+                required_utxos,
+                optional_utxos,
+                fee_rate,
+                target_amount,
+                drain_script,
+            ),
+            CoinSelectionStrategy::LargestFirst => LargestFirstCoinSelection.coin_select(
+                required_utxos,
+                optional_utxos,
+                fee_rate,
+                target_amount,
+                drain_script,
+            ),
+            CoinSelectionStrategy::OldestFirst => OldestFirstCoinSelection.coin_select(
+                required_utxos,
+                optional_utxos,
+                fee_rate,
+                target_amount,
+                drain_script,
+            ),
+        }
+    }
+
+    /// Keeps only the largest optional utxo of each address, skipping
+    /// addresses that required utxos already spend from
+    fn one_per_address(
+        required_utxos: &[WeightedUtxo],
+        optional_utxos: &[WeightedUtxo],
+    ) -> Vec<WeightedUtxo> {
+        let mut largest: BTreeMap<ScriptBuf, WeightedUtxo> = BTreeMap::new();
+        for weighted_utxo in optional_utxos {
+            let script = &weighted_utxo.utxo.txout().script_pubkey;
+            if required_utxos
+                .iter()
+                .any(|r| &r.utxo.txout().script_pubkey == script)
+            {
+                continue;
+            }
+            match largest.get(script) {
+                Some(current)
+                    if current.utxo.txout().value >= weighted_utxo.utxo.txout().value => {}
+                _ => {
+                    largest.insert(script.clone(), weighted_utxo.clone());
+                }
+            }
         }
+        largest.into_values().collect()
     }
 }
 
@@ -985,13 +1308,42 @@ impl CoinSelectionAlgorithm for SpacesAwareCoinSelection {
                     .any(|o| o.outpoint == weighted_utxo.utxo.outpoint())
         });
 
-        let mut result = self.default_algorithm.coin_select(
-            required_utxos,
-            optional_utxos,
-            fee_rate,
-            target_amount,
-            drain_script,
-        )?;
+        let mut result = if self.avoid_address_reuse {
+            let spread = Self::one_per_address(&required_utxos, &optional_utxos);
+            match self.select(
+                required_utxos.clone(),
+                spread,
+                fee_rate,
+                target_amount,
+                drain_script,
+            ) {
+                // Combining outputs of the same address is needed to meet the target
+                Err(Error::InsufficientFunds { .. }) => self.select(
+                    required_utxos,
+                    optional_utxos,
+                    fee_rate,
+                    target_amount,
+                    drain_script,
+                ),
+                result => result,
+            }
+        } else {
+            self.select(
+                required_utxos,
+                optional_utxos,
+                fee_rate,
+                target_amount,
+                drain_script,
+            )
+        }?;
+
+        let dust_change = match result.excess {
+            Excess::NoChange {
+                remaining_amount, ..
+            } => remaining_amount,
+            Excess::Change { .. } => 0,
+        };
+        self.dust_change.store(dust_change, Ordering::Relaxed);
 
         let mut optional = Vec::with_capacity(result.selected.len() - required.len());
         for utxo in result.selected.drain(..) {
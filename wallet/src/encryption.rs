@@ -0,0 +1,605 @@
+//! At-rest encryption of wallet files with a key derived from a user passphrase.
+//!
+//! Sealed files start with a header holding the key derivation salt followed by
+//! a random nonce and the ChaCha20-Poly1305 ciphertext. All files of a wallet
+//! share the salt so the key is only derived once when the wallet is loaded.
+//!
+//! Encrypted stores use the same header followed by length prefixed records,
+//! each sealing one changeset, so appending doesn't rewrite the whole store.
+
+use std::{
+    fs,
+    io::{self, Read, Write},
+    num::NonZeroU32,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context};
+use bdk_wallet::{chain::Append, wallet::ChangeSet};
+use bincode::config;
+use ring::{
+    aead, pbkdf2,
+    rand::{SecureRandom, SystemRandom},
+};
+
+const MAGIC: &[u8; 8] = b"SPACEENC";
+/// A single sealed file
+const VERSION: u8 = 1;
+/// A store of sealed records
+const STORE_VERSION: u8 = 2;
+const SALT_LEN: usize = 16;
+const HEADER_LEN: usize = MAGIC.len() + 1 + SALT_LEN;
+const RECORD_LEN_SIZE: usize = 4;
+const KDF_ITERATIONS: u32 = 600_000;
+
+#[derive(Clone)]
+pub struct WalletCipher {
+    key: [u8; 32],
+    salt: [u8; SALT_LEN],
+}
+
+impl WalletCipher {
+    /// Derives a new key from the passphrase with a random salt
+    pub fn new(passphrase: &str) -> anyhow::Result<Self> {
+        let mut salt = [0u8; SALT_LEN];
+        SystemRandom::new()
+            .fill(&mut salt)
+            .map_err(|_| anyhow!("could not generate salt"))?;
+        Ok(Self::derive(passphrase, salt))
+    }
+
+    /// Derives the key `data` was sealed with from the passphrase and decrypts it
+    pub fn unseal(passphrase: &str, data: &[u8]) -> anyhow::Result<(Self, Vec<u8>)> {
+        let cipher = Self::derive(passphrase, header_salt(data, VERSION)?);
+        let plaintext = cipher.open(data)?;
+        Ok((cipher, plaintext))
+    }
+
+    fn derive(passphrase: &str, salt: [u8; SALT_LEN]) -> Self {
+        let mut key = [0u8; 32];
+        pbkdf2::derive(
+            pbkdf2::PBKDF2_HMAC_SHA256,
+            NonZeroU32::new(KDF_ITERATIONS).expect("non zero iterations"),
+            &salt,
+            passphrase.as_bytes(),
+            &mut key,
+        );
+        Self { key, salt }
+    }
+
+    fn aead_key(&self) -> aead::LessSafeKey {
+        aead::LessSafeKey::new(
+            aead::UnboundKey::new(&aead::CHACHA20_POLY1305, &self.key).expect("valid key length"),
+        )
+    }
+
+    fn header(&self, version: u8) -> Vec<u8> {
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(MAGIC);
+        header.push(version);
+        header.extend_from_slice(&self.salt);
+        header
+    }
+
+    /// Encrypts `plaintext` returning the nonce followed by the ciphertext
+    fn seal_with(&self, aad: &[u8], plaintext: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let mut nonce = [0u8; aead::NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| anyhow!("could not generate nonce"))?;
+
+        let mut in_out = plaintext.to_vec();
+        self.aead_key()
+            .seal_in_place_append_tag(
+                aead::Nonce::assume_unique_for_key(nonce),
+                aead::Aad::from(aad),
+                &mut in_out,
+            )
+            .map_err(|_| anyhow!("could not encrypt wallet data"))?;
+        let mut sealed = Vec::with_capacity(nonce.len() + in_out.len());
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&in_out);
+        Ok(sealed)
+    }
+
+    /// Decrypts the output of [WalletCipher::seal_with], `None` if it doesn't
+    /// authenticate
+    fn open_with(&self, aad: &[u8], sealed: &[u8]) -> Option<Vec<u8>> {
+        if sealed.len() < aead::NONCE_LEN + aead::CHACHA20_POLY1305.tag_len() {
+            return None;
+        }
+        let nonce: [u8; aead::NONCE_LEN] =
+            sealed[..aead::NONCE_LEN].try_into().expect("nonce length");
+        let mut in_out = sealed[aead::NONCE_LEN..].to_vec();
+        let plaintext = self
+            .aead_key()
+            .open_in_place(
+                aead::Nonce::assume_unique_for_key(nonce),
+                aead::Aad::from(aad),
+                &mut in_out,
+            )
+            .ok()?;
+        Some(plaintext.to_vec())
+    }
+
+    pub fn seal(&self, plaintext: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let mut sealed = self.header(VERSION);
+        let ciphertext = self.seal_with(&sealed, plaintext)?;
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    pub fn open(&self, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        if header_salt(data, VERSION)? != self.salt {
+            return Err(anyhow!(
+                "wallet file was encrypted with a different passphrase"
+            ));
+        }
+        self.open_with(&data[..HEADER_LEN], &data[HEADER_LEN..])
+            .ok_or_else(|| anyhow!("wrong passphrase"))
+    }
+
+    /// Seals the record at position `index` of a store, prefixed with its length
+    fn seal_record(&self, index: u64, plaintext: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let sealed = self.seal_with(&self.record_aad(index), plaintext)?;
+        let mut record = Vec::with_capacity(RECORD_LEN_SIZE + sealed.len());
+        record.extend_from_slice(&(sealed.len() as u32).to_le_bytes());
+        record.extend_from_slice(&sealed);
+        Ok(record)
+    }
+
+    /// Records are bound to their position so they can't be reordered
+    fn record_aad(&self, index: u64) -> Vec<u8> {
+        let mut aad = self.header(STORE_VERSION);
+        aad.extend_from_slice(&index.to_le_bytes());
+        aad
+    }
+
+    /// Decrypts the records of a store along with the length of the data they
+    /// span. A record cut short by an interrupted append is left out.
+    fn open_records(&self, data: &[u8]) -> anyhow::Result<(Vec<Vec<u8>>, usize)> {
+        if header_salt(data, STORE_VERSION)? != self.salt {
+            return Err(anyhow!(
+                "wallet store was encrypted with a different passphrase"
+            ));
+        }
+        let mut records = Vec::new();
+        let mut offset = HEADER_LEN;
+        while data.len() - offset >= RECORD_LEN_SIZE {
+            let len = u32::from_le_bytes(
+                data[offset..offset + RECORD_LEN_SIZE]
+                    .try_into()
+                    .expect("record length"),
+            ) as usize;
+            let start = offset + RECORD_LEN_SIZE;
+            if data.len() - start < len {
+                break;
+            }
+            let record = self
+                .open_with(
+                    &self.record_aad(records.len() as u64),
+                    &data[start..start + len],
+                )
+                .ok_or_else(|| {
+                    anyhow!(
+                        "encrypted wallet store is corrupted at record {}",
+                        records.len()
+                    )
+                })?;
+            records.push(record);
+            offset = start + len;
+        }
+        Ok((records, offset))
+    }
+
+    /// A store holding only `record`, if any
+    fn store_bytes(&self, record: Option<&[u8]>) -> anyhow::Result<Vec<u8>> {
+        let mut data = self.header(STORE_VERSION);
+        if let Some(record) = record {
+            data.extend_from_slice(&self.seal_record(0, record)?);
+        }
+        Ok(data)
+    }
+
+    /// Seals `plaintext` into `path`, replacing the file only once fully written
+    pub fn write(&self, path: &Path, plaintext: &[u8]) -> anyhow::Result<()> {
+        replace_file(path, &self.seal(plaintext)?, false)
+    }
+
+    /// Like [WalletCipher::write] but shreds the unencrypted file at `path`
+    /// before it's replaced
+    pub fn replace_plaintext(&self, path: &Path, plaintext: &[u8]) -> anyhow::Result<()> {
+        replace_file(path, &self.seal(plaintext)?, true)
+    }
+}
+
+/// Writes `data` to a temporary file that's renamed over `path` once fully
+/// written and synced, shredding the file it replaces if asked to
+fn replace_file(path: &Path, data: &[u8], shred: bool) -> anyhow::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    let mut file = fs::File::create(&tmp_path)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    drop(file);
+    if shred && path.exists() {
+        shred_file(path)?;
+    }
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Overwrites a file with zeros before removing it so its plaintext isn't left
+/// behind in freed blocks. This is best effort: copy-on-write or journaling
+/// filesystems and SSD wear levelling may still keep older copies of the data.
+pub fn shred_file(path: &Path) -> anyhow::Result<()> {
+    let len = fs::metadata(path)?.len();
+    let mut file = fs::OpenOptions::new().write(true).open(path)?;
+    io::copy(&mut io::repeat(0).take(len), &mut file)?;
+    file.sync_all()?;
+    drop(file);
+    fs::remove_file(path)?;
+    Ok(())
+}
+
+/// Whether the data was written by [WalletCipher::seal] or is an encrypted store
+pub fn is_sealed(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Like [is_sealed] but only reads the start of the file at `path`
+fn is_sealed_file(path: &Path) -> bool {
+    let mut magic = [0u8; MAGIC.len()];
+    fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok_and(|_| is_sealed(&magic))
+}
+
+fn header_salt(data: &[u8], version: u8) -> anyhow::Result<[u8; SALT_LEN]> {
+    if !is_sealed(data) {
+        return Err(anyhow!("not an encrypted wallet file"));
+    }
+    let min_len = match version {
+        VERSION => HEADER_LEN + aead::NONCE_LEN + aead::CHACHA20_POLY1305.tag_len(),
+        _ => HEADER_LEN,
+    };
+    if data.len() < min_len {
+        return Err(anyhow!("encrypted wallet file is truncated"));
+    }
+    if data[MAGIC.len()] != version {
+        return Err(anyhow!(
+            "unsupported encrypted wallet file version {}",
+            data[MAGIC.len()]
+        ));
+    }
+    Ok(data[MAGIC.len() + 1..HEADER_LEN]
+        .try_into()
+        .expect("salt length"))
+}
+
+/// Persists wallet changesets in bdk's append-only file store or, for encrypted
+/// wallets, as sealed records appended to a store of their own. Appending costs
+/// as much as the changeset appended, loading decrypts every record just like
+/// bdk's store decodes them.
+pub enum WalletStore {
+    File(bdk_file_store::Store<ChangeSet>),
+    Encrypted {
+        path: PathBuf,
+        cipher: WalletCipher,
+        /// Number of records in the store
+        records: u64,
+    },
+}
+
+impl WalletStore {
+    /// Opens or creates the store at `path`. With a cipher, a store written
+    /// before the wallet was encrypted is migrated to an encrypted one.
+    pub fn open(
+        magic: &[u8],
+        path: PathBuf,
+        cipher: Option<&WalletCipher>,
+    ) -> anyhow::Result<Self> {
+        let cipher = match cipher {
+            None if is_sealed_file(&path) => {
+                return Err(anyhow!(
+                    "wallet store is encrypted, a passphrase is required"
+                ))
+            }
+            None => {
+                return Ok(WalletStore::File(
+                    bdk_file_store::Store::open_or_create_new(magic, path)
+                        .context("create store for spaces")?,
+                ))
+            }
+            Some(cipher) => cipher.clone(),
+        };
+
+        let data = match fs::read(&path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                // A migration interrupted after shredding the unencrypted store
+                // leaves the encrypted one in its temporary file
+                let tmp_path = path.with_extension("tmp");
+                match fs::read(&tmp_path) {
+                    Ok(data) if is_sealed(&data) => {
+                        fs::rename(&tmp_path, &path)?;
+                        data
+                    }
+                    _ => {
+                        replace_file(&path, &cipher.store_bytes(None)?, false)?;
+                        return Ok(WalletStore::Encrypted {
+                            path,
+                            cipher,
+                            records: 0,
+                        });
+                    }
+                }
+            }
+            Err(e) => return Err(e.into()),
+            Ok(data) => data,
+        };
+
+        if !is_sealed(&data) {
+            let changeset = {
+                let mut plain = bdk_file_store::Store::<ChangeSet>::open(magic, &path)
+                    .context("open unencrypted store for spaces")?;
+                plain.aggregate_changesets()?
+            };
+            let record = changeset.as_ref().map(encode_changeset);
+            replace_file(&path, &cipher.store_bytes(record.as_deref())?, true)?;
+            return Ok(WalletStore::Encrypted {
+                path,
+                cipher,
+                records: record.is_some() as u64,
+            });
+        }
+
+        let (records, len) = cipher.open_records(&data)?;
+        if len < data.len() {
+            // Drop a torn record so the next append lines up
+            fs::OpenOptions::new()
+                .write(true)
+                .open(&path)?
+                .set_len(len as u64)?;
+        }
+        Ok(WalletStore::Encrypted {
+            path,
+            cipher,
+            records: records.len() as u64,
+        })
+    }
+
+    /// Writes a new store at `path` holding only `changeset`, replacing any
+    /// existing one once fully written
+    pub fn write_new(
+        magic: &[u8],
+        path: &Path,
+        changeset: &ChangeSet,
+        cipher: Option<&WalletCipher>,
+    ) -> anyhow::Result<()> {
+        if let Some(cipher) = cipher {
+            let data = cipher.store_bytes(Some(&encode_changeset(changeset)))?;
+            return replace_file(path, &data, false);
+        }
+
+        let tmp_path = path.with_extension("db.tmp");
+        if tmp_path.exists() {
+            fs::remove_file(&tmp_path)?;
+        }
+        {
+            let mut tmp_db = bdk_file_store::Store::<ChangeSet>::create_new(magic, &tmp_path)
+                .context("create temporary store for spaces")?;
+            tmp_db.append_changeset(changeset)?;
+        }
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    pub fn aggregate_changesets(&mut self) -> anyhow::Result<Option<ChangeSet>> {
+        match self {
+            WalletStore::File(store) => Ok(store.aggregate_changesets()?),
+            WalletStore::Encrypted { path, cipher, .. } => {
+                let (records, _) = cipher.open_records(&fs::read(path)?)?;
+                let mut aggregate: Option<ChangeSet> = None;
+                for record in records {
+                    let changeset = decode_changeset(&record)?;
+                    match aggregate.as_mut() {
+                        None => aggregate = Some(changeset),
+                        Some(aggregate) => aggregate.append(changeset),
+                    }
+                }
+                Ok(aggregate)
+            }
+        }
+    }
+
+    pub fn append_changeset(&mut self, changeset: &ChangeSet) -> anyhow::Result<()> {
+        match self {
+            WalletStore::File(store) => Ok(store.append_changeset(changeset)?),
+            WalletStore::Encrypted {
+                path,
+                cipher,
+                records,
+            } => {
+                if changeset.is_empty() {
+                    return Ok(());
+                }
+                let record = cipher.seal_record(*records, &encode_changeset(changeset))?;
+                let mut file = fs::OpenOptions::new().append(true).open(path)?;
+                file.write_all(&record)?;
+                file.sync_data()?;
+                *records += 1;
+                Ok(())
+            }
+        }
+    }
+}
+
+fn encode_changeset(changeset: &ChangeSet) -> Vec<u8> {
+    bincode::serde::encode_to_vec(changeset, config::standard()).expect("wallet changeset")
+}
+
+fn decode_changeset(data: &[u8]) -> anyhow::Result<ChangeSet> {
+    let (changeset, _) = bincode::serde::decode_from_slice(data, config::standard())
+        .context("could not decode encrypted wallet store")?;
+    Ok(changeset)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bitcoin::{
+        absolute::LockTime, transaction::Version, Amount, ScriptBuf, Transaction, TxOut,
+    };
+
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("spaces-encryption-{}-{}", name, std::process::id()));
+        _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn changeset(lock_time: u32) -> ChangeSet {
+        let mut changeset = ChangeSet::default();
+        changeset
+            .indexed_tx_graph
+            .graph
+            .txs
+            .insert(Arc::new(Transaction {
+                version: Version::TWO,
+                lock_time: LockTime::from_consensus(lock_time),
+                input: vec![],
+                output: vec![TxOut {
+                    value: Amount::from_sat(1000),
+                    script_pubkey: ScriptBuf::new(),
+                }],
+            }));
+        changeset
+    }
+
+    fn tx_count(changeset: Option<ChangeSet>) -> usize {
+        changeset.map_or(0, |changeset| changeset.indexed_tx_graph.graph.txs.len())
+    }
+
+    #[test]
+    fn test_seal_open_round_trip() {
+        let cipher = WalletCipher::new("correct horse").unwrap();
+        let sealed = cipher.seal(b"wallet data").unwrap();
+        assert!(is_sealed(&sealed));
+        assert_eq!(cipher.open(&sealed).unwrap(), b"wallet data");
+
+        let (unsealed, plaintext) = WalletCipher::unseal("correct horse", &sealed).unwrap();
+        assert_eq!(plaintext, b"wallet data");
+        assert_eq!(
+            unsealed.key, cipher.key,
+            "same passphrase and salt derive the same key"
+        );
+    }
+
+    #[test]
+    fn test_wrong_passphrase() {
+        let cipher = WalletCipher::new("correct horse").unwrap();
+        let sealed = cipher.seal(b"wallet data").unwrap();
+        let error = WalletCipher::unseal("battery staple", &sealed)
+            .err()
+            .expect("wrong passphrase must not unseal");
+        assert_eq!(error.to_string(), "wrong passphrase");
+    }
+
+    #[test]
+    fn test_truncated_and_tampered() {
+        let cipher = WalletCipher::new("correct horse").unwrap();
+        let sealed = cipher.seal(b"wallet data").unwrap();
+
+        let error = cipher.open(&sealed[..HEADER_LEN + 4]).unwrap_err();
+        assert_eq!(error.to_string(), "encrypted wallet file is truncated");
+
+        let mut tampered = sealed.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert!(
+            cipher.open(&tampered).is_err(),
+            "tampered ciphertext must not open"
+        );
+
+        let mut tampered = sealed;
+        tampered[MAGIC.len() + 1] ^= 1;
+        assert!(
+            cipher.open(&tampered).is_err(),
+            "tampered salt must not open"
+        );
+    }
+
+    #[test]
+    fn test_is_sealed() {
+        assert!(!is_sealed(b"{\"descriptor\":\"tr(...)\"}"));
+        assert!(!is_sealed(b""));
+        assert!(is_sealed(
+            &WalletCipher::new("pass").unwrap().seal(b"").unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_store_appends_records() {
+        let dir = temp_dir("append");
+        let path = dir.join("spaces.db");
+        let cipher = WalletCipher::new("pass").unwrap();
+
+        let mut store = WalletStore::open(b"magic", path.clone(), Some(&cipher)).unwrap();
+        assert_eq!(tx_count(store.aggregate_changesets().unwrap()), 0);
+        store.append_changeset(&changeset(1)).unwrap();
+        let first = fs::read(&path).unwrap();
+        store.append_changeset(&changeset(2)).unwrap();
+        assert!(
+            fs::read(&path).unwrap().starts_with(&first),
+            "appending must not rewrite earlier records"
+        );
+
+        // A record torn by an interrupted append is dropped on open
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[200, 0, 0, 0, 1, 2, 3]).unwrap();
+        drop(file);
+
+        let mut store = WalletStore::open(b"magic", path.clone(), Some(&cipher)).unwrap();
+        store.append_changeset(&changeset(3)).unwrap();
+        assert_eq!(tx_count(store.aggregate_changesets().unwrap()), 3);
+
+        let other = WalletCipher::new("pass").unwrap();
+        assert!(
+            WalletStore::open(b"magic", path, Some(&other)).is_err(),
+            "a key with another salt must not open the store"
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_store_migrates_plaintext() {
+        let dir = temp_dir("migrate");
+        let path = dir.join("spaces.db");
+        {
+            let mut plain =
+                bdk_file_store::Store::<ChangeSet>::create_new(b"magic", &path).unwrap();
+            plain.append_changeset(&changeset(1)).unwrap();
+            plain.append_changeset(&changeset(2)).unwrap();
+        }
+        assert!(!is_sealed(&fs::read(&path).unwrap()));
+
+        let cipher = WalletCipher::new("pass").unwrap();
+        let mut store = WalletStore::open(b"magic", path.clone(), Some(&cipher)).unwrap();
+        assert!(is_sealed(&fs::read(&path).unwrap()));
+        assert!(!path.with_extension("tmp").exists());
+        assert_eq!(tx_count(store.aggregate_changesets().unwrap()), 2);
+
+        store.append_changeset(&changeset(3)).unwrap();
+        let mut store = WalletStore::open(b"magic", path.clone(), Some(&cipher)).unwrap();
+        assert_eq!(tx_count(store.aggregate_changesets().unwrap()), 3);
+
+        assert!(
+            WalletStore::open(b"magic", path, None).is_err(),
+            "a sealed store must not be opened as a plain one"
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
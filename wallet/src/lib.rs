@@ -14,18 +14,22 @@ use bdk_wallet::{
         tx_builder::TxOrdering,
         ChangeSet, InsertTxError,
     },
+    miniscript::{Descriptor, DescriptorPublicKey, ForEachKey},
     KeychainKind, LocalOutput, SignOptions, WeightedUtxo,
 };
 use bincode::config;
 use bitcoin::{
     absolute::{Height, LockTime},
-    psbt::raw::ProprietaryKey,
+    bip32::{DerivationPath, Fingerprint},
+    psbt::{self, raw::ProprietaryKey},
     script,
+    key::TapTweak,
+    secp256k1::Secp256k1,
     sighash::{Prevouts, SighashCache},
     taproot,
     taproot::LeafVersion,
-    Amount, Block, BlockHash, FeeRate, Network, OutPoint, Psbt, Sequence, TapLeafHash,
-    TapSighashType, Transaction, TxOut, Witness,
+    Amount, Block, BlockHash, FeeRate, Network, OutPoint, Psbt, Sequence, TapLeafHash, TapNodeHash,
+    TapSighashType, Transaction, TxOut, Txid, Witness,
 };
 use protocol::{
     bitcoin::{
@@ -42,21 +46,27 @@ use protocol::prepare::is_magic_lock_time;
 use crate::{
     address::SpaceAddress,
     builder::{is_connector_dust, is_space_dust, SpacesAwareCoinSelection},
+    encryption::{is_sealed, WalletCipher, WalletStore},
 };
 
 pub extern crate bdk_wallet;
 pub extern crate bitcoin;
 
 pub mod address;
+pub mod bip322;
 pub mod builder;
+pub mod encryption;
 pub mod export;
 
 const WALLET_SPACE_MAGIC: &[u8; 12] = b"WALLET_SPACE";
 
+/// Number of scripts derived from each keychain when checking they don't overlap
+const DESCRIPTOR_OVERLAP_CHECK_DEPTH: u32 = 100;
+
 pub struct SpacesWallet {
     pub config: WalletConfig,
     pub spaces: bdk_wallet::wallet::Wallet,
-    pub spaces_db: bdk_file_store::Store<ChangeSet>,
+    pub spaces_db: WalletStore,
     pub watch_bid_spends: HashSet<OutPoint>,
 }
 
@@ -74,6 +84,25 @@ pub struct DescriptorInfo {
     pub descriptor: String,
     pub internal: bool,
     pub spaces: bool,
+    /// Fingerprint of the master key the descriptor's first key derives from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<Fingerprint>,
+    /// Derivation path from the master key, excluding the final wildcard
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub derivation_path: Option<DerivationPath>,
+}
+
+/// What's needed to recreate the wallet elsewhere, without any secrets
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalletMetadata {
+    pub label: String,
+    pub network: Network,
+    /// Height the wallet was created at, it has no transactions in earlier blocks
+    pub birth_height: u32,
+    /// Fingerprint of the master key (seed) of the wallet
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<Fingerprint>,
+    pub descriptors: Vec<DescriptorInfo>,
 }
 
 #[derive(Debug, Clone)]
@@ -89,7 +118,12 @@ pub struct SpaceScriptSigningInfo {
 pub struct DoubleUtxo {
     pub spend: FullTxOut,
     pub auction: FullTxOut,
+    /// Whether the pair is confirmed and can be used for bidding without
+    /// adding unconfirmed inputs to a replacement
     pub confirmed: bool,
+    /// Number of confirmations (0 if unconfirmed)
+    #[serde(default)]
+    pub depth: u32,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -105,6 +139,8 @@ pub struct WalletConfig {
     pub network: Network,
     pub genesis_hash: Option<BlockHash>,
     pub space_descriptors: WalletDescriptors,
+    /// Encrypts the wallet's files at rest when set
+    pub cipher: Option<WalletCipher>,
 }
 
 pub struct WalletDescriptors {
@@ -122,10 +158,58 @@ impl SpacesWallet {
             fs::create_dir_all(config.data_dir.clone())?;
         }
 
+        Self::check_distinct_descriptors(&config.space_descriptors)?;
+        let (spaces_wallet, spaces_db) = Self::open_spaces(&config)?;
+        let wallet = Self {
+            config,
+            spaces: spaces_wallet,
+            spaces_db,
+            watch_bid_spends: HashSet::new(),
+        };
+
+        wallet.clear_unused_signing_info();
+        Ok(wallet)
+    }
+
+    /// Rejects descriptors whose keychains derive any of the same scripts. Outputs
+    /// are classified by the keychain they belong to, so overlapping keychains would
+    /// misclassify spaces and count coin value twice.
+    fn check_distinct_descriptors(descriptors: &WalletDescriptors) -> anyhow::Result<()> {
+        let secp = Secp256k1::new();
+        let (external, _) =
+            Descriptor::<DescriptorPublicKey>::parse_descriptor(&secp, &descriptors.external)
+                .context("invalid external descriptor")?;
+        let (internal, _) =
+            Descriptor::<DescriptorPublicKey>::parse_descriptor(&secp, &descriptors.internal)
+                .context("invalid internal descriptor")?;
+        if external == internal {
+            return Err(anyhow!("external and internal descriptors must be distinct"));
+        }
+
+        let derive = |descriptor: &Descriptor<DescriptorPublicKey>| {
+            (0..DESCRIPTOR_OVERLAP_CHECK_DEPTH)
+                .map(|index| Ok(descriptor.at_derivation_index(index)?.script_pubkey()))
+                .collect::<anyhow::Result<HashSet<ScriptBuf>>>()
+        };
+        let external_scripts = derive(&external)?;
+        if let Some(script) = derive(&internal)?
+            .iter()
+            .find(|script| external_scripts.contains(*script))
+        {
+            return Err(anyhow!(
+                "external and internal descriptors overlap, both derive script {}",
+                script.to_hex_string()
+            ));
+        }
+        Ok(())
+    }
+
+    fn open_spaces(
+        config: &WalletConfig,
+    ) -> anyhow::Result<(bdk_wallet::wallet::Wallet, WalletStore)> {
         let spaces_path = config.data_dir.join("spaces.db");
         let mut spaces_db =
-            bdk_file_store::Store::<ChangeSet>::open_or_create_new(WALLET_SPACE_MAGIC, spaces_path)
-                .context("create store for spaces")?;
+            WalletStore::open(WALLET_SPACE_MAGIC, spaces_path, config.cipher.as_ref())?;
 
         let genesis_hash = match config.genesis_hash {
             None => genesis_block(config.network).block_hash(),
@@ -140,16 +224,79 @@ impl SpacesWallet {
             config.network,
             genesis_hash,
         )?;
+        Ok((spaces_wallet, spaces_db))
+    }
 
-        let wallet = Self {
-            config,
-            spaces: spaces_wallet,
-            spaces_db,
-            watch_bid_spends: HashSet::new(),
-        };
+    /// Removes unconfirmed transactions along with any unconfirmed descendants
+    /// from the wallet, freeing their inputs. The transaction graph is append-only
+    /// so the wallet store is rewritten without them and reloaded.
+    ///
+    /// Returns the removed transactions, children before parents.
+    pub fn remove_unconfirmed_txs(&mut self, txids: &HashSet<Txid>) -> anyhow::Result<Vec<Txid>> {
+        for txid in txids {
+            match self.spaces.get_tx(*txid) {
+                None => return Err(anyhow!("no wallet transaction with txid {}", txid)),
+                Some(tx) if tx.chain_position.is_confirmed() => {
+                    return Err(anyhow!("transaction {} is already confirmed", txid))
+                }
+                Some(_) => {}
+            }
+        }
 
-        wallet.clear_unused_signing_info();
-        Ok(wallet)
+        // Walk down the unconfirmed graph so children are removed with their parents
+        let unconfirmed: Vec<_> = self
+            .spaces
+            .transactions()
+            .filter(|tx| !tx.chain_position.is_confirmed())
+            .map(|tx| tx.tx_node.tx.clone())
+            .collect();
+        let mut removed: Vec<Txid> = txids.iter().copied().collect();
+        let mut removing: HashSet<Txid> = txids.clone();
+        loop {
+            let children: Vec<_> = unconfirmed
+                .iter()
+                .filter(|tx| !removing.contains(&tx.compute_txid()))
+                .filter(|tx| {
+                    tx.input
+                        .iter()
+                        .any(|input| removing.contains(&input.previous_output.txid))
+                })
+                .map(|tx| tx.compute_txid())
+                .collect();
+            if children.is_empty() {
+                break;
+            }
+            for child in children {
+                removing.insert(child);
+                removed.push(child);
+            }
+        }
+        removed.reverse();
+        if removed.is_empty() {
+            return Ok(removed);
+        }
+
+        self.commit()?;
+        let mut changeset = self.spaces_db.aggregate_changesets()?.unwrap_or_default();
+        let graph = &mut changeset.indexed_tx_graph.graph;
+        graph
+            .txs
+            .retain(|tx| !removing.contains(&tx.compute_txid()));
+        graph.anchors.retain(|(_, txid)| !removing.contains(txid));
+        graph.last_seen.retain(|txid, _| !removing.contains(txid));
+
+        // Written to a temporary store first so the wallet isn't lost if interrupted
+        WalletStore::write_new(
+            WALLET_SPACE_MAGIC,
+            &self.config.data_dir.join("spaces.db"),
+            &changeset,
+            self.config.cipher.as_ref(),
+        )?;
+
+        let (spaces, spaces_db) = Self::open_spaces(&self.config)?;
+        self.spaces = spaces;
+        self.spaces_db = spaces_db;
+        Ok(removed)
     }
 
     pub fn watch_bid_spend(&mut self, outpoint: OutPoint) {
@@ -165,24 +312,24 @@ impl SpacesWallet {
     }
 
     pub fn get_info(&self) -> WalletInfo {
-        let mut descriptors = Vec::with_capacity(2);
-
-        descriptors.push(DescriptorInfo {
-            descriptor: self
-                .spaces
-                .public_descriptor(KeychainKind::External)
-                .to_string(),
-            internal: false,
-            spaces: true,
-        });
-        descriptors.push(DescriptorInfo {
-            descriptor: self
-                .spaces
-                .public_descriptor(KeychainKind::Internal)
-                .to_string(),
-            internal: true,
-            spaces: true,
-        });
+        let descriptors = [KeychainKind::External, KeychainKind::Internal]
+            .into_iter()
+            .map(|keychain| {
+                let descriptor = self.spaces.public_descriptor(keychain);
+                let mut origin = None;
+                descriptor.for_any_key(|key| {
+                    origin = Some((key.master_fingerprint(), key.full_derivation_path()));
+                    true
+                });
+                DescriptorInfo {
+                    descriptor: descriptor.to_string(),
+                    internal: keychain == KeychainKind::Internal,
+                    spaces: true,
+                    fingerprint: origin.as_ref().map(|(fingerprint, _)| *fingerprint),
+                    derivation_path: origin.and_then(|(_, path)| path),
+                }
+            })
+            .collect();
 
         WalletInfo {
             label: self.config.name.clone(),
@@ -192,6 +339,17 @@ impl SpacesWallet {
         }
     }
 
+    pub fn get_metadata(&self) -> WalletMetadata {
+        let info = self.get_info();
+        WalletMetadata {
+            label: info.label,
+            network: self.config.network,
+            birth_height: info.start_block,
+            fingerprint: info.descriptors.iter().find_map(|desc| desc.fingerprint),
+            descriptors: info.descriptors,
+        }
+    }
+
     pub fn next_unused_space_address(&mut self) -> SpaceAddress {
         let info = self.spaces.next_unused_address(KeychainKind::External);
         SpaceAddress(info.address)
@@ -225,6 +383,72 @@ impl SpacesWallet {
         Ok(())
     }
 
+    /// Encrypts the wallet's store, saved signing info and the given `data_files`
+    /// at rest, everything it persists afterwards is encrypted as well
+    pub fn encrypt(&mut self, cipher: WalletCipher, data_files: &[&str]) -> anyhow::Result<()> {
+        if self.config.cipher.is_some() {
+            return Err(anyhow!("wallet is already encrypted"));
+        }
+        self.commit()?;
+        self.config.cipher = Some(cipher);
+        // Reopening the store migrates it
+        let (spaces, spaces_db) = Self::open_spaces(&self.config)?;
+        self.spaces = spaces;
+        self.spaces_db = spaces_db;
+
+        let mut paths: Vec<_> = data_files
+            .iter()
+            .map(|name| self.config.data_dir.join(name))
+            .filter(|path| path.exists())
+            .collect();
+        if let Ok(entries) = fs::read_dir(self.config.data_dir.join("script_solutions")) {
+            paths.extend(entries.flatten().map(|entry| entry.path()));
+        }
+        for path in paths {
+            let raw = fs::read(&path)?;
+            if !is_sealed(&raw) {
+                self.config
+                    .cipher
+                    .as_ref()
+                    .expect("cipher")
+                    .replace_plaintext(&path, &raw)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a file from the wallet's data directory, decrypting it if the wallet
+    /// is encrypted. `None` if the file doesn't exist.
+    pub fn read_data_file(&self, name: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let raw = match fs::read(self.config.data_dir.join(name)) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            raw => raw?,
+        };
+        match self.config.cipher.as_ref() {
+            Some(cipher) if is_sealed(&raw) => Ok(Some(cipher.open(&raw)?)),
+            None if is_sealed(&raw) => Err(anyhow!("{} is encrypted", name)),
+            _ => Ok(Some(raw)),
+        }
+    }
+
+    /// Writes a file to the wallet's data directory, encrypted if the wallet is
+    pub fn write_data_file(&self, name: &str, data: &[u8]) -> anyhow::Result<()> {
+        let path = self.config.data_dir.join(name);
+        match self.config.cipher.as_ref() {
+            None => fs::write(path, data)?,
+            Some(cipher) => cipher.write(&path, data)?,
+        }
+        Ok(())
+    }
+
+    /// Drops changes made since the last commit by reloading the wallet from its store
+    pub fn discard_staged(&mut self) -> anyhow::Result<()> {
+        let (spaces, spaces_db) = Self::open_spaces(&self.config)?;
+        self.spaces = spaces;
+        self.spaces_db = spaces_db;
+        Ok(())
+    }
+
     /// List outputs that can be safely auctioned off
     pub fn list_bidouts(
         &mut self,
@@ -237,6 +461,8 @@ impl SpacesWallet {
             return Ok(not_auctioned);
         }
 
+        let tip_height = self.spaces.local_chain().tip().height();
+
         // Sort UTXOs by transaction ID and then by output index (vout)
         // to group UTXOs from the same transaction together and in sequential order
         unspent.sort_by(|a, b| {
@@ -303,6 +529,12 @@ impl SpacesWallet {
                         txout: utxo2.txout.clone(),
                     },
                     confirmed: utxo1.confirmation_time.is_confirmed(),
+                    depth: match utxo1.confirmation_time {
+                        ConfirmationTime::Confirmed { height, .. } => {
+                            tip_height.saturating_sub(height) + 1
+                        }
+                        ConfirmationTime::Unconfirmed { .. } => 0,
+                    },
                 });
             }
         }
@@ -528,11 +760,190 @@ impl SpacesWallet {
         Ok(tx)
     }
 
+    /// Spends a commitment output back to the wallet through the taproot key path
+    /// so the space script is never revealed. Used to reclaim the funds of an open
+    /// that was abandoned.
+    ///
+    /// The commitment alone is usually too small to pay for a replacement of an open
+    /// still in the mempool, so the open's own wallet `inputs` are spent alongside it.
+    /// When replacing a transaction that paid `replaced_fee`, the fee is raised to
+    /// cover it plus the incremental relay fee for the reclaim's size as BIP-125 requires.
+    pub fn reclaim_commitment(
+        &mut self,
+        outpoint: OutPoint,
+        txout: TxOut,
+        inputs: &[(OutPoint, TxOut)],
+        fee_rate: FeeRate,
+        replaced_fee: Option<Amount>,
+    ) -> anyhow::Result<Transaction> {
+        let raw = self
+            .get_signing_info(&txout.script_pubkey)
+            .ok_or_else(|| anyhow!("no signing info found for commitment {}", outpoint))?;
+        let signing_info = SpaceScriptSigningInfo::from_slice(raw.as_slice())
+            .context("expected commitment signing info")?;
+
+        let change = self
+            .spaces
+            .next_unused_address(KeychainKind::Internal)
+            .script_pubkey();
+        let mut fee_absolute = None;
+        let psbt = loop {
+            let mut builder = self.spaces.build_tx();
+            builder
+                .ordering(TxOrdering::Untouched)
+                .enable_rbf()
+                .manually_selected_only()
+                .drain_to(change.clone());
+            match fee_absolute {
+                None => builder.fee_rate(fee_rate),
+                Some(fee) => builder.fee_absolute(fee),
+            };
+            builder.add_foreign_utxo(
+                outpoint,
+                psbt::Input {
+                    witness_utxo: Some(txout.clone()),
+                    // signed below through the key path
+                    final_script_witness: Some(Witness::default()),
+                    final_script_sig: Some(ScriptBuf::new()),
+                    ..Default::default()
+                },
+                66,
+            )?;
+            for (outpoint, txout) in inputs {
+                builder.add_foreign_utxo(
+                    *outpoint,
+                    psbt::Input {
+                        witness_utxo: Some(txout.clone()),
+                        ..Default::default()
+                    },
+                    66,
+                )?;
+            }
+            let psbt = builder.finish().map_err(|e| {
+                anyhow!(
+                    "commitment {} is too small to reclaim at {} sat/vB: {}",
+                    outpoint,
+                    fee_rate.to_sat_per_vb_ceil(),
+                    e
+                )
+            })?;
+            if let (None, Some(replaced_fee)) = (fee_absolute, replaced_fee) {
+                // Every input is a taproot key spend
+                let weight = psbt.unsigned_tx.weight().to_wu() + 2 + 66 * psbt.inputs.len() as u64;
+                let required = replaced_fee + Amount::from_sat(weight.div_ceil(4));
+                if psbt.fee()? < required {
+                    fee_absolute = Some(required);
+                    continue;
+                }
+            }
+            break psbt;
+        };
+
+        let prevouts: Vec<_> = psbt
+            .inputs
+            .iter()
+            .map(|input| input.witness_utxo.clone().expect("witness utxo"))
+            .collect();
+        let commitment_idx = psbt
+            .unsigned_tx
+            .input
+            .iter()
+            .position(|input| input.previous_output == outpoint)
+            .expect("commitment input");
+        let mut tx = self.sign(psbt, None)?;
+
+        let merkle_root = TapNodeHash::from(TapLeafHash::from_script(
+            &signing_info.script,
+            LeafVersion::TapScript,
+        ));
+        let keypair = signing_info
+            .temp_key_pair
+            .tap_tweak(&signing_info.ctx, Some(merkle_root))
+            .to_inner();
+
+        let sighash = SighashCache::new(&tx).taproot_key_spend_signature_hash(
+            commitment_idx,
+            &Prevouts::All(&prevouts),
+            TapSighashType::Default,
+        )?;
+        let msg = bitcoin::secp256k1::Message::from_digest_slice(sighash.as_ref())?;
+        let signature = signing_info.ctx.sign_schnorr(&msg, &keypair);
+        tx.input[commitment_idx].witness = Witness::from_slice(&[taproot::Signature {
+            signature,
+            sighash_type: TapSighashType::Default,
+        }
+        .to_vec()]);
+        Ok(tx)
+    }
+
+    /// Whether any of the wallet descriptors require multiple signers
+    pub fn is_multisig(&self) -> bool {
+        [KeychainKind::External, KeychainKind::Internal]
+            .into_iter()
+            .any(|keychain| {
+                let descriptor = self.spaces.public_descriptor(keychain).to_string();
+                descriptor.contains("multi(") || descriptor.contains("multi_a(")
+            })
+    }
+
+    /// Adds this wallet's signatures to the psbt without finalizing it
+    /// so that it can be passed on to the remaining co-signers.
+    pub fn sign_partial(&mut self, mut psbt: Psbt) -> anyhow::Result<Psbt> {
+        self.spaces.sign(
+            &mut psbt,
+            SignOptions {
+                try_finalize: false,
+                ..Default::default()
+            },
+        )?;
+        Ok(psbt)
+    }
+
+    /// Signs a BIP-322 message with the key controlling the given wallet output,
+    /// returning the simple signature
+    pub fn sign_message(&mut self, outpoint: OutPoint, message: &[u8]) -> anyhow::Result<String> {
+        if self.is_multisig() {
+            return Err(anyhow!("signing messages is not supported by multisig wallets"));
+        }
+        let mut utxo = self
+            .spaces
+            .get_utxo(outpoint)
+            .ok_or_else(|| anyhow!("output {} is not owned by this wallet", outpoint))?;
+
+        let to_spend = bip322::to_spend(&utxo.txout.script_pubkey, message);
+        let to_sign = bip322::to_sign(&to_spend);
+
+        // Sign for the virtual output with the keys of the real one
+        utxo.outpoint = to_sign.input[0].previous_output;
+        utxo.txout = to_spend.output[0].clone();
+        let mut input = self.spaces.get_psbt_input(utxo, None, true)?;
+        input.witness_utxo = Some(to_spend.output[0].clone());
+
+        let mut psbt = Psbt::from_unsigned_tx(to_sign)?;
+        psbt.inputs[0] = input;
+        let finalized = self.spaces.sign(
+            &mut psbt,
+            SignOptions {
+                trust_witness_utxo: true,
+                ..Default::default()
+            },
+        )?;
+        let witness = match (finalized, psbt.inputs[0].final_script_witness.as_ref()) {
+            (true, Some(witness)) => witness,
+            _ => return Err(anyhow!("could not sign message")),
+        };
+        Ok(bip322::encode_simple(witness))
+    }
+
     fn get_signing_info(&self, script: &ScriptBuf) -> Option<Vec<u8>> {
         let script_info_dir = self.config.data_dir.join("script_solutions");
         let filename = hex::encode(script.as_bytes());
         let file_path = script_info_dir.join(filename);
-        fs::read(file_path).ok()
+        let raw = fs::read(file_path).ok()?;
+        match self.config.cipher.as_ref() {
+            Some(cipher) if is_sealed(&raw) => cipher.open(&raw).ok(),
+            _ => Some(raw),
+        }
     }
 
     fn save_signing_info(&self, script: ScriptBuf, raw: Vec<u8>) -> anyhow::Result<()> {
@@ -540,7 +951,10 @@ impl SpacesWallet {
         fs::create_dir_all(&script_info_dir).context("could not create script_info directory")?;
         let filename = hex::encode(script.as_bytes());
         let file_path = script_info_dir.join(filename);
-        fs::write(file_path, raw)?;
+        match self.config.cipher.as_ref() {
+            None => fs::write(file_path, raw)?,
+            Some(cipher) => cipher.write(&file_path, &raw)?,
+        }
         Ok(())
     }
 
@@ -713,3 +1127,174 @@ impl<'de> Deserialize<'de> for SpaceScriptSigningInfo {
         deserializer.deserialize_seq(OpenSigningInfoVisitor)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::{
+        bip32::Xpriv,
+        block::{Header, Version},
+        hashes::Hash,
+        transaction, CompactTarget, TxIn, TxMerkleNode,
+    };
+
+    use super::*;
+
+    fn config(data_dir: &PathBuf) -> WalletConfig {
+        let xpriv = Xpriv::new_master(Network::Regtest, &[9u8; 32]).unwrap();
+        WalletConfig {
+            name: "test".to_string(),
+            data_dir: data_dir.clone(),
+            start_block: 0,
+            network: Network::Regtest,
+            genesis_hash: None,
+            space_descriptors: WalletDescriptors {
+                external: format!("tr({}/86'/1'/0'/0/*)", xpriv),
+                internal: format!("tr({}/86'/1'/0'/1/*)", xpriv),
+            },
+            cipher: None,
+        }
+    }
+
+    fn spend(wallet: &mut SpacesWallet, previous_output: OutPoint, value: u64) -> Transaction {
+        let script_pubkey = wallet
+            .spaces
+            .reveal_next_address(KeychainKind::External)
+            .address
+            .script_pubkey();
+        Transaction {
+            version: transaction::Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output,
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(value),
+                script_pubkey,
+            }],
+        }
+    }
+
+    fn is_unspent(wallet: &SpacesWallet, outpoint: OutPoint) -> bool {
+        wallet
+            .spaces
+            .list_unspent()
+            .any(|utxo| utxo.outpoint == outpoint)
+    }
+
+    #[test]
+    fn test_remove_unconfirmed_txs() {
+        let data_dir = std::env::temp_dir().join(format!("spaces-remove-{}", std::process::id()));
+        _ = fs::remove_dir_all(&data_dir);
+        let mut wallet = SpacesWallet::new(config(&data_dir)).unwrap();
+
+        // Confirm a coin in a block on top of genesis
+        let funding = spend(&mut wallet, OutPoint::null(), 10_000);
+        let coin = OutPoint {
+            txid: funding.compute_txid(),
+            vout: 0,
+        };
+        let genesis = genesis_block(Network::Regtest).block_hash();
+        let block = Block {
+            header: Header {
+                version: Version::TWO,
+                prev_blockhash: genesis,
+                merkle_root: TxMerkleNode::all_zeros(),
+                time: 0,
+                bits: CompactTarget::from_consensus(0x207fffff),
+                nonce: 0,
+            },
+            txdata: vec![funding],
+        };
+        wallet
+            .apply_block_connected_to(
+                1,
+                &block,
+                BlockId {
+                    height: 0,
+                    hash: genesis,
+                },
+            )
+            .unwrap();
+
+        let parent = spend(&mut wallet, coin, 9_000);
+        let parent_txid = parent.compute_txid();
+        let child = spend(
+            &mut wallet,
+            OutPoint {
+                txid: parent_txid,
+                vout: 0,
+            },
+            8_000,
+        );
+        let child_txid = child.compute_txid();
+        for tx in [parent, child] {
+            wallet
+                .insert_tx(tx, ConfirmationTime::Unconfirmed { last_seen: 0 })
+                .unwrap();
+        }
+        wallet.commit().unwrap();
+        assert!(!is_unspent(&wallet, coin));
+
+        assert!(
+            wallet
+                .remove_unconfirmed_txs(&HashSet::from([coin.txid]))
+                .is_err(),
+            "confirmed transactions can't be removed"
+        );
+
+        let removed = wallet
+            .remove_unconfirmed_txs(&HashSet::from([parent_txid]))
+            .unwrap();
+        assert_eq!(removed, vec![child_txid, parent_txid], "children go first");
+        assert!(wallet.spaces.get_tx(child_txid).is_none());
+        assert!(
+            is_unspent(&wallet, coin),
+            "the parent's input must be freed"
+        );
+
+        // The removal is persisted
+        drop(wallet);
+        let wallet = SpacesWallet::new(config(&data_dir)).unwrap();
+        assert!(wallet.spaces.get_tx(parent_txid).is_none());
+        assert!(wallet.spaces.get_tx(child_txid).is_none());
+        assert!(wallet.spaces.get_tx(coin.txid).is_some());
+        assert!(is_unspent(&wallet, coin));
+        assert_eq!(wallet.spaces.local_chain().tip().height(), 1);
+
+        fs::remove_dir_all(data_dir).unwrap();
+    }
+
+    #[test]
+    fn test_encrypt_data_files() {
+        let data_dir =
+            std::env::temp_dir().join(format!("spaces-data-files-{}", std::process::id()));
+        _ = fs::remove_dir_all(&data_dir);
+        let mut wallet = SpacesWallet::new(config(&data_dir)).unwrap();
+        assert_eq!(wallet.read_data_file("notes.json").unwrap(), None);
+        wallet.write_data_file("notes.json", b"{}").unwrap();
+
+        wallet
+            .encrypt(WalletCipher::new("pass").unwrap(), &["notes.json"])
+            .unwrap();
+        assert!(is_sealed(&fs::read(data_dir.join("notes.json")).unwrap()));
+        assert_eq!(
+            wallet.read_data_file("notes.json").unwrap().as_deref(),
+            Some(&b"{}"[..])
+        );
+
+        wallet.write_data_file("notes.json", b"[]").unwrap();
+        assert!(is_sealed(&fs::read(data_dir.join("notes.json")).unwrap()));
+        assert_eq!(
+            wallet.read_data_file("notes.json").unwrap().as_deref(),
+            Some(&b"[]"[..])
+        );
+
+        drop(wallet);
+        assert!(
+            SpacesWallet::new(config(&data_dir)).is_err(),
+            "an encrypted wallet can't be opened without its cipher"
+        );
+        fs::remove_dir_all(data_dir).unwrap();
+    }
+}